@@ -0,0 +1,124 @@
+//! Hook to observe what engines do: every `zpool`/`zfs` command and libzfs_core call is reported
+//! with its duration and outcome. Install it once with `setup_hook` to feed your metrics.
+//!
+//! With `metrics` feature enabled [`MetricsHook`](struct.MetricsHook.html) exports them through
+//! [metrics](https://docs.rs/metrics) crate.
+use std::{process::Command,
+          time::{Duration, Instant}};
+
+use once_cell::sync::OnceCell;
+
+static GLOBAL_HOOK: OnceCell<Box<dyn ObservabilityHook>> = OnceCell::new();
+
+/// Receives reports about operations. Called from the thread that did the operation, so it should
+/// be quick.
+pub trait ObservabilityHook: Send + Sync {
+    /// Called after every operation.
+    ///
+    /// * `operation` - `zpool <subcommand>`, `zfs <subcommand>` or name of libzfs_core function.
+    /// * `duration` - How long it took.
+    /// * `success` - Whether it succeeded.
+    fn operation(&self, operation: &str, duration: Duration, success: bool);
+
+    /// Called with size of a send stream in bytes (as estimated by `send_space`).
+    fn stream_bytes(&self, _operation: &str, _bytes: u64) {}
+}
+
+/// Set global hook. Optional.
+/// Can only called once. Returns Ok(()) if the cell was empty and Err(value) if it was full.
+pub fn setup_hook<H: ObservabilityHook + 'static>(
+    hook: H,
+) -> Result<(), Box<dyn ObservabilityHook>> {
+    GLOBAL_HOOK.set(Box::new(hook))
+}
+
+/// Report operation that started at `started`.
+pub(crate) fn finished(operation: &str, started: Instant, success: bool) {
+    if let Some(hook) = GLOBAL_HOOK.get() {
+        hook.operation(operation, started.elapsed(), success);
+    }
+}
+
+/// Report command that started at `started`. Operation is named after `program` and the first
+/// argument of the command.
+pub(crate) fn command_finished(program: &str, z: &Command, started: Instant, success: bool) {
+    if let Some(hook) = GLOBAL_HOOK.get() {
+        let operation = match z.get_args().next() {
+            Some(subcommand) => format!("{} {}", program, subcommand.to_string_lossy()),
+            None => String::from(program),
+        };
+        hook.operation(&operation, started.elapsed(), success);
+    }
+}
+
+/// Report size of a stream.
+pub(crate) fn stream_bytes(operation: &str, bytes: u64) {
+    if let Some(hook) = GLOBAL_HOOK.get() {
+        hook.stream_bytes(operation, bytes);
+    }
+}
+
+/// Hook that exports operations with `metrics` crate:
+///
+/// * `libzetta_operations_total` counter with `operation` and `outcome` (`ok` or `error`) labels.
+/// * `libzetta_operation_duration_seconds` histogram with `operation` label.
+/// * `libzetta_stream_bytes` gauge with `operation` label.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsHook;
+
+#[cfg(feature = "metrics")]
+impl ObservabilityHook for MetricsHook {
+    fn operation(&self, operation: &str, duration: Duration, success: bool) {
+        let outcome = if success { "ok" } else { "error" };
+        let labels = [("operation", operation.to_string()), ("outcome", outcome.to_string())];
+        metrics::counter!("libzetta_operations_total", &labels).increment(1);
+        let labels = [("operation", operation.to_string())];
+        metrics::histogram!("libzetta_operation_duration_seconds", &labels)
+            .record(duration.as_secs_f64());
+    }
+
+    fn stream_bytes(&self, operation: &str, bytes: u64) {
+        metrics::gauge!("libzetta_stream_bytes", "operation" => operation.to_string())
+            .set(bytes as f64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default, Clone)]
+    struct Recorder {
+        seen: Arc<Mutex<Vec<(String, bool)>>>,
+    }
+
+    impl ObservabilityHook for Recorder {
+        fn operation(&self, operation: &str, _duration: Duration, success: bool) {
+            self.seen.lock().unwrap().push((operation.to_string(), success));
+        }
+
+        fn stream_bytes(&self, operation: &str, bytes: u64) {
+            self.seen.lock().unwrap().push((format!("{} {}", operation, bytes), true));
+        }
+    }
+
+    #[test]
+    fn test_hook_receives_operations() {
+        let recorder = Recorder::default();
+        assert!(setup_hook(recorder.clone()).is_ok());
+        assert!(setup_hook(Recorder::default()).is_err());
+
+        let mut z = Command::new("/sbin/zfs-test-hook");
+        z.args(&["snapshot", "tank@a"]);
+        command_finished("zfs", &z, Instant::now(), false);
+        finished("lzc_hook_test", Instant::now(), true);
+        stream_bytes("lzc_send_space_hook_test", 42);
+
+        let seen = recorder.seen.lock().unwrap();
+        assert!(seen.contains(&(String::from("zfs snapshot"), false)));
+        assert!(seen.contains(&(String::from("lzc_hook_test"), true)));
+        assert!(seen.contains(&(String::from("lzc_send_space_hook_test 42"), true)));
+    }
+}
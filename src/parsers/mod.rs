@@ -9,13 +9,15 @@ pub struct StdoutParser;
 
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
+    use std::{path::PathBuf, time::Duration};
 
+    use chrono::NaiveDateTime;
     use pest::{consumes_to, parses_to, Parser};
 
     use crate::{parsers::*,
                 zpool::{vdev::{CreateVdevRequest, ErrorStatistics},
-                        CreateZpoolRequestBuilder, Health, Reason, Zpool}};
+                        CreateZpoolRequestBuilder, Health, Reason, ScanStatus, VdevFaultReason,
+                        Zpool}};
 
     #[test]
     fn test_issue_78_minimal() {
@@ -27,6 +29,15 @@ mod test {
         let span = inner.as_str();
         let expected = "resilver in progress since Tue Aug 13 23:03:11 2019\n\t42.5K scanned at 42.5K/s, 80K issued at 80K/s, 83K total\n\t512 resilvered, 96.39% done, no estimated completion time\n";
         assert_eq!(expected, span);
+
+        let since = NaiveDateTime::parse_from_str("2019-08-13 23:03:11", "%Y-%m-%d %H:%M:%S");
+        let expected = ScanStatus::ResilverInProgress {
+            since:      since.ok(),
+            permille:   964,
+            eta:        None,
+            resilvered: 512,
+        };
+        assert_eq!(expected, ScanStatus::from_scan_text(span));
     }
 
     #[test]
@@ -252,8 +263,21 @@ errors: Pretend this is actual error
         let vdev_expected = CreateVdevRequest::SingleDisk(std::path::PathBuf::from("nvd0p2"));
         assert_eq!(vdev, &vdev_expected);
 
+        let expected = ScanStatus::ScrubCompleted {
+            repaired: 0,
+            errors:   0,
+            duration: Some(Duration::from_secs(0)),
+        };
+        assert_eq!(&expected, first.scan());
+
         let second = zpools.next().unwrap();
         assert_eq!(second.name(), &String::from("z"));
+        let expected = ScanStatus::ScrubCompleted {
+            repaired: 0,
+            errors:   0,
+            duration: Some(Duration::from_secs(114)),
+        };
+        assert_eq!(&expected, second.scan());
 
         assert!(second.errors().is_some());
 
@@ -386,12 +410,84 @@ errors: No known data errors
         assert_eq!(&Health::Offline, first_disk.health());
         assert_eq!(&ErrorStatistics::default(), first_disk.error_statistics());
         assert_eq!(&Some(Reason::Other(String::from("was /vdevs/vdev0"))), first_disk.reason());
+        let was = VdevFaultReason::Was(PathBuf::from("/vdevs/vdev0"));
+        assert_eq!(Some(was), first_disk.fault_reason());
 
         let second_disk = &mirror.disks()[1];
         assert_eq!(&Health::Online, second_disk.health());
         assert_eq!(&expected_errors, second_disk.error_statistics());
     }
 
+    #[test]
+    fn test_checksum_errors_on_online_disks() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+status: One or more devices has experienced an unrecoverable error.  An
+        attempt was made to correct the error.  Applications are unaffected.
+action: Determine if the device needs to be replaced, and clear the errors
+        using 'zpool clear' or replace the device with 'zpool replace'.
+  scan: none requested
+config:
+
+        NAME            STATE     READ WRITE CKSUM
+        tank            ONLINE       0     0     0
+          mirror-0      ONLINE       0     0     0
+            /vdevs/sda  ONLINE       0     0    17
+            /vdevs/sdb  ONLINE       3     0  1.5K
+
+errors: No known data errors
+"#;
+        let mut pairs =
+            StdoutParser::parse(Rule::zpool, stdout).unwrap_or_else(|e| panic!("{}", e));
+        let zpool = Zpool::from_pest_pair(pairs.next().unwrap());
+        assert_eq!(&Health::Online, zpool.health());
+        assert_eq!(&ErrorStatistics::default(), zpool.error_statistics());
+
+        let disks = zpool.vdevs()[0].disks();
+        let expected = ErrorStatistics { read: 0, write: 0, checksum: 17 };
+        assert_eq!(&expected, disks[0].error_statistics());
+        let expected = ErrorStatistics { read: 3, write: 0, checksum: 1536 };
+        assert_eq!(&expected, disks[1].error_statistics());
+        assert_eq!(&None, disks[1].reason());
+    }
+
+    #[test]
+    fn test_fault_reasons() {
+        let stdout = r#"  pool: tank
+ state: UNAVAIL
+status: One or more devices could not be opened.  There are insufficient
+        replicas for the pool to continue functioning.
+action: Attach the missing device and online it using 'zpool online'.
+   see: http://zfsonlinux.org/msg/ZFS-8000-3C
+  scan: none requested
+config:
+
+        NAME            STATE     READ WRITE CKSUM
+        tank            UNAVAIL      0     0     0  insufficient replicas
+          mirror-0      UNAVAIL      0     0     0  insufficient replicas
+            /vdevs/sda  UNAVAIL      0     0     0  cannot open
+            /vdevs/sdb  FAULTED     12     0     0  too many errors
+
+errors: No known data errors
+"#;
+        let mut pairs =
+            StdoutParser::parse(Rule::zpool, stdout).unwrap_or_else(|e| panic!("{}", e));
+        let zpool = Zpool::from_pest_pair(pairs.next().unwrap());
+        assert_eq!(Some(VdevFaultReason::InsufficientReplicas), zpool.fault_reason());
+
+        let mirror = &zpool.vdevs()[0];
+        assert_eq!(Some(VdevFaultReason::InsufficientReplicas), mirror.fault_reason());
+        assert_eq!(&Health::Unavailable, mirror.disks()[0].health());
+        assert_eq!(Some(VdevFaultReason::CannotOpen), mirror.disks()[0].fault_reason());
+        assert_eq!(&Health::Faulted, mirror.disks()[1].health());
+        assert_eq!(Some(VdevFaultReason::TooManyErrors), mirror.disks()[1].fault_reason());
+        assert_eq!(&12, &mirror.disks()[1].error_statistics().read);
+
+        assert_eq!(VdevFaultReason::CorruptedData, VdevFaultReason::from_text("corrupted data"));
+        let other = VdevFaultReason::from_text("(resilvering)");
+        assert_eq!(VdevFaultReason::Other(String::from("(resilvering)")), other);
+    }
+
     #[test]
     fn test_zpools_on_single_zpool() {
         let stdout = r#"  pool: test
@@ -429,6 +525,30 @@ errors: No known data errors
         assert_eq!(&Health::Degraded, zpool.health());
     }
 
+    #[test]
+    fn test_suspended_with_removed_disk() {
+        let stdout = r#"  pool: test
+ state: SUSPENDED
+status: One or more devices are faulted in response to IO failures.
+action: Make sure the affected devices are connected, then run 'zpool clear'.
+  scan: none requested
+config:
+
+        NAME              STATE     READ WRITE CKSUM
+        test              SUSPENDED    0     0     0
+          /vdevs/vdev0    REMOVED      0     0     0
+
+errors: 2 data errors, use '-v' for a list
+"#;
+        let mut pairs =
+            StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
+        let zpool = Zpool::from_pest_pair(pairs.next().unwrap());
+        assert_eq!(&Health::Suspended, zpool.health());
+
+        let disk = &zpool.vdevs()[0].disks()[0];
+        assert_eq!(&Health::Removed, disk.health());
+    }
+
     #[test]
     fn test_zpool_with_cache_and_log() {
         let stdout = r#"  pool: hell
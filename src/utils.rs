@@ -10,3 +10,18 @@ pub fn parse_float(input: &mut String) -> Result<f64, std::num::ParseFloatError>
     }
     input.parse()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_ratio() {
+        assert_eq!(Ok(1.0), parse_float(&mut String::from("1.00x")));
+        assert_eq!(Ok(2.45), parse_float(&mut String::from("2.45x")));
+        assert_eq!(Ok(12.5), parse_float(&mut String::from("12.50x")));
+        // `-p` drops the suffix.
+        assert_eq!(Ok(2.45), parse_float(&mut String::from("2.45")));
+        assert!(parse_float(&mut String::from("x")).is_err());
+    }
+}
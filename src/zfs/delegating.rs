@@ -1,5 +1,7 @@
 use crate::zfs::{lzc::ZfsLzc, open3::ZfsOpen3, BookmarkRequest, CreateDatasetRequest, DatasetKind,
-                 DestroyTiming, Properties, Result, SendFlags, ZfsEngine};
+                 DatasetPermissions, Delegation, DestroyTiming, Encryption, Error, Properties,
+                 PropertyValue, ReceiveOptions, ReceivedStream, Result, ResumeToken, SendFlags,
+                 SentStream, ShareProtocol, SnapshotInfo, SpaceUsage, StreamHasher, ZfsEngine};
 use std::{collections::HashMap, os::unix::io::AsRawFd, path::PathBuf};
 
 /// Handy wrapper that delegates your call to correct implementation.
@@ -9,17 +11,40 @@ pub struct DelegatingZfsEngine {
 }
 
 impl DelegatingZfsEngine {
-    pub fn new() -> Result<Self> {
+    pub fn new() -> Result<Self> { DelegatingZfsEngine::with_open3(ZfsOpen3::new()) }
+
+    /// Same as `new`, but commands are run by given (configured) `ZfsOpen3`.
+    pub fn with_open3(open3: ZfsOpen3) -> Result<Self> {
         let lzc = ZfsLzc::new()?;
-        let open3 = ZfsOpen3::new();
         Ok(DelegatingZfsEngine { lzc, open3 })
     }
 }
 
 impl ZfsEngine for DelegatingZfsEngine {
-    fn exists<N: Into<PathBuf>>(&self, name: N) -> Result<bool> { self.lzc.exists(name) }
+    fn exists<N: Into<PathBuf>>(&self, name: N) -> Result<bool> {
+        let name = name.into();
+        // lzc_exists only knows about datasets and snapshots.
+        if DatasetKind::from_name(&name) == Some(DatasetKind::Bookmark) {
+            return self.open3.kind_of(name).map(|kind| kind.is_some());
+        }
+        self.lzc.exists(name)
+    }
+
+    fn kind_of<N: Into<PathBuf>>(&self, name: N) -> Result<Option<DatasetKind>> {
+        let name = name.into();
+        // Snapshots can be told apart by name, filesystems and volumes can't.
+        match DatasetKind::from_name(&name) {
+            Some(DatasetKind::Bookmark) => self.open3.kind_of(name),
+            _ if !self.lzc.exists(name.clone())? => Ok(None),
+            Some(kind) => Ok(Some(kind)),
+            None => self.open3.kind_of(name),
+        }
+    }
 
-    fn create(&self, request: CreateDatasetRequest) -> Result<()> { self.lzc.create(request) }
+    fn create(&self, request: CreateDatasetRequest) -> Result<()> {
+        request.validate_encryption(self)?;
+        self.lzc.create(request)
+    }
 
     fn snapshot(
         &self,
@@ -37,6 +62,16 @@ impl ZfsEngine for DelegatingZfsEngine {
         self.lzc.destroy_snapshots(snapshots, timing)
     }
 
+    fn destroy_snapshot_range<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        first: Option<&str>,
+        last: Option<&str>,
+        timing: DestroyTiming,
+    ) -> Result<()> {
+        self.open3.destroy_snapshot_range(dataset, first, last, timing)
+    }
+
     fn destroy_bookmarks(&self, bookmarks: &[PathBuf]) -> Result<()> {
         self.lzc.destroy_bookmarks(bookmarks)
     }
@@ -61,10 +96,129 @@ impl ZfsEngine for DelegatingZfsEngine {
         self.open3.list_volumes(pool)
     }
 
+    fn clones(&self, snapshot: PathBuf) -> Result<Vec<PathBuf>> { self.open3.clones(snapshot) }
+
+    fn encryption<N: Into<PathBuf>>(&self, name: N) -> Result<Encryption> {
+        self.open3.encryption(name)
+    }
+
+    fn unmount_all<N: Into<PathBuf>>(&self, pool: N, force: bool) -> Result<()> {
+        self.open3.unmount_all(pool, force)
+    }
+
+    fn share<N: Into<PathBuf>>(&self, name: N, protocol: ShareProtocol) -> Result<()> {
+        self.open3.share(name, protocol)
+    }
+
+    fn unshare<N: Into<PathBuf>>(&self, name: N, protocol: ShareProtocol) -> Result<()> {
+        self.open3.unshare(name, protocol)
+    }
+
+    fn space<N: Into<PathBuf>>(&self, name: N) -> Result<SpaceUsage> { self.open3.space(name) }
+
+    fn reclaimable_space<N: Into<PathBuf>>(&self, dataset: N, snaps: &[PathBuf]) -> Result<u64> {
+        self.open3.reclaimable_space(dataset, snaps)
+    }
+
+    fn space_snapshot<N: Into<PathBuf>>(&self, root: N) -> Result<HashMap<PathBuf, SpaceUsage>> {
+        self.open3.space_snapshot(root)
+    }
+
+    fn written_since<N: Into<PathBuf>, S: AsRef<str>>(
+        &self,
+        dataset: N,
+        since_snap: S,
+    ) -> Result<u64> {
+        self.open3.written_since(dataset, since_snap)
+    }
+
+    fn snapshot_info<N: Into<PathBuf>>(&self, snapshot: N) -> Result<SnapshotInfo> {
+        self.open3.snapshot_info(snapshot)
+    }
+
+    fn list_snapshots_by_creation<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        recursive: bool,
+    ) -> Result<Vec<SnapshotInfo>> {
+        self.open3.list_snapshots_by_creation(dataset, recursive)
+    }
+
+    fn bookmark_guids<N: Into<PathBuf>>(&self, dataset: N) -> Result<HashMap<u64, PathBuf>> {
+        self.open3.bookmark_guids(dataset)
+    }
+
+    fn zvol_device_path(&self, name: PathBuf) -> Result<Option<PathBuf>> {
+        self.open3.zvol_device_path(name)
+    }
+
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        name: N,
+        fd: FD,
+        options: ReceiveOptions,
+    ) -> Result<()> {
+        self.open3.receive(name, fd, options)
+    }
+
+    fn load_key<N: Into<PathBuf>>(&self, name: N, key_location: Option<&str>) -> Result<()> {
+        self.open3.load_key(name, key_location)
+    }
+
+    fn receive_dry_run<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        name: N,
+        fd: FD,
+        options: ReceiveOptions,
+    ) -> Result<Vec<ReceivedStream>> {
+        self.open3.receive_dry_run(name, fd, options)
+    }
+
+    fn set_property_many(
+        &self,
+        datasets: &[PathBuf],
+        prop: &str,
+        value: &str,
+    ) -> Result<Vec<(PathBuf, Result<()>)>> {
+        self.open3.set_property_many(datasets, prop, value)
+    }
+
+    fn resize_volume<N: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_size: u64,
+        grow_reservation: bool,
+    ) -> Result<()> {
+        self.open3.resize_volume(name, new_size, grow_reservation)
+    }
+
+    fn upgrade_dataset<N: Into<PathBuf>>(&self, name: N, recursive: bool) -> Result<()> {
+        self.open3.upgrade_dataset(name, recursive)
+    }
+
+    fn allow<N: Into<PathBuf>>(&self, name: N, who: Delegation, perms: &[&str]) -> Result<()> {
+        self.open3.allow(name, who, perms)
+    }
+
+    fn unallow<N: Into<PathBuf>>(&self, name: N, who: Delegation, perms: &[&str]) -> Result<()> {
+        self.open3.unallow(name, who, perms)
+    }
+
+    fn list_permissions<N: Into<PathBuf>>(&self, name: N) -> Result<Vec<DatasetPermissions>> {
+        self.open3.list_permissions(name)
+    }
+
     fn read_properties<N: Into<PathBuf>>(&self, path: N) -> Result<Properties> {
         self.open3.read_properties(path)
     }
 
+    fn read_properties_with_source<N: Into<PathBuf>>(
+        &self,
+        name: N,
+    ) -> Result<HashMap<String, PropertyValue>> {
+        self.open3.read_properties_with_source(name)
+    }
+
     fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
         &self,
         path: N,
@@ -84,6 +238,71 @@ impl ZfsEngine for DelegatingZfsEngine {
         self.lzc.send_incremental(path, from, fd, flags)
     }
 
+    fn send_replication<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        skip_missing: bool,
+    ) -> Result<Vec<PathBuf>> {
+        self.open3.send_replication(path, from, fd, flags, skip_missing)
+    }
+
+    fn send_with_progress<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        progress: Option<&mut dyn FnMut(u64)>,
+    ) -> Result<()> {
+        self.open3.send_with_progress(path, from, fd, flags, progress)
+    }
+
+    fn send_with_digest<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        hasher: &mut dyn StreamHasher,
+    ) -> Result<SentStream> {
+        self.open3.send_with_digest(path, from, fd, flags, hasher)
+    }
+
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+    ) -> Result<u64> {
+        self.lzc.send_space(path, from, flags)
+    }
+
+    fn resume_token<N: Into<PathBuf>>(&self, name: N) -> Result<Option<String>> {
+        self.open3.resume_token(name)
+    }
+
+    fn decode_resume_token(&self, token: &str) -> Result<ResumeToken> {
+        self.open3.decode_resume_token(token)
+    }
+
+    fn resume_remaining(&self, token: &str) -> Result<u64> {
+        let token = self.decode_resume_token(token)?;
+        let from = match token.from_guid() {
+            Some(guid) => {
+                let dataset = token.to_name().to_string_lossy();
+                let dataset = dataset.split('@').next().unwrap_or_default();
+                let from = self.open3.snapshot_by_guid(dataset, *guid)?;
+                Some(from.ok_or_else(|| Error::DatasetNotFound(token.to_name().clone()))?)
+            },
+            None => None,
+        };
+        let total = self.send_space(token.to_name().clone(), from, *token.flags())?;
+        Ok(token.remaining(total))
+    }
+
     fn run_channel_program<N: Into<PathBuf>>(
         &self,
         pool: N,
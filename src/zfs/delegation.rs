@@ -0,0 +1,195 @@
+//! Delegated administration (`zfs allow`/`zfs unallow`). Lets non-root users manage datasets as
+//! long as `delegation` property of the pool is on.
+use std::{collections::HashMap, path::PathBuf};
+
+use pest::{iterators::Pair, Parser};
+
+use crate::{parsers::zfs::{Rule, ZfsParser},
+            zfs::{Error, Result}};
+
+/// Who permissions are granted to (or revoked from).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Delegation {
+    /// Single user, by name or numeric ID.
+    User(String),
+    /// Single group, by name or numeric ID.
+    Group(String),
+    /// Everyone.
+    Everyone,
+    /// Permissions granted to the creator of a descendent filesystem.
+    CreateTime,
+    /// Named permission set, name goes without leading `@`.
+    PermissionSet(String),
+}
+
+impl Delegation {
+    /// Arguments of `zfs allow`/`zfs unallow` that select who.
+    pub(crate) fn as_args(&self) -> Vec<String> {
+        match self {
+            Delegation::User(name) => vec![String::from("-u"), name.clone()],
+            Delegation::Group(name) => vec![String::from("-g"), name.clone()],
+            Delegation::Everyone => vec![String::from("-e")],
+            Delegation::CreateTime => vec![String::from("-c")],
+            Delegation::PermissionSet(name) => vec![String::from("-s"), format!("@{}", name)],
+        }
+    }
+}
+
+/// Permissions set on a single dataset, as reported by `zfs allow`. Permissions are either names
+/// of subcommands and properties (`snapshot`, `mount`, `compression`) or names of permission sets
+/// with leading `@`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct DatasetPermissions {
+    /// Dataset permissions are set on. Either the requested one or one of its ancestors.
+    dataset:          PathBuf,
+    /// Permission sets defined on the dataset, by name without `@`.
+    sets:             HashMap<String, Vec<String>>,
+    /// Permissions granted to the creator of a descendent filesystem.
+    create_time:      Vec<String>,
+    /// Permissions that apply only to the dataset itself.
+    local:            Vec<(Delegation, Vec<String>)>,
+    /// Permissions that apply only to descendents of the dataset.
+    descendent:       Vec<(Delegation, Vec<String>)>,
+    /// Permissions that apply to the dataset and its descendents.
+    local_descendent: Vec<(Delegation, Vec<String>)>,
+}
+
+impl DatasetPermissions {
+    /// Parse output of `zfs allow <dataset>`. Empty output means no permissions are delegated.
+    pub(crate) fn from_stdout(stdout: &str) -> Result<Vec<DatasetPermissions>> {
+        let pairs = ZfsParser::parse(Rule::permissions, stdout)
+            .map_err(|_| Error::UnknownSoFar(String::from(stdout)))?;
+        Ok(pairs
+            .flat_map(Pair::into_inner)
+            .filter(|pair| pair.as_rule() == Rule::dataset_permissions)
+            .map(DatasetPermissions::from_pair)
+            .collect())
+    }
+
+    fn from_pair(pair: Pair<'_, Rule>) -> DatasetPermissions {
+        let mut ret = DatasetPermissions::default();
+        for section in pair.into_inner() {
+            match section.as_rule() {
+                Rule::dataset_name => ret.dataset = PathBuf::from(section.as_str()),
+                Rule::permission_sets => {
+                    for set in section.into_inner() {
+                        let mut inner = set.into_inner();
+                        if let (Some(name), Some(names)) = (inner.next(), inner.next()) {
+                            ret.sets.insert(String::from(name.as_str()), permission_names(names));
+                        }
+                    }
+                },
+                Rule::create_time_permissions => {
+                    ret.create_time.extend(section.into_inner().flat_map(permission_names));
+                },
+                Rule::local_permissions => ret.local = who_permissions(section),
+                Rule::descendent_permissions => ret.descendent = who_permissions(section),
+                Rule::local_descendent_permissions => {
+                    ret.local_descendent = who_permissions(section)
+                },
+                _ => {},
+            }
+        }
+        ret
+    }
+}
+
+fn permission_names(pair: Pair<'_, Rule>) -> Vec<String> {
+    pair.into_inner().map(|name| String::from(name.as_str())).collect()
+}
+
+fn who_permissions(section: Pair<'_, Rule>) -> Vec<(Delegation, Vec<String>)> {
+    section
+        .into_inner()
+        .filter_map(|pair| {
+            let rule = pair.as_rule();
+            let mut inner = pair.into_inner();
+            let who = match rule {
+                Rule::user_permissions => Delegation::User(String::from(inner.next()?.as_str())),
+                Rule::group_permissions => Delegation::Group(String::from(inner.next()?.as_str())),
+                Rule::everyone_permissions => Delegation::Everyone,
+                _ => return None,
+            };
+            Some((who, permission_names(inner.next()?)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn perms(names: &[&str]) -> Vec<String> { names.iter().map(|n| String::from(*n)).collect() }
+
+    #[test]
+    fn test_parse_allow_output() {
+        let stdout = "---- Permissions on tank/home/alice -----------------------------------
+Permission sets:
+\t@backup send,snapshot
+\t@admin @backup,destroy
+Create time permissions:
+\tdestroy,mount
+Local permissions:
+\tuser alice create,mount
+Descendent permissions:
+\tgroup staff snapshot
+Local+Descendent permissions:
+\tuser bob @backup
+\teveryone userprop
+---- Permissions on tank -----------------------------------------------
+Local+Descendent permissions:
+\tgroup 1001 mount
+";
+        let permissions = DatasetPermissions::from_stdout(stdout).unwrap();
+        assert_eq!(2, permissions.len());
+
+        let home = &permissions[0];
+        assert_eq!(&PathBuf::from("tank/home/alice"), home.dataset());
+        assert_eq!(2, home.sets().len());
+        assert_eq!(Some(&perms(&["send", "snapshot"])), home.sets().get("backup"));
+        assert_eq!(Some(&perms(&["@backup", "destroy"])), home.sets().get("admin"));
+        assert_eq!(&perms(&["destroy", "mount"]), home.create_time());
+        assert_eq!(
+            &vec![(Delegation::User(String::from("alice")), perms(&["create", "mount"]))],
+            home.local()
+        );
+        assert_eq!(
+            &vec![(Delegation::Group(String::from("staff")), perms(&["snapshot"]))],
+            home.descendent()
+        );
+        assert_eq!(
+            &vec![
+                (Delegation::User(String::from("bob")), perms(&["@backup"])),
+                (Delegation::Everyone, perms(&["userprop"])),
+            ],
+            home.local_descendent()
+        );
+
+        let tank = &permissions[1];
+        assert_eq!(&PathBuf::from("tank"), tank.dataset());
+        assert!(tank.local().is_empty());
+        assert_eq!(
+            &vec![(Delegation::Group(String::from("1001")), perms(&["mount"]))],
+            tank.local_descendent()
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_and_garbage() {
+        assert!(DatasetPermissions::from_stdout("").unwrap().is_empty());
+        assert!(DatasetPermissions::from_stdout("cannot open 'tank/nope'").is_err());
+    }
+
+    #[test]
+    fn test_delegation_args() {
+        assert_eq!(vec!["-u", "alice"], Delegation::User(String::from("alice")).as_args());
+        assert_eq!(vec!["-g", "staff"], Delegation::Group(String::from("staff")).as_args());
+        assert_eq!(vec!["-e"], Delegation::Everyone.as_args());
+        assert_eq!(vec!["-c"], Delegation::CreateTime.as_args());
+        assert_eq!(
+            vec!["-s", "@backup"],
+            Delegation::PermissionSet(String::from("backup")).as_args()
+        );
+    }
+}
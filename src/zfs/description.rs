@@ -1,4 +1,4 @@
-use std::default::Default;
+use std::{default::Default, path::Path};
 use strum_macros::{AsRefStr, Display, EnumString};
 
 #[derive(AsRefStr, EnumString, Display, Eq, PartialEq, Debug, Clone)]
@@ -9,6 +9,8 @@ pub enum DatasetKind {
     Volume,
     #[strum(serialize = "snapshot")]
     Snapshot,
+    #[strum(serialize = "bookmark")]
+    Bookmark,
 }
 
 impl Default for DatasetKind {
@@ -16,6 +18,23 @@ impl Default for DatasetKind {
 }
 
 impl DatasetKind {
+    /// Kind that can be told by name alone: snapshot has `@` and bookmark has `#` in its last
+    /// component. Filesystems and volumes look the same, for them it's `None`.
+    pub fn from_name<P: AsRef<Path>>(name: P) -> Option<DatasetKind> {
+        let name = name.as_ref().file_name()?.to_string_lossy();
+        if name.contains('#') {
+            Some(DatasetKind::Bookmark)
+        } else if name.contains('@') {
+            Some(DatasetKind::Snapshot)
+        } else {
+            None
+        }
+    }
+
+    /// Type understood by `lzc_create`.
+    ///
+    /// # Panics
+    /// Only filesystems and volumes can be created, panics on snapshots and bookmarks.
     pub fn as_c_uint(&self) -> zfs_core_sys::lzc_dataset_type::Type {
         match self {
             DatasetKind::Filesystem => zfs_core_sys::lzc_dataset_type::LZC_DATSET_TYPE_ZFS,
@@ -24,6 +43,10 @@ impl DatasetKind {
         }
     }
 
+    /// Value of `type` pair in nvlists passed to libzfs_core.
+    ///
+    /// # Panics
+    /// Panics on snapshots and bookmarks, they have no such value.
     pub fn as_nvpair_value(&self) -> &str {
         match &self {
             DatasetKind::Filesystem => "zfs",
@@ -32,3 +55,20 @@ impl DatasetKind {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind_from_name() {
+        assert_eq!(None, DatasetKind::from_name("tank/usr"));
+        assert_eq!(Some(DatasetKind::Snapshot), DatasetKind::from_name("tank/usr@snap"));
+        assert_eq!(Some(DatasetKind::Bookmark), DatasetKind::from_name("tank/usr#snap"));
+        assert_eq!(Some(DatasetKind::Bookmark), DatasetKind::from_name("tank/usr#snap@1"));
+        assert_eq!(None, DatasetKind::from_name(""));
+
+        assert_eq!(Ok(DatasetKind::Bookmark), "bookmark".parse());
+        assert_eq!("bookmark", DatasetKind::Bookmark.as_ref());
+    }
+}
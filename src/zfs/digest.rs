@@ -0,0 +1,71 @@
+//! Checksumming of send streams on the fly. Stream is hashed while it's copied to its destination,
+//! so backup tools can store the digest next to the stream without reading gigabytes twice.
+
+/// Incremental hash function fed with the stream as it goes. Implement it to use any algorithm.
+/// With `sha2` feature it's implemented for `sha2::Sha256` and `sha2::Sha512`.
+pub trait StreamHasher {
+    /// Feed next chunk of the stream.
+    fn update(&mut self, data: &[u8]);
+
+    /// Digest of everything fed so far. Hasher is reset afterwards.
+    fn finish(&mut self) -> Vec<u8>;
+}
+
+#[cfg(feature = "sha2")]
+impl StreamHasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) { sha2::Digest::update(self, data) }
+
+    fn finish(&mut self) -> Vec<u8> { sha2::Digest::finalize_reset(self).to_vec() }
+}
+
+#[cfg(feature = "sha2")]
+impl StreamHasher for sha2::Sha512 {
+    fn update(&mut self, data: &[u8]) { sha2::Digest::update(self, data) }
+
+    fn finish(&mut self) -> Vec<u8> { sha2::Digest::finalize_reset(self).to_vec() }
+}
+
+/// Stream that was sent and hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct SentStream {
+    /// Size of the stream in bytes.
+    size:   u64,
+    /// Digest of the stream as returned by [`StreamHasher::finish`](trait.StreamHasher.html).
+    digest: Vec<u8>,
+}
+
+impl SentStream {
+    pub(crate) fn new(size: u64, digest: Vec<u8>) -> SentStream { SentStream { size, digest } }
+
+    /// Digest as lowercase hex string, the way `sha256sum` prints it.
+    pub fn hex_digest(&self) -> String {
+        self.digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hex_digest() {
+        let stream = SentStream::new(3, vec![0x00, 0x0f, 0xba, 0xff]);
+        assert_eq!("000fbaff", stream.hex_digest());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_sha256() {
+        let mut hasher = sha2::Sha256::default();
+        StreamHasher::update(&mut hasher, b"ab");
+        StreamHasher::update(&mut hasher, b"c");
+        let stream = SentStream::new(3, StreamHasher::finish(&mut hasher));
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            stream.hex_digest()
+        );
+        let empty = StreamHasher::finish(&mut hasher);
+        assert_eq!(0xe3, empty[0]);
+    }
+}
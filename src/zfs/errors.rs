@@ -1,10 +1,33 @@
 use crate::parsers::zfs::{Rule, ZfsParser};
 use pest::Parser;
-use std::{borrow::Cow, collections::HashMap, io, path::PathBuf};
+use regex::Regex;
+use std::{borrow::Cow,
+          collections::HashMap,
+          fmt, io,
+          path::{Path, PathBuf}};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub type ValidationResult<T = (), E = ValidationError> = std::result::Result<T, E>;
 
+lazy_static! {
+    static ref RE_PERMISSION_DENIED: Regex =
+        Regex::new(r"[Pp]ermission denied|[Oo]peration not permitted")
+            .expect("failed to compile RE_PERMISSION_DENIED");
+    static ref RE_SOURCE_MISMATCH: Regex =
+        Regex::new(r"most recent snapshot of (\S+) does not\s+match incremental source")
+            .expect("failed to compile RE_SOURCE_MISMATCH");
+    static ref RE_DESTINATION_MODIFIED: Regex =
+        Regex::new(r"destination (\S+) has been modified\s+since most recent snapshot")
+            .expect("failed to compile RE_DESTINATION_MODIFIED");
+    static ref RE_OUT_OF_SPACE: Regex =
+        Regex::new(r"'([^']+)': out of space").expect("failed to compile RE_OUT_OF_SPACE");
+    static ref RE_QUOTA_EXCEEDED: Regex = Regex::new(r"'([^']+)': (?:[Dd]is[ck] )?quota exceeded")
+        .expect("failed to compile RE_QUOTA_EXCEEDED");
+    static ref RE_ORIGIN_NOT_FOUND: Regex =
+        Regex::new(r"local origin for clone (\S+) does not exist")
+            .expect("failed to compile RE_ORIGIN_NOT_FOUND");
+}
+
 quick_error! {
     #[derive(Debug)]
     pub enum Error {
@@ -13,17 +36,68 @@ quick_error! {
         LZCInitializationFailed(err: std::io::Error) {
             cause(err)
         }
+        /// `/dev/zfs` is missing, ZFS kernel module isn't loaded or isn't installed at all.
+        ZfsModuleNotLoaded {
+            display("ZFS kernel module is not loaded: /dev/zfs is unavailable")
+        }
         NvOpError(err: libnv::NvError) {
             cause(err)
             from()
         }
         Io(err: std::io::Error) {
             cause(err)
-            from()
         }
+        /// Not running as root or operation isn't delegated to the user (`EPERM`/`EACCES`).
+        PermissionDenied {}
+        /// Command was cancelled with a flag given to
+        /// [`ZfsOpen3::cancel_on`](struct.ZfsOpen3.html#method.cancel_on).
+        Cancelled {}
         Unknown {}
-        UnknownSoFar(err: String) {}
+        /// Error that isn't categorized yet. Contains stderr of the failed command.
+        UnknownSoFar(err: String) {
+            display("{}", err.trim_end())
+        }
         DatasetNotFound(dataset: PathBuf) {}
+        /// Dataset can't be unmounted because it's in use.
+        DatasetBusy(dataset: PathBuf) {}
+        /// Trying to share dataset over protocol that isn't enabled on it.
+        ShareNotEnabled(dataset: PathBuf) {}
+        /// Snapshots can't be destroyed right now because they have user holds. Contains hold
+        /// tags of every held snapshot.
+        SnapshotsHeld(holds: HashMap<PathBuf, Vec<String>>) {
+            display("snapshots have holds: {:?}", holds)
+        }
+        /// Incremental stream isn't based on the most recent snapshot of the destination (or on
+        /// the origin it's received as a clone of).
+        IncrementalSourceMismatch(dataset: PathBuf) {
+            display("incremental source of the stream doesn't match {}", dataset.display())
+        }
+        /// Destination was modified since its most recent snapshot, so incremental stream can't be
+        /// received on top of it. Receive with `force` to roll those changes back.
+        DestinationModified(dataset: PathBuf) {
+            display("{} was modified since its most recent snapshot", dataset.display())
+        }
+        /// Pool has no space left for the dataset or snapshot (`ENOSPC`).
+        OutOfSpace(dataset: PathBuf) {
+            display("out of space for {}", dataset.display())
+        }
+        /// `quota`, `filesystem_limit` or `snapshot_limit` of the dataset or one of its parents
+        /// doesn't allow the operation (`EDQUOT`).
+        QuotaExceeded(dataset: PathBuf) {
+            display("quota exceeded for {}", dataset.display())
+        }
+        /// Dataset has no snapshots to send.
+        NoSnapshots(dataset: PathBuf) {
+            display("{} has no snapshots", dataset.display())
+        }
+        /// Running ZFS can't create bookmark from another bookmark, only from a snapshot.
+        BookmarkFromBookmarkUnsupported {
+            display("creating bookmark from bookmark is unsupported on this version of ZFS")
+        }
+        /// Pool doesn't support requested filesystem version.
+        UnsupportedVersion(dataset: PathBuf, version: u64) {
+            display("pool of {} doesn't support filesystem version {}", dataset.display(), version)
+        }
         ValidationErrors(errors: Vec<ValidationError>) {
             from()
         }
@@ -32,10 +106,36 @@ quick_error! {
         }
         ChanProgInval(err: HashMap<String, libnv::nvpair::Value>) {}
         ChanProgRuntime(err: HashMap<String, libnv::nvpair::Value>) {}
+        /// Value can't be converted to or from nvlist.
+        NvConversion(msg: String) {
+            display("nvlist conversion failed: {}", msg)
+        }
         Unimplemented {}
     }
 }
 
+/// Payload of `io::Error` returned when command was killed because of cancellation. Tells it
+/// apart from genuine `EINTR`.
+#[derive(Debug)]
+pub(crate) struct CancelledByFlag;
+
+impl fmt::Display for CancelledByFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str("cancelled") }
+}
+
+impl std::error::Error for CancelledByFlag {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        let cancelled = err.get_ref().map_or(false, |inner| inner.is::<CancelledByFlag>());
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied,
+            io::ErrorKind::Interrupted if cancelled => Error::Cancelled,
+            _ => Error::Io(err),
+        }
+    }
+}
+
 impl From<ValidationError> for Error {
     fn from(err: ValidationError) -> Error { Error::ValidationErrors(vec![err]) }
 }
@@ -45,18 +145,59 @@ impl Error {
         match self {
             Error::CmdNotFound => ErrorKind::CmdNotFound,
             Error::LZCInitializationFailed(_) => ErrorKind::LZCInitializationFailed,
+            Error::ZfsModuleNotLoaded => ErrorKind::ZfsModuleNotLoaded,
             Error::NvOpError(_) => ErrorKind::NvOpError,
             Error::Io(_) => ErrorKind::Io,
+            Error::PermissionDenied => ErrorKind::PermissionDenied,
+            Error::Cancelled => ErrorKind::Cancelled,
             Error::DatasetNotFound(_) => ErrorKind::DatasetNotFound,
+            Error::DatasetBusy(_) => ErrorKind::DatasetBusy,
+            Error::ShareNotEnabled(_) => ErrorKind::ShareNotEnabled,
+            Error::UnsupportedVersion(..) => ErrorKind::UnsupportedVersion,
+            Error::SnapshotsHeld(_) => ErrorKind::SnapshotsHeld,
+            Error::IncrementalSourceMismatch(_) => ErrorKind::IncrementalSourceMismatch,
+            Error::DestinationModified(_) => ErrorKind::DestinationModified,
+            Error::OutOfSpace(_) => ErrorKind::OutOfSpace,
+            Error::QuotaExceeded(_) => ErrorKind::QuotaExceeded,
+            Error::NoSnapshots(_) => ErrorKind::NoSnapshots,
+            Error::BookmarkFromBookmarkUnsupported => ErrorKind::BookmarkFromBookmarkUnsupported,
             Error::Unknown | Error::UnknownSoFar(_) => ErrorKind::Unknown,
             Error::ValidationErrors(_) => ErrorKind::ValidationErrors,
             Error::MultiOpError(_) => ErrorKind::MultiOpError,
             Error::ChanProgInval(_) => ErrorKind::ChanProgInval,
             Error::ChanProgRuntime(_) => ErrorKind::ChanProgRuntime,
+            Error::NvConversion(_) => ErrorKind::NvConversion,
             Error::Unimplemented => ErrorKind::Unimplemented,
         }
     }
 
+    /// Whether dataset (or snapshot, or bookmark) doesn't exist. Covers both `zfs` reporting
+    /// it and libzfs_core returning `ENOENT`.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::DatasetNotFound(_) => true,
+            Error::Io(err) => err.kind() == io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+
+    /// Map `ENOSPC` and `EDQUOT` returned by libzfs_core to `OutOfSpace` and `QuotaExceeded`.
+    pub(crate) fn from_space_errno(errno: i32, dataset: &Path) -> Option<Self> {
+        match errno {
+            libc::ENOSPC => Some(Error::OutOfSpace(dataset.to_path_buf())),
+            libc::EDQUOT => Some(Error::QuotaExceeded(dataset.to_path_buf())),
+            _ => None,
+        }
+    }
+
+    /// Map errno of `libzfs_core_init`. `ENOENT` and `ENXIO` mean there is no `/dev/zfs` to open.
+    pub(crate) fn from_init_errno(errno: i32) -> Self {
+        match errno {
+            libc::ENOENT | libc::ENXIO => Error::ZfsModuleNotLoaded,
+            _ => Error::LZCInitializationFailed(io::Error::from_raw_os_error(errno)),
+        }
+    }
+
     fn unknown_so_far(stderr: Cow<'_, str>) -> Self { Error::UnknownSoFar(stderr.into()) }
 
     #[allow(clippy::option_unwrap_used)]
@@ -73,6 +214,18 @@ impl Error {
                 },
                 _ => Self::unknown_so_far(stderr),
             }
+        } else if let Some(caps) = RE_SOURCE_MISMATCH.captures(&stderr) {
+            Error::IncrementalSourceMismatch(PathBuf::from(&caps[1]))
+        } else if let Some(caps) = RE_DESTINATION_MODIFIED.captures(&stderr) {
+            Error::DestinationModified(PathBuf::from(&caps[1]))
+        } else if let Some(caps) = RE_OUT_OF_SPACE.captures(&stderr) {
+            Error::OutOfSpace(PathBuf::from(&caps[1]))
+        } else if let Some(caps) = RE_QUOTA_EXCEEDED.captures(&stderr) {
+            Error::QuotaExceeded(PathBuf::from(&caps[1]))
+        } else if let Some(caps) = RE_ORIGIN_NOT_FOUND.captures(&stderr) {
+            Error::DatasetNotFound(PathBuf::from(&caps[1]))
+        } else if RE_PERMISSION_DENIED.is_match(&stderr) {
+            Error::PermissionDenied
         } else {
             Self::unknown_so_far(stderr)
         }
@@ -85,16 +238,30 @@ impl Error {
 pub enum ErrorKind {
     CmdNotFound,
     LZCInitializationFailed,
+    ZfsModuleNotLoaded,
     NvOpError,
     InvalidInput,
     Io,
+    PermissionDenied,
+    Cancelled,
     Unknown,
     DatasetNotFound,
+    DatasetBusy,
+    ShareNotEnabled,
+    UnsupportedVersion,
+    SnapshotsHeld,
+    IncrementalSourceMismatch,
+    DestinationModified,
+    OutOfSpace,
+    QuotaExceeded,
+    NoSnapshots,
+    BookmarkFromBookmarkUnsupported,
     ValidationErrors,
     Unimplemented,
     MultiOpError,
     ChanProgInval,
     ChanProgRuntime,
+    NvConversion,
 }
 
 impl PartialEq for Error {
@@ -113,6 +280,80 @@ quick_error! {
         MissingName(dataset: PathBuf) {}
         MissingSnapshotName(dataset: PathBuf) {}
         MissingPool(dataset: PathBuf) {}
+        /// Snapshot is expected, but there is no `@` in the name.
+        MissingAtSign(dataset: PathBuf) {}
+        /// Name contains a character ZFS doesn't allow. Allowed are alphanumeric characters and
+        /// `-`, `_`, `.`, `:` and space.
+        InvalidCharacter(dataset: PathBuf, character: char) {
+            display("invalid character {:?} in {}", character, dataset.display())
+        }
+        /// Name uses reserved component (`.` or `..`) or pool name (`log`, `mirror*`, `raidz*`,
+        /// `draid*`, `spare*`).
+        ReservedName(dataset: PathBuf) {}
+        /// Trying to create unencrypted dataset under encrypted parent.
+        UnencryptedChild(dataset: PathBuf) {}
+        /// Volume is missing `volume_size`.
+        MissingVolumeSize(dataset: PathBuf) {}
+        /// Volume only property (`volume_size`, `volume_block_size`) set on filesystem.
+        VolumePropertyOnFilesystem(dataset: PathBuf) {}
+        /// `volume_block_size` is not a power of two between 512 bytes and 128 KiB.
+        InvalidVolumeBlockSize(dataset: PathBuf, size: u64) {
+            display("invalid volume block size {} for {}: must be a power of two between 512 and \
+                     131072", size, dataset.display())
+        }
+        /// `record_size` is not a power of two between 512 bytes and 16 MiB.
+        InvalidRecordSize(dataset: PathBuf, size: u64) {
+            display("invalid record size {} for {}: must be a power of two between 512 and \
+                     16777216", size, dataset.display())
+        }
+        /// Filesystem only property (`version`, `can_mount`, `mount_point`, `overlay`) set on
+        /// volume.
+        FilesystemPropertyOnVolume(dataset: PathBuf) {}
+        /// Requested filesystem `version` doesn't exist.
+        InvalidVersion(dataset: PathBuf, version: u64) {
+            display("invalid filesystem version {} for {}: must be between 1 and 5", version,
+                    dataset.display())
+        }
+        /// `mount_point` is a relative path.
+        RelativeMountPoint(dataset: PathBuf, mount_point: PathBuf) {
+            display("mount point {} of {} must be an absolute path", mount_point.display(),
+                    dataset.display())
+        }
+        /// Bookmark is expected, but there is no `#` in the name.
+        MissingHashSign(dataset: PathBuf) {}
+        /// Bookmark doesn't belong to the dataset of the snapshot.
+        BookmarkOfOtherDataset(bookmark: PathBuf, snapshot: PathBuf) {
+            display("{} isn't a bookmark of the dataset of {}", bookmark.display(),
+                    snapshot.display())
+        }
+        /// Snapshot doesn't belong to the dataset it's expected to.
+        SnapshotOfOtherDataset(snapshot: PathBuf, dataset: PathBuf) {
+            display("{} isn't a snapshot of {}", snapshot.display(), dataset.display())
+        }
+        /// Bookmark source is neither a snapshot nor a bookmark.
+        InvalidBookmarkSource(dataset: PathBuf) {}
+        /// Operation only makes sense for volumes.
+        NotAVolume(dataset: PathBuf) {}
+        /// New `volume_size` is not a multiple of `volume_block_size`.
+        UnalignedVolumeSize(dataset: PathBuf, size: u64, block_size: u64) {
+            display("volume size {} for {} is not a multiple of volume block size {}", size,
+                    dataset.display(), block_size)
+        }
+        /// New `volume_size` is smaller than data already referenced by the volume.
+        VolumeSizeBelowReferenced(dataset: PathBuf, size: u64, referenced: u64) {
+            display("can't shrink {} to {} bytes: {} bytes are referenced", dataset.display(),
+                    size, referenced)
+        }
+        /// Required field of a builder is not set.
+        UninitializedField(field: &'static str) {
+            display("{} must be set", field)
+        }
         Unknown(dataset: PathBuf) {}
     }
 }
+
+impl From<derive_builder::UninitializedFieldError> for ValidationError {
+    fn from(err: derive_builder::UninitializedFieldError) -> ValidationError {
+        ValidationError::UninitializedField(err.field_name())
+    }
+}
@@ -1,19 +1,22 @@
-use crate::{zfs::{BookmarkRequest, Checksum, Compression, Copies, CreateDatasetRequest,
-                  DatasetKind, DestroyTiming, Error, Result, SendFlags, SnapDir, ValidationError,
-                  ZfsEngine},
+use crate::{observe,
+            zfs::{BookmarkRequest, CanMount, Checksum, Compression, Copies, CreateDatasetRequest,
+                  DatasetKind, DestroyTiming, Encryption, Error, Result, SendFlags, SnapDir,
+                  ValidationError, ZfsEngine},
             GlobalLogger};
-use cstr_argument::CStrArgument;
 use libnv::nvpair::NvList;
 use slog::Logger;
 
 use crate::zfs::{errors::Error::ValidationErrors,
                  properties::{AclInheritMode, AclMode, ZfsProp},
-                 PathExt};
+                 validators, PathExt};
 use std::{collections::HashMap,
           ffi::CString,
+          fmt::Debug,
           os::unix::io::{AsRawFd, RawFd},
-          path::PathBuf,
-          ptr::null_mut};
+          path::{Path, PathBuf},
+          ptr::null_mut,
+          sync::{Arc, Mutex, PoisonError, Weak},
+          time::Instant};
 use zfs_core_sys as sys;
 
 #[cfg(target_os = "freebsd")]
@@ -21,28 +24,114 @@ const ECHRNG: libc::c_int = libc::ENXIO;
 #[cfg(target_os = "linux")]
 const ECHRNG: libc::c_int = libc::ECHRNG;
 
+/// Convert path to C string. Paths that aren't valid UTF-8 or have NUL inside are invalid input.
+fn path_to_cstring(path: &Path) -> Result<CString> {
+    let path = path.to_str().ok_or_else(Error::invalid_input)?;
+    CString::new(path).map_err(|_| Error::invalid_input())
+}
+
+lazy_static! {
+    static ref LZC_HANDLE: Mutex<Weak<LzcHandle>> = Mutex::new(Weak::new());
+}
+
+/// Initialized libzfs_core. Shared by all `ZfsLzc` instances, `libzfs_core_fini` is called when
+/// the last one is dropped.
+#[derive(Debug)]
+struct LzcHandle;
+
+impl LzcHandle {
+    /// Get existing handle or initialize libzfs_core if there is none.
+    fn get() -> Result<Arc<LzcHandle>> {
+        let mut shared = LZC_HANDLE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(handle) = shared.upgrade() {
+            return Ok(handle);
+        }
+        let errno = unsafe { sys::libzfs_core_init() };
+        if errno != 0 {
+            return Err(Error::from_init_errno(errno));
+        }
+        let handle = Arc::new(LzcHandle);
+        *shared = Arc::downgrade(&handle);
+        Ok(handle)
+    }
+}
+
+impl Drop for LzcHandle {
+    fn drop(&mut self) {
+        // Hold the lock, so `get` can't init while fini is in progress.
+        let _shared = LZC_HANDLE.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe { sys::libzfs_core_fini() };
+    }
+}
+
+/// libzfs_core backed ZfsEngine.
+///
+/// libzfs_core is initialized once per process: the first `ZfsLzc::new()` does it, others (and
+/// clones) share it, so creating an engine per request is cheap. `libzfs_core_fini` is called
+/// after the last instance is dropped. `ZfsLzc` is `Send` and `Sync`, libzfs_core calls are
+/// independent ioctls on a shared descriptor, so single instance can be used from many threads
+/// at once.
 #[derive(Debug, Clone)]
 pub struct ZfsLzc {
-    logger: Logger,
+    logger:  Logger,
+    _handle: Arc<LzcHandle>,
 }
 
 impl ZfsLzc {
-    /// Initialize libzfs_core backed ZfsEngine.
+    /// Initialize libzfs_core backed ZfsEngine, unless it's already initialized. Fails with
+    /// [`Error::ZfsModuleNotLoaded`](../enum.Error.html#variant.ZfsModuleNotLoaded) if there is no
+    /// `/dev/zfs`.
     /// If root logger is None, then StdLog drain used.
     pub fn new() -> Result<Self> {
-        let errno = unsafe { sys::libzfs_core_init() };
-
-        if errno != 0 {
-            let io_error = std::io::Error::from_raw_os_error(errno);
-            return Err(Error::LZCInitializationFailed(io_error));
-        }
+        let handle = LzcHandle::get()?;
         let logger = GlobalLogger::get().new(o!("zetta_module" => "zfs", "zfs_impl" => "lzc"));
 
-        Ok(ZfsLzc { logger })
+        Ok(ZfsLzc { logger, _handle: handle })
     }
 
     pub fn logger(&self) -> &Logger { &self.logger }
 
+    /// Create missing ancestors of `name` (except the pool) as filesystems with default
+    /// properties, topmost first.
+    fn create_parents(&self, name: &Path) -> Result<()> {
+        let mut missing = Vec::new();
+        for parent in name.ancestors().skip(1).filter(|p| p.parent() != Some(Path::new(""))) {
+            if parent == Path::new("") || self.exists(parent)? {
+                break;
+            }
+            missing.push(parent);
+        }
+        for parent in missing.into_iter().rev() {
+            let request = CreateDatasetRequest {
+                name: parent.to_path_buf(),
+                kind: DatasetKind::Filesystem,
+                ..CreateDatasetRequest::default()
+            };
+            self.create(request)?;
+        }
+        Ok(())
+    }
+
+    /// Call libzfs_core function that returns errno. Logs the call and reports it to
+    /// observability hook.
+    fn call<F: FnOnce() -> libc::c_int>(
+        &self,
+        operation: &str,
+        target: &dyn Debug,
+        call: F,
+    ) -> libc::c_int {
+        trace!(self.logger, "calling"; "op" => operation, "target" => format_args!("{:?}", target));
+        let started = Instant::now();
+        let errno = call();
+        debug!(self.logger, "called";
+            "op" => operation,
+            "target" => format_args!("{:?}", target),
+            "errno" => errno,
+            "elapsed" => format_args!("{:?}", started.elapsed()));
+        observe::finished(operation, started, errno == 0);
+        errno
+    }
+
     fn send(
         &self,
         path: PathBuf,
@@ -50,24 +139,20 @@ impl ZfsLzc {
         fd: RawFd,
         flags: SendFlags,
     ) -> Result<()> {
-        let snapshot =
-            CString::new(path.to_str().unwrap()).expect("Failed to create CString from path");
+        let snapshot = path_to_cstring(&path)?;
         let snapshot_ptr = snapshot.as_ptr();
-        let from_cstr = from.map(|f| {
-            CString::new(f.to_str().unwrap()).expect("Failed to create CString from path")
-        });
+        let from_cstr = from.as_deref().map(path_to_cstring).transpose()?;
         let fd_raw = fd;
-        let errno = if let Some(src) = from_cstr {
-            unsafe { zfs_core_sys::lzc_send(snapshot_ptr, src.as_ptr(), fd_raw, flags.bits) }
-        } else {
-            unsafe { zfs_core_sys::lzc_send(snapshot_ptr, std::ptr::null(), fd_raw, flags.bits) }
-        };
+        let from_ptr = from_cstr.as_ref().map_or(std::ptr::null(), |src| src.as_ptr());
+        let errno = self.call("lzc_send", &path, || unsafe {
+            zfs_core_sys::lzc_send(snapshot_ptr, from_ptr, fd_raw, flags.bits)
+        });
 
         match errno {
             0 => Ok(()),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -76,8 +161,11 @@ impl ZfsLzc {
 impl ZfsEngine for ZfsLzc {
     fn exists<N: Into<PathBuf>>(&self, name: N) -> Result<bool> {
         let path = name.into();
-        let n = path.to_str().expect("Invalid Path").into_cstr();
-        let ret = unsafe { sys::lzc_exists(n.as_ref().as_ptr()) };
+        let n = path_to_cstring(&path)?;
+        let started = Instant::now();
+        let ret = unsafe { sys::lzc_exists(n.as_ptr()) };
+        trace!(self.logger, "called"; "op" => "lzc_exists", "target" => ?path, "exists" => ret);
+        observe::finished("lzc_exists", started, true);
 
         if ret == 1 {
             Ok(true)
@@ -88,11 +176,14 @@ impl ZfsEngine for ZfsLzc {
 
     fn create(&self, request: CreateDatasetRequest) -> Result<()> {
         request.validate()?;
+        request.validate_encryption(self)?;
+        if request.create_parents {
+            self.create_parents(request.name())?;
+        }
 
         //let mut props = nvpair::NvList::new()?;
         let mut props = NvList::default();
-        let name_c_string =
-            CString::new(request.name().to_str().expect("Non UTF-8 name")).expect("NULL in name");
+        let name_c_string = path_to_cstring(request.name())?;
         // LZC wants _everything_ as u64 even booleans.
         if let Some(acl_inherit) = request.acl_inherit {
             props.insert_u64(AclInheritMode::nv_key(), acl_inherit.as_nv_value())?;
@@ -103,6 +194,9 @@ impl ZfsEngine for ZfsLzc {
         if let Some(atime) = request.atime {
             props.insert_u64("atime", bool_to_u64(atime))?;
         }
+        if let Some(can_mount) = request.can_mount {
+            props.insert_u64(CanMount::nv_key(), can_mount.as_nv_value())?;
+        }
         if let Some(checksum) = request.checksum {
             props.insert_u64(Checksum::nv_key(), checksum.as_nv_value())?;
         }
@@ -115,10 +209,30 @@ impl ZfsEngine for ZfsLzc {
         if let Some(devices) = request.devices {
             props.insert_u64("devices", bool_to_u64(devices))?;
         }
+        if let Some(encryption) = request.encryption {
+            props.insert_u64(Encryption::nv_key(), encryption.as_nv_value())?;
+        }
         if let Some(exec) = request.exec {
             props.insert_u64("exec", bool_to_u64(exec))?;
         }
-        // saved fore mount point
+        if let Some(context) = request.context() {
+            props.insert_string("context", context)?;
+        }
+        if let Some(fs_context) = request.fs_context() {
+            props.insert_string("fscontext", fs_context)?;
+        }
+        if let Some(def_context) = request.def_context() {
+            props.insert_string("defcontext", def_context)?;
+        }
+        if let Some(root_context) = request.root_context() {
+            props.insert_string("rootcontext", root_context)?;
+        }
+        if let Some(mount_point) = request.mount_point() {
+            props.insert_string("mountpoint", &mount_point.as_nv_value())?;
+        }
+        if let Some(overlay) = request.overlay {
+            props.insert_u64("overlay", bool_to_u64(overlay))?;
+        }
         if let Some(primary_cache) = request.primary_cache {
             props.insert_u64("primarycache", primary_cache.as_nv_value())?;
         }
@@ -147,16 +261,6 @@ impl ZfsEngine for ZfsLzc {
             props.insert_u64(SnapDir::nv_key(), snap_dir.as_nv_value())?;
         }
 
-        if request.kind == DatasetKind::Filesystem
-            && (request.volume_size.is_some() || request.volume_block_size.is_some())
-        {
-            return Err(Error::invalid_input());
-        }
-
-        if request.kind == DatasetKind::Volume && request.volume_size.is_none() {
-            return Err(Error::invalid_input());
-        }
-
         if let Some(vol_size) = request.volume_size {
             props.insert_u64("volsize", vol_size)?;
         }
@@ -164,6 +268,9 @@ impl ZfsEngine for ZfsLzc {
             props.insert_u64("volblocksize", vol_block_size)?;
         }
 
+        if let Some(version) = request.version {
+            props.insert_u64("version", version)?;
+        }
         if let Some(xattr) = request.xattr {
             props.insert("xattr", bool_to_u64(xattr))?;
         }
@@ -172,22 +279,23 @@ impl ZfsEngine for ZfsLzc {
                 props.insert_string(key, value)?;
             }
         }
-        let errno = unsafe {
+        let errno = self.call("lzc_create", request.name(), || unsafe {
             zfs_core_sys::lzc_create(
-                name_c_string.as_ref().as_ptr(),
+                name_c_string.as_ptr(),
                 request.kind().as_c_uint(),
                 props.as_ptr(),
                 std::ptr::null_mut(),
                 0,
             )
-        };
+        });
 
-        match errno {
-            0 => Ok(()),
-            _ => {
-                let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+        match (errno, request.version) {
+            (0, _) => Ok(()),
+            (libc::ENOTSUP, Some(version)) => {
+                Err(Error::UnsupportedVersion(request.name().to_owned(), version))
             },
+            _ => Err(Error::from_space_errno(errno, request.name())
+                .unwrap_or_else(|| Error::from(std::io::Error::from_raw_os_error(errno)))),
         }
     }
 
@@ -197,7 +305,7 @@ impl ZfsEngine for ZfsLzc {
         user_properties: Option<HashMap<String, String>>,
     ) -> Result<()> {
         let validation_errors: Vec<ValidationError> =
-            snapshots.iter().map(PathBuf::validate).filter_map(Result::err).collect();
+            snapshots.iter().map(PathBuf::validate_snapshot).filter_map(Result::err).collect();
         if !validation_errors.is_empty() {
             return Err(ValidationErrors(validation_errors));
         }
@@ -213,24 +321,35 @@ impl ZfsEngine for ZfsLzc {
                 props.insert_string(&key, &value)?;
             }
         }
-        let errno = unsafe {
+        let errno = self.call("lzc_snapshot", &snapshots, || unsafe {
             zfs_core_sys::lzc_snapshot(
                 snapshots_list.as_ptr(),
                 props.as_ptr(),
                 &mut errors_list_ptr,
             )
-        };
+        });
+        let mut errors = HashMap::new();
         if !errors_list_ptr.is_null() {
-            let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
-            if !errors.is_empty() {
-                return Err(Error::from(errors.into_hashmap()));
-            }
+            errors = unsafe { NvList::from_ptr(errors_list_ptr) }.into_hashmap();
+        }
+        // Snapshots are created atomically, so running out of space fails all of them. Report the
+        // one kernel complained about.
+        let failed = errors.keys().next().map(PathBuf::from);
+        if let Some(err) = failed
+            .as_ref()
+            .or_else(|| snapshots.first())
+            .and_then(|snapshot| Error::from_space_errno(errno, snapshot))
+        {
+            return Err(err);
+        }
+        if !errors.is_empty() {
+            return Err(Error::from(errors));
         }
         match errno {
             0 => Ok(()),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -238,8 +357,9 @@ impl ZfsEngine for ZfsLzc {
     fn bookmark(&self, bookmarks: &[BookmarkRequest]) -> Result<()> {
         let validation_errors: Vec<ValidationError> = bookmarks
             .iter()
-            .flat_map(|BookmarkRequest { snapshot, bookmark }| vec![snapshot, bookmark])
-            .map(PathBuf::validate)
+            .flat_map(|BookmarkRequest { snapshot, bookmark }| {
+                vec![validators::validate_bookmark_source(snapshot), bookmark.validate()]
+            })
             .filter_map(Result::err)
             .collect();
         if !validation_errors.is_empty() {
@@ -253,8 +373,15 @@ impl ZfsEngine for ZfsLzc {
         }
 
         let mut errors_list_ptr = null_mut();
-        let errno =
-            unsafe { zfs_core_sys::lzc_bookmark(bookmarks_list.as_ptr(), &mut errors_list_ptr) };
+        let errno = self.call("lzc_bookmark", &bookmarks, || unsafe {
+            zfs_core_sys::lzc_bookmark(bookmarks_list.as_ptr(), &mut errors_list_ptr)
+        });
+        if errno == libc::ENOTSUP && bookmarks.iter().any(BookmarkRequest::is_from_bookmark) {
+            if !errors_list_ptr.is_null() {
+                drop(unsafe { NvList::from_ptr(errors_list_ptr) });
+            }
+            return Err(Error::BookmarkFromBookmarkUnsupported);
+        }
         if !errors_list_ptr.is_null() {
             let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
             if !errors.is_empty() {
@@ -265,7 +392,7 @@ impl ZfsEngine for ZfsLzc {
             0 => Ok(()),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -273,7 +400,7 @@ impl ZfsEngine for ZfsLzc {
     fn destroy_snapshots(&self, snapshots: &[PathBuf], timing: DestroyTiming) -> Result<()> {
         let validation_errors: Vec<ValidationError> = snapshots
             .iter()
-            .map(PathBuf::validate)
+            .map(PathBuf::validate_snapshot)
             .filter(Result::is_err)
             .map(Result::unwrap_err)
             .collect();
@@ -288,13 +415,13 @@ impl ZfsEngine for ZfsLzc {
         }
 
         let mut errors_list_ptr = null_mut();
-        let errno = unsafe {
+        let errno = self.call("lzc_destroy_snaps", &snapshots, || unsafe {
             zfs_core_sys::lzc_destroy_snaps(
                 snapshots_list.as_ptr(),
                 timing.as_c_uint(),
                 &mut errors_list_ptr,
             )
-        };
+        });
         if !errors_list_ptr.is_null() {
             let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
             if !errors.is_empty() {
@@ -305,7 +432,7 @@ impl ZfsEngine for ZfsLzc {
             0 => Ok(()),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -328,9 +455,9 @@ impl ZfsEngine for ZfsLzc {
         }
 
         let mut errors_list_ptr = null_mut();
-        let errno = unsafe {
+        let errno = self.call("lzc_destroy_bookmarks", &bookmarks, || unsafe {
             zfs_core_sys::lzc_destroy_bookmarks(bookmarks_list.as_ptr(), &mut errors_list_ptr)
-        };
+        });
         if !errors_list_ptr.is_null() {
             let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
             if !errors.is_empty() {
@@ -341,7 +468,7 @@ impl ZfsEngine for ZfsLzc {
             0 => Ok(()),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -365,6 +492,33 @@ impl ZfsEngine for ZfsLzc {
         self.send(path.into(), Some(from.into()), fd.as_raw_fd(), flags)
     }
 
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+    ) -> Result<u64> {
+        let path = path.into();
+        let snapshot = path_to_cstring(&path)?;
+        let from_cstr = from.as_deref().map(path_to_cstring).transpose()?;
+        let from_ptr = from_cstr.as_ref().map_or(std::ptr::null(), |f| f.as_ptr());
+        let mut space = 0;
+        let errno = self.call("lzc_send_space", &path, || unsafe {
+            zfs_core_sys::lzc_send_space(snapshot.as_ptr(), from_ptr, flags.bits, &mut space)
+        });
+
+        match errno {
+            0 => {
+                observe::stream_bytes("lzc_send_space", space);
+                Ok(space)
+            },
+            _ => {
+                let io_error = std::io::Error::from_raw_os_error(errno);
+                Err(Error::from(io_error))
+            },
+        }
+    }
+
     fn run_channel_program<N: Into<PathBuf>>(
         &self,
         pool: N,
@@ -375,15 +529,15 @@ impl ZfsEngine for ZfsLzc {
         args: NvList,
     ) -> Result<NvList> {
         let pool = pool.into();
-        let pool_c_string = pool.to_str().expect("Non UTF-8 pool name").into_cstr();
-        let prog_c_string = program.into_cstr();
+        let pool_c_string = path_to_cstring(&pool)?;
+        let prog_c_string = CString::new(program).map_err(|_| Error::invalid_input())?;
 
         let mut out_nvlist_ptr = null_mut();
-        let errno = unsafe {
+        let errno = self.call("lzc_channel_program", &pool, || unsafe {
             if sync {
                 zfs_core_sys::lzc_channel_program(
-                    pool_c_string.as_ref().as_ptr(),
-                    prog_c_string.as_ref().as_ptr(),
+                    pool_c_string.as_ptr(),
+                    prog_c_string.as_ptr(),
                     instr_limit,
                     mem_limit,
                     args.as_ptr(),
@@ -391,15 +545,15 @@ impl ZfsEngine for ZfsLzc {
                 )
             } else {
                 zfs_core_sys::lzc_channel_program_nosync(
-                    pool_c_string.as_ref().as_ptr(),
-                    prog_c_string.as_ref().as_ptr(),
+                    pool_c_string.as_ptr(),
+                    prog_c_string.as_ptr(),
                     instr_limit,
                     mem_limit,
                     args.as_ptr(),
                     &mut out_nvlist_ptr,
                 )
             }
-        };
+        });
         match errno {
             0 => Ok(unsafe { NvList::from_ptr(out_nvlist_ptr) }),
             libc::EINVAL => Err(Error::ChanProgInval(
@@ -410,7 +564,7 @@ impl ZfsEngine for ZfsLzc {
             )),
             _ => {
                 let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+                Err(Error::from(io_error))
             },
         }
     }
@@ -424,3 +578,20 @@ fn bool_to_u64(src: bool) -> u64 {
         0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    #[test]
+    fn test_path_to_cstring() {
+        let cstring = path_to_cstring(Path::new("tank/data@snap")).unwrap();
+        assert_eq!(b"tank/data@snap", cstring.as_bytes());
+
+        let non_utf8 = Path::new(OsStr::from_bytes(b"tank/\xff"));
+        assert_eq!(Error::invalid_input(), path_to_cstring(non_utf8).unwrap_err());
+        let with_nul = Path::new("tank/da\0ta");
+        assert_eq!(Error::invalid_input(), path_to_cstring(with_nul).unwrap_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! In-memory [`ZfsEngine`](../trait.ZfsEngine.html) to test code that uses the trait without ZFS
+//! installed and without root. Test only: nothing touches the kernel and nothing outlives the
+//! engine. Available with `mock` feature.
+//!
+//! Filesystems, volumes, snapshots and bookmarks are tracked by name. Errors mirror what
+//! [`DelegatingZfsEngine`](../struct.DelegatingZfsEngine.html) returns for the same mistake:
+//! creating something that exists is `EEXIST`, creating under a missing parent is `ENOENT` (see
+//! [`Error::is_not_found`](../enum.Error.html#method.is_not_found)), listing or destroying a
+//! missing dataset is `DatasetNotFound`. Operations the model can't answer (sending, space
+//! accounting, properties) return `Unimplemented`.
+//!
+//! ```rust
+//! use libzetta::zfs::{mock::MockZfs, CreateDatasetRequest, ZfsEngine};
+//! use std::path::PathBuf;
+//!
+//! let zfs = MockZfs::new().with_pool("tank");
+//! zfs.create(CreateDatasetRequest::filesystem("tank/data").build().unwrap()).unwrap();
+//! zfs.snapshot(&[PathBuf::from("tank/data@first")], None).unwrap();
+//! assert_eq!(vec![PathBuf::from("tank/data@first")], zfs.list_snapshots("tank").unwrap());
+//! ```
+use std::{collections::{BTreeMap, HashMap},
+          io,
+          path::{Path, PathBuf},
+          sync::{Mutex, MutexGuard, PoisonError}};
+
+use crate::zfs::{BookmarkRequest, CreateDatasetRequest, DatasetKind, DestroyTiming, Encryption,
+                 Error, PathExt, Result, SnapshotInfo, ValidationError, ZfsEngine};
+
+#[derive(Debug, Clone)]
+struct MockDataset {
+    kind:            DatasetKind,
+    guid:            u64,
+    /// Value of the fake clock at the moment of creation.
+    creation:        u64,
+    encryption:      Encryption,
+    user_properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    datasets:  BTreeMap<PathBuf, MockDataset>,
+    /// Bookmark with GUID of the snapshot it was created from.
+    bookmarks: BTreeMap<PathBuf, u64>,
+    /// Ticks on every created dataset, so creation order is always strict.
+    clock:     u64,
+}
+
+impl State {
+    fn insert(&mut self, name: PathBuf, kind: DatasetKind) -> &mut MockDataset {
+        self.clock += 1;
+        let dataset = MockDataset {
+            kind,
+            guid: self.clock,
+            creation: self.clock,
+            encryption: Encryption::Off,
+            user_properties: HashMap::new(),
+        };
+        self.datasets.entry(name).or_insert(dataset)
+    }
+
+    fn kind_of(&self, name: &Path) -> Option<DatasetKind> {
+        if self.bookmarks.contains_key(name) {
+            return Some(DatasetKind::Bookmark);
+        }
+        self.datasets.get(name).map(|dataset| dataset.kind.clone())
+    }
+
+    /// Everything named `prefix` or below it, including snapshots and bookmarks.
+    fn is_under(name: &Path, prefix: &Path) -> bool {
+        let name = name.to_string_lossy();
+        let prefix = prefix.to_string_lossy();
+        name == prefix
+            || [b'/', b'@', b'#']
+                .iter()
+                .any(|sep| name.starts_with(&*prefix) && name.as_bytes()[prefix.len()] == *sep)
+    }
+
+    fn list<F>(&self, prefix: &Path, filter: F) -> Result<Vec<PathBuf>>
+    where
+        F: Fn(&DatasetKind) -> bool,
+    {
+        if !self.datasets.contains_key(prefix) {
+            return Err(Error::DatasetNotFound(prefix.to_path_buf()));
+        }
+        Ok(self
+            .datasets
+            .iter()
+            .filter(|(name, dataset)| Self::is_under(name, prefix) && filter(&dataset.kind))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    /// Snapshots of the dataset, oldest first.
+    fn snapshots_of(&self, dataset: &Path) -> Vec<(&PathBuf, &MockDataset)> {
+        let mut snapshots: Vec<_> = self
+            .datasets
+            .iter()
+            .filter(|(name, snapshot)| {
+                snapshot.kind == DatasetKind::Snapshot && dataset_of(name) == dataset
+            })
+            .collect();
+        snapshots.sort_by_key(|(_, snapshot)| snapshot.creation);
+        snapshots
+    }
+}
+
+/// Filesystem or volume snapshot (or bookmark) belongs to.
+fn dataset_of(name: &Path) -> PathBuf {
+    let name = name.to_string_lossy();
+    PathBuf::from(name.split(|c| c == '@' || c == '#').next().unwrap_or_default())
+}
+
+fn os_error(errno: i32) -> Error { Error::from(io::Error::from_raw_os_error(errno)) }
+
+/// In-memory implementation of `ZfsEngine`. See [module documentation](index.html).
+#[derive(Debug, Default)]
+pub struct MockZfs {
+    state: Mutex<State>,
+}
+
+impl MockZfs {
+    /// Empty engine without pools.
+    pub fn new() -> Self { MockZfs::default() }
+
+    /// Add a pool, that is a root filesystem datasets can be created under.
+    pub fn with_pool<N: Into<PathBuf>>(self, name: N) -> Self {
+        self.state().insert(name.into(), DatasetKind::Filesystem);
+        self
+    }
+
+    /// User properties given to [`snapshot`](../trait.ZfsEngine.html#method.snapshot). `None` if
+    /// there is no such snapshot.
+    pub fn user_properties<N: Into<PathBuf>>(&self, name: N) -> Option<HashMap<String, String>> {
+        self.state().datasets.get(&name.into()).map(|dataset| dataset.user_properties.clone())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ZfsEngine for MockZfs {
+    fn exists<N: Into<PathBuf>>(&self, name: N) -> Result<bool> {
+        Ok(self.state().kind_of(&name.into()).is_some())
+    }
+
+    fn kind_of<N: Into<PathBuf>>(&self, name: N) -> Result<Option<DatasetKind>> {
+        Ok(self.state().kind_of(&name.into()))
+    }
+
+    fn create(&self, request: CreateDatasetRequest) -> Result<()> {
+        request.validate()?;
+        request.validate_encryption(self)?;
+        let mut state = self.state();
+        let name = request.name();
+        if state.datasets.contains_key(name) {
+            return Err(os_error(libc::EEXIST));
+        }
+        let mut missing = Vec::new();
+        let mut parent = name.parent();
+        while let Some(dir) = parent.filter(|dir| state.kind_of(dir).is_none()) {
+            missing.push(dir.to_path_buf());
+            parent = dir.parent();
+        }
+        let inherited = match parent.and_then(|parent| state.datasets.get(parent)) {
+            Some(dataset) if dataset.kind == DatasetKind::Filesystem => dataset.encryption,
+            _ => return Err(os_error(libc::ENOENT)),
+        };
+        if !missing.is_empty() && !request.create_parents() {
+            return Err(os_error(libc::ENOENT));
+        }
+        for dir in missing.into_iter().rev() {
+            state.insert(dir, DatasetKind::Filesystem).encryption = inherited;
+        }
+        let encryption = match request.encryption() {
+            None | Some(Encryption::Inherit) => inherited,
+            Some(encryption) => *encryption,
+        };
+        state.insert(name.clone(), request.kind().clone()).encryption = encryption;
+        Ok(())
+    }
+
+    fn snapshot(
+        &self,
+        snapshots: &[PathBuf],
+        user_properties: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        let errors: Vec<ValidationError> =
+            snapshots.iter().map(PathBuf::validate_snapshot).filter_map(Result::err).collect();
+        if !errors.is_empty() {
+            return Err(Error::ValidationErrors(errors));
+        }
+        let mut state = self.state();
+        // All or nothing, same as the real thing.
+        for snapshot in snapshots {
+            match state.kind_of(&dataset_of(snapshot)) {
+                Some(DatasetKind::Filesystem) | Some(DatasetKind::Volume) => {},
+                _ => return Err(os_error(libc::ENOENT)),
+            }
+            if state.datasets.contains_key(snapshot) {
+                return Err(os_error(libc::EEXIST));
+            }
+        }
+        for snapshot in snapshots {
+            let created = state.insert(snapshot.clone(), DatasetKind::Snapshot);
+            created.user_properties = user_properties.clone().unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    fn bookmark(&self, bookmarks: &[BookmarkRequest]) -> Result<()> {
+        let mut state = self.state();
+        let mut guids = Vec::with_capacity(bookmarks.len());
+        for BookmarkRequest { snapshot, bookmark } in bookmarks {
+            let guid = match (state.datasets.get(snapshot), state.bookmarks.get(snapshot)) {
+                (Some(source), _) if source.kind == DatasetKind::Snapshot => source.guid,
+                (None, Some(guid)) => *guid,
+                _ => return Err(os_error(libc::ENOENT)),
+            };
+            if !bookmark.is_bookmark() || dataset_of(bookmark) != dataset_of(snapshot) {
+                return Err(os_error(libc::EINVAL));
+            }
+            if state.bookmarks.contains_key(bookmark) {
+                return Err(os_error(libc::EEXIST));
+            }
+            guids.push(guid);
+        }
+        for (request, guid) in bookmarks.iter().zip(guids) {
+            state.bookmarks.insert(request.bookmark.clone(), guid);
+        }
+        Ok(())
+    }
+
+    fn destroy<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let name = name.into();
+        let mut state = self.state();
+        if !state.datasets.contains_key(&name) {
+            return Err(Error::DatasetNotFound(name));
+        }
+        let has_dependents =
+            state.datasets.keys().any(|other| other != &name && State::is_under(other, &name));
+        if has_dependents {
+            return Err(Error::DatasetBusy(name));
+        }
+        state.datasets.remove(&name);
+        state.bookmarks.retain(|bookmark, _| dataset_of(bookmark) != name);
+        Ok(())
+    }
+
+    fn destroy_snapshots(&self, snapshots: &[PathBuf], _timing: DestroyTiming) -> Result<()> {
+        let errors: Vec<ValidationError> =
+            snapshots.iter().map(PathBuf::validate_snapshot).filter_map(Result::err).collect();
+        if !errors.is_empty() {
+            return Err(Error::ValidationErrors(errors));
+        }
+        let mut state = self.state();
+        // Missing snapshots are ignored, the same way libzfs_core does.
+        for snapshot in snapshots {
+            state.datasets.remove(snapshot);
+        }
+        Ok(())
+    }
+
+    fn destroy_snapshot_range<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        first: Option<&str>,
+        last: Option<&str>,
+        timing: DestroyTiming,
+    ) -> Result<()> {
+        let dataset = dataset.into();
+        let range: Vec<PathBuf> = {
+            let state = self.state();
+            if !state.datasets.contains_key(&dataset) {
+                return Err(Error::DatasetNotFound(dataset));
+            }
+            let snapshots: Vec<PathBuf> =
+                state.snapshots_of(&dataset).into_iter().map(|(name, _)| name.clone()).collect();
+            let position = |name: Option<&str>, default: usize| match name {
+                None => Ok(default),
+                Some(name) => {
+                    let snapshot = PathBuf::from(format!("{}@{}", dataset.display(), name));
+                    snapshots
+                        .iter()
+                        .position(|candidate| candidate == &snapshot)
+                        .ok_or(Error::DatasetNotFound(snapshot))
+                },
+            };
+            let start = position(first, 0)?;
+            let end = position(last, snapshots.len().saturating_sub(1))?;
+            snapshots.into_iter().skip(start).take((end + 1).saturating_sub(start)).collect()
+        };
+        self.destroy_snapshots(&range, timing)
+    }
+
+    fn destroy_bookmarks(&self, bookmarks: &[PathBuf]) -> Result<()> {
+        let mut state = self.state();
+        for bookmark in bookmarks {
+            state.bookmarks.remove(bookmark);
+        }
+        Ok(())
+    }
+
+    fn list<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<(DatasetKind, PathBuf)>> {
+        let pool = pool.into();
+        let state = self.state();
+        let mut names = state.list(&pool, |_| true)?;
+        names.extend(state.bookmarks.keys().filter(|name| State::is_under(name, &pool)).cloned());
+        names.sort();
+        Ok(names
+            .into_iter()
+            .filter_map(|name| state.kind_of(&name).map(|kind| (kind, name)))
+            .collect())
+    }
+
+    fn list_filesystems<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
+        self.state().list(&pool.into(), |kind| kind == &DatasetKind::Filesystem)
+    }
+
+    fn list_snapshots<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
+        self.state().list(&pool.into(), |kind| kind == &DatasetKind::Snapshot)
+    }
+
+    fn list_bookmarks<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
+        let pool = pool.into();
+        let state = self.state();
+        if !state.datasets.contains_key(&pool) {
+            return Err(Error::DatasetNotFound(pool));
+        }
+        Ok(state.bookmarks.keys().filter(|name| State::is_under(name, &pool)).cloned().collect())
+    }
+
+    fn list_volumes<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
+        self.state().list(&pool.into(), |kind| kind == &DatasetKind::Volume)
+    }
+
+    fn encryption<N: Into<PathBuf>>(&self, name: N) -> Result<Encryption> {
+        let name = name.into();
+        match self.state().datasets.get(&name) {
+            Some(dataset) if dataset.kind != DatasetKind::Snapshot => Ok(dataset.encryption),
+            _ => Err(Error::DatasetNotFound(name)),
+        }
+    }
+
+    fn snapshot_info<N: Into<PathBuf>>(&self, snapshot: N) -> Result<SnapshotInfo> {
+        let snapshot = snapshot.into();
+        let state = self.state();
+        match state.datasets.get(&snapshot) {
+            Some(info) if info.kind == DatasetKind::Snapshot => Ok(to_info(&snapshot, info)),
+            _ => Err(Error::DatasetNotFound(snapshot)),
+        }
+    }
+
+    fn list_snapshots_by_creation<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        recursive: bool,
+    ) -> Result<Vec<SnapshotInfo>> {
+        let dataset = dataset.into();
+        let state = self.state();
+        if !state.datasets.contains_key(&dataset) {
+            return Err(Error::DatasetNotFound(dataset));
+        }
+        let mut snapshots: Vec<(&PathBuf, &MockDataset)> = if recursive {
+            state
+                .datasets
+                .iter()
+                .filter(|(name, snapshot)| {
+                    snapshot.kind == DatasetKind::Snapshot && State::is_under(name, &dataset)
+                })
+                .collect()
+        } else {
+            state.snapshots_of(&dataset)
+        };
+        snapshots.sort_by_key(|(_, snapshot)| snapshot.creation);
+        Ok(snapshots.into_iter().map(|(name, snapshot)| to_info(name, snapshot)).collect())
+    }
+
+    fn bookmark_guids<N: Into<PathBuf>>(&self, dataset: N) -> Result<HashMap<u64, PathBuf>> {
+        let dataset = dataset.into();
+        let state = self.state();
+        if !state.datasets.contains_key(&dataset) {
+            return Err(Error::DatasetNotFound(dataset));
+        }
+        let prefix = format!("{}#", dataset.display());
+        Ok(state
+            .bookmarks
+            .iter()
+            .filter(|(name, _)| name.to_string_lossy().starts_with(&prefix))
+            .map(|(name, guid)| (*guid, name.clone()))
+            .collect())
+    }
+}
+
+fn to_info(name: &Path, snapshot: &MockDataset) -> SnapshotInfo {
+    let values = [snapshot.creation.to_string(), snapshot.guid.to_string(), 0.to_string()];
+    let values = [&*values[0], &*values[1], &*values[2], &*values[2], &*values[2]];
+    SnapshotInfo::from_values(name.to_path_buf(), &values).expect("numbers are always valid")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zfs::ErrorKind;
+
+    fn filesystem(name: &str) -> CreateDatasetRequest {
+        CreateDatasetRequest::filesystem(name).build().unwrap()
+    }
+
+    #[test]
+    fn test_create_and_destroy() {
+        let zfs = MockZfs::new().with_pool("tank");
+        zfs.create(filesystem("tank/usr")).unwrap();
+        zfs.create(CreateDatasetRequest::volume("tank/usr/disk0", 1024 * 1024).build().unwrap())
+            .unwrap();
+        assert_eq!(Some(DatasetKind::Volume), zfs.kind_of("tank/usr/disk0").unwrap());
+
+        let exists = zfs.create(filesystem("tank/usr")).unwrap_err();
+        assert_eq!(ErrorKind::Io, exists.kind());
+        assert!(zfs.create(filesystem("tank/var/log")).unwrap_err().is_not_found());
+        assert!(zfs.create(filesystem("tank/usr/disk0/nope")).unwrap_err().is_not_found());
+        let invalid = zfs.create(filesystem("tank/usr*")).unwrap_err();
+        assert_eq!(ErrorKind::ValidationErrors, invalid.kind());
+
+        let expected = vec![
+            (DatasetKind::Filesystem, PathBuf::from("tank")),
+            (DatasetKind::Filesystem, PathBuf::from("tank/usr")),
+            (DatasetKind::Volume, PathBuf::from("tank/usr/disk0")),
+        ];
+        assert_eq!(expected, zfs.list("tank").unwrap());
+        assert_eq!(vec![PathBuf::from("tank/usr/disk0")], zfs.list_volumes("tank/usr").unwrap());
+        assert_eq!(ErrorKind::DatasetNotFound, zfs.list("dozer").unwrap_err().kind());
+
+        assert_eq!(ErrorKind::DatasetBusy, zfs.destroy("tank/usr").unwrap_err().kind());
+        zfs.destroy("tank/usr/disk0").unwrap();
+        zfs.destroy("tank/usr").unwrap();
+        assert_eq!(ErrorKind::DatasetNotFound, zfs.destroy("tank/usr").unwrap_err().kind());
+        assert!(!zfs.exists("tank/usr").unwrap());
+
+        let with_parents = |name: &str| {
+            CreateDatasetRequest::filesystem(name).create_parents(true).build().unwrap()
+        };
+        zfs.create(with_parents("tank/var/log")).unwrap();
+        assert_eq!(Some(DatasetKind::Filesystem), zfs.kind_of("tank/var").unwrap());
+        assert_eq!(Some(DatasetKind::Filesystem), zfs.kind_of("tank/var/log").unwrap());
+        assert!(zfs.create(with_parents("dozer/var")).unwrap_err().is_not_found());
+        assert!(!zfs.exists("dozer").unwrap());
+    }
+
+    #[test]
+    fn test_unencrypted_child() {
+        let zfs = MockZfs::new().with_pool("tank");
+        let with_encryption = |name: &str, encryption| {
+            CreateDatasetRequest::filesystem(name)
+                .encryption(encryption)
+                .create_parents(true)
+                .build()
+                .unwrap()
+        };
+        zfs.create(with_encryption("tank/plain", Encryption::Off)).unwrap();
+        zfs.create(with_encryption("tank/secret", Encryption::On)).unwrap();
+        zfs.create(filesystem("tank/secret/child")).unwrap();
+        assert_eq!(Encryption::On, zfs.encryption("tank/secret/child").unwrap());
+        assert_eq!(Encryption::Off, zfs.encryption("tank/plain").unwrap());
+
+        let expected = ValidationError::UnencryptedChild(PathBuf::from("tank/secret/leak"));
+        match zfs.create(with_encryption("tank/secret/leak", Encryption::Off)).unwrap_err() {
+            Error::ValidationErrors(errors) => assert_eq!(vec![expected], errors),
+            e => panic!("unexpected error: {}", e),
+        }
+        let nested = zfs.create(with_encryption("tank/secret/a/b", Encryption::Off)).unwrap_err();
+        assert_eq!(ErrorKind::ValidationErrors, nested.kind());
+        assert!(!zfs.exists("tank/secret/a").unwrap());
+        assert_eq!(ErrorKind::DatasetNotFound, zfs.encryption("tank/nope").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_snapshots_and_bookmarks() {
+        let zfs = MockZfs::new().with_pool("tank");
+        zfs.create(filesystem("tank/data")).unwrap();
+        zfs.create(filesystem("tank/data/child")).unwrap();
+        let snapshot = |name: &str| PathBuf::from(format!("tank/data@{}", name));
+
+        let mut props = HashMap::new();
+        props.insert(String::from("backup:kind"), String::from("daily"));
+        zfs.snapshot(&[snapshot("a"), snapshot("b")], Some(props.clone())).unwrap();
+        zfs.snapshot(&[PathBuf::from("tank/data/child@a"), snapshot("c")], None).unwrap();
+        assert_eq!(Some(props), zfs.user_properties(snapshot("a")));
+
+        let missing = zfs.snapshot(&[snapshot("d"), PathBuf::from("tank/nope@d")], None);
+        assert!(missing.unwrap_err().is_not_found());
+        assert!(!zfs.exists(snapshot("d")).unwrap());
+        let duplicate = zfs.snapshot(&[snapshot("a")], None).unwrap_err();
+        assert_eq!(ErrorKind::Io, duplicate.kind());
+
+        let names = |infos: Vec<SnapshotInfo>| -> Vec<PathBuf> {
+            infos.into_iter().map(|info| info.name().clone()).collect()
+        };
+        let own = zfs.list_snapshots_by_creation("tank/data", false).unwrap();
+        let guid_of_a = *own[0].guid();
+        assert_eq!(vec![snapshot("a"), snapshot("b"), snapshot("c")], names(own));
+        let all = zfs.list_snapshots_by_creation("tank/data", true).unwrap();
+        assert_eq!(4, all.len());
+        assert_eq!(Some(snapshot("c")), zfs.latest_snapshot("tank/data").unwrap());
+
+        let bookmark = PathBuf::from("tank/data#a");
+        let request = BookmarkRequest::new(snapshot("a"), bookmark.clone());
+        zfs.bookmark(&[request]).unwrap();
+        assert_eq!(vec![bookmark.clone()], zfs.list_bookmarks("tank").unwrap());
+        let bookmarks = zfs.bookmark_guids("tank/data").unwrap();
+        assert_eq!(Some(&bookmark), bookmarks.get(&guid_of_a));
+        assert!(zfs.bookmark_guids("tank").unwrap().is_empty());
+
+        zfs.destroy_snapshot_range("tank/data", Some("a"), Some("b"), DestroyTiming::RightNow)
+            .unwrap();
+        assert_eq!(
+            vec![PathBuf::from("tank/data/child@a"), snapshot("c")],
+            zfs.list_snapshots("tank").unwrap()
+        );
+        // Bookmark outlives the snapshot.
+        assert_eq!(vec![bookmark.clone()], zfs.list_bookmarks("tank").unwrap());
+        assert_eq!(Some(DatasetKind::Bookmark), zfs.kind_of(bookmark.clone()).unwrap());
+        assert!(zfs
+            .list("tank/data")
+            .unwrap()
+            .contains(&(DatasetKind::Bookmark, bookmark.clone())));
+        zfs.destroy_bookmarks(&[bookmark.clone()]).unwrap();
+        assert!(zfs.list_bookmarks("tank").unwrap().is_empty());
+        assert!(!zfs.exists(bookmark).unwrap());
+
+        zfs.destroy_snapshots(&[snapshot("c"), snapshot("gone")], DestroyTiming::RightNow).unwrap();
+        assert_eq!(
+            ErrorKind::DatasetNotFound,
+            zfs.snapshot_info(snapshot("c")).unwrap_err().kind()
+        );
+    }
+}
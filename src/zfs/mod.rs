@@ -1,4 +1,5 @@
-use std::{os::unix::io::AsRawFd, path::PathBuf};
+use std::{os::unix::io::AsRawFd,
+          path::{Path, PathBuf}};
 
 use bitflags::bitflags;
 
@@ -16,16 +17,55 @@ pub use lzc::ZfsLzc;
 use std::collections::HashMap;
 
 pub mod properties;
-pub use properties::{CacheMode, CanMount, Checksum, Compression, Copies, FilesystemProperties,
-                     Properties, SnapDir, VolumeProperties};
+pub use properties::{CacheMode, CanMount, Checksum, Compression, Copies, Encryption,
+                     FilesystemProperties, MountPoint, Properties, PropertySource, PropertyValue,
+                     SnapDir, VolumeProperties, XAttr};
 
 mod pathext;
 pub use pathext::PathExt;
 
+pub mod space;
+pub use space::{SpaceDelta, SpaceUsage};
+
+pub mod resume;
+pub use resume::ResumeToken;
+
+pub mod delegation;
+pub use delegation::{DatasetPermissions, Delegation};
+
+pub mod snapshot_info;
+pub use snapshot_info::SnapshotInfo;
+
+#[cfg(feature = "mock")] pub mod mock;
+#[cfg(feature = "mock")] pub use mock::MockZfs;
+
+pub mod receive;
+pub use receive::{ReceiveOptions, ReceiveOptionsBuilder, ReceivedStream, StreamKind};
+
+pub mod replication;
+pub use replication::ReplicationReport;
+
+pub mod digest;
+pub use digest::{SentStream, StreamHasher};
+
+#[cfg(feature = "serde")] pub mod nvlist_serde;
+#[cfg(feature = "serde")]
+pub use nvlist_serde::{nvlist_from_serde, nvlist_to_serde, values_to_serde};
+
 pub static DATASET_NAME_MAX_LENGTH: usize = 255;
+/// Smallest block size ZFS supports (`SPA_MINBLOCKSIZE`).
+pub static MIN_BLOCK_SIZE: u64 = 512;
+/// Largest `recordsize` ZFS supports (`SPA_MAXBLOCKSIZE`). Anything above 1 MiB needs
+/// `large_blocks` feature and, before OpenZFS 2.2, raised `zfs_max_recordsize` module parameter.
+pub static MAX_RECORD_SIZE: u64 = 16 * 1024 * 1024;
+/// Largest `volblocksize` allowed.
+pub static MAX_VOLUME_BLOCK_SIZE: u64 = 128 * 1024;
+/// Latest filesystem (ZPL) version (`ZPL_VERSION`).
+pub static MAX_ZPL_VERSION: u64 = 5;
 
 mod errors;
 
+pub(crate) use errors::CancelledByFlag;
 pub use errors::{Error, ErrorKind, Result, ValidationError, ValidationResult};
 
 /// Whether to mark busy snapshots for deferred destruction rather than immediately failing if can't
@@ -36,7 +76,10 @@ pub enum DestroyTiming {
     /// snapshots will be destroyed.
     RightNow,
     /// If a snapshot has user holds or clones, it will be marked for deferred destruction, and
-    /// will be destroyed when the last hold or clone is removed/destroyed.
+    /// will be destroyed when the last hold or clone is removed/destroyed. Until then the snapshot
+    /// still exists, its `defer_destroy` property is on and its space isn't reclaimed. Destroy
+    /// itself succeeds either way, [`DestroyReport`](struct.DestroyReport.html) returned by
+    /// `destroy_snapshots_with_report` tells which snapshots are gone.
     Defer,
 }
 
@@ -49,8 +92,51 @@ impl DestroyTiming {
     }
 }
 
+/// Outcome of destroying snapshots with
+/// [`destroy_snapshots_with_report`](trait.ZfsEngine.html#method.destroy_snapshots_with_report).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct DestroyReport {
+    /// Snapshots that are gone.
+    destroyed: Vec<PathBuf>,
+    /// Snapshots marked for deferred destruction because of holds or clones. They still exist
+    /// and use space until the last hold is released or the last clone is destroyed.
+    deferred:  Vec<PathBuf>,
+}
+
+impl DestroyReport {
+    /// Whether every snapshot is actually gone.
+    pub fn is_complete(&self) -> bool { self.deferred.is_empty() }
+}
+
+/// Protocol to share a filesystem over.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShareProtocol {
+    /// Share over NFS. Requires `sharenfs` to be set.
+    Nfs,
+    /// Share over SMB. Requires `sharesmb` to be set.
+    Smb,
+    /// Share over every protocol enabled on the filesystem.
+    All,
+}
+
+impl ShareProtocol {
+    /// Share properties that must be enabled for this protocol. For `All` at least one of them.
+    pub(crate) fn properties(self) -> &'static [&'static str] {
+        match self {
+            ShareProtocol::Nfs => &["sharenfs"],
+            ShareProtocol::Smb => &["sharesmb"],
+            ShareProtocol::All => &["sharenfs", "sharesmb"],
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct BookmarkRequest {
+    /// Snapshot or bookmark to create bookmark from. Creating bookmark from bookmark needs
+    /// `bookmark_v2` feature, on older ZFS it fails with `Error::BookmarkFromBookmarkUnsupported`.
     pub snapshot: PathBuf,
+    /// Bookmark to create.
     pub bookmark: PathBuf,
 }
 
@@ -58,6 +144,9 @@ impl BookmarkRequest {
     pub fn new(snapshot: PathBuf, bookmark: PathBuf) -> Self {
         BookmarkRequest { snapshot, bookmark }
     }
+
+    /// Whether the source is a bookmark rather than a snapshot.
+    pub fn is_from_bookmark(&self) -> bool { self.snapshot.is_bookmark() }
 }
 
 bitflags! {
@@ -70,14 +159,41 @@ bitflags! {
         const LZC_SEND_FLAG_SAVED = 1 << 4;
     }
 }
+
+bitflags! {
+    /// Flags of `zfs receive`, kept apart from `SendFlags` so the two can't be mixed up. Used by
+    /// [`ReceiveOptions`](struct.ReceiveOptions.html).
+    #[derive(Default)]
+    pub struct RecvFlags: u32 {
+        /// Roll back destination to its most recent snapshot first (`-F`).
+        const FORCE = 1 << 0;
+        /// Keep partially received state, so interrupted receive can be resumed (`-s`).
+        const RESUMABLE = 1 << 1;
+        /// Don't mount received filesystems (`-u`).
+        const UNMOUNTED = 1 << 2;
+        /// Corrective receive (`-c`): repair corrupted blocks of existing snapshot with data from
+        /// the stream. Requires OpenZFS 2.2 or newer.
+        const HEAL = 1 << 3;
+    }
+}
 pub trait ZfsEngine {
-    /// Check if a dataset (a filesystem, or a volume, or a snapshot with the given name exists.
+    /// Check if a dataset (a filesystem, or a volume, or a snapshot, or a bookmark) with the given
+    /// name exists.
     ///
-    /// NOTE: Can't be used to check for existence of bookmarks.
+    /// NOTE: `ZfsLzc` can't be used to check for existence of bookmarks, `ZfsOpen3` and
+    /// `DelegatingZfsEngine` can.
     ///  * `name` - The dataset name to check.
     #[cfg_attr(tarpaulin, skip)]
     fn exists<N: Into<PathBuf>>(&self, _name: N) -> Result<bool> { Err(Error::Unimplemented) }
 
+    /// Check if a dataset exists and what kind it is. Returns `None` if it doesn't exist.
+    ///
+    /// * `name` - Name of filesystem, volume, snapshot or bookmark.
+    #[cfg_attr(tarpaulin, skip)]
+    fn kind_of<N: Into<PathBuf>>(&self, _name: N) -> Result<Option<DatasetKind>> {
+        Err(Error::Unimplemented)
+    }
+
     /// Create a new dataset.
     #[cfg_attr(tarpaulin, skip)]
     fn create(&self, _request: CreateDatasetRequest) -> Result<()> { Err(Error::Unimplemented) }
@@ -107,6 +223,62 @@ pub trait ZfsEngine {
         Err(Error::Unimplemented)
     }
 
+    /// Same as [`destroy_snapshots`](#method.destroy_snapshots), but reports which snapshots were
+    /// destroyed and which were only marked for deferred destruction. Snapshots that still exist
+    /// after destroy are deferred ones, those can only appear with `DestroyTiming::Defer`, because
+    /// `RightNow` fails on held snapshots.
+    #[cfg_attr(tarpaulin, skip)]
+    fn destroy_snapshots_with_report(
+        &self,
+        snapshots: &[PathBuf],
+        timing: DestroyTiming,
+    ) -> Result<DestroyReport> {
+        self.destroy_snapshots(snapshots, timing)?;
+        let mut report = DestroyReport::default();
+        for snapshot in snapshots {
+            if self.exists(snapshot.clone())? {
+                report.deferred.push(snapshot.clone());
+            } else {
+                report.destroyed.push(snapshot.clone());
+            }
+        }
+        Ok(report)
+    }
+
+    /// Delete a range of snapshots of the dataset. Same as `zfs destroy dataset@first%last`.
+    /// Range is inclusive and follows creation order, not names. If any snapshot in range has
+    /// user holds, nothing is destroyed and
+    /// [`Error::SnapshotsHeld`](enum.Error.html#variant.SnapshotsHeld) with hold tags is returned,
+    /// unless `timing` is `Defer`, then held snapshots are destroyed once released.
+    ///
+    /// * `dataset` - Filesystem or volume snapshots belong to.
+    /// * `first` - Name of the first snapshot (without `@`), `None` to start from the oldest.
+    /// * `last` - Name of the last snapshot (without `@`), `None` to go up to the newest. With both
+    ///   `None` every snapshot of the dataset is destroyed.
+    #[cfg_attr(tarpaulin, skip)]
+    fn destroy_snapshot_range<N: Into<PathBuf>>(
+        &self,
+        _dataset: N,
+        _first: Option<&str>,
+        _last: Option<&str>,
+        _timing: DestroyTiming,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// How much space destroying given snapshots of the dataset would free, without destroying
+    /// anything. Same as the last line of `zfs destroy -nv dataset@a,b,c`. It's more than sum of
+    /// their `used`: blocks shared only by those snapshots are counted too, so it depends on the
+    /// whole set, e.g. a contiguous run frees more than its parts.
+    ///
+    /// * `dataset` - Filesystem or volume snapshots belong to.
+    /// * `snaps` - Full names of the snapshots, each of them must be a snapshot of `dataset`,
+    ///   otherwise `SnapshotOfOtherDataset` is returned.
+    #[cfg_attr(tarpaulin, skip)]
+    fn reclaimable_space<N: Into<PathBuf>>(&self, _dataset: N, _snaps: &[PathBuf]) -> Result<u64> {
+        Err(Error::Unimplemented)
+    }
+
     /// Delete bookmarks as one atomic operation
     #[cfg_attr(tarpaulin, skip)]
     fn destroy_bookmarks(&self, _bookmarks: &[PathBuf]) -> Result<()> { Err(Error::Unimplemented) }
@@ -131,12 +303,217 @@ pub trait ZfsEngine {
     fn list_volumes<N: Into<PathBuf>>(&self, _pool: N) -> Result<Vec<PathBuf>> {
         Err(Error::Unimplemented)
     }
+    /// List clones created from given snapshot. Empty if snapshot has no clones.
+    #[cfg_attr(tarpaulin, skip)]
+    fn clones(&self, _snapshot: PathBuf) -> Result<Vec<PathBuf>> { Err(Error::Unimplemented) }
+    /// Encryption suite of filesystem or volume, `Encryption::Off` if it isn't encrypted.
+    #[cfg_attr(tarpaulin, skip)]
+    fn encryption<N: Into<PathBuf>>(&self, _name: N) -> Result<Encryption> {
+        Err(Error::Unimplemented)
+    }
+    /// Unmount every mounted filesystem in the pool. Datasets are unmounted deepest-first, so
+    /// children are unmounted before their parents. If a dataset is busy and `force` is not set,
+    /// [`Error::DatasetBusy`](enum.Error.html#variant.DatasetBusy) with the blocking dataset is
+    /// returned.
+    #[cfg_attr(tarpaulin, skip)]
+    fn unmount_all<N: Into<PathBuf>>(&self, _pool: N, _force: bool) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+    /// Share filesystem. Same as `zfs share`. Filesystem must have share property of `protocol`
+    /// enabled, otherwise [`Error::ShareNotEnabled`](enum.Error.html#variant.ShareNotEnabled) is
+    /// returned. NOTE: `zfs` doesn't allow to pick a protocol for a single filesystem, so it's
+    /// shared over every enabled protocol regardless of `protocol`.
+    #[cfg_attr(tarpaulin, skip)]
+    fn share<N: Into<PathBuf>>(&self, _name: N, _protocol: ShareProtocol) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+    /// Stop sharing filesystem. Same as `zfs unshare`. Same restrictions as for
+    /// [`share`](#method.share) apply.
+    #[cfg_attr(tarpaulin, skip)]
+    fn unshare<N: Into<PathBuf>>(&self, _name: N, _protocol: ShareProtocol) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+    /// Read space usage of a single filesystem, volume or snapshot.
+    #[cfg_attr(tarpaulin, skip)]
+    fn space<N: Into<PathBuf>>(&self, _name: N) -> Result<SpaceUsage> { Err(Error::Unimplemented) }
+
+    /// Amount of data written to the dataset since the snapshot, i.e. `written@snapshot` property.
+    /// Much cheaper than diffing, zero means nothing changed.
+    ///
+    /// * `dataset` - Filesystem or volume.
+    /// * `since_snap` - Short name of the snapshot (after `@`) or full name, which for clones can
+    ///   be a snapshot of the origin. Missing snapshot is
+    ///   [`Error::DatasetNotFound`](enum.Error.html#variant.DatasetNotFound) with its full name.
+    #[cfg_attr(tarpaulin, skip)]
+    fn written_since<N: Into<PathBuf>, S: AsRef<str>>(
+        &self,
+        _dataset: N,
+        _since_snap: S,
+    ) -> Result<u64> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Read creation time, GUID and space usage of a snapshot.
+    #[cfg_attr(tarpaulin, skip)]
+    fn snapshot_info<N: Into<PathBuf>>(&self, _snapshot: N) -> Result<SnapshotInfo> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List snapshots of a dataset, oldest first. Unlike
+    /// [`list_snapshots`](#method.list_snapshots), which returns bare names of every snapshot
+    /// under a pool or dataset sorted by name, only snapshots of `dataset` itself are listed unless
+    /// `recursive` is set, and every one comes with its creation time, GUID and space used.
+    ///
+    /// * `dataset` - Filesystem or volume whose snapshots to list.
+    /// * `recursive` - Include snapshots of descendent datasets too.
+    #[cfg_attr(tarpaulin, skip)]
+    fn list_snapshots_by_creation<N: Into<PathBuf>>(
+        &self,
+        _dataset: N,
+        _recursive: bool,
+    ) -> Result<Vec<SnapshotInfo>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Bookmarks of a dataset by GUID. Bookmark has the same GUID as the snapshot it was created
+    /// from, so it can be matched with snapshots on the other side of replication.
+    ///
+    /// * `dataset` - Filesystem or volume whose bookmarks to list. Bookmarks of descendent datasets
+    ///   aren't included.
+    #[cfg_attr(tarpaulin, skip)]
+    fn bookmark_guids<N: Into<PathBuf>>(&self, _dataset: N) -> Result<HashMap<u64, PathBuf>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Most recent snapshot of a dataset, `None` if it has no snapshots. Snapshots of descendent
+    /// datasets are not considered, see
+    /// [`list_snapshots_by_creation`](#method.list_snapshots_by_creation).
+    #[cfg_attr(tarpaulin, skip)]
+    fn latest_snapshot<N: Into<PathBuf>>(&self, dataset: N) -> Result<Option<PathBuf>> {
+        let snapshots = self.list_snapshots_by_creation(dataset, false)?;
+        Ok(snapshots.last().map(|snapshot| snapshot.name().clone()))
+    }
+
+    /// Newest snapshot `source` and `destination` have in common, i.e. a base for incremental
+    /// send from `source` to `destination`. Snapshots are matched by GUID, returned name is the
+    /// one of the snapshot on `source`. `None` means there is no common base and full send is
+    /// required. Only snapshots of the datasets themselves are compared, as listed by
+    /// [`list_snapshots_by_creation`](#method.list_snapshots_by_creation).
+    #[cfg_attr(tarpaulin, skip)]
+    fn common_snapshot<A: Into<PathBuf>, B: Into<PathBuf>>(
+        &self,
+        source: A,
+        destination: B,
+    ) -> Result<Option<PathBuf>> {
+        let source = self.list_snapshots_by_creation(source, false)?;
+        let destination = self.list_snapshots_by_creation(destination, false)?;
+        Ok(SnapshotInfo::newest_common(&source, &destination).map(|s| s.name().clone()))
+    }
+
+    /// Read space usage of every filesystem and volume under `root` (including `root` itself) in
+    /// one call.
+    #[cfg_attr(tarpaulin, skip)]
+    fn space_snapshot<N: Into<PathBuf>>(&self, _root: N) -> Result<HashMap<PathBuf, SpaceUsage>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Resolve device node of a volume (`/dev/zvol/...`). Returns `None` if dataset isn't a
+    /// volume, its `volmode` doesn't expose a device or device node is not there (yet).
+    #[cfg_attr(tarpaulin, skip)]
+    fn zvol_device_path(&self, _name: PathBuf) -> Result<Option<PathBuf>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Change size of a volume. Same as `zfs set volsize`.
+    ///
+    /// * `name` - Volume to resize.
+    /// * `new_size` - New size in bytes. Must be a multiple of `volume_block_size` and can't be
+    ///   smaller than space the volume references.
+    /// * `grow_reservation` - Recalculate `refreservation` for the new size. Only applies to
+    ///   non-sparse volumes, sparse volumes stay sparse.
+    #[cfg_attr(tarpaulin, skip)]
+    fn resize_volume<N: Into<PathBuf>>(
+        &self,
+        _name: N,
+        _new_size: u64,
+        _grow_reservation: bool,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Set the same property to the same value on many datasets. Failure of one dataset doesn't
+    /// stop the others, outcome of each dataset is reported next to its name, in the same order
+    /// as `datasets`. Error of the whole call means nothing was attempted.
+    ///
+    /// * `datasets` - Datasets to change.
+    /// * `prop` - Native or user property name.
+    /// * `value` - New value, as `zfs set` would take it.
+    #[cfg_attr(tarpaulin, skip)]
+    fn set_property_many(
+        &self,
+        _datasets: &[PathBuf],
+        _prop: &str,
+        _value: &str,
+    ) -> Result<Vec<(PathBuf, Result<()>)>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Upgrade filesystem to the latest on-disk version supported by the running software. Same
+    /// as `zfs upgrade`. Upgrading an up-to-date filesystem is a no-op.
+    ///
+    /// * `name` - Filesystem to upgrade.
+    /// * `recursive` - Upgrade all descendant filesystems too.
+    #[cfg_attr(tarpaulin, skip)]
+    fn upgrade_dataset<N: Into<PathBuf>>(&self, _name: N, _recursive: bool) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Delegate permissions on dataset to non-privileged users. Same as `zfs allow`. Permissions
+    /// apply to the dataset and its descendents. Requires `delegation` property of the pool to
+    /// be on.
+    ///
+    /// * `name` - Dataset to delegate permissions on.
+    /// * `who` - Who gets the permissions. For `PermissionSet` this defines (or extends) the set.
+    /// * `perms` - Names of subcommands, properties or permission sets (with leading `@`). Can't be
+    ///   empty.
+    #[cfg_attr(tarpaulin, skip)]
+    fn allow<N: Into<PathBuf>>(&self, _name: N, _who: Delegation, _perms: &[&str]) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Revoke permissions granted with [`allow`](#method.allow). Same as `zfs unallow`.
+    ///
+    /// * `name` - Dataset to revoke permissions on.
+    /// * `who` - Who loses the permissions.
+    /// * `perms` - Permissions to revoke. Empty to revoke everything granted to `who`.
+    #[cfg_attr(tarpaulin, skip)]
+    fn unallow<N: Into<PathBuf>>(&self, _name: N, _who: Delegation, _perms: &[&str]) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List permissions delegated on dataset. Permissions set on ancestors come after the
+    /// dataset's own permissions, one entry per dataset.
+    #[cfg_attr(tarpaulin, skip)]
+    fn list_permissions<N: Into<PathBuf>>(&self, _name: N) -> Result<Vec<DatasetPermissions>> {
+        Err(Error::Unimplemented)
+    }
+
     /// Read all properties of filesystem/volume/snapshot/bookmark.
     #[cfg_attr(tarpaulin, skip)]
     fn read_properties<N: Into<PathBuf>>(&self, _path: N) -> Result<Properties> {
         Err(Error::Unimplemented)
     }
 
+    /// Read all properties of dataset together with where each value comes from: set locally,
+    /// inherited, default or received.
+    #[cfg_attr(tarpaulin, skip)]
+    fn read_properties_with_source<N: Into<PathBuf>>(
+        &self,
+        _name: N,
+    ) -> Result<HashMap<String, PropertyValue>> {
+        Err(Error::Unimplemented)
+    }
+
     /// Send a full snapshot to a specified file descriptor.
     #[cfg_attr(tarpaulin, skip)]
     fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
@@ -160,6 +537,266 @@ pub trait ZfsEngine {
         Err(Error::Unimplemented)
     }
 
+    /// Send an incremental stream from a bookmark. Bookmark keeps only what's needed to be an
+    /// incremental source, so the snapshot it was created from can be destroyed on this side.
+    /// Receiving side still needs that snapshot.
+    ///
+    /// * `path` - Snapshot to send.
+    /// * `bookmark` - Incremental source. Must be a bookmark of the same dataset as `path`.
+    fn send_incremental_from_bookmark<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        bookmark: PathBuf,
+        fd: FD,
+        flags: SendFlags,
+    ) -> Result<()> {
+        let path = path.into();
+        validators::validate_bookmark_for(&path, &bookmark)?;
+        self.send_incremental(path, bookmark, fd, flags)
+    }
+
+    /// Snapshot dataset, send full stream of the snapshot to file descriptor and optionally
+    /// destroy the snapshot afterwards. Steps run in that order, nothing is sent if snapshot
+    /// can't be created. With `cleanup` the snapshot is destroyed even if send failed, error of
+    /// send takes precedence over error of destroy.
+    ///
+    /// * `dataset` - Dataset to replicate.
+    /// * `snap_name` - Name of the snapshot, without dataset name and `@`.
+    /// * `cleanup` - Destroy the snapshot after send.
+    fn replicate_once<FD: AsRawFd>(
+        &self,
+        dataset: PathBuf,
+        snap_name: &str,
+        fd: FD,
+        cleanup: bool,
+        flags: SendFlags,
+    ) -> Result<()> {
+        let snapshot = PathBuf::from(format!("{}@{}", dataset.display(), snap_name));
+        self.snapshot(&[snapshot.clone()], None)?;
+        let sent = self.send_full(snapshot.clone(), fd, flags);
+        if !cleanup {
+            return sent;
+        }
+        let destroyed = self.destroy_snapshots(&[snapshot], DestroyTiming::RightNow);
+        sent.and(destroyed)
+    }
+
+    /// Send a replication stream to a specified file descriptor. Replication stream includes
+    /// all descendant datasets with their snapshots, properties and clones. Same as `zfs send -R`.
+    ///
+    /// * `path` - Snapshot to send. Snapshot with the same name must exist on every descendant.
+    /// * `from` - Send all intermediate snapshots starting from this one (`-I`), `None` for full
+    ///   stream.
+    /// * `skip_missing` - Skip descendants that don't have the snapshot instead of failing the
+    ///   whole send (`-s`). Skipped datasets are skipped together with their children.
+    ///
+    /// Returns datasets that were skipped, always empty if `skip_missing` is `false`.
+    #[cfg_attr(tarpaulin, skip)]
+    fn send_replication<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _path: N,
+        _from: Option<PathBuf>,
+        _fd: FD,
+        _flags: SendFlags,
+        _skip_missing: bool,
+    ) -> Result<Vec<PathBuf>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Send a snapshot to file descriptor, reporting how many bytes were sent so far. Useful to
+    /// draw a progress bar without piping stream through `pv`.
+    ///
+    /// * `path` - Snapshot to send.
+    /// * `from` - Incremental source (snapshot or bookmark), `None` for full stream.
+    /// * `progress` - Called with total number of bytes sent every few megabytes and once more when
+    ///   stream ends. With `None` stream is written to `fd` directly, without extra copying.
+    #[cfg_attr(tarpaulin, skip)]
+    fn send_with_progress<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _path: N,
+        _from: Option<PathBuf>,
+        _fd: FD,
+        _flags: SendFlags,
+        _progress: Option<&mut dyn FnMut(u64)>,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Send a snapshot to file descriptor, hashing the stream on the way. Stream isn't buffered,
+    /// every chunk is fed to `hasher` right before it's written to `fd`.
+    ///
+    /// * `path` - Snapshot to send.
+    /// * `from` - Incremental source (snapshot or bookmark), `None` for full stream.
+    /// * `hasher` - Hash function to use. It's expected to be fresh, digest is taken with
+    ///   [`StreamHasher::finish`](trait.StreamHasher.html#tymethod.finish) once stream ends.
+    #[cfg_attr(tarpaulin, skip)]
+    fn send_with_digest<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _path: N,
+        _from: Option<PathBuf>,
+        _fd: FD,
+        _flags: SendFlags,
+        _hasher: &mut dyn StreamHasher,
+    ) -> Result<SentStream> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Receive a stream from file descriptor. Same as `zfs receive`.
+    ///
+    /// * `name` - Dataset (or snapshot) to create from the stream.
+    /// * `fd` - Where to read the stream from.
+    /// * `options` - Options to use for receive. Mismatched `origin` results in
+    ///   [`Error::IncrementalSourceMismatch`](enum.Error.html#variant.IncrementalSourceMismatch),
+    ///   incremental stream onto modified destination without `force` in
+    ///   [`Error::DestinationModified`](enum.Error.html#variant.DestinationModified).
+    ///
+    /// Raw stream (`LZC_SEND_FLAG_RAW`) of encrypted dataset doesn't need a key to be received.
+    /// Received dataset becomes its own encryption root and stays locked (and unmounted) until
+    /// [`load_key`](#method.load_key) is called.
+    #[cfg_attr(tarpaulin, skip)]
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _name: N,
+        _fd: FD,
+        _options: ReceiveOptions,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Bring `dest` on `dest_engine` up to date with the latest snapshot of `source`. The first
+    /// run sends full stream, following runs send incremental stream from the newest snapshot both
+    /// sides have in common. Stream goes through a pipe, `dest_engine` can be this engine or one
+    /// that talks to another host.
+    ///
+    /// * `source` - Filesystem or volume to replicate. Must have at least one snapshot, otherwise
+    ///   it's [`Error::NoSnapshots`](enum.Error.html#variant.NoSnapshots).
+    /// * `dest` - Dataset to receive into, created on the first run. If it was modified since the
+    ///   previous run, receive fails with
+    ///   [`Error::DestinationModified`](enum.Error.html#variant.DestinationModified).
+    /// * `bookmark` - Bookmark the sent snapshot (`source#name`), so it stays an incremental source
+    ///   after the snapshot itself is destroyed. Bookmarks of `source` are considered as a base for
+    ///   incremental send if this engine implements [`bookmark_guids`](#method.bookmark_guids).
+    ///
+    /// Stream is sent with [`send_with_progress`](#method.send_with_progress) to count bytes,
+    /// so this engine has to implement it. If both sides fail, error of receive is returned:
+    /// send usually fails only because receive closed the pipe.
+    fn replicate<E: ZfsEngine + Sync>(
+        &self,
+        source: PathBuf,
+        dest_engine: &E,
+        dest: PathBuf,
+        flags: SendFlags,
+        bookmark: bool,
+    ) -> Result<ReplicationReport>
+    where
+        Self: Sync,
+    {
+        let snapshots = self.list_snapshots_by_creation(source.clone(), false)?;
+        let latest = snapshots.last().ok_or_else(|| Error::NoSnapshots(source.clone()))?;
+        let existing = match dest_engine.list_snapshots_by_creation(dest.clone(), false) {
+            Ok(existing) => existing,
+            Err(e) if e.is_not_found() => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let bookmarks = match self.bookmark_guids(source.clone()) {
+            Ok(bookmarks) => bookmarks,
+            Err(e) if e.kind() == ErrorKind::Unimplemented => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        // Snapshot that was sent last time might be destroyed on source already, its bookmark
+        // is as good as incremental source.
+        let from = existing.iter().rev().find_map(|received| {
+            snapshots
+                .iter()
+                .find(|snapshot| snapshot.guid() == received.guid())
+                .map(|snapshot| snapshot.name().clone())
+                .or_else(|| bookmarks.get(received.guid()).cloned())
+        });
+        let snap_name = latest.name().get_snapshot().unwrap_or_default();
+        let received = PathBuf::from(format!("{}@{}", dest.display(), snap_name));
+        let mut report = ReplicationReport::new(latest.name().clone(), received, from.clone());
+        if report.is_up_to_date() {
+            return Ok(report);
+        }
+
+        let (reader, writer) = replication::pipe()?;
+        let mut bytes = 0;
+        let (sent, received) = std::thread::scope(|scope| {
+            let receiver =
+                scope.spawn(move || dest_engine.receive(dest, reader, ReceiveOptions::default()));
+            let mut progress = |total| bytes = total;
+            let snapshot = report.snapshot().clone();
+            let sent = self.send_with_progress(snapshot, from, writer, flags, Some(&mut progress));
+            let received = receiver.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            (sent, received)
+        });
+        match (sent, received) {
+            (_, Err(e)) => return Err(e),
+            (Err(e), Ok(())) => return Err(e),
+            (Ok(()), Ok(())) => report.set_bytes(bytes),
+        }
+
+        if bookmark {
+            let name = PathBuf::from(format!("{}#{}", source.display(), snap_name));
+            self.bookmark(&[BookmarkRequest::new(report.snapshot().clone(), name.clone())])?;
+            report.set_bookmark(name);
+        }
+        Ok(report)
+    }
+
+    /// Load wrapping key of encryption root. Same as `zfs load-key`.
+    ///
+    /// * `name` - Encryption root.
+    /// * `key_location` - Where to read the key from (`file://...`), `None` to use `keylocation`
+    ///   property. Keys that have to be prompted for aren't supported.
+    #[cfg_attr(tarpaulin, skip)]
+    fn load_key<N: Into<PathBuf>>(&self, _name: N, _key_location: Option<&str>) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Check stream against destination without receiving it. Same as `zfs receive -n -v`.
+    /// Returns snapshots the stream would create. Stream is read to the end, so `fd` can't be
+    /// reused for actual receive unless it's seekable.
+    #[cfg_attr(tarpaulin, skip)]
+    fn receive_dry_run<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _name: N,
+        _fd: FD,
+        _options: ReceiveOptions,
+    ) -> Result<Vec<ReceivedStream>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Estimate size of the stream `send_full` or `send_incremental` would produce.
+    ///
+    /// * `path` - Snapshot to send.
+    /// * `from` - Incremental source (snapshot or bookmark), `None` for full stream.
+    #[cfg_attr(tarpaulin, skip)]
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        _path: N,
+        _from: Option<PathBuf>,
+        _flags: SendFlags,
+    ) -> Result<u64> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Read `receive_resume_token` of a dataset. `None` if there is no interrupted resumable
+    /// receive into it. Missing dataset is an error (`DatasetNotFound`).
+    #[cfg_attr(tarpaulin, skip)]
+    fn resume_token<N: Into<PathBuf>>(&self, _name: N) -> Result<Option<String>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Decode `receive_resume_token` of partially received dataset.
+    #[cfg_attr(tarpaulin, skip)]
+    fn decode_resume_token(&self, _token: &str) -> Result<ResumeToken> { Err(Error::Unimplemented) }
+
+    /// Estimate how many bytes are left to send to complete interrupted receive. Stream of a
+    /// completed token yields 0.
+    #[cfg_attr(tarpaulin, skip)]
+    fn resume_remaining(&self, _token: &str) -> Result<u64> { Err(Error::Unimplemented) }
+
     /// Run a channel program
     #[cfg_attr(tarpaulin, skip)]
     fn run_channel_program<N: Into<PathBuf>>(
@@ -176,10 +813,10 @@ pub trait ZfsEngine {
 }
 
 #[derive(Default, Builder, Debug, Clone, Getters)]
-#[builder(setter(into))]
+#[builder(setter(into), build_fn(private, name = "build_unvalidated", error = "ValidationError"))]
 #[get = "pub"]
 /// Consumer friendly builder for NvPair. Use this to create your datasets. Some properties only
-/// work on filesystems, some only on volumes.
+/// work on filesystems, some only on volumes, `build()` rejects requests that mix them up.
 pub struct CreateDatasetRequest {
     /// Name of the dataset. First crumb of path is name of zpool.
     name:            PathBuf,
@@ -194,6 +831,10 @@ pub struct CreateDatasetRequest {
     ///  - Maximum user property name is 256 characters.
     #[builder(default)]
     user_properties: Option<HashMap<String, String>>,
+    /// Create missing parent filesystems first, same as `zfs create -p`. Parents are created with
+    /// default (inherited) properties, requested properties only apply to the dataset itself.
+    #[builder(default)]
+    create_parents:  bool,
 
     //
     // the rest is zfs native properties
@@ -206,15 +847,19 @@ pub struct CreateDatasetRequest {
     /// Controls whether the access time for files updated when they are read.
     #[builder(default)]
     atime:             Option<bool>,
-    /// Controls whether a file system can be mounted.
+    /// Controls whether a file system can be mounted. With `NoAuto` or `Off` new filesystem isn't
+    /// mounted.
     #[builder(default)]
-    can_mount:         CanMount,
+    can_mount:         Option<CanMount>,
     /// Controls the checksum used to verify data integrity.
     #[builder(default)]
     checksum:          Option<Checksum>,
     /// Enables or disables compression for a dataset.
     #[builder(default)]
     compression:       Option<Compression>,
+    /// SELinux context used for all files in the file system. Only makes sense on filesystems.
+    #[builder(default)]
+    context:           Option<String>,
     /// Sets the number of copies of user data per file system. Available values are 1, 2, or 3.
     /// These copies are in addition to any pool-level redundancy. Disk space used by multiple
     /// copies of user data charged to the corresponding file and dataset, and counts against
@@ -223,16 +868,29 @@ pub struct CreateDatasetRequest {
     /// property on an existing file system only affects newly written data.
     #[builder(default)]
     copies:            Option<Copies>,
+    /// SELinux context used for unlabeled files.
+    #[builder(default)]
+    def_context:       Option<String>,
     /// Controls whether device files in a file system can be opened.
     #[builder(default)]
     devices:           Option<bool>,
+    /// Controls the encryption cipher suite. Leave it empty to inherit encryption from the parent.
+    /// Setting it to `off` under an encrypted parent is not allowed.
+    #[builder(default)]
+    encryption:        Option<Encryption>,
     /// Controls whether programs in a file system allowed to be executed. Also, when set to
     /// `false`, `mmap(2)` calls with `PROT_EXEC` disallowed.
     #[builder(default)]
     exec:              Option<bool>,
-    /// Controls the mount point used for this file system.
+    /// SELinux context of the file system itself.
+    #[builder(default)]
+    fs_context:        Option<String>,
+    /// Controls the mount point used for this file system. Inherited from the parent if not set.
+    #[builder(default)]
+    mount_point:       Option<MountPoint>,
+    /// Allow mounting on top of a non-empty directory.
     #[builder(default)]
-    mount_point:       Option<PathBuf>,
+    overlay:           Option<bool>,
     /// Controls what is cached in the primary cache (ARC).
     #[builder(default)]
     primary_cache:     Option<CacheMode>,
@@ -244,7 +902,7 @@ pub struct CreateDatasetRequest {
     readonly:          Option<bool>,
     /// Specifies a suggested block size for files in a file system in bytes. The size specified
     /// must be a power of two greater than or equal to 512 and less than or equal to 128 KiB.
-    /// If the large_blocks feature is enabled on the pool, the size may be up to 1 MiB.
+    /// If the large_blocks feature is enabled on the pool, the size may be up to 16 MiB.
     #[builder(default)]
     record_size:       Option<u64>,
     /// Sets the amount of disk space a dataset can consume. This property enforces a hard limit on
@@ -259,6 +917,9 @@ pub struct CreateDatasetRequest {
     /// Sets the minimum amount of disk space guaranteed to a dataset and its descendants.
     #[builder(default)]
     reservation:       Option<u64>,
+    /// SELinux context of the root inode of the file system.
+    #[builder(default)]
+    root_context:      Option<String>,
     /// Controls what is cached in the secondary cache (L2ARC).
     #[builder(default)]
     secondary_cache:   Option<CacheMode>,
@@ -277,6 +938,11 @@ pub struct CreateDatasetRequest {
     /// valid.
     #[builder(default)]
     volume_block_size: Option<u64>,
+    /// On-disk version of the filesystem, independent of the pool version. Useful to create
+    /// datasets that can be sent to older systems. Valid versions are 1 to 5, latest supported
+    /// version is used if not set.
+    #[builder(default)]
+    version:           Option<u64>,
     /// Indicates whether extended attributes are enabled or disabled.
     #[builder(default)]
     xattr:             Option<bool>,
@@ -285,12 +951,27 @@ pub struct CreateDatasetRequest {
 impl CreateDatasetRequest {
     pub fn builder() -> CreateDatasetRequestBuilder { CreateDatasetRequestBuilder::default() }
 
+    /// Builder for a filesystem. Volume only properties are rejected by `validate()`.
+    pub fn filesystem<N: Into<PathBuf>>(name: N) -> CreateDatasetRequestBuilder {
+        let mut builder = Self::builder();
+        builder.name(name).kind(DatasetKind::Filesystem);
+        builder
+    }
+
+    /// Builder for a volume of `size` bytes.
+    pub fn volume<N: Into<PathBuf>>(name: N, size: u64) -> CreateDatasetRequestBuilder {
+        let mut builder = Self::builder();
+        builder.name(name).kind(DatasetKind::Volume).volume_size(size);
+        builder
+    }
+
     pub fn validate(&self) -> Result<()> {
         let mut errors = Vec::new();
 
         if let Err(e) = validators::validate_name(self.name()) {
             errors.push(e);
         }
+        errors.extend(self.property_errors());
 
         if errors.is_empty() {
             Ok(())
@@ -298,12 +979,134 @@ impl CreateDatasetRequest {
             Err(errors.into())
         }
     }
+
+    /// ZFS doesn't allow unencrypted children under encrypted parent and only says `EINVAL` about
+    /// it. Look up encryption of the parent (or the closest existing ancestor if parents are
+    /// created too) with `engine` and reject such request with `UnencryptedChild`. Engines run it
+    /// in `create`, engines that can't read encryption (`ZfsLzc`) let the request through.
+    pub fn validate_encryption<E: ZfsEngine>(&self, engine: &E) -> Result<()> {
+        if self.encryption() != &Some(Encryption::Off) {
+            return Ok(());
+        }
+        let mut parent = match self.name().parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => return Ok(()),
+        };
+        // Missing parents inherit encryption from the closest existing ancestor.
+        if self.create_parents {
+            while !engine.exists(parent)? {
+                match parent.parent() {
+                    Some(ancestor) if ancestor != Path::new("") => parent = ancestor,
+                    _ => return Ok(()),
+                }
+            }
+        }
+        match engine.encryption(parent) {
+            Ok(Encryption::Off) => Ok(()),
+            Ok(_) => Err(ValidationError::UnencryptedChild(self.name().clone()).into()),
+            Err(ref e) if e.kind() == ErrorKind::Unimplemented => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Problems with properties alone: properties of the other kind, missing `volume_size`, out of
+    /// range sizes and versions.
+    fn property_errors(&self) -> Vec<ValidationError> {
+        vec![
+            validators::validate_volume_properties(self),
+            validators::validate_record_size(self),
+            validators::validate_version(self),
+            validators::validate_mount_properties(self),
+        ]
+        .into_iter()
+        .filter_map(|result| result.err())
+        .collect()
+    }
+}
+
+impl CreateDatasetRequestBuilder {
+    /// Build the request, rejecting properties that don't fit its kind or are out of range. Errors
+    /// are the same `ValidationErrors` engines return from `create`. Name is only checked by
+    /// [`validate`](struct.CreateDatasetRequest.html#method.validate).
+    pub fn build(&self) -> Result<CreateDatasetRequest> {
+        let request = self.build_unvalidated()?;
+        let errors = request.property_errors();
+        if errors.is_empty() {
+            Ok(request)
+        } else {
+            Err(errors.into())
+        }
+    }
 }
 
 pub(crate) mod validators {
-    use crate::zfs::{errors::ValidationResult, ValidationError, DATASET_NAME_MAX_LENGTH};
+    use crate::zfs::{errors::ValidationResult, CreateDatasetRequest, DatasetKind, MountPoint,
+                     ValidationError, DATASET_NAME_MAX_LENGTH, MAX_RECORD_SIZE,
+                     MAX_VOLUME_BLOCK_SIZE, MAX_ZPL_VERSION, MIN_BLOCK_SIZE};
     use std::path::Path;
 
+    pub fn validate_volume_properties(request: &CreateDatasetRequest) -> ValidationResult {
+        let name = request.name();
+        match request.kind() {
+            DatasetKind::Filesystem
+                if request.volume_size.is_some() || request.volume_block_size.is_some() =>
+            {
+                Err(ValidationError::VolumePropertyOnFilesystem(name.to_owned()))
+            },
+            DatasetKind::Volume if request.volume_size.is_none() => {
+                Err(ValidationError::MissingVolumeSize(name.to_owned()))
+            },
+            DatasetKind::Volume => match request.volume_block_size {
+                Some(size) if !is_valid_block_size(size, MAX_VOLUME_BLOCK_SIZE) => {
+                    Err(ValidationError::InvalidVolumeBlockSize(name.to_owned(), size))
+                },
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate_record_size(request: &CreateDatasetRequest) -> ValidationResult {
+        match request.record_size {
+            Some(size) if !is_valid_block_size(size, MAX_RECORD_SIZE) => {
+                Err(ValidationError::InvalidRecordSize(request.name().to_owned(), size))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate_version(request: &CreateDatasetRequest) -> ValidationResult {
+        match request.version {
+            Some(_) if *request.kind() != DatasetKind::Filesystem => {
+                Err(ValidationError::FilesystemPropertyOnVolume(request.name().to_owned()))
+            },
+            Some(version) if version == 0 || version > MAX_ZPL_VERSION => {
+                Err(ValidationError::InvalidVersion(request.name().to_owned(), version))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate_mount_properties(request: &CreateDatasetRequest) -> ValidationResult {
+        let name = request.name();
+        let has_mount_properties = request.can_mount.is_some()
+            || request.mount_point.is_some()
+            || request.overlay.is_some();
+        match &request.mount_point {
+            _ if has_mount_properties && *request.kind() != DatasetKind::Filesystem => {
+                Err(ValidationError::FilesystemPropertyOnVolume(name.to_owned()))
+            },
+            Some(MountPoint::Path(path)) if !path.is_absolute() => {
+                Err(ValidationError::RelativeMountPoint(name.to_owned(), path.clone()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn is_valid_block_size(size: u64, max: u64) -> bool {
+        size.is_power_of_two() && size >= MIN_BLOCK_SIZE && size <= max
+    }
+
     pub fn validate_name<P: AsRef<Path>>(dataset: P) -> ValidationResult {
         _validate_name(dataset.as_ref())
     }
@@ -324,14 +1127,100 @@ pub(crate) mod validators {
                     return Err(ValidationError::NameTooLong(dataset.to_owned()));
                 }
                 Ok(())
-            })
+            })?;
+        validate_components(dataset, &name)
     }
+
+    /// Same as `validate_name`, but also requires name to be a snapshot.
+    pub fn validate_snapshot_name<P: AsRef<Path>>(snapshot: P) -> ValidationResult {
+        let snapshot = snapshot.as_ref();
+        _validate_name(snapshot)?;
+        if snapshot.to_string_lossy().contains('@') {
+            Ok(())
+        } else {
+            Err(ValidationError::MissingAtSign(snapshot.to_owned()))
+        }
+    }
+
+    /// Bookmark must be a bookmark of the dataset `snapshot` belongs to, to be its incremental
+    /// source.
+    pub fn validate_bookmark_for(snapshot: &Path, bookmark: &Path) -> ValidationResult {
+        validate_snapshot_name(snapshot)?;
+        _validate_name(bookmark)?;
+        let bookmark_name = bookmark.to_string_lossy();
+        let dataset = match bookmark_name.rfind('#') {
+            Some(idx) => &bookmark_name[..idx],
+            None => return Err(ValidationError::MissingHashSign(bookmark.to_owned())),
+        };
+        if snapshot.to_string_lossy().split('@').next() == Some(dataset) {
+            Ok(())
+        } else {
+            Err(ValidationError::BookmarkOfOtherDataset(bookmark.to_owned(), snapshot.to_owned()))
+        }
+    }
+
+    /// Source of a bookmark must be a snapshot or another bookmark.
+    pub fn validate_bookmark_source<P: AsRef<Path>>(source: P) -> ValidationResult {
+        let source = source.as_ref();
+        _validate_name(source)?;
+        let name = source.to_string_lossy();
+        if name.contains('@') || name.contains('#') {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidBookmarkSource(source.to_owned()))
+        }
+    }
+
+    /// Check characters and reserved names, the way `zfs_namecheck.c` does. Only the last
+    /// component may have snapshot (`@`) or bookmark (`#`) part.
+    fn validate_components(dataset: &Path, name: &str) -> ValidationResult {
+        let components: Vec<&str> = name.split('/').collect();
+        let last = components.len() - 1;
+        for (idx, component) in components.iter().enumerate() {
+            let (head, tail) = match component.find(|c| c == '@' || c == '#') {
+                Some(pos) if idx == last => (&component[..pos], Some(&component[pos..])),
+                _ => (*component, None),
+            };
+            if head.is_empty() {
+                return Err(ValidationError::MissingName(dataset.to_owned()));
+            }
+            if head == "." || head == ".." {
+                return Err(ValidationError::ReservedName(dataset.to_owned()));
+            }
+            let tail_name = tail.map(|tail| &tail[1..]);
+            if let Some("") = tail_name {
+                return Err(ValidationError::MissingSnapshotName(dataset.to_owned()));
+            }
+            let invalid = head
+                .chars()
+                .chain(tail_name.unwrap_or_default().chars())
+                .find(|c| !is_valid_char(*c));
+            if let Some(c) = invalid {
+                return Err(ValidationError::InvalidCharacter(dataset.to_owned(), c));
+            }
+        }
+
+        let pool = components[0];
+        if pool == "log" || RESERVED_POOL_PREFIXES.iter().any(|prefix| pool.starts_with(prefix)) {
+            return Err(ValidationError::ReservedName(dataset.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn is_valid_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || c == ':' || c == ' '
+    }
+
+    /// Pool names can't start with these, because they are keywords in vdev specification.
+    const RESERVED_POOL_PREFIXES: [&str; 4] = ["mirror", "raidz", "draid", "spare"];
 }
 
 #[cfg(test)]
 mod test {
-    use super::{CreateDatasetRequest, DatasetKind, Error, ErrorKind, ValidationError};
-    use std::path::PathBuf;
+    use super::{properties::ZfsProp, validators, BookmarkRequest, CanMount, Copies,
+                CreateDatasetRequest, DatasetKind, Error, ErrorKind, MountPoint, ValidationError,
+                MAX_ZPL_VERSION};
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn test_error_ds_not_found() {
@@ -340,6 +1229,59 @@ mod test {
         let err = Error::from_stderr(stderr);
         assert_eq!(Error::DatasetNotFound(PathBuf::from("s/asd/asd")), err);
         assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    fn test_error_is_not_found() {
+        let enoent = Error::from(std::io::Error::from_raw_os_error(libc::ENOENT));
+        assert_eq!(ErrorKind::Io, enoent.kind());
+        assert!(enoent.is_not_found());
+        assert!(!Error::from(std::io::Error::from_raw_os_error(libc::EEXIST)).is_not_found());
+        assert!(!Error::PermissionDenied.is_not_found());
+    }
+
+    #[test]
+    fn test_error_destination_modified() {
+        let stderr = b"cannot receive incremental stream: destination backup/dst has been modified
+since most recent snapshot
+";
+        let err = Error::from_stderr(stderr);
+        assert_eq!(Error::DestinationModified(PathBuf::from("backup/dst")), err);
+        assert_eq!(ErrorKind::DestinationModified, err.kind());
+    }
+
+    #[test]
+    fn test_error_out_of_space() {
+        let stderr = b"cannot create snapshot 'tank/full@now': out of space\n";
+        let err = Error::from_stderr(stderr);
+        assert_eq!(Error::OutOfSpace(PathBuf::from("tank/full@now")), err);
+        assert_eq!(ErrorKind::OutOfSpace, err.kind());
+
+        let stderr = b"cannot create 'tank/limited/child': Disc quota exceeded\n";
+        let err = Error::from_stderr(stderr);
+        assert_eq!(ErrorKind::QuotaExceeded, err.kind());
+        if let Error::QuotaExceeded(dataset) = err {
+            assert_eq!(Path::new("tank/limited/child"), dataset);
+        }
+
+        let dataset = Path::new("tank/full");
+        assert_eq!(
+            Some(ErrorKind::OutOfSpace),
+            Error::from_space_errno(libc::ENOSPC, dataset).map(|err| err.kind())
+        );
+        assert_eq!(
+            Some(ErrorKind::QuotaExceeded),
+            Error::from_space_errno(libc::EDQUOT, dataset).map(|err| err.kind())
+        );
+        assert!(Error::from_space_errno(libc::EEXIST, dataset).is_none());
+    }
+
+    #[test]
+    fn test_error_init() {
+        assert_eq!(ErrorKind::ZfsModuleNotLoaded, Error::from_init_errno(libc::ENOENT).kind());
+        assert_eq!(ErrorKind::ZfsModuleNotLoaded, Error::from_init_errno(libc::ENXIO).kind());
+        assert_eq!(ErrorKind::LZCInitializationFailed, Error::from_init_errno(libc::EACCES).kind());
     }
 
     #[test]
@@ -352,6 +1294,69 @@ mod test {
         assert_eq!(ErrorKind::Unknown, err.kind());
     }
 
+    #[test]
+    fn test_error_permission_denied() {
+        let stderr = b"cannot create 'tank/data': permission denied\n";
+        assert_eq!(ErrorKind::PermissionDenied, Error::from_stderr(stderr).kind());
+
+        let stderr = b"cannot open 'tank': Operation not permitted\n";
+        assert_eq!(ErrorKind::PermissionDenied, Error::from_stderr(stderr).kind());
+
+        let err = Error::from(std::io::Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(ErrorKind::PermissionDenied, err.kind());
+        let err = Error::from(std::io::Error::from_raw_os_error(libc::EACCES));
+        assert_eq!(ErrorKind::PermissionDenied, err.kind());
+        let err = Error::from(std::io::Error::from_raw_os_error(libc::EIO));
+        assert_eq!(ErrorKind::Io, err.kind());
+    }
+
+    #[test]
+    fn test_copies_values() {
+        // `Copies` can't hold anything outside of 1..=3, so there is nothing to validate in
+        // requests. Values that can't be represented are rejected when parsed.
+        assert_eq!(1, Copies::One.as_nv_value());
+        assert_eq!(2, Copies::Two.as_nv_value());
+        assert_eq!(3, Copies::Three.as_nv_value());
+        assert_eq!(Ok(Copies::Three), "3".parse());
+        assert!("0".parse::<Copies>().is_err());
+        assert!("4".parse::<Copies>().is_err());
+    }
+
+    #[test]
+    fn test_bookmark_for_validator() {
+        let snapshot = Path::new("z/asd@snap");
+        let check =
+            |bookmark: &str| validators::validate_bookmark_for(snapshot, Path::new(bookmark));
+        assert_eq!(Ok(()), check("z/asd#mark"));
+        assert_eq!(
+            Err(ValidationError::MissingHashSign(PathBuf::from("z/asd@old"))),
+            check("z/asd@old")
+        );
+        assert_eq!(
+            Err(ValidationError::BookmarkOfOtherDataset(
+                PathBuf::from("z/asd/child#mark"),
+                snapshot.to_path_buf()
+            )),
+            check("z/asd/child#mark")
+        );
+        assert_eq!(
+            Err(ValidationError::MissingAtSign(PathBuf::from("z/asd"))),
+            validators::validate_bookmark_for(Path::new("z/asd"), Path::new("z/asd#mark"))
+        );
+    }
+
+    #[test]
+    fn test_bookmark_source_validator() {
+        assert_eq!(Ok(()), validators::validate_bookmark_source("z/asd@snap"));
+        assert_eq!(Ok(()), validators::validate_bookmark_source("z/asd#mark"));
+        assert_eq!(
+            Err(ValidationError::InvalidBookmarkSource(PathBuf::from("z/asd"))),
+            validators::validate_bookmark_source("z/asd")
+        );
+        assert!(BookmarkRequest::new("z/a#m".into(), "z/a#n".into()).is_from_bookmark());
+        assert!(!BookmarkRequest::new("z/a@s".into(), "z/a#n".into()).is_from_bookmark());
+    }
+
     #[test]
     fn test_name_validator() {
         let path = PathBuf::from("z/asd/");
@@ -376,4 +1381,121 @@ mod test {
         let expected = Error::from(vec![ValidationError::NameTooLong(path.clone())]);
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn test_typed_constructors() {
+        let request = CreateDatasetRequest::volume("z/vol", 1024 * 1024)
+            .volume_block_size(16384u64)
+            .build()
+            .unwrap();
+        assert_eq!(&DatasetKind::Volume, request.kind());
+        assert_eq!(&Some(1024 * 1024), request.volume_size());
+        assert!(request.validate().is_ok());
+
+        let request = CreateDatasetRequest::filesystem("z/fs").atime(false).build().unwrap();
+        assert_eq!(&DatasetKind::Filesystem, request.kind());
+        assert!(request.validate().is_ok());
+    }
+
+    #[test]
+    fn test_volume_properties_validator() {
+        let path = PathBuf::from("z/vol");
+        let request = CreateDatasetRequest::volume(path.clone(), 1024 * 1024)
+            .volume_block_size(3000u64)
+            .build();
+        let expected =
+            Error::from(vec![ValidationError::InvalidVolumeBlockSize(path.clone(), 3000)]);
+        assert_eq!(expected, request.unwrap_err());
+
+        let request =
+            CreateDatasetRequest::builder().name(path.clone()).kind(DatasetKind::Volume).build();
+        let expected = Error::from(vec![ValidationError::MissingVolumeSize(path.clone())]);
+        assert_eq!(expected, request.unwrap_err());
+
+        let request =
+            CreateDatasetRequest::filesystem(path.clone()).volume_block_size(8192u64).build();
+        let expected = Error::from(vec![ValidationError::VolumePropertyOnFilesystem(path)]);
+        assert_eq!(expected, request.unwrap_err());
+
+        let request = CreateDatasetRequest::builder().name("z/vol").build();
+        let expected = Error::from(vec![ValidationError::UninitializedField("kind")]);
+        assert_eq!(expected, request.unwrap_err());
+    }
+
+    #[test]
+    fn test_block_size_boundaries() {
+        let path = PathBuf::from("z/fs");
+        let with_record_size =
+            |size: u64| CreateDatasetRequest::filesystem(path.clone()).record_size(size).build();
+        for size in &[512, 4096, 128 * 1024, 1024 * 1024, 16 * 1024 * 1024] {
+            assert!(with_record_size(*size).unwrap().validate().is_ok(), "{}", size);
+        }
+        for size in &[0, 256, 511, 513, 3000, 33_554_432] {
+            let expected =
+                Error::from(vec![ValidationError::InvalidRecordSize(path.clone(), *size)]);
+            assert_eq!(expected, with_record_size(*size).unwrap_err());
+        }
+
+        let path = PathBuf::from("z/vol");
+        let with_block_size = |size: u64| {
+            CreateDatasetRequest::volume(path.clone(), 1024 * 1024).volume_block_size(size).build()
+        };
+        for size in &[512, 8192, 128 * 1024] {
+            assert!(with_block_size(*size).unwrap().validate().is_ok(), "{}", size);
+        }
+        for size in &[511, 256 * 1024, 2_097_152] {
+            let expected =
+                Error::from(vec![ValidationError::InvalidVolumeBlockSize(path.clone(), *size)]);
+            assert_eq!(expected, with_block_size(*size).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_version_bounds() {
+        let path = PathBuf::from("z/fs");
+        let with_version =
+            |version: u64| CreateDatasetRequest::filesystem(path.clone()).version(version).build();
+        for version in 1..=MAX_ZPL_VERSION {
+            assert!(with_version(version).unwrap().validate().is_ok(), "{}", version);
+        }
+        for version in &[0, MAX_ZPL_VERSION + 1] {
+            let expected =
+                Error::from(vec![ValidationError::InvalidVersion(path.clone(), *version)]);
+            assert_eq!(expected, with_version(*version).unwrap_err());
+        }
+
+        let path = PathBuf::from("z/vol");
+        let request = CreateDatasetRequest::volume(path.clone(), 1024 * 1024).version(3).build();
+        let expected = Error::from(vec![ValidationError::FilesystemPropertyOnVolume(path)]);
+        assert_eq!(expected, request.unwrap_err());
+    }
+
+    #[test]
+    fn test_mount_properties_validator() {
+        let path = PathBuf::from("z/fs");
+        let request = CreateDatasetRequest::filesystem(path.clone())
+            .can_mount(CanMount::NoAuto)
+            .mount_point(MountPoint::Path(PathBuf::from("/srv/fs")))
+            .overlay(true)
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+        let request =
+            CreateDatasetRequest::filesystem(path.clone()).mount_point(MountPoint::Legacy).build();
+        assert!(request.unwrap().validate().is_ok());
+
+        let relative = PathBuf::from("srv/fs");
+        let request = CreateDatasetRequest::filesystem(path.clone())
+            .mount_point(MountPoint::Path(relative.clone()))
+            .build();
+        let expected = Error::from(vec![ValidationError::RelativeMountPoint(path, relative)]);
+        assert_eq!(expected, request.unwrap_err());
+
+        let path = PathBuf::from("z/vol");
+        let request = CreateDatasetRequest::volume(path.clone(), 1024 * 1024)
+            .can_mount(CanMount::Off)
+            .build();
+        let expected = Error::from(vec![ValidationError::FilesystemPropertyOnVolume(path)]);
+        assert_eq!(expected, request.unwrap_err());
+    }
 }
@@ -0,0 +1,610 @@
+//! Convert between `NvList` and anything that implements serde traits, so arguments of a channel
+//! program can be a struct and its result can be read into one. Available with `serde` feature.
+//!
+//! Channel programs only understand booleans, 64-bit signed integers, strings, nested nvlists
+//! and arrays of those, so that's what values are converted to:
+//!
+//! - integers become `int64`, unsigned ones that don't fit are an error;
+//! - sequences of booleans, integers or strings become typed arrays, sequences of structs or maps
+//!   become arrays of nvlists. Empty sequence becomes empty nvlist, Lua can't tell the difference;
+//! - unit variants of enums become strings, other variants become nvlist with one key;
+//! - `None` and `()` are left out, missing key reads back as `None`.
+//!
+//! Lua tables returned by a channel program come back as nvlists with keys `"1"`, `"2"`, ...
+//! Those can be read into a `Vec` as well as into a map.
+//!
+//! ```rust,ignore
+//! #[derive(Serialize)]
+//! struct Args {
+//!     snapshots: Vec<String>,
+//! }
+//!
+//! let args = nvlist_from_serde(&Args { snapshots: vec![String::from("tank/usr@old")] })?;
+//! let out = zfs.run_channel_program("tank", PROGRAM, 10_000_000, 10 * 1024 * 1024, true, args)?;
+//! let destroyed: HashMap<String, Vec<String>> = nvlist_to_serde(out)?;
+//! ```
+use std::{collections::HashMap, fmt::Display};
+
+use libnv::nvpair::{NvList, Value};
+use serde::{de::{self,
+                 value::{MapDeserializer, SeqDeserializer},
+                 DeserializeOwned, IntoDeserializer, Visitor},
+            forward_to_deserialize_any,
+            ser::{self, Serialize}};
+
+use crate::zfs::{Error, Result};
+
+/// Serialize struct or map into `NvList`. Anything else can't be top level of nvlist.
+pub fn nvlist_from_serde<T: Serialize + ?Sized>(value: &T) -> Result<NvList> {
+    match value.serialize(NodeSerializer)? {
+        Node::Map(entries) => write_nvlist(entries),
+        _ => Err(Error::NvConversion(String::from("only struct or map can become nvlist"))),
+    }
+}
+
+/// Deserialize `NvList`, e.g. result of
+/// [`run_channel_program`](trait.ZfsEngine.html#method.run_channel_program), into `T`.
+pub fn nvlist_to_serde<T: DeserializeOwned>(nv: NvList) -> Result<T> {
+    values_to_serde(nv.into_hashmap())
+}
+
+/// Same as [`nvlist_to_serde`](fn.nvlist_to_serde.html), but for nvlist that is already turned
+/// into a map, like details of
+/// [`Error::ChanProgRuntime`](enum.Error.html#variant.ChanProgRuntime).
+pub fn values_to_serde<T: DeserializeOwned>(values: HashMap<String, Value>) -> Result<T> {
+    T::deserialize(NvValue(Value::NvList(values)))
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self { Error::NvConversion(msg.to_string()) }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self { Error::NvConversion(msg.to_string()) }
+}
+
+/// Serialized value before it's written into `NvList`.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Nothing,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+fn write_nvlist(entries: Vec<(String, Node)>) -> Result<NvList> {
+    let mut list = NvList::default();
+    for (key, node) in entries {
+        match node {
+            Node::Nothing => {},
+            Node::Bool(value) => list.insert(&key, value)?,
+            Node::Int(value) => list.insert(&key, value)?,
+            Node::Str(value) => list.insert(&key, value.as_str())?,
+            Node::Map(entries) => list.insert_nvlist(&key, &write_nvlist(entries)?)?,
+            Node::Seq(items) => write_array(&mut list, &key, items)?,
+        }
+    }
+    Ok(list)
+}
+
+fn write_array(list: &mut NvList, key: &str, items: Vec<Node>) -> Result<()> {
+    let mixed = || Error::NvConversion(format!("elements of {} must be of the same type", key));
+    match items.first() {
+        None => list.insert_nvlist(key, &NvList::default())?,
+        Some(Node::Bool(_)) => {
+            let values = items
+                .into_iter()
+                .map(|item| if let Node::Bool(value) = item { Ok(value) } else { Err(mixed()) })
+                .collect::<Result<Vec<bool>>>()?;
+            list.insert_bool_array(key, &values)?
+        },
+        Some(Node::Int(_)) => {
+            let values = items
+                .into_iter()
+                .map(|item| if let Node::Int(value) = item { Ok(value) } else { Err(mixed()) })
+                .collect::<Result<Vec<i64>>>()?;
+            list.insert_i64_array(key, &values)?
+        },
+        Some(Node::Str(_)) => {
+            let values = items
+                .into_iter()
+                .map(|item| if let Node::Str(value) = item { Ok(value) } else { Err(mixed()) })
+                .collect::<Result<Vec<String>>>()?;
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            list.insert_string_array(key, &values)?
+        },
+        Some(Node::Map(_)) => {
+            let values = items
+                .into_iter()
+                .map(|item| {
+                    if let Node::Map(entries) = item {
+                        write_nvlist(entries)
+                    } else {
+                        Err(mixed())
+                    }
+                })
+                .collect::<Result<Vec<NvList>>>()?;
+            list.insert_nvlist_array(key, &values)?
+        },
+        Some(_) => {
+            return Err(Error::NvConversion(format!(
+                "{} can't be stored in nvlist array, only booleans, integers, strings and maps",
+                key
+            )))
+        },
+    }
+    Ok(())
+}
+
+struct NodeSerializer;
+
+impl NodeSerializer {
+    fn tagged(variant: &str, node: Node) -> Node { Node::Map(vec![(String::from(variant), node)]) }
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Error = Error;
+    type Ok = Node;
+    type SerializeMap = MapBuilder;
+    type SerializeSeq = SeqBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = MapBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Node> { Ok(Node::Bool(v)) }
+
+    fn serialize_i8(self, v: i8) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_i16(self, v: i16) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_i32(self, v: i32) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_i64(self, v: i64) -> Result<Node> { Ok(Node::Int(v)) }
+
+    fn serialize_u8(self, v: u8) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_u16(self, v: u16) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_u32(self, v: u32) -> Result<Node> { Ok(Node::Int(v.into())) }
+
+    fn serialize_u64(self, v: u64) -> Result<Node> {
+        if v > i64::max_value() as u64 {
+            return Err(Error::NvConversion(format!("{} doesn't fit into int64", v)));
+        }
+        Ok(Node::Int(v as i64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node> { self.serialize_f64(v.into()) }
+
+    fn serialize_f64(self, _v: f64) -> Result<Node> {
+        Err(Error::NvConversion(String::from("floating point numbers aren't supported")))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node> { Ok(Node::Str(v.to_string())) }
+
+    fn serialize_str(self, v: &str) -> Result<Node> { Ok(Node::Str(String::from(v))) }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node> {
+        Ok(Node::Seq(v.iter().map(|byte| Node::Int((*byte).into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Node> { Ok(Node::Nothing) }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node> { Ok(Node::Nothing) }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node> { Ok(Node::Nothing) }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node> {
+        Ok(Node::Str(String::from(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node> {
+        Ok(Self::tagged(variant, value.serialize(NodeSerializer)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder> {
+        Ok(SeqBuilder { variant: None, items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder> { self.serialize_seq(Some(len)) }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqBuilder> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder> {
+        Ok(SeqBuilder { variant: Some(variant), items: Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder> {
+        Ok(MapBuilder {
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key:     None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapBuilder> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapBuilder> {
+        Ok(MapBuilder { variant: Some(variant), entries: Vec::with_capacity(len), key: None })
+    }
+}
+
+struct SeqBuilder {
+    variant: Option<&'static str>,
+    items:   Vec<Node>,
+}
+
+impl SeqBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node> {
+        let node = Node::Seq(self.items);
+        Ok(match self.variant {
+            Some(variant) => NodeSerializer::tagged(variant, node),
+            None => node,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+struct MapBuilder {
+    variant: Option<&'static str>,
+    entries: Vec<(String, Node)>,
+    key:     Option<String>,
+}
+
+impl MapBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        self.entries.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node> {
+        let node = Node::Map(self.entries);
+        Ok(match self.variant {
+            Some(variant) => NodeSerializer::tagged(variant, node),
+            None => node,
+        })
+    }
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    /// Keys of nvlist are strings, integer keys are written in decimal like Lua does it.
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = match key.serialize(NodeSerializer)? {
+            Node::Str(key) => Some(key),
+            Node::Int(key) => Some(key.to_string()),
+            _ => return Err(Error::NvConversion(String::from("key must be string or integer"))),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+impl ser::SerializeStruct for MapBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(String::from(key), value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for MapBuilder {
+    type Error = Error;
+    type Ok = Node;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(String::from(key), value)
+    }
+
+    fn end(self) -> Result<Node> { self.finish() }
+}
+
+/// `Value` of nvpair that can be deserialized.
+struct NvValue(Value);
+
+impl<'de> IntoDeserializer<'de, Error> for NvValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self { self }
+}
+
+/// Lua array comes back as nvlist keyed by `"1"`, `"2"`, ... Elements in index order, `None` if
+/// keys aren't like that.
+fn lua_array(values: &mut HashMap<String, Value>) -> Option<Vec<Value>> {
+    let mut indexed: Vec<(usize, String)> = values
+        .keys()
+        .map(|key| key.parse().map(|index| (index, key.clone())))
+        .collect::<std::result::Result<_, _>>()
+        .ok()?;
+    indexed.sort();
+    if indexed.iter().enumerate().any(|(pos, (index, _))| *index != pos + 1) {
+        return None;
+    }
+    Some(indexed.into_iter().filter_map(|(_, key)| values.remove(&key)).collect())
+}
+
+fn visit_values<'de, V, T>(visitor: V, values: Vec<T>) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+    T: IntoDeserializer<'de, Error>,
+{
+    let mut seq = SeqDeserializer::new(values.into_iter());
+    let ret = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(ret)
+}
+
+impl<'de> de::Deserializer<'de> for NvValue {
+    type Error = Error;
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Bool(value) | Value::Boolean(value) => visitor.visit_bool(value),
+            Value::Int8(value) => visitor.visit_i8(value),
+            Value::Int16(value) => visitor.visit_i16(value),
+            Value::Int32(value) => visitor.visit_i32(value),
+            Value::Int64(value) => visitor.visit_i64(value),
+            Value::Uint8(value) => visitor.visit_u8(value),
+            Value::Uint16(value) => visitor.visit_u16(value),
+            Value::Uint32(value) => visitor.visit_u32(value),
+            Value::Uint64(value) => visitor.visit_u64(value),
+            Value::String(value) => visitor.visit_string(value),
+            Value::BoolArray(values) => visit_values(visitor, values),
+            Value::Int64Array(values) => visit_values(visitor, values),
+            Value::Uint64Array(values) => visit_values(visitor, values),
+            Value::StringArray(values) => visit_values(visitor, values),
+            Value::NvListArray(values) => visit_values(
+                visitor,
+                values.into_iter().map(|v| NvValue(Value::NvList(v))).collect(),
+            ),
+            Value::NvList(values) => {
+                let entries = values.into_iter().map(|(key, value)| (key, NvValue(value)));
+                let mut map = MapDeserializer::new(entries);
+                let ret = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(ret)
+            },
+            _ => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::NvList(mut values) => match lua_array(&mut values) {
+                Some(items) => visit_values(visitor, items.into_iter().map(NvValue).collect()),
+                None => NvValue(Value::NvList(values)).deserialize_any(visitor),
+            },
+            value => NvValue(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Unknown => visitor.visit_none(),
+            value => visitor.visit_some(NvValue(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            _ => Err(Error::NvConversion(String::from("only unit variants can be read"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn serialize<T: Serialize>(value: &T) -> Node { value.serialize(NodeSerializer).unwrap() }
+
+    #[test]
+    fn test_serialize() {
+        let mut args = BTreeMap::new();
+        args.insert("snapshots", vec!["tank/usr@a", "tank/usr@b"]);
+        let expected = Node::Map(vec![(
+            String::from("snapshots"),
+            Node::Seq(vec![
+                Node::Str(String::from("tank/usr@a")),
+                Node::Str(String::from("tank/usr@b")),
+            ]),
+        )]);
+        assert_eq!(expected, serialize(&args));
+
+        let mut counts = BTreeMap::new();
+        counts.insert(1u32, (true, Some(-5i8), None::<u64>));
+        let expected = Node::Map(vec![(
+            String::from("1"),
+            Node::Seq(vec![Node::Bool(true), Node::Int(-5), Node::Nothing]),
+        )]);
+        assert_eq!(expected, serialize(&counts));
+
+        assert_eq!(Node::Int(i64::max_value()), serialize(&(i64::max_value() as u64)));
+        assert!(u64::max_value().serialize(NodeSerializer).is_err());
+        assert!(1.5f64.serialize(NodeSerializer).is_err());
+        assert!(nvlist_from_serde(&42u64).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let mut table = HashMap::new();
+        table.insert(String::from("2"), Value::String(String::from("tank/usr@b")));
+        table.insert(String::from("1"), Value::String(String::from("tank/usr@a")));
+        let mut values = HashMap::new();
+        values.insert(String::from("return"), Value::NvList(table.clone()));
+        values.insert(String::from("count"), Value::Int64(2));
+        values.insert(String::from("flags"), Value::BoolArray(vec![true, false]));
+
+        let snapshots: Vec<String> = values_to_serde(table.clone()).unwrap();
+        assert_eq!(vec!["tank/usr@a", "tank/usr@b"], snapshots);
+
+        let pair: (String, String) = values_to_serde(table.clone()).unwrap();
+        assert_eq!((String::from("tank/usr@a"), String::from("tank/usr@b")), pair);
+
+        // `count` isn't a map
+        assert!(
+            values_to_serde::<BTreeMap<String, BTreeMap<String, String>>>(values.clone()).is_err()
+        );
+
+        values.remove("count");
+        let out: BTreeMap<String, Vec<String>> = values_to_serde({
+            let mut values = values.clone();
+            values.remove("flags");
+            values
+        })
+        .unwrap();
+        assert_eq!(vec!["tank/usr@a", "tank/usr@b"], out["return"]);
+
+        values.remove("return");
+        let flags: BTreeMap<String, Option<Vec<bool>>> = values_to_serde(values).unwrap();
+        assert_eq!(Some(vec![true, false]), flags["flags"]);
+
+        let mut gaps = HashMap::new();
+        gaps.insert(String::from("1"), Value::Int64(1));
+        gaps.insert(String::from("3"), Value::Int64(3));
+        assert!(values_to_serde::<Vec<i64>>(gaps).is_err());
+    }
+}
@@ -1,24 +1,53 @@
-use crate::zfs::{DatasetKind, Error, FilesystemProperties, Properties, Result, VolumeProperties,
+use crate::zfs::{DatasetKind, DatasetPermissions, Delegation, DestroyTiming, Encryption, Error,
+                 FilesystemProperties, PathExt, Properties, PropertyValue, ReceiveOptions,
+                 ReceivedStream, Result, ResumeToken, SendFlags, SentStream, ShareProtocol,
+                 SnapshotInfo, SpaceUsage, StreamHasher, ValidationError, VolumeProperties,
                  ZfsEngine};
 use chrono::NaiveDateTime;
 use slog::Logger;
-use std::{ffi::OsString,
-          path::PathBuf,
-          process::{Command, Stdio}};
+use std::{collections::HashMap,
+          ffi::OsString,
+          fs::File,
+          io,
+          io::{Read, Write},
+          os::unix::{io::{AsRawFd, FromRawFd, RawFd},
+                     process::ExitStatusExt},
+          path::{Path, PathBuf},
+          process::{Command, ExitStatus, Output, Stdio},
+          sync::{atomic::{AtomicBool, Ordering},
+                 Arc, Mutex},
+          thread,
+          time::{Duration, Instant}};
 
-use crate::{parsers::zfs::{Rule, ZfsParser},
+use crate::{observe,
+            parsers::zfs::{Rule, ZfsParser},
             utils::parse_float,
-            zfs::properties::{BookmarkProperties, SnapshotProperties},
+            zfs::{properties::{BookmarkProperties, SnapshotProperties, VolumeMode},
+                  CancelledByFlag},
             GlobalLogger};
 use pest::Parser;
 use std::str::Lines;
 
 static FAILED_TO_PARSE: &str = "Failed to parse value";
 static DATE_FORMAT: &str = "%a %b %e %k:%M %Y";
+static ZVOL_DEV_DIR: &str = "/dev/zvol";
+/// How often cancellable command checks the flag.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How many bytes of send stream pass between two progress callbacks.
+const SEND_PROGRESS_INTERVAL: u64 = 4 * 1024 * 1024;
+/// Size of a single read from `zfs send` when progress is reported.
+const SEND_BUFFER_SIZE: usize = 128 * 1024;
+/// Datasets passed to a single `zfs set` invocation.
+const SET_BATCH_SIZE: usize = 512;
 
 pub struct ZfsOpen3 {
-    cmd_name: OsString,
-    logger:   Logger,
+    cmd_name:     OsString,
+    logger:       Logger,
+    dry_run:      bool,
+    last_command: Mutex<Option<String>>,
+    cancel:       Option<Arc<AtomicBool>>,
+    env:          Vec<(OsString, OsString)>,
+    working_dir:  Option<PathBuf>,
 }
 
 impl ZfsOpen3 {
@@ -31,12 +60,164 @@ impl ZfsOpen3 {
             None => "zfs".into(),
         };
 
-        ZfsOpen3 { logger, cmd_name }
+        ZfsOpen3 {
+            logger,
+            cmd_name,
+            dry_run: false,
+            last_command: Mutex::new(None),
+            cancel: None,
+            env: Vec::new(),
+            working_dir: None,
+        }
     }
 
     pub fn logger(&self) -> &Logger { &self.logger }
 
-    fn zfs(&self) -> Command { Command::new(&self.cmd_name) }
+    /// Use given `zfs` binary instead of the one from `ZFS_CMD` or `$PATH`.
+    pub fn with_zfs_cmd<I: Into<OsString>>(mut self, cmd_name: I) -> Self {
+        self.cmd_name = cmd_name.into();
+        self
+    }
+
+    /// Set environment variable of every spawned `zfs`, on top of inherited environment.
+    pub fn with_env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Run every `zfs` in given directory instead of current one.
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Don't execute anything, only record commands. Every command is treated as successful with
+    /// empty output, so methods that have to parse output will fail. Use
+    /// [`last_command`](#method.last_command) to see what would have been executed.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Make commands cancellable. Once `flag` is set, running command is killed and
+    /// [`Error::Cancelled`](enum.Error.html#variant.Cancelled) is returned, commands started
+    /// afterwards fail right away. Useful to stop long `send_replication` or `receive`. Killed
+    /// `zfs receive` leaves partially received state behind only if it was resumable.
+    ///
+    /// Only commands run by this engine are cancellable. To cancel through
+    /// `DelegatingZfsEngine`, build it with
+    /// [`with_open3`](struct.DelegatingZfsEngine.html#method.with_open3); calls it routes to
+    /// libzfs_core (`send_full`, `send_incremental`, `snapshot` and so on) run to completion.
+    /// Use [`send_with_progress`](trait.ZfsEngine.html#method.send_with_progress) for a send
+    /// that can be stopped.
+    pub fn cancel_on(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Last command executed (or recorded in dry run mode), rendered the same way as in logs.
+    pub fn last_command(&self) -> Option<String> {
+        self.last_command.lock().ok().and_then(|last| last.clone())
+    }
+
+    /// New `zfs` command. Stdio is set the way `Command::output` sets it by default, so it can be
+    /// spawned as well.
+    fn zfs(&self) -> Command {
+        let mut z = Command::new(&self.cmd_name);
+        z.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        z.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(dir) = &self.working_dir {
+            z.current_dir(dir);
+        }
+        z
+    }
+
+    /// Remember the command. Returns `true` if command shouldn't be executed.
+    fn record(&self, z: &Command) -> bool {
+        if let Ok(mut last) = self.last_command.lock() {
+            *last = Some(format!("{:?}", z));
+        }
+        self.dry_run
+    }
+
+    fn output(&self, z: &mut Command) -> io::Result<Output> {
+        let cancel = self.cancel.clone();
+        self.execute(z, move |z| match &cancel {
+            Some(cancel) => output_cancellable(z, cancel),
+            None => z.output(),
+        })
+    }
+
+    /// Same as `output`, but stdout of the command is copied to `sink` and `progress` is called
+    /// with number of bytes copied so far. Every copied chunk is also fed to `hasher`, if any.
+    fn output_with_progress(
+        &self,
+        z: &mut Command,
+        sink: &mut File,
+        progress: &mut dyn FnMut(u64),
+        hasher: Option<&mut dyn StreamHasher>,
+    ) -> io::Result<Output> {
+        let cancel = self.cancel.clone();
+        self.execute(z, move |z| {
+            let cancel = cancel.as_ref().map(|cancel| &**cancel);
+            output_with_progress(z, sink, progress, hasher, cancel)
+        })
+    }
+
+    /// Build `zfs send` of a single snapshot writing to duplicate of `fd`, so caller's descriptor
+    /// isn't closed when `Command` is dropped.
+    fn send_cmd(
+        &self,
+        path: PathBuf,
+        from: Option<PathBuf>,
+        fd: RawFd,
+        flags: SendFlags,
+    ) -> Result<(Command, RawFd)> {
+        let mut z = self.zfs();
+        z.arg("send");
+        z.args(send_flags_to_args(flags));
+        if let Some(from) = from {
+            z.arg("-i");
+            z.arg(from.as_os_str());
+        }
+        z.arg(path.as_os_str());
+        let raw_fd = unsafe { libc::dup(fd) };
+        if raw_fd < 0 {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+        Ok((z, raw_fd))
+    }
+
+    /// Record, run and log the command. `run` does the actual execution.
+    fn execute<F>(&self, z: &mut Command, run: F) -> io::Result<Output>
+    where
+        F: FnOnce(&mut Command) -> io::Result<Output>,
+    {
+        if self.record(z) {
+            Ok(Output { status: ExitStatus::from_raw(0), stdout: Vec::new(), stderr: Vec::new() })
+        } else {
+            let started = Instant::now();
+            let out = run(z);
+            match &out {
+                Ok(out) => {
+                    debug!(self.logger, "executed";
+                        "cmd" => format_args!("{:?}", z),
+                        "status" => %out.status,
+                        "elapsed" => format_args!("{:?}", started.elapsed()));
+                    trace!(self.logger, "output";
+                        "stdout" => %String::from_utf8_lossy(&out.stdout),
+                        "stderr" => %String::from_utf8_lossy(&out.stderr));
+                },
+                Err(e) => debug!(self.logger, "failed to execute";
+                    "cmd" => format_args!("{:?}", z),
+                    "error" => %e,
+                    "elapsed" => format_args!("{:?}", started.elapsed())),
+            }
+            let success = out.as_ref().map_or(false, |out| out.status.success());
+            observe::command_finished("zfs", z, started, success);
+            out
+        }
+    }
 
     #[allow(dead_code)]
     /// Force disable logging by using `/dev/null` as drain.
@@ -46,16 +227,176 @@ impl ZfsOpen3 {
         z.stderr(Stdio::null());
         z
     }
+
+    /// Find snapshot or bookmark of `dataset` with given GUID.
+    pub(crate) fn snapshot_by_guid<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        guid: u64,
+    ) -> Result<Option<PathBuf>> {
+        let mut z = self.zfs();
+        z.args(&["list", "-t", "snapshot,bookmark", "-Hp", "-d", "1", "-o", "name,guid"]);
+        z.arg(dataset.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            Ok(find_name_by_guid(&stdout, guid))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn allow_or_unallow(
+        &self,
+        action: &str,
+        name: PathBuf,
+        who: &Delegation,
+        perms: &[&str],
+    ) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg(action);
+        z.args(who.as_args());
+        if !perms.is_empty() {
+            z.arg(perms.join(","));
+        }
+        z.arg(name.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    /// `zfs receive` reading stream from a duplicate of `fd`.
+    fn receive_cmd<FD: AsRawFd>(
+        &self,
+        name: PathBuf,
+        fd: FD,
+        options: ReceiveOptions,
+        extra_args: &[&str],
+    ) -> Result<Command> {
+        if let Some(origin) = options.origin() {
+            origin.validate_snapshot()?;
+        }
+        let mut z = self.zfs();
+        z.arg("receive");
+        z.args(options.into_args());
+        z.args(extra_args);
+        // Duplicate descriptor, so caller's one isn't closed when `Command` is dropped.
+        let raw_fd = unsafe { libc::dup(fd.as_raw_fd()) };
+        if raw_fd < 0 {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+        z.stdin(unsafe { Stdio::from_raw_fd(raw_fd) });
+        z.arg(name.as_os_str());
+        Ok(z)
+    }
+
+    /// Hold tags of given snapshots. Snapshots without holds are left out.
+    fn holds(&self, snapshots: &[PathBuf]) -> Result<HashMap<PathBuf, Vec<String>>> {
+        if snapshots.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut z = self.zfs();
+        z.args(&["holds", "-H"]);
+        z.args(snapshots);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(parse_holds(&String::from_utf8_lossy(&out.stdout)))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn share_or_unshare(&self, action: &str, name: PathBuf, protocol: ShareProtocol) -> Result<()> {
+        let mut z = self.zfs();
+        z.args(&["get", "-H", "-o", "value", &protocol.properties().join(",")]);
+        z.arg(name.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(Error::from_stderr(&out.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if !stdout.lines().any(|value| value != "off" && value != "-") {
+            return Err(Error::ShareNotEnabled(name));
+        }
+
+        let mut z = self.zfs();
+        z.arg(action);
+        z.arg(name.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+}
+
+/// Snapshots listed by `zfs destroy -npv`.
+fn parse_destroy_dry_run(stdout: &str) -> Vec<PathBuf> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            match (columns.next(), columns.next()) {
+                (Some("destroy"), Some(name)) => Some(PathBuf::from(name)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Full name of the snapshot `written@` refers to. Short name is a snapshot of the dataset itself.
+fn since_snapshot(dataset: &Path, since_snap: &str) -> PathBuf {
+    if since_snap.contains('@') {
+        PathBuf::from(since_snap)
+    } else {
+        PathBuf::from(format!("{}@{}", dataset.display(), since_snap))
+    }
+}
+
+/// Bytes `zfs destroy -npv` would reclaim.
+fn parse_reclaim(stdout: &str) -> Option<u64> {
+    stdout.lines().find_map(|line| {
+        let mut columns = line.split('\t');
+        match (columns.next(), columns.next()) {
+            (Some("reclaim"), Some(bytes)) => bytes.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Hold tags of each snapshot listed by `zfs holds -H`.
+fn parse_holds(stdout: &str) -> HashMap<PathBuf, Vec<String>> {
+    let mut holds: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    for line in stdout.lines() {
+        let mut columns = line.split('\t');
+        if let (Some(name), Some(tag)) = (columns.next(), columns.next()) {
+            holds.entry(PathBuf::from(name)).or_default().push(String::from(tag));
+        }
+    }
+    holds
 }
 
 impl ZfsEngine for ZfsOpen3 {
+    fn exists<N: Into<PathBuf>>(&self, name: N) -> Result<bool> {
+        self.kind_of(name).map(|kind| kind.is_some())
+    }
+
     fn destroy<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
         let mut z = self.zfs_mute();
         z.arg("destroy");
         z.arg(name.into().as_os_str());
 
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -63,6 +404,74 @@ impl ZfsEngine for ZfsOpen3 {
         }
     }
 
+    fn destroy_snapshot_range<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        first: Option<&str>,
+        last: Option<&str>,
+        timing: DestroyTiming,
+    ) -> Result<()> {
+        let dataset = dataset.into();
+        dataset.validate()?;
+        let is_valid_name =
+            |name: &str| !name.is_empty() && !name.contains(|c| c == '@' || c == '%' || c == '/');
+        if !first.into_iter().chain(last).all(is_valid_name) {
+            return Err(Error::invalid_input());
+        }
+        let range = format!(
+            "{}@{}%{}",
+            dataset.display(),
+            first.unwrap_or_default(),
+            last.unwrap_or_default()
+        );
+
+        if timing == DestroyTiming::RightNow {
+            let mut z = self.zfs();
+            z.args(&["destroy", "-n", "-p", "-v", &range]);
+            debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+            let out = self.output(&mut z)?;
+            if !out.status.success() {
+                return Err(Error::from_stderr(&out.stderr));
+            }
+            let snapshots = parse_destroy_dry_run(&String::from_utf8_lossy(&out.stdout));
+            let holds = self.holds(&snapshots)?;
+            if !holds.is_empty() {
+                return Err(Error::SnapshotsHeld(holds));
+            }
+        }
+
+        let mut z = self.zfs();
+        z.arg("destroy");
+        if timing == DestroyTiming::Defer {
+            z.arg("-d");
+        }
+        z.arg(&range);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn kind_of<N: Into<PathBuf>>(&self, name: N) -> Result<Option<DatasetKind>> {
+        let mut z = self.zfs();
+        z.args(&["list", "-t", "all", "-o", "type", "-H"]);
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            stdout.trim().parse().map(Some).map_err(|_| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            match Error::from_stderr(&out.stderr) {
+                Error::DatasetNotFound(_) => Ok(None),
+                e => Err(e),
+            }
+        }
+    }
+
     #[allow(clippy::option_unwrap_used)]
     #[allow(clippy::result_unwrap_used)]
     fn list<N: Into<PathBuf>>(&self, prefix: N) -> Result<Vec<(DatasetKind, PathBuf)>> {
@@ -71,7 +480,7 @@ impl ZfsEngine for ZfsOpen3 {
         z.arg(prefix.into().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
 
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             let stdout = String::from_utf8_lossy(&out.stdout);
             ZfsParser::parse(Rule::datasets_with_type, &stdout)
@@ -108,7 +517,7 @@ impl ZfsEngine for ZfsOpen3 {
         z.args(&["list", "-t", "filesystem", "-o", "name", "-Hpr"]);
         z.arg(pool.into().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        ZfsOpen3::stdout_to_list_of_datasets(&mut z)
+        self.stdout_to_list_of_datasets(&mut z)
     }
 
     fn list_snapshots<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
@@ -116,7 +525,7 @@ impl ZfsEngine for ZfsOpen3 {
         z.args(&["list", "-t", "snapshot", "-o", "name", "-Hpr"]);
         z.arg(pool.into().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        ZfsOpen3::stdout_to_list_of_datasets(&mut z)
+        self.stdout_to_list_of_datasets(&mut z)
     }
 
     fn list_bookmarks<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
@@ -124,7 +533,39 @@ impl ZfsEngine for ZfsOpen3 {
         z.args(&["list", "-t", "bookmark", "-o", "name", "-Hpr"]);
         z.arg(pool.into().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        ZfsOpen3::stdout_to_list_of_datasets(&mut z)
+        self.stdout_to_list_of_datasets(&mut z)
+    }
+
+    fn clones(&self, snapshot: PathBuf) -> Result<Vec<PathBuf>> {
+        snapshot.validate_snapshot()?;
+        let mut z = self.zfs();
+        z.args(&["get", "-H", "-o", "value", "clones"]);
+        z.arg(snapshot.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            Ok(parse_list_of_pathbufs(stdout.trim()).unwrap_or_default())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn encryption<N: Into<PathBuf>>(&self, name: N) -> Result<Encryption> {
+        let mut z = self.zfs();
+        z.args(&["get", "-H", "-o", "value", "encryption"]);
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            match stdout.trim() {
+                "-" => Ok(Encryption::Off),
+                value => value.parse().map_err(|_| Error::UnknownSoFar(stdout.to_string())),
+            }
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
     }
 
     fn list_volumes<N: Into<PathBuf>>(&self, pool: N) -> Result<Vec<PathBuf>> {
@@ -132,7 +573,492 @@ impl ZfsEngine for ZfsOpen3 {
         z.args(&["list", "-t", "volume", "-o", "name", "-Hpr"]);
         z.arg(pool.into().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        ZfsOpen3::stdout_to_list_of_datasets(&mut z)
+        self.stdout_to_list_of_datasets(&mut z)
+    }
+
+    fn unmount_all<N: Into<PathBuf>>(&self, pool: N, force: bool) -> Result<()> {
+        let mut z = self.zfs();
+        z.args(&["list", "-t", "filesystem", "-o", "name,mounted", "-Hpr"]);
+        z.arg(pool.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(Error::from_stderr(&out.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        for dataset in parse_mounted_datasets(&stdout) {
+            let mut z = self.zfs();
+            z.arg("unmount");
+            if force {
+                z.arg("-f");
+            }
+            z.arg(dataset.as_os_str());
+            debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+            let out = self.output(&mut z)?;
+            if !out.status.success() {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                if stderr.contains("busy") {
+                    return Err(Error::DatasetBusy(dataset));
+                }
+                return Err(Error::from_stderr(&out.stderr));
+            }
+        }
+        Ok(())
+    }
+
+    fn share<N: Into<PathBuf>>(&self, name: N, protocol: ShareProtocol) -> Result<()> {
+        self.share_or_unshare("share", name.into(), protocol)
+    }
+
+    fn unshare<N: Into<PathBuf>>(&self, name: N, protocol: ShareProtocol) -> Result<()> {
+        self.share_or_unshare("unshare", name.into(), protocol)
+    }
+
+    fn space_snapshot<N: Into<PathBuf>>(&self, root: N) -> Result<HashMap<PathBuf, SpaceUsage>> {
+        let mut z = self.zfs();
+        z.args(&["list", "-t", "filesystem,volume", "-Hpr", "-o"]);
+        z.arg("name,used,available,referenced,usedbysnapshots,usedbychildren,compressratio");
+        z.arg(root.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            parse_space_lines(&stdout)
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn space<N: Into<PathBuf>>(&self, name: N) -> Result<SpaceUsage> {
+        let mut z = self.zfs();
+        z.args(&["get", "-Hp", "-o", "value"]);
+        z.arg("used,available,referenced,usedbysnapshots,usedbychildren,compressratio");
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let values: Vec<&str> = stdout.lines().collect();
+            parse_space_values(&values).ok_or_else(|| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn reclaimable_space<N: Into<PathBuf>>(&self, dataset: N, snaps: &[PathBuf]) -> Result<u64> {
+        let dataset = dataset.into();
+        dataset.validate()?;
+        let mut names = Vec::with_capacity(snaps.len());
+        for snap in snaps {
+            snap.validate_snapshot()?;
+            match (snap.to_str().and_then(|s| s.split('@').next()), snap.get_snapshot()) {
+                (Some(parent), Some(name)) if Path::new(parent) == dataset => names.push(name),
+                _ => {
+                    let err = ValidationError::SnapshotOfOtherDataset(snap.clone(), dataset);
+                    return Err(err.into());
+                },
+            }
+        }
+        if names.is_empty() {
+            return Ok(0);
+        }
+
+        let mut z = self.zfs();
+        z.args(&["destroy", "-n", "-p", "-v"]);
+        z.arg(format!("{}@{}", dataset.display(), names.join(",")));
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            parse_reclaim(&stdout).ok_or_else(|| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn written_since<N: Into<PathBuf>, S: AsRef<str>>(
+        &self,
+        dataset: N,
+        since_snap: S,
+    ) -> Result<u64> {
+        let dataset = dataset.into();
+        dataset.validate()?;
+        let snapshot = since_snapshot(&dataset, since_snap.as_ref());
+        snapshot.validate_snapshot()?;
+        let mut z = self.zfs();
+        z.args(&["get", "-Hp", "-o", "value"]);
+        z.arg(format!("written@{}", since_snap.as_ref()));
+        z.arg(dataset.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            // zfs prints `-` instead of failing when the snapshot doesn't exist.
+            match stdout.trim() {
+                "-" => Err(Error::DatasetNotFound(snapshot)),
+                value => value.parse().map_err(|_| Error::UnknownSoFar(stdout.to_string())),
+            }
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn snapshot_info<N: Into<PathBuf>>(&self, snapshot: N) -> Result<SnapshotInfo> {
+        let snapshot = snapshot.into();
+        snapshot.validate_snapshot()?;
+        let mut z = self.zfs();
+        z.args(&["get", "-Hp", "-o", "value", "creation,guid,used,referenced,written"]);
+        z.arg(snapshot.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let values: Vec<&str> = stdout.lines().collect();
+            SnapshotInfo::from_values(snapshot, &values)
+                .ok_or_else(|| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn list_snapshots_by_creation<N: Into<PathBuf>>(
+        &self,
+        dataset: N,
+        recursive: bool,
+    ) -> Result<Vec<SnapshotInfo>> {
+        let mut z = self.zfs();
+        z.args(&["list", "-Hp", "-t", "snapshot", "-s", "creation"]);
+        z.args(&["-o", "name,creation,guid,used,referenced,written"]);
+        if recursive {
+            z.arg("-r");
+        } else {
+            z.args(&["-d", "1"]);
+        }
+        z.arg(dataset.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            SnapshotInfo::from_list_stdout(&stdout)
+                .ok_or_else(|| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn bookmark_guids<N: Into<PathBuf>>(&self, dataset: N) -> Result<HashMap<u64, PathBuf>> {
+        let mut z = self.zfs();
+        z.args(&["list", "-Hp", "-t", "bookmark", "-o", "name,guid", "-d", "1"]);
+        z.arg(dataset.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            parse_bookmark_guids(&stdout).ok_or_else(|| Error::UnknownSoFar(stdout.to_string()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn send_replication<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        skip_missing: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let mut z = self.zfs();
+        z.args(&["send", "-R"]);
+        z.args(send_flags_to_args(flags));
+        if skip_missing {
+            z.arg("-s");
+        }
+        if let Some(from) = from {
+            z.arg("-I");
+            z.arg(from.as_os_str());
+        }
+        z.arg(path.into().as_os_str());
+        // Duplicate descriptor, so caller's one isn't closed when `Command` is dropped.
+        let raw_fd = unsafe { libc::dup(fd.as_raw_fd()) };
+        if raw_fd < 0 {
+            return Err(Error::Io(std::io::Error::last_os_error()));
+        }
+        z.stdout(unsafe { Stdio::from_raw_fd(raw_fd) });
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(parse_skipped_datasets(&String::from_utf8_lossy(&out.stderr)))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn send_with_progress<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        progress: Option<&mut dyn FnMut(u64)>,
+    ) -> Result<()> {
+        let (mut z, raw_fd) = self.send_cmd(path.into(), from, fd.as_raw_fd(), flags)?;
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = match progress {
+            Some(progress) => {
+                let mut sink = unsafe { File::from_raw_fd(raw_fd) };
+                self.output_with_progress(&mut z, &mut sink, progress, None)?
+            },
+            None => {
+                z.stdout(unsafe { Stdio::from_raw_fd(raw_fd) });
+                self.output(&mut z)?
+            },
+        };
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn send_with_digest<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        fd: FD,
+        flags: SendFlags,
+        hasher: &mut dyn StreamHasher,
+    ) -> Result<SentStream> {
+        let (mut z, raw_fd) = self.send_cmd(path.into(), from, fd.as_raw_fd(), flags)?;
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let mut sink = unsafe { File::from_raw_fd(raw_fd) };
+        let mut size = 0;
+        let out =
+            self.output_with_progress(&mut z, &mut sink, &mut |sent| size = sent, Some(hasher))?;
+        if out.status.success() {
+            Ok(SentStream::new(size, hasher.finish()))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        name: N,
+        fd: FD,
+        options: ReceiveOptions,
+    ) -> Result<()> {
+        let mut z = self.receive_cmd(name.into(), fd, options, &[])?;
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn load_key<N: Into<PathBuf>>(&self, name: N, key_location: Option<&str>) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("load-key");
+        if let Some(key_location) = key_location {
+            z.args(&["-L", key_location]);
+        }
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn receive_dry_run<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        name: N,
+        fd: FD,
+        options: ReceiveOptions,
+    ) -> Result<Vec<ReceivedStream>> {
+        let mut z = self.receive_cmd(name.into(), fd, options, &["-n", "-v"])?;
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            ReceivedStream::from_stdout(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn upgrade_dataset<N: Into<PathBuf>>(&self, name: N, recursive: bool) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("upgrade");
+        if recursive {
+            z.arg("-r");
+        }
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn allow<N: Into<PathBuf>>(&self, name: N, who: Delegation, perms: &[&str]) -> Result<()> {
+        if perms.is_empty() {
+            return Err(Error::invalid_input());
+        }
+        self.allow_or_unallow("allow", name.into(), &who, perms)
+    }
+
+    fn unallow<N: Into<PathBuf>>(&self, name: N, who: Delegation, perms: &[&str]) -> Result<()> {
+        self.allow_or_unallow("unallow", name.into(), &who, perms)
+    }
+
+    fn list_permissions<N: Into<PathBuf>>(&self, name: N) -> Result<Vec<DatasetPermissions>> {
+        let mut z = self.zfs();
+        z.arg("allow");
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            DatasetPermissions::from_stdout(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn resume_token<N: Into<PathBuf>>(&self, name: N) -> Result<Option<String>> {
+        let mut z = self.zfs();
+        z.args(&["get", "-Hp", "-o", "value", "receive_resume_token"]);
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            match stdout.trim() {
+                "-" | "" => Ok(None),
+                token => Ok(Some(String::from(token))),
+            }
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn decode_resume_token(&self, token: &str) -> Result<ResumeToken> {
+        let mut z = self.zfs();
+        z.args(&["send", "-nvt", token]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            ResumeToken::from_stdout(&stdout)
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn zvol_device_path(&self, name: PathBuf) -> Result<Option<PathBuf>> {
+        let volume_mode = match self.read_properties(name.clone())? {
+            Properties::Volume(properties) => *properties.volume_mode(),
+            _ => return Ok(None),
+        };
+        if volume_mode == Some(VolumeMode::None) {
+            return Ok(None);
+        }
+        let device = Path::new(ZVOL_DEV_DIR).join(&name);
+        if device.exists() {
+            Ok(Some(device))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn set_property_many(
+        &self,
+        datasets: &[PathBuf],
+        prop: &str,
+        value: &str,
+    ) -> Result<Vec<(PathBuf, Result<()>)>> {
+        if prop.is_empty() || prop.contains('=') {
+            return Err(Error::invalid_input());
+        }
+        let assignment = format!("{}={}", prop, value);
+        let mut ret = Vec::with_capacity(datasets.len());
+        let valid: Vec<&PathBuf> = datasets.iter().filter(|dataset| dataset.is_valid()).collect();
+        let mut failures: HashMap<PathBuf, Error> = HashMap::new();
+        for batch in valid.chunks(SET_BATCH_SIZE) {
+            let mut z = self.zfs();
+            z.arg("set");
+            z.arg(&assignment);
+            z.args(batch.iter().map(|dataset| dataset.as_os_str()));
+            debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+            let out = self.output(&mut z)?;
+            if out.status.success() {
+                continue;
+            }
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let per_dataset = parse_set_failures(&stderr);
+            for dataset in batch {
+                // Nothing about particular dataset means the whole command failed, e.g. because
+                // of unknown property.
+                let err = match per_dataset.get(*dataset) {
+                    Some(line) => Error::from_stderr(line.as_bytes()),
+                    None if per_dataset.is_empty() => Error::from_stderr(&out.stderr),
+                    None => continue,
+                };
+                failures.insert((*dataset).clone(), err);
+            }
+        }
+        for dataset in datasets {
+            let outcome = match dataset.validate() {
+                Err(e) => Err(Error::from(e)),
+                Ok(()) => failures.remove(dataset).map_or(Ok(()), Err),
+            };
+            ret.push((dataset.clone(), outcome));
+        }
+        Ok(ret)
+    }
+
+    fn resize_volume<N: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_size: u64,
+        grow_reservation: bool,
+    ) -> Result<()> {
+        let name = name.into();
+        let properties = match self.read_properties(name.clone())? {
+            Properties::Volume(properties) => properties,
+            _ => return Err(ValidationError::NotAVolume(name).into()),
+        };
+        validate_volume_size(&name, &properties, new_size)?;
+
+        let mut z = self.zfs();
+        z.arg("set");
+        z.arg(format!("volsize={}", new_size));
+        z.arg(name.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(Error::from_stderr(&out.stderr));
+        }
+
+        // Sparse volumes have no refreservation, keep it that way.
+        if !grow_reservation || *properties.ref_reservation() == 0 {
+            return Ok(());
+        }
+        let mut z = self.zfs();
+        z.args(&["set", "refreservation=auto"]);
+        z.arg(name.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
     }
 
     fn read_properties<N: Into<PathBuf>>(&self, path: N) -> Result<Properties> {
@@ -141,7 +1067,7 @@ impl ZfsEngine for ZfsOpen3 {
         z.args(&["get", "-Hp", "all"]);
         z.arg(path.clone().as_os_str());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             let stdout = String::from_utf8_lossy(&out.stdout);
             let mut lines = stdout.lines();
@@ -160,12 +1086,28 @@ impl ZfsEngine for ZfsOpen3 {
             Err(Error::from_stderr(&out.stderr))
         }
     }
+
+    fn read_properties_with_source<N: Into<PathBuf>>(
+        &self,
+        name: N,
+    ) -> Result<HashMap<String, PropertyValue>> {
+        let mut z = self.zfs();
+        z.args(&["get", "-Hp", "-o", "name,property,value,source", "all"]);
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            PropertyValue::from_stdout(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
 }
 
 impl ZfsOpen3 {
     #[allow(clippy::option_unwrap_used)]
-    fn stdout_to_list_of_datasets(z: &mut Command) -> Result<Vec<PathBuf>, Error> {
-        let out = z.output()?;
+    fn stdout_to_list_of_datasets(&self, z: &mut Command) -> Result<Vec<PathBuf>, Error> {
+        let out = self.output(z)?;
         if out.status.success() {
             let stdout = String::from_utf8_lossy(&out.stdout);
             ZfsParser::parse(Rule::datasets, &stdout)
@@ -187,6 +1129,123 @@ impl ZfsOpen3 {
     }
 }
 
+/// Check that volume can be resized to `new_size`.
+fn validate_volume_size(
+    name: &Path,
+    properties: &VolumeProperties,
+    new_size: u64,
+) -> Result<(), ValidationError> {
+    let block_size = *properties.volume_block_size();
+    if block_size == 0 || new_size == 0 || new_size % block_size != 0 {
+        return Err(ValidationError::UnalignedVolumeSize(name.to_path_buf(), new_size, block_size));
+    }
+    let referenced = *properties.referenced();
+    if new_size < referenced {
+        return Err(ValidationError::VolumeSizeBelowReferenced(
+            name.to_path_buf(),
+            new_size,
+            referenced,
+        ));
+    }
+    Ok(())
+}
+
+/// Read pipe to the end in a separate thread.
+fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+fn cancelled() -> io::Error { io::Error::new(io::ErrorKind::Interrupted, CancelledByFlag) }
+
+/// Same as `Command::output`, but kills the command as soon as `cancel` is set. Cancellation is
+/// reported as `Interrupted` error.
+fn output_cancellable(z: &mut Command, cancel: &AtomicBool) -> io::Result<Output> {
+    if cancel.load(Ordering::SeqCst) {
+        return Err(cancelled());
+    }
+    let mut child = z.spawn()?;
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let status = loop {
+        if cancel.load(Ordering::SeqCst) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(cancelled());
+        }
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        thread::sleep(CANCEL_POLL_INTERVAL);
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Run command, copying its stdout to `sink`. `progress` gets total number of bytes copied every
+/// `SEND_PROGRESS_INTERVAL` bytes and once more after the last chunk. `hasher` sees every chunk
+/// before it's written. Returned `Output` has empty stdout.
+fn output_with_progress(
+    z: &mut Command,
+    sink: &mut File,
+    progress: &mut dyn FnMut(u64),
+    mut hasher: Option<&mut dyn StreamHasher>,
+    cancel: Option<&AtomicBool>,
+) -> io::Result<Output> {
+    let is_cancelled = || cancel.map_or(false, |cancel| cancel.load(Ordering::SeqCst));
+    if is_cancelled() {
+        return Err(cancelled());
+    }
+    let mut child = z.spawn()?;
+    let stderr = read_all(child.stderr.take());
+    let mut stdout =
+        child.stdout.take().ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+
+    let mut buf = vec![0; SEND_BUFFER_SIZE];
+    let mut copied = 0;
+    let mut reported = 0;
+    let result = loop {
+        if is_cancelled() {
+            break Err(cancelled());
+        }
+        let read = match stdout.read(&mut buf) {
+            Ok(0) => break Ok(()),
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => break Err(e),
+        };
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&buf[..read]);
+        }
+        if let Err(e) = sink.write_all(&buf[..read]) {
+            break Err(e);
+        }
+        copied += read as u64;
+        if copied - reported >= SEND_PROGRESS_INTERVAL {
+            progress(copied);
+            reported = copied;
+        }
+    };
+    if let Err(e) = result {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+    }
+    if copied != reported {
+        progress(copied);
+    }
+    let status = child.wait()?;
+    Ok(Output { status, stdout: Vec::new(), stderr: stderr.join().unwrap_or_default() })
+}
+
 fn parse_prop_line(line: &str) -> (String, String) {
     let mut splits = line.split('\t');
     // consume dataset name
@@ -196,6 +1255,132 @@ fn parse_prop_line(line: &str) -> (String, String) {
     (name, value)
 }
 
+/// Parse output of `zfs list -o name,mounted` into list of mounted datasets sorted deepest-first.
+fn parse_mounted_datasets(stdout: &str) -> Vec<PathBuf> {
+    let mut datasets: Vec<PathBuf> = stdout
+        .lines()
+        .filter_map(|line| {
+            let mut splits = line.split('\t');
+            match (splits.next(), splits.next()) {
+                (Some(name), Some("yes")) => Some(PathBuf::from(name)),
+                _ => None,
+            }
+        })
+        .collect();
+    datasets.sort_by(|a, b| b.components().count().cmp(&a.components().count()));
+    datasets
+}
+
+/// Parse output of `zfs list -o name,used,available,referenced,usedbysnapshots,usedbychildren`.
+fn parse_space_lines(stdout: &str) -> Result<HashMap<PathBuf, SpaceUsage>> {
+    let mut ret = HashMap::new();
+    for line in stdout.lines() {
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() != 7 {
+            return Err(Error::UnknownSoFar(String::from(line)));
+        }
+        let usage =
+            parse_space_values(&columns[1..]).ok_or_else(|| Error::UnknownSoFar(line.into()))?;
+        ret.insert(PathBuf::from(columns[0]), usage);
+    }
+    Ok(ret)
+}
+
+/// Build `SpaceUsage` from values of `used`, `available`, `referenced`, `usedbysnapshots`,
+/// `usedbychildren` and `compressratio` in that order. Snapshots don't have some of these and
+/// print `-` instead, such values are reported as zero.
+fn parse_space_values(values: &[&str]) -> Option<SpaceUsage> {
+    if values.len() != 6 {
+        return None;
+    }
+    let bytes: Vec<u64> = values[..5]
+        .iter()
+        .filter_map(|v| if *v == "-" { Some(0) } else { v.parse().ok() })
+        .collect();
+    if bytes.len() != 5 {
+        return None;
+    }
+    let compress_ratio = parse_float(&mut String::from(values[5])).ok()?;
+    SpaceUsage::builder()
+        .used(bytes[0])
+        .available(bytes[1])
+        .referenced(bytes[2])
+        .used_by_snapshots(bytes[3])
+        .used_by_children(bytes[4])
+        .compress_ratio(compress_ratio)
+        .build()
+        .ok()
+}
+
+/// Map libzfs_core send flags to `zfs send` arguments.
+fn send_flags_to_args(flags: SendFlags) -> Vec<&'static str> {
+    let mut args = Vec::new();
+    if flags.contains(SendFlags::LZC_SEND_FLAG_EMBED_DATA) {
+        args.push("-e");
+    }
+    if flags.contains(SendFlags::LZC_SEND_FLAG_LARGE_BLOCK) {
+        args.push("-L");
+    }
+    if flags.contains(SendFlags::LZC_SEND_FLAG_COMPRESS) {
+        args.push("-c");
+    }
+    if flags.contains(SendFlags::LZC_SEND_FLAG_RAW) {
+        args.push("-w");
+    }
+    if flags.contains(SendFlags::LZC_SEND_FLAG_SAVED) {
+        args.push("-S");
+    }
+    args
+}
+
+/// Datasets `zfs send -R -s` warned about. Warning looks like: `WARNING: skipping dataset
+/// tank/a/b and its children: snapshot snap does not exist`.
+fn parse_skipped_datasets(stderr: &str) -> Vec<PathBuf> {
+    stderr
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("WARNING: skipping dataset "))
+        .filter_map(|rest| rest.find(" and its children").map(|end| PathBuf::from(&rest[..end])))
+        .collect()
+}
+
+/// Lines of `zfs set` stderr about datasets it failed to change, by dataset. `zfs set` goes on
+/// after a failure, so there is one line per failed dataset: `cannot open 'tank/a': dataset does
+/// not exist` or `cannot set property for 'tank/b': ...`.
+fn parse_set_failures(stderr: &str) -> HashMap<PathBuf, &str> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let rest = line
+                .strip_prefix("cannot open '")
+                .or_else(|| line.strip_prefix("cannot set property for '"))?;
+            rest.find("':").map(|end| (PathBuf::from(&rest[..end]), line))
+        })
+        .collect()
+}
+
+/// Parse output of `zfs list -t bookmark -o name,guid -Hp` into map of GUID to bookmark.
+fn parse_bookmark_guids(stdout: &str) -> Option<HashMap<u64, PathBuf>> {
+    stdout
+        .lines()
+        .map(|line| {
+            let mut splits = line.split('\t');
+            let name = PathBuf::from(splits.next()?);
+            Some((splits.next()?.trim().parse().ok()?, name))
+        })
+        .collect()
+}
+
+/// Parse output of `zfs list -o name,guid -Hp` and find name with given GUID.
+fn find_name_by_guid(stdout: &str, guid: u64) -> Option<PathBuf> {
+    stdout.lines().find_map(|line| {
+        let mut columns = line.split('\t');
+        match (columns.next(), columns.next().and_then(|g| g.parse::<u64>().ok())) {
+            (Some(name), Some(g)) if g == guid => Some(PathBuf::from(name)),
+            _ => None,
+        }
+    })
+}
+
 fn parse_list_of_pathbufs(value: &str) -> Option<Vec<PathBuf>> {
     if value == "-" || value == "" {
         return None;
@@ -245,6 +1430,9 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
                 properties
                     .compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
             },
+            "context" => {
+                properties.context(parse_selinux_context(value));
+            },
             "copies" => {
                 properties.copies(value.parse().expect(FAILED_TO_PARSE));
             },
@@ -257,18 +1445,30 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
             "dedup" => {
                 properties.dedup(value.parse().expect(FAILED_TO_PARSE));
             },
+            "defcontext" => {
+                properties.def_context(parse_selinux_context(value));
+            },
             "devices" => {
                 properties.devices(parse_bool(&value));
             },
             "dnodesize" => {
                 properties.dnode_size(value.parse().expect(FAILED_TO_PARSE));
             },
+            "encryption" => {
+                properties.encryption(value.parse().expect(FAILED_TO_PARSE));
+            },
+            "encryptionroot" => {
+                properties.encryption_root(parse_mount_point(&value));
+            },
             "exec" => {
                 properties.exec(parse_bool(&value));
             },
             "filesystem_count" => {
                 properties.filesystem_count(parse_opt_num(&value));
             },
+            "fscontext" => {
+                properties.fs_context(parse_selinux_context(value));
+            },
             "filesystem_limit" => {
                 properties.filesystem_limit(parse_opt_num(&value));
             },
@@ -299,6 +1499,9 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
             "nbmand" => {
                 properties.nbmand(parse_bool(&value));
             },
+            "overlay" => {
+                properties.overlay(Some(parse_bool(&value)));
+            },
             "normalization" => {
                 properties.normalization(value.parse().expect(FAILED_TO_PARSE));
             },
@@ -314,6 +1517,9 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
             "readonly" => {
                 properties.readonly(parse_bool(&value));
             },
+            "relatime" => {
+                properties.relatime(parse_bool(&value));
+            },
             "recordsize" => {
                 properties.record_size(value.parse().expect(FAILED_TO_PARSE));
             },
@@ -336,6 +1542,9 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
             "reservation" => {
                 properties.reservation(value.parse().expect(FAILED_TO_PARSE));
             },
+            "rootcontext" => {
+                properties.root_context(parse_selinux_context(value));
+            },
             "secondarycache" => {
                 properties.secondary_cache(value.parse().expect(FAILED_TO_PARSE));
             },
@@ -385,7 +1594,7 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
                 properties.written(value.parse().expect(FAILED_TO_PARSE));
             },
             "xattr" => {
-                properties.xattr(parse_bool(&value));
+                properties.xattr(value.parse().expect(FAILED_TO_PARSE));
             },
             "type" => { /* no-op */ },
 
@@ -474,7 +1683,7 @@ pub(crate) fn parse_snapshot_lines(lines: &mut Lines, name: PathBuf) -> Properti
                 properties.written(value.parse().expect(FAILED_TO_PARSE));
             },
             "xattr" => {
-                properties.xattr(parse_bool(&value));
+                properties.xattr(value.parse().expect(FAILED_TO_PARSE));
             },
             "type" => { /* no-op */ },
 
@@ -513,6 +1722,12 @@ pub(crate) fn parse_volume_lines(lines: &mut Lines, name: PathBuf) -> Properties
             "dedup" => {
                 properties.dedup(value.parse().expect(FAILED_TO_PARSE));
             },
+            "encryption" => {
+                properties.encryption(value.parse().expect(FAILED_TO_PARSE));
+            },
+            "encryptionroot" => {
+                properties.encryption_root(parse_mount_point(&value));
+            },
             "guid" => {
                 properties.guid(Some(value.parse().expect(FAILED_TO_PARSE)));
             },
@@ -644,14 +1859,248 @@ fn parse_mls_label(val: String) -> Option<String> {
         _ => Some(val),
     }
 }
+fn parse_selinux_context(val: String) -> Option<String> {
+    match val.as_str() {
+        "-" | "none" | "" => None,
+        _ => Some(val),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::zfs::{properties::{AclInheritMode, AclMode, BookmarkProperties, CaseSensitivity,
                                   Dedup, DnodeSize, LogBias, Normalization, RedundantMetadata,
                                   SnapshotProperties, SyncMode, VolumeMode},
-                     CacheMode, CanMount, Checksum, Compression, Copies, SnapDir, VolumeProperties};
-    use std::collections::HashMap;
+                     CacheMode, CanMount, Checksum, Compression, Copies, ErrorKind, SnapDir,
+                     VolumeProperties, XAttr};
+
+    #[test]
+    fn test_parse_mounted_datasets() {
+        let stdout = "z\tyes\nz/usr\tno\nz/usr/home\tyes\nz/var\tyes\nz/var/log/nginx\tyes\n";
+        let expected = vec![
+            PathBuf::from("z/var/log/nginx"),
+            PathBuf::from("z/usr/home"),
+            PathBuf::from("z/var"),
+            PathBuf::from("z"),
+        ];
+        assert_eq!(expected, parse_mounted_datasets(stdout));
+    }
+
+    #[test]
+    fn test_parse_space_lines() {
+        let stdout = "z\t3000\t7000\t1000\t0\t2000\t1.00\nz/usr\t2000\t7000\t1500\t500\t0\t2.45\n";
+        let space = parse_space_lines(stdout).unwrap();
+        assert_eq!(2, space.len());
+
+        let usr = space.get(&PathBuf::from("z/usr")).unwrap();
+        assert_eq!(&2000, usr.used());
+        assert_eq!(&7000, usr.available());
+        assert_eq!(&1500, usr.referenced());
+        assert_eq!(&500, usr.used_by_snapshots());
+        assert_eq!(&0, usr.used_by_children());
+        assert!((usr.compress_ratio() - 2.45).abs() < f64::EPSILON);
+
+        let err = parse_space_lines("z\t3000\t-\n").unwrap_err();
+        assert_eq!(ErrorKind::Unknown, err.kind());
+    }
+
+    #[test]
+    fn test_parse_space_values() {
+        let usage = parse_space_values(&["3000", "7000", "1000", "0", "2000", "1.50x"]).unwrap();
+        assert_eq!(&3000, usage.used());
+        assert_eq!(&2000, usage.used_by_children());
+        assert!((usage.compress_ratio() - 1.5).abs() < f64::EPSILON);
+
+        assert!(parse_space_values(&["3000", "7000", "1000", "0", "2000"]).is_none());
+        assert!(parse_space_values(&["3000", "lots", "1000", "0", "2000", "1.00"]).is_none());
+
+        let snapshot = parse_space_values(&["512", "-", "1000", "-", "-", "1.20x"]).unwrap();
+        assert_eq!(&512, snapshot.used());
+        assert_eq!(&0, snapshot.available());
+        assert_eq!(&1000, snapshot.referenced());
+        assert_eq!(&0, snapshot.used_by_snapshots());
+        assert_eq!(&0, snapshot.used_by_children());
+    }
+
+    #[test]
+    fn test_find_name_by_guid() {
+        let stdout = "tank/data@a\t100\ntank/data@b\t200\ntank/data#c\t300\n";
+        assert_eq!(Some(PathBuf::from("tank/data@b")), find_name_by_guid(stdout, 200));
+        assert_eq!(Some(PathBuf::from("tank/data#c")), find_name_by_guid(stdout, 300));
+        assert_eq!(None, find_name_by_guid(stdout, 400));
+    }
+
+    #[test]
+    fn test_volume_mode_full_is_geom() {
+        assert_eq!(VolumeMode::GEOM, "full".parse().unwrap());
+        assert_eq!(VolumeMode::GEOM, "geom".parse().unwrap());
+        assert_eq!("geom", VolumeMode::GEOM.to_string());
+    }
+
+    #[test]
+    fn test_send_flags_to_args() {
+        assert!(send_flags_to_args(SendFlags::empty()).is_empty());
+        let flags = SendFlags::LZC_SEND_FLAG_COMPRESS | SendFlags::LZC_SEND_FLAG_LARGE_BLOCK;
+        assert_eq!(vec!["-L", "-c"], send_flags_to_args(flags));
+        assert_eq!(vec!["-w"], send_flags_to_args(SendFlags::LZC_SEND_FLAG_RAW));
+    }
+
+    #[test]
+    fn test_parse_skipped_datasets() {
+        let stderr = "WARNING: skipping dataset tank/a/b and its children: snapshot snap does not \
+                      exist\nWARNING: skipping dataset tank/c and its children: snapshot snap does \
+                      not exist\n";
+        let expected = vec![PathBuf::from("tank/a/b"), PathBuf::from("tank/c")];
+        assert_eq!(expected, parse_skipped_datasets(stderr));
+        assert!(parse_skipped_datasets("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_destroy_dry_run() {
+        let stdout = "destroy\ttank/data@a\ndestroy\ttank/data@b\nreclaim\t12345\n";
+        let expected = vec![PathBuf::from("tank/data@a"), PathBuf::from("tank/data@b")];
+        assert_eq!(expected, parse_destroy_dry_run(stdout));
+        assert!(parse_destroy_dry_run("reclaim\t0\n").is_empty());
+    }
+
+    #[test]
+    fn test_since_snapshot() {
+        let dataset = Path::new("tank/usr");
+        assert_eq!(PathBuf::from("tank/usr@daily"), since_snapshot(dataset, "daily"));
+        assert_eq!(PathBuf::from("tank/origin@base"), since_snapshot(dataset, "tank/origin@base"));
+    }
+
+    #[test]
+    fn test_parse_bookmark_guids() {
+        let stdout = "z/usr#first\t8670277898870184975\nz/usr#second\t17281919826273746521\n";
+        let bookmarks = parse_bookmark_guids(stdout).unwrap();
+        assert_eq!(2, bookmarks.len());
+        assert_eq!(Some(&PathBuf::from("z/usr#first")), bookmarks.get(&8_670_277_898_870_184_975));
+        assert!(parse_bookmark_guids("").unwrap().is_empty());
+        assert_eq!(None, parse_bookmark_guids("z/usr#first\t-\n"));
+    }
+
+    #[test]
+    fn test_parse_set_failures() {
+        let stderr = "cannot open 'tank/gone': dataset does not exist\ncannot set property for \
+                      'tank/ro': permission denied\n";
+        let failures = parse_set_failures(stderr);
+        assert_eq!(2, failures.len());
+        let err = Error::from_stderr(failures[Path::new("tank/gone")].as_bytes());
+        assert_eq!(Error::DatasetNotFound(PathBuf::from("tank/gone")), err);
+        let err = Error::from_stderr(failures[Path::new("tank/ro")].as_bytes());
+        assert_eq!(ErrorKind::PermissionDenied, err.kind());
+        assert!(parse_set_failures("bad property list: invalid property 'nope'\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_reclaim() {
+        let stdout = "destroy\ttank/data@a\ndestroy\ttank/data@b\nreclaim\t12345\n";
+        assert_eq!(Some(12345), parse_reclaim(stdout));
+        assert_eq!(None, parse_reclaim("destroy\ttank/data@a\n"));
+    }
+
+    #[test]
+    fn test_parse_holds() {
+        let stdout = "tank/data@a\tkeep\tFri Oct 16 12:00 2026
+tank/data@a\tbackup\tFri Oct 16 12:01 2026
+tank/data@c\tkeep\tFri Oct 16 12:02 2026
+";
+        let holds = parse_holds(stdout);
+        assert_eq!(2, holds.len());
+        let tags = |tags: &[&str]| tags.iter().map(|t| String::from(*t)).collect::<Vec<_>>();
+        assert_eq!(Some(&tags(&["keep", "backup"])), holds.get(Path::new("tank/data@a")));
+        assert_eq!(Some(&tags(&["keep"])), holds.get(Path::new("tank/data@c")));
+        assert!(parse_holds("").is_empty());
+    }
+
+    #[test]
+    fn test_output_cancellable() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut echo = Command::new("echo");
+        echo.arg("hello").stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let out = output_cancellable(&mut echo, &cancel).unwrap();
+        assert!(out.status.success());
+        assert_eq!(b"hello\n".to_vec(), out.stdout);
+
+        let mut sleep = Command::new("sleep");
+        sleep.arg("30").stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let flag = cancel.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            flag.store(true, Ordering::SeqCst);
+        });
+        let started = Instant::now();
+        let err = output_cancellable(&mut sleep, &cancel).unwrap_err();
+        canceller.join().unwrap();
+        assert_eq!(io::ErrorKind::Interrupted, err.kind());
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // Already cancelled, nothing is started.
+        assert!(output_cancellable(&mut echo, &cancel).is_err());
+        assert_eq!(ErrorKind::Cancelled, Error::from(err).kind());
+        let eintr = io::Error::from(io::ErrorKind::Interrupted);
+        assert_eq!(ErrorKind::Io, Error::from(eintr).kind());
+    }
+
+    /// Sums up lengths of chunks, good enough to see that every byte went through.
+    struct CountingHasher(u64);
+
+    impl StreamHasher for CountingHasher {
+        fn update(&mut self, data: &[u8]) { self.0 += data.len() as u64; }
+
+        fn finish(&mut self) -> Vec<u8> { std::mem::replace(&mut self.0, 0).to_be_bytes().to_vec() }
+    }
+
+    #[test]
+    fn test_output_with_progress() {
+        let zfs = ZfsOpen3::new().with_zfs_cmd("sh");
+        let mut z = zfs.zfs();
+        z.args(&["-c", "head -c 9437184 /dev/zero; echo oops >&2"]);
+        let mut sink = tempfile::tempfile().unwrap();
+        let mut reports = Vec::new();
+        let mut hasher = CountingHasher(0);
+        let out = zfs
+            .output_with_progress(
+                &mut z,
+                &mut sink,
+                &mut |sent| reports.push(sent),
+                Some(&mut hasher),
+            )
+            .unwrap();
+        assert_eq!(9_437_184, hasher.0);
+        assert!(out.status.success());
+        assert!(out.stdout.is_empty());
+        assert_eq!(b"oops\n".to_vec(), out.stderr);
+        assert_eq!(9_437_184, sink.metadata().unwrap().len());
+        // Every 4MiB and once at the end.
+        assert_eq!(3, reports.len());
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Some(&9_437_184), reports.last());
+    }
+
+    #[test]
+    fn test_env_and_working_dir() {
+        let dir = tempdir::TempDir::new("zfs-cwd").unwrap();
+        let zfs = ZfsOpen3::new()
+            .with_zfs_cmd("sh")
+            .with_env("LIBZETTA_TEST", "from-env")
+            .with_working_dir(dir.path());
+        let mut z = zfs.zfs();
+        z.args(&["-c", "echo $LIBZETTA_TEST; pwd"]);
+        let out = zfs.output(&mut z).unwrap();
+        let expected = format!("from-env\n{}\n", dir.path().canonicalize().unwrap().display());
+        assert_eq!(expected, String::from_utf8_lossy(&out.stdout));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let zfs = ZfsOpen3::new().dry_run();
+        zfs.destroy("tank/data").unwrap();
+        let rendered = zfs.last_command().unwrap();
+        assert!(rendered.ends_with(r#" "destroy" "tank/data""#), "{}", rendered);
+    }
 
     #[test]
     fn test_hashmap_eq() {
@@ -663,6 +2112,48 @@ mod test {
         right.insert("foo", "bar");
         assert_eq!(left, right);
     }
+    #[test]
+    fn filesystem_properties_selinux_context() {
+        let selinux = "z/usr/home\tcontext\tsystem_u:object_r:httpd_sys_content_t:s0\tlocal
+z/usr/home\tfscontext\tnone\tdefault
+";
+        let stdout =
+            format!("{}{}", include_str!("fixtures/filesystem_properties_freebsd.sorted"), selinux);
+        let name = PathBuf::from("z/usr/home");
+        let properties = match parse_filesystem_lines(&mut stdout.lines(), name) {
+            Properties::Filesystem(properties) => properties,
+            _ => panic!("Expected filesystem properties"),
+        };
+        assert_eq!(
+            &Some(String::from("system_u:object_r:httpd_sys_content_t:s0")),
+            properties.context()
+        );
+        assert_eq!(&None, properties.fs_context());
+        assert_eq!(&None, properties.def_context());
+        assert!(properties.unknown_properties().get("fscontext").is_none());
+    }
+
+    #[test]
+    fn filesystem_properties_linux_booleans() {
+        let linux = "z/usr/home\trelatime\ton\tdefault
+z/usr/home\toverlay\toff\tlocal
+z/usr/home\txattr\tsa\tlocal
+";
+        let stdout =
+            format!("{}{}", include_str!("fixtures/filesystem_properties_freebsd.sorted"), linux);
+        let name = PathBuf::from("z/usr/home");
+        let properties = match parse_filesystem_lines(&mut stdout.lines(), name) {
+            Properties::Filesystem(properties) => properties,
+            _ => panic!("Expected filesystem properties"),
+        };
+        assert!(*properties.relatime());
+        assert_eq!(&Some(false), properties.overlay());
+        assert_eq!(&XAttr::Sa, properties.xattr());
+        assert!(properties.xattr().is_enabled());
+        assert!(!XAttr::Off.is_enabled());
+        assert!(properties.unknown_properties().get("relatime").is_none());
+    }
+
     #[test]
     fn filesystem_properties_freebsd() {
         let stdout = include_str!("fixtures/filesystem_properties_freebsd.sorted");
@@ -730,7 +2221,7 @@ mod test {
             .version(5)
             .vscan(false)
             .written(35_372_666_880)
-            .xattr(false)
+            .xattr(XAttr::Off)
             .volume_mode(Some(VolumeMode::Default))
             .unknown_properties(unknown)
             .build()
@@ -787,6 +2278,27 @@ mod test {
         assert_eq!(Properties::Volume(expected), result);
     }
 
+    #[test]
+    fn test_validate_volume_size() {
+        let stdout = include_str!("fixtures/volume_properties_freebsd.sorted");
+        let name = PathBuf::from("z/iohyve/rancher/disk0");
+        let properties = match parse_volume_lines(&mut stdout.lines(), name.clone()) {
+            Properties::Volume(properties) => properties,
+            other => panic!("not a volume: {:?}", other),
+        };
+
+        assert!(validate_volume_size(&name, &properties, 0x0020_0000_0000).is_ok());
+        assert!(validate_volume_size(&name, &properties, 2_781_577_216).is_ok());
+        assert_eq!(
+            Err(ValidationError::UnalignedVolumeSize(name.clone(), 0x0010_0000_1000, 8192)),
+            validate_volume_size(&name, &properties, 0x0010_0000_1000)
+        );
+        assert_eq!(
+            Err(ValidationError::VolumeSizeBelowReferenced(name.clone(), 8192, 2_781_577_216)),
+            validate_volume_size(&name, &properties, 8192)
+        );
+    }
+
     #[test]
     fn snapshot_properties_freebsd() {
         let stdout = include_str!("fixtures/snapshot_properties_freebsd.sorted");
@@ -821,7 +2333,7 @@ mod test {
             .version(5)
             .volume_mode(Some(VolumeMode::Default))
             .written(0)
-            .xattr(true)
+            .xattr(XAttr::On)
             .unknown_properties(unknown)
             .build()
             .unwrap();
@@ -19,6 +19,9 @@ pub trait PathExt {
     }
 
     fn validate(&self) -> ValidationResult;
+
+    /// Same as [`validate`](#tymethod.validate), but also requires path to be a snapshot.
+    fn validate_snapshot(&self) -> ValidationResult;
 }
 
 impl PathExt for Path {
@@ -54,6 +57,10 @@ impl PathExt for Path {
     }
 
     fn validate(&self) -> ValidationResult { crate::zfs::validators::validate_name(self) }
+
+    fn validate_snapshot(&self) -> ValidationResult {
+        crate::zfs::validators::validate_snapshot_name(self)
+    }
 }
 
 impl<P: AsRef<Path>> PathExt for P {
@@ -64,11 +71,14 @@ impl<P: AsRef<Path>> PathExt for P {
     fn get_bookmark(&self) -> Option<String> { self.as_ref().get_bookmark() }
 
     fn validate(&self) -> ValidationResult { self.as_ref().validate() }
+
+    fn validate_snapshot(&self) -> ValidationResult { self.as_ref().validate_snapshot() }
 }
 
 #[cfg(test)]
 mod test {
     use super::PathExt;
+    use crate::zfs::ValidationError;
     use std::path::PathBuf;
 
     #[test]
@@ -132,4 +142,42 @@ mod test {
         let path = PathBuf::from("tank/usr#wat/home");
         assert!(!path.is_bookmark());
     }
+
+    #[test]
+    fn specific_validation_errors() {
+        let cases = vec![
+            (
+                "tank/foo$bar@snap",
+                ValidationError::InvalidCharacter("tank/foo$bar@snap".into(), '$'),
+            ),
+            (
+                "tank/usr@wat/home",
+                ValidationError::InvalidCharacter("tank/usr@wat/home".into(), '@'),
+            ),
+            ("tank/foo@sn*p", ValidationError::InvalidCharacter("tank/foo@sn*p".into(), '*')),
+            ("tank/foo@", ValidationError::MissingSnapshotName("tank/foo@".into())),
+            ("tank/../foo", ValidationError::ReservedName("tank/../foo".into())),
+            ("mirror1/foo", ValidationError::ReservedName("mirror1/foo".into())),
+            ("log/foo", ValidationError::ReservedName("log/foo".into())),
+            ("foo", ValidationError::MissingPool("foo".into())),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(Err(expected), PathBuf::from(path).validate(), "{}", path);
+        }
+        // Spaces are fine as far as ZFS concerned.
+        assert!(PathBuf::from("tank/foo bar@snap").is_valid());
+        assert!(PathBuf::from("tank/logs").is_valid());
+    }
+
+    #[test]
+    fn snapshot_requires_at_sign() {
+        let path = PathBuf::from("tank/foo");
+        assert_eq!(Err(ValidationError::MissingAtSign(path.clone())), path.validate_snapshot());
+        assert_eq!(Ok(()), PathBuf::from("tank/foo@snap").validate_snapshot());
+        let bookmark = PathBuf::from("tank/foo#mark");
+        assert_eq!(
+            Err(ValidationError::MissingAtSign(bookmark.clone())),
+            bookmark.validate_snapshot()
+        );
+    }
 }
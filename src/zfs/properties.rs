@@ -3,6 +3,11 @@ use strum_macros::{AsRefStr, Display, EnumString};
 
 use std::collections::HashMap;
 
+use pest::Parser;
+
+use crate::{parsers::zfs::{Rule, ZfsParser},
+            zfs::{Error, Result}};
+
 macro_rules! impl_zfs_prop {
     ($type_:ty, $as_str:literal) => {
         impl ZfsProp for $type_ {
@@ -250,6 +255,28 @@ impl Default for CanMount {
     fn default() -> Self { CanMount::On }
 }
 
+/// Where a filesystem is mounted.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum MountPoint {
+    /// Mount at given absolute path.
+    Path(PathBuf),
+    /// Managed by `mount(8)` and `fstab(5)`, ZFS doesn't mount it.
+    Legacy,
+    /// Filesystem isn't mounted.
+    None,
+}
+
+impl MountPoint {
+    /// Value of `mountpoint` property.
+    pub fn as_nv_value(&self) -> String {
+        match self {
+            MountPoint::Path(path) => path.to_string_lossy().into_owned(),
+            MountPoint::Legacy => String::from("legacy"),
+            MountPoint::None => String::from("none"),
+        }
+    }
+}
+
 /// Controls the behavior of synchronous requests.
 #[derive(AsRefStr, EnumString, Display, Eq, PartialEq, Debug, Clone, Copy)]
 #[repr(u64)]
@@ -277,7 +304,8 @@ pub enum VolumeMode {
     #[strum(serialize = "default")]
     Default = 0,
     /// Volumes with this property are exposed as [`geom(4)`](https://www.freebsd.org/cgi/man.cgi?geom(4)) device.
-    #[strum(serialize = "geom")]
+    /// ZoL calls it `full`.
+    #[strum(to_string = "geom", serialize = "full")]
     GEOM    = 1,
     /// Volumes with this property are exposed as cdev in devfs.
     #[strum(serialize = "dev")]
@@ -418,6 +446,64 @@ pub enum DnodeSize {
 impl Default for DnodeSize {
     fn default() -> Self { DnodeSize::Legacy }
 }
+
+/// Controls the encryption cipher suite used for a dataset. Can only be set at creation time.
+/// Children of an encrypted dataset inherit its encryption unless told otherwise.
+#[derive(AsRefStr, EnumString, Display, Eq, PartialEq, Debug, Clone, Copy)]
+#[repr(u64)]
+pub enum Encryption {
+    /// Use value from the parent
+    #[strum(serialize = "inherit")]
+    Inherit   = 0,
+    /// Use default cipher suite. Currently, it is `aes-256-gcm`.
+    #[strum(serialize = "on")]
+    On        = 1,
+    /// Disable encryption. Not allowed under encrypted parent.
+    #[strum(serialize = "off")]
+    Off       = 2,
+    #[strum(serialize = "aes-128-ccm")]
+    Aes128Ccm = 3,
+    #[strum(serialize = "aes-192-ccm")]
+    Aes192Ccm = 4,
+    #[strum(serialize = "aes-256-ccm")]
+    Aes256Ccm = 5,
+    #[strum(serialize = "aes-128-gcm")]
+    Aes128Gcm = 6,
+    #[strum(serialize = "aes-192-gcm")]
+    Aes192Gcm = 7,
+    #[strum(serialize = "aes-256-gcm")]
+    Aes256Gcm = 8,
+}
+
+impl Default for Encryption {
+    fn default() -> Self { Encryption::Off }
+}
+
+/// Controls whether extended attributes are enabled and how they are stored.
+#[derive(AsRefStr, EnumString, Display, Eq, PartialEq, Debug, Clone, Copy)]
+pub enum XAttr {
+    /// Extended attributes are disabled.
+    #[strum(serialize = "off")]
+    Off,
+    /// Extended attributes are enabled, stored the platform's default way.
+    #[strum(serialize = "on")]
+    On,
+    /// Extended attributes are stored in hidden directories, one file per attribute.
+    #[strum(serialize = "dir")]
+    Dir,
+    /// Extended attributes are stored as system attributes in dnodes. Linux only.
+    #[strum(serialize = "sa")]
+    Sa,
+}
+
+impl XAttr {
+    /// Whether extended attributes are enabled at all.
+    pub fn is_enabled(self) -> bool { self != XAttr::Off }
+}
+
+impl Default for XAttr {
+    fn default() -> Self { XAttr::On }
+}
 /// Most of native properties of filesystem dataset - both immutable and mutable. Default values
 /// taken from FreeBSD 12.
 ///
@@ -439,6 +525,14 @@ pub struct FilesystemProperties {
     acl_mode:                Option<AclMode>,
     /// Controls whether the access time for files updated when they are read.
     atime:                   bool,
+    /// Access time is only updated if it's older than modification or change time, or wasn't
+    /// updated for a day. Only has effect when `atime` is on. Linux only.
+    #[builder(default)]
+    relatime:                bool,
+    /// Allow mounting on a busy directory or a directory which already contains files. `None` on
+    /// platforms without this property.
+    #[builder(default)]
+    overlay:                 Option<bool>,
     /// Read-only property that identifies the amount of disk space available to a dataset and all
     /// its children, assuming no other activity in the pool. Because disk space shared within a
     /// pool, available space can be limited by various factors including physical pool size,
@@ -477,9 +571,27 @@ pub struct FilesystemProperties {
     /// Specifies a compatibility mode or literal value for the size of dnodes in the file system.
     #[builder(default)]
     dnode_size:              DnodeSize,
+    /// Encryption cipher suite used by the dataset.
+    #[builder(default)]
+    encryption:              Encryption,
+    /// Dataset that holds the encryption key of this dataset. `None` if dataset isn't encrypted.
+    #[builder(default)]
+    encryption_root:         Option<PathBuf>,
     /// Controls whether programs in a file system allowed to be executed. Also, when set to
     /// `false`, `mmap(2)` calls with `PROT_EXEC` disallowed.
     exec:                    bool,
+    /// SELinux context used for all files in the file system. `None` if set to `none`.
+    #[builder(default)]
+    context:                 Option<String>,
+    /// SELinux context of the file system itself. `None` if set to `none`.
+    #[builder(default)]
+    fs_context:              Option<String>,
+    /// SELinux context used for unlabeled files. `None` if set to `none`.
+    #[builder(default)]
+    def_context:             Option<String>,
+    /// SELinux context of the root inode of the file system. `None` if set to `none`.
+    #[builder(default)]
+    root_context:            Option<String>,
     /// The total number of filesystems that exist under this location in the dataset tree.  This
     /// value is only available when a filesystem_limit has been set somewhere in the tree under
     /// which the dataset resides.
@@ -513,7 +625,7 @@ pub struct FilesystemProperties {
     readonly:                bool,
     /// Specifies a suggested block size for files in a file system in bytes. The size specified
     /// must be a power of two greater than or equal to 512 and less than or equal to 128 KiB.
-    /// If the large_blocks feature is enabled on the pool, the size may be up to 1 MiB.
+    /// If the large_blocks feature is enabled on the pool, the size may be up to 16 MiB.
     record_size:             u64,
     /// Controls what types of metadata are stored redundantly
     redundant_metadata:      RedundantMetadata,
@@ -559,8 +671,8 @@ pub struct FilesystemProperties {
     /// Read-only property that identifies the amount of disk space is consumed by snapshots of a
     /// dataset.
     used_by_snapshots:       u64,
-    /// Indicates whether extended attributes are enabled or disabled.
-    xattr:                   bool,
+    /// Indicates whether extended attributes are enabled and how they are stored.
+    xattr:                   XAttr,
     /// Controls whether the dataset is managed from a jail.
     #[builder(default)]
     jailed:                  Option<bool>,
@@ -651,6 +763,12 @@ pub struct VolumeProperties {
     /// Configures deduplication for a dataset.
     #[builder(default)]
     dedup:                   Dedup,
+    /// Encryption cipher suite used by the dataset.
+    #[builder(default)]
+    encryption:              Encryption,
+    /// Dataset that holds the encryption key of this dataset. `None` if dataset isn't encrypted.
+    #[builder(default)]
+    encryption_root:         Option<PathBuf>,
     /// GUID of the dataset
     #[builder(default)]
     guid:                    Option<u64>,
@@ -765,8 +883,8 @@ pub struct SnapshotProperties {
     exec:                  bool,
     /// Controls whether the `setuid` bit is honored in a file system.
     setuid:                bool,
-    /// Indicates whether extended attributes are enabled or disabled.
-    xattr:                 bool,
+    /// Indicates whether extended attributes are enabled and how they are stored.
+    xattr:                 XAttr,
     /// Version (should 5)
     version:               u64,
     /// Indicates whether the file system should reject file names that include characters that are
@@ -879,11 +997,109 @@ pub enum Properties {
     Unknown(HashMap<String, String>),
 }
 
+/// Where value of a property comes from, `SOURCE` column of `zfs get`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertySource {
+    /// Set on the dataset itself.
+    Local,
+    /// Inherited from the given ancestor.
+    Inherited(PathBuf),
+    /// Nobody set it, default value is in effect.
+    Default,
+    /// Set by `zfs receive` from the stream.
+    Received,
+    /// Set for the current mount only with `mount -o`, not persisted.
+    Temporary,
+    /// Read-only property, it has no source.
+    None,
+}
+
+impl PropertySource {
+    fn from_str(source: &str) -> PropertySource {
+        match source {
+            "local" => PropertySource::Local,
+            "default" => PropertySource::Default,
+            "received" => PropertySource::Received,
+            "temporary" => PropertySource::Temporary,
+            _ if source.starts_with("inherited from ") => {
+                PropertySource::Inherited(PathBuf::from(&source["inherited from ".len()..]))
+            },
+            _ => PropertySource::None,
+        }
+    }
+}
+
+/// Value of a property together with where it comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct PropertyValue {
+    /// Value as `zfs get -p` prints it.
+    value:  String,
+    /// Where value comes from.
+    source: PropertySource,
+}
+
+impl PropertyValue {
+    /// Parse output of `zfs get -Hp -o name,property,value,source` into map of property name to
+    /// its value.
+    pub(crate) fn from_stdout(stdout: &str) -> Result<HashMap<String, PropertyValue>> {
+        let pairs = ZfsParser::parse(Rule::property_rows, stdout)
+            .map_err(|_| Error::UnknownSoFar(String::from(stdout)))?;
+        let mut ret = HashMap::new();
+        for row in pairs.flat_map(|pair| pair.into_inner()) {
+            let mut fields = row.into_inner().map(|field| field.as_str()).skip(1);
+            if let (Some(property), Some(value), Some(source)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                let source = PropertySource::from_str(source);
+                ret.insert(
+                    property.to_string(),
+                    PropertyValue { value: value.to_string(), source },
+                );
+            }
+        }
+        Ok(ret)
+    }
+}
+
 impl_zfs_prop!(AclInheritMode, "aclinherit");
 impl_zfs_prop!(AclMode, "aclmode");
 impl_zfs_prop!(CanMount, "canmount");
 impl_zfs_prop!(Checksum, "checksum");
 impl_zfs_prop!(Compression, "compression");
 impl_zfs_prop!(Copies, "copies");
+impl_zfs_prop!(Encryption, "encryption");
 impl_zfs_prop!(SnapDir, "snapdir");
 impl_zfs_prop!(VolumeMode, "volmod");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_property_value_from_stdout() {
+        let stdout = "tank/usr\tcompression\tlz4\tinherited from tank\n\
+                      tank/usr\tatime\toff\tlocal\n\
+                      tank/usr\trecordsize\t131072\tdefault\n\
+                      tank/usr\tused\t24576\t-\n\
+                      tank/usr\tcom.example:owner\tbob\treceived\n\
+                      tank/usr\treadonly\ton\ttemporary\n\
+                      tank/usr\tcomment\t\t-\n";
+        let props = PropertyValue::from_stdout(stdout).unwrap();
+        assert_eq!(7, props.len());
+
+        let compression = &props["compression"];
+        assert_eq!("lz4", compression.value());
+        assert_eq!(&PropertySource::Inherited(PathBuf::from("tank")), compression.source());
+        assert_eq!(&PropertySource::Local, props["atime"].source());
+        assert_eq!(&PropertySource::Default, props["recordsize"].source());
+        assert_eq!("24576", props["used"].value());
+        assert_eq!(&PropertySource::None, props["used"].source());
+        assert_eq!(&PropertySource::Received, props["com.example:owner"].source());
+        assert_eq!(&PropertySource::Temporary, props["readonly"].source());
+        assert_eq!("", props["comment"].value());
+
+        assert!(PropertyValue::from_stdout("").unwrap().is_empty());
+        assert!(PropertyValue::from_stdout("tank\tatime\n").is_err());
+    }
+}
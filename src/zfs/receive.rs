@@ -0,0 +1,206 @@
+//! Options used when receiving a stream with `zfs receive`.
+//!
+//! ### Examples
+//!
+//! ```rust
+//! use libzetta::zfs::ReceiveOptions;
+//! use std::path::PathBuf;
+//!
+//! let options = ReceiveOptions::builder()
+//!     .origin(PathBuf::from("tank/base@golden"))
+//!     .unmounted(true)
+//!     .build()
+//!     .unwrap();
+//! ```
+use std::{ffi::OsString, path::PathBuf};
+
+use pest::{iterators::Pair, Parser};
+
+use crate::{parsers::zfs::{Rule, ZfsParser},
+            zfs::{Error, RecvFlags, Result}};
+
+#[derive(Default, Builder, Debug, Clone, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+/// Options for `zfs receive`.
+pub struct ReceiveOptions {
+    /// Receive flags. Without `FORCE`, receiving incremental stream onto destination that was
+    /// modified since its most recent snapshot fails with
+    /// [`Error::DestinationModified`](enum.Error.html#variant.DestinationModified).
+    #[builder(default)]
+    flags:        RecvFlags,
+    /// Receive as a clone of given snapshot. Incremental stream must be based on this snapshot,
+    /// received dataset shares blocks with it.
+    #[builder(default)]
+    origin:       Option<PathBuf>,
+    /// `keylocation` of received encryption root. Only applies to raw streams of encrypted
+    /// datasets: these are received without a key and stay locked until the key is loaded with
+    /// [`ZfsEngine::load_key`](trait.ZfsEngine.html#method.load_key).
+    #[builder(default)]
+    key_location: Option<String>,
+}
+
+impl ReceiveOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> ReceiveOptionsBuilder { ReceiveOptionsBuilder::default() }
+
+    /// Make args for `zfs receive`.
+    pub(crate) fn into_args(self) -> Vec<OsString> {
+        let mut ret = Vec::with_capacity(5);
+        if self.flags.contains(RecvFlags::FORCE) {
+            ret.push("-F".into());
+        }
+        if self.flags.contains(RecvFlags::UNMOUNTED) {
+            ret.push("-u".into());
+        }
+        if self.flags.contains(RecvFlags::RESUMABLE) {
+            ret.push("-s".into());
+        }
+        if self.flags.contains(RecvFlags::HEAL) {
+            ret.push("-c".into());
+        }
+        if let Some(origin) = self.origin {
+            let mut arg = OsString::from("origin=");
+            arg.push(origin);
+            ret.push("-o".into());
+            ret.push(arg);
+        }
+        if let Some(key_location) = self.key_location {
+            ret.push("-o".into());
+            ret.push(format!("keylocation={}", key_location).into());
+        }
+        ret
+    }
+}
+
+impl ReceiveOptionsBuilder {
+    /// Set or clear `RecvFlags::FORCE`.
+    pub fn force(&mut self, value: bool) -> &mut Self { self.flag(RecvFlags::FORCE, value) }
+
+    /// Set or clear `RecvFlags::UNMOUNTED`.
+    pub fn unmounted(&mut self, value: bool) -> &mut Self { self.flag(RecvFlags::UNMOUNTED, value) }
+
+    /// Set or clear `RecvFlags::RESUMABLE`.
+    pub fn resumable(&mut self, value: bool) -> &mut Self { self.flag(RecvFlags::RESUMABLE, value) }
+
+    fn flag(&mut self, flag: RecvFlags, value: bool) -> &mut Self {
+        let mut flags = self.flags.unwrap_or_default();
+        flags.set(flag, value);
+        self.flags = Some(flags);
+        self
+    }
+}
+
+/// Kind of stream in `zfs receive` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    /// Full stream, destination must not exist (unless forced).
+    Full,
+    /// Incremental stream, most recent snapshot of destination (or origin) must be its source.
+    Incremental,
+}
+
+/// Snapshot a stream would create, as reported by `zfs receive -n -v`. Replication stream has one
+/// of these per snapshot in it.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ReceivedStream {
+    /// Whether it's full or incremental stream.
+    kind:        StreamKind,
+    /// Snapshot the stream was sent from.
+    source:      PathBuf,
+    /// Snapshot it would be received as.
+    destination: PathBuf,
+}
+
+impl ReceivedStream {
+    /// Parse `would receive ... stream of ... into ...` lines, other lines are ignored.
+    pub(crate) fn from_stdout(stdout: &str) -> Result<Vec<ReceivedStream>> {
+        let pairs = ZfsParser::parse(Rule::would_receive_lines, stdout)
+            .map_err(|_| Error::UnknownSoFar(String::from(stdout)))?;
+        Ok(pairs
+            .flat_map(Pair::into_inner)
+            .filter(|pair| pair.as_rule() == Rule::would_receive)
+            .filter_map(ReceivedStream::from_pair)
+            .collect())
+    }
+
+    fn from_pair(pair: Pair<'_, Rule>) -> Option<ReceivedStream> {
+        let mut inner = pair.into_inner();
+        let kind = match inner.next()?.as_str() {
+            "incremental" => StreamKind::Incremental,
+            _ => StreamKind::Full,
+        };
+        let source = PathBuf::from(inner.next()?.as_str());
+        let destination = PathBuf::from(inner.next()?.as_str());
+        Some(ReceivedStream { kind, source, destination })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_into_args() {
+        assert!(ReceiveOptions::default().into_args().is_empty());
+
+        let options = ReceiveOptions::builder()
+            .force(true)
+            .unmounted(true)
+            .resumable(true)
+            .origin(PathBuf::from("tank/base@golden"))
+            .key_location(String::from("file:///etc/zfs/backup.key"))
+            .build()
+            .unwrap();
+        let expected: Vec<OsString> = vec![
+            "-F".into(),
+            "-u".into(),
+            "-s".into(),
+            "-o".into(),
+            "origin=tank/base@golden".into(),
+            "-o".into(),
+            "keylocation=file:///etc/zfs/backup.key".into(),
+        ];
+        assert_eq!(expected, options.into_args());
+    }
+
+    #[test]
+    fn test_flags() {
+        let options = ReceiveOptions::builder()
+            .flags(RecvFlags::FORCE | RecvFlags::HEAL)
+            .unmounted(true)
+            .force(false)
+            .build()
+            .unwrap();
+        assert_eq!(&(RecvFlags::UNMOUNTED | RecvFlags::HEAL), options.flags());
+        let expected: Vec<OsString> = vec!["-u".into(), "-c".into()];
+        assert_eq!(expected, options.into_args());
+    }
+
+    #[test]
+    fn test_parse_would_receive() {
+        let stdout = "would receive full stream of tank/src@a into backup/dst@a
+would receive incremental stream of tank/src@b into backup/dst@b
+would receive incremental stream of tank/src/child@b into backup/dst/child@b
+";
+        let streams = ReceivedStream::from_stdout(stdout).unwrap();
+        assert_eq!(3, streams.len());
+        assert_eq!(&StreamKind::Full, streams[0].kind());
+        assert_eq!(&PathBuf::from("tank/src@a"), streams[0].source());
+        assert_eq!(&PathBuf::from("backup/dst@a"), streams[0].destination());
+        assert_eq!(&StreamKind::Incremental, streams[2].kind());
+        assert_eq!(&PathBuf::from("tank/src/child@b"), streams[2].source());
+        assert_eq!(&PathBuf::from("backup/dst/child@b"), streams[2].destination());
+    }
+
+    #[test]
+    fn test_parse_would_receive_other_lines() {
+        assert!(ReceivedStream::from_stdout("").unwrap().is_empty());
+        let stdout = "would destroy backup/dst@old
+would receive incremental stream of tank/src@b into backup/dst@b";
+        let streams = ReceivedStream::from_stdout(stdout).unwrap();
+        assert_eq!(1, streams.len());
+        assert_eq!(&PathBuf::from("backup/dst@b"), streams[0].destination());
+    }
+}
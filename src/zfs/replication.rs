@@ -0,0 +1,68 @@
+//! Outcome of [`ZfsEngine::replicate`](../trait.ZfsEngine.html#method.replicate) and the pipe
+//! stream goes through.
+use std::{fs::File, io, os::unix::io::FromRawFd, path::PathBuf};
+
+/// What [`replicate`](../trait.ZfsEngine.html#method.replicate) did.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ReplicationReport {
+    /// Snapshot that was sent, as it's named on the source.
+    snapshot: PathBuf,
+    /// The same snapshot as it's named on the destination.
+    received: PathBuf,
+    /// Incremental source, `None` if full stream was sent.
+    from:     Option<PathBuf>,
+    /// Size of the stream. Zero if destination already had the snapshot and nothing was sent.
+    bytes:    u64,
+    /// Bookmark created on the source to be incremental source of the next run.
+    bookmark: Option<PathBuf>,
+}
+
+impl ReplicationReport {
+    pub(crate) fn new(snapshot: PathBuf, received: PathBuf, from: Option<PathBuf>) -> Self {
+        ReplicationReport { snapshot, received, from, bytes: 0, bookmark: None }
+    }
+
+    /// Destination has the snapshot already, there was nothing to send.
+    pub fn is_up_to_date(&self) -> bool { self.from.as_ref() == Some(&self.snapshot) }
+
+    pub(crate) fn set_bytes(&mut self, bytes: u64) { self.bytes = bytes; }
+
+    pub(crate) fn set_bookmark(&mut self, bookmark: PathBuf) { self.bookmark = Some(bookmark); }
+}
+
+/// Pipe with both ends closed on exec, so `zfs receive` started by one engine doesn't keep the
+/// write end of the other one open and never sees end of the stream.
+pub(crate) fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_pipe() {
+        let (mut reader, mut writer) = pipe().unwrap();
+        writer.write_all(b"stream").unwrap();
+        drop(writer);
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).unwrap();
+        assert_eq!("stream", buf);
+    }
+
+    #[test]
+    fn test_up_to_date() {
+        let snapshot = PathBuf::from("tank/usr@b");
+        let received = PathBuf::from("backup/usr@b");
+        let report = ReplicationReport::new(snapshot.clone(), received.clone(), None);
+        assert!(!report.is_up_to_date());
+        let report = ReplicationReport::new(snapshot.clone(), received, Some(snapshot));
+        assert!(report.is_up_to_date());
+    }
+}
@@ -0,0 +1,140 @@
+//! Resumable send support. Receive resume token is an opaque compressed nvlist, the easiest
+//! portable way to look inside of it is `zfs send -nvt <token>`.
+use std::path::PathBuf;
+
+use crate::zfs::{Error, Result, SendFlags};
+
+/// Decoded content of `receive_resume_token`.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ResumeToken {
+    /// Snapshot that was being sent.
+    to_name:   PathBuf,
+    /// GUID of the snapshot that was being sent.
+    to_guid:   u64,
+    /// GUID of incremental source, `None` for full streams.
+    from_guid: Option<u64>,
+    /// Object the send will resume from.
+    object:    u64,
+    /// Offset within `object` the send will resume from.
+    offset:    u64,
+    /// Amount of bytes already received.
+    bytes:     u64,
+    /// Flags the original send was made with.
+    flags:     SendFlags,
+}
+
+impl ResumeToken {
+    /// Parse output of `zfs send -nvt <token>`.
+    pub(crate) fn from_stdout(stdout: &str) -> Result<ResumeToken> {
+        let mut to_name = None;
+        let mut to_guid = None;
+        let mut from_guid = None;
+        let mut object = 0;
+        let mut offset = 0;
+        let mut bytes = 0;
+        let mut flags = SendFlags::empty();
+
+        for line in stdout.lines() {
+            let mut kv = line.trim().splitn(2, " = ");
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key, value.trim()),
+                _ => continue,
+            };
+            match key {
+                "toname" => to_name = Some(PathBuf::from(value)),
+                "toguid" => to_guid = Some(parse_nv_u64(value)?),
+                "fromguid" => from_guid = Some(parse_nv_u64(value)?),
+                "object" => object = parse_nv_u64(value)?,
+                "offset" => offset = parse_nv_u64(value)?,
+                "bytes" => bytes = parse_nv_u64(value)?,
+                "embedok" => flags |= SendFlags::LZC_SEND_FLAG_EMBED_DATA,
+                "largeblockok" => flags |= SendFlags::LZC_SEND_FLAG_LARGE_BLOCK,
+                "compressok" => flags |= SendFlags::LZC_SEND_FLAG_COMPRESS,
+                "rawok" => flags |= SendFlags::LZC_SEND_FLAG_RAW,
+                _ => {},
+            }
+        }
+
+        match (to_name, to_guid) {
+            (Some(to_name), Some(to_guid)) => {
+                Ok(ResumeToken { to_name, to_guid, from_guid, object, offset, bytes, flags })
+            },
+            _ => Err(Error::UnknownSoFar(String::from(stdout))),
+        }
+    }
+
+    /// Bytes left to send given estimated size of the whole stream. Never underflows: a token of
+    /// a completed (or over-estimated) stream yields 0.
+    pub fn remaining(&self, total: u64) -> u64 { total.saturating_sub(self.bytes) }
+}
+
+/// `nvlist_print` prints integers either as hex or as decimal depending on type.
+fn parse_nv_u64(value: &str) -> Result<u64> {
+    let parsed =
+        if value.starts_with("0x") { u64::from_str_radix(&value[2..], 16) } else { value.parse() };
+    parsed.map_err(|_| Error::UnknownSoFar(String::from(value)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static MID_STREAM: &str = "resume token contents:
+nvlist version: 0
+	object = 0x80
+	offset = 0x4e0000
+	bytes = 0x50b2d0
+	toguid = 0x3b6d3b7a4e8b0f21
+	toname = tank/data@snap1
+	compressok = 1
+	largeblockok = 1
+";
+
+    #[test]
+    fn test_decode_mid_stream() {
+        let token = ResumeToken::from_stdout(MID_STREAM).unwrap();
+        assert_eq!(&PathBuf::from("tank/data@snap1"), token.to_name());
+        assert_eq!(&0x3b6d_3b7a_4e8b_0f21, token.to_guid());
+        assert_eq!(&None, token.from_guid());
+        assert_eq!(&0x80, token.object());
+        assert_eq!(&0x4e_0000, token.offset());
+        assert_eq!(&5_288_656, token.bytes());
+        let expected_flags =
+            SendFlags::LZC_SEND_FLAG_COMPRESS | SendFlags::LZC_SEND_FLAG_LARGE_BLOCK;
+        assert_eq!(&expected_flags, token.flags());
+
+        let total = 10 * 1024 * 1024;
+        let remaining = token.remaining(total);
+        assert!(remaining > 0 && remaining < total);
+        assert_eq!(total - 5_288_656, remaining);
+    }
+
+    #[test]
+    fn test_completed_token() {
+        let token = ResumeToken::from_stdout(MID_STREAM).unwrap();
+        assert_eq!(0, token.remaining(*token.bytes()));
+        assert_eq!(0, token.remaining(1024));
+    }
+
+    #[test]
+    fn test_incremental_token() {
+        let stdout = "resume token contents:
+nvlist version: 0
+	fromguid = 0x1f
+	object = 0x1
+	offset = 0x0
+	bytes = 0x0
+	toguid = 0x20
+	toname = tank/data@snap2
+";
+        let token = ResumeToken::from_stdout(stdout).unwrap();
+        assert_eq!(&Some(0x1f), token.from_guid());
+        assert_eq!(&PathBuf::from("tank/data@snap2"), token.to_name());
+    }
+
+    #[test]
+    fn test_garbage() {
+        assert!(ResumeToken::from_stdout("cannot resume send: invalid token\n").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Metadata of a single snapshot that retention logic and replication tools usually need.
+use std::{collections::HashSet,
+          path::PathBuf,
+          time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Metadata of a snapshot. Sizes are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct SnapshotInfo {
+    /// Full name of the snapshot.
+    name:       PathBuf,
+    /// When snapshot was created.
+    creation:   SystemTime,
+    /// GUID of the snapshot. Stays the same after send and receive, so it can be used to match
+    /// snapshots on both sides.
+    guid:       u64,
+    /// Space that would be freed if snapshot was destroyed.
+    used:       u64,
+    /// Amount of data accessible by snapshot.
+    referenced: u64,
+    /// Space written to the dataset between previous snapshot and this one.
+    written:    u64,
+}
+
+impl SnapshotInfo {
+    /// Parse values of `creation,guid,used,referenced,written` as printed by `zfs get -Hp`.
+    /// Creation is seconds since Unix epoch, so time zone doesn't matter.
+    pub(crate) fn from_values(name: PathBuf, values: &[&str]) -> Option<SnapshotInfo> {
+        if values.len() != 5 {
+            return None;
+        }
+        let numbers: Vec<u64> = values.iter().filter_map(|v| v.trim().parse().ok()).collect();
+        if numbers.len() != 5 {
+            return None;
+        }
+        Some(SnapshotInfo {
+            name,
+            creation: UNIX_EPOCH + Duration::from_secs(numbers[0]),
+            guid: numbers[1],
+            used: numbers[2],
+            referenced: numbers[3],
+            written: numbers[4],
+        })
+    }
+
+    /// Find the newest snapshot of `source` that `destination` has as well. Snapshots are matched
+    /// by GUID, so names don't have to be the same. Both lists are expected to be ordered by
+    /// creation, oldest first.
+    pub fn newest_common<'a>(
+        source: &'a [SnapshotInfo],
+        destination: &[SnapshotInfo],
+    ) -> Option<&'a SnapshotInfo> {
+        let guids: HashSet<u64> = destination.iter().map(|snapshot| snapshot.guid).collect();
+        source.iter().rev().find(|snapshot| guids.contains(&snapshot.guid))
+    }
+
+    /// Parse output of `zfs list -Hp -o name,creation,guid,used,referenced,written`, one snapshot
+    /// per line.
+    pub(crate) fn from_list_stdout(stdout: &str) -> Option<Vec<SnapshotInfo>> {
+        stdout
+            .lines()
+            .map(|line| {
+                let mut values = line.split('\t');
+                let name = PathBuf::from(values.next()?);
+                SnapshotInfo::from_values(name, &values.collect::<Vec<_>>())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_values() {
+        let values = ["1531943675", "8670277898870184975", "16384", "2781577216", "8192"];
+        let info = SnapshotInfo::from_values(PathBuf::from("z/usr@a"), &values).unwrap();
+        assert_eq!(&PathBuf::from("z/usr@a"), info.name());
+        assert_eq!(&(UNIX_EPOCH + Duration::from_secs(1_531_943_675)), info.creation());
+        assert_eq!(&8_670_277_898_870_184_975, info.guid());
+        assert_eq!(&16384, info.used());
+        assert_eq!(&2_781_577_216, info.referenced());
+        assert_eq!(&8192, info.written());
+
+        assert_eq!(None, SnapshotInfo::from_values(PathBuf::from("z/usr@a"), &values[..4]));
+        let values = ["-", "1", "2", "3", "4"];
+        assert_eq!(None, SnapshotInfo::from_values(PathBuf::from("z/usr@a"), &values));
+    }
+
+    #[test]
+    fn test_newest_common() {
+        let snapshot = |name: &str, creation: &str, guid: &str| {
+            SnapshotInfo::from_values(PathBuf::from(name), &[creation, guid, "0", "0", "0"])
+                .unwrap()
+        };
+        let source = vec![
+            snapshot("tank/src@a", "100", "1"),
+            snapshot("tank/src@b", "200", "2"),
+            snapshot("tank/src@c", "300", "3"),
+        ];
+        let destination =
+            vec![snapshot("backup/dst@a", "400", "1"), snapshot("backup/dst@renamed", "500", "2")];
+        let common = SnapshotInfo::newest_common(&source, &destination).unwrap();
+        assert_eq!(&PathBuf::from("tank/src@b"), common.name());
+
+        let unrelated = vec![snapshot("backup/other@c", "300", "42")];
+        assert_eq!(None, SnapshotInfo::newest_common(&source, &unrelated));
+        assert_eq!(None, SnapshotInfo::newest_common(&source, &[]));
+    }
+
+    #[test]
+    fn test_from_list_stdout() {
+        let stdout = "z/usr@a\t1531943675\t8670277898870184975\t16384\t2781577216\t8192
+z/usr@b\t1531943700\t1234\t0\t2781577216\t0
+";
+        let snapshots = SnapshotInfo::from_list_stdout(stdout).unwrap();
+        assert_eq!(2, snapshots.len());
+        assert_eq!(&PathBuf::from("z/usr@a"), snapshots[0].name());
+        assert_eq!(&PathBuf::from("z/usr@b"), snapshots[1].name());
+        assert_eq!(&1234, snapshots[1].guid());
+
+        assert!(SnapshotInfo::from_list_stdout("").unwrap().is_empty());
+        assert_eq!(None, SnapshotInfo::from_list_stdout("z/usr@a\t1531943675\n"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Space accounting of datasets. Meant for monitoring: take a snapshot of space usage of the whole
+//! tree with one call and compare it with previous one.
+use std::{collections::HashMap, convert::TryFrom, path::PathBuf};
+
+/// Space usage of a single dataset. All values except `compress_ratio` are in bytes.
+#[derive(Debug, Clone, PartialEq, Getters, Builder)]
+#[get = "pub"]
+pub struct SpaceUsage {
+    /// Amount of disk space consumed by dataset and all its descendents.
+    used:              u64,
+    /// Amount of disk space available to dataset and all its children.
+    available:         u64,
+    /// Amount of data that is accessible by dataset, which may or may not be shared with other
+    /// datasets in the pool.
+    referenced:        u64,
+    /// Amount of disk space consumed by snapshots of dataset.
+    used_by_snapshots: u64,
+    /// Amount of disk space consumed by children of dataset.
+    used_by_children:  u64,
+    /// Compression ratio achieved for the used space of dataset.
+    compress_ratio:    f64,
+}
+
+impl SpaceUsage {
+    /// Create a builder.
+    pub fn builder() -> SpaceUsageBuilder { SpaceUsageBuilder::default() }
+}
+
+/// Change of space usage of a single dataset between two polls.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpaceDelta {
+    /// Dataset appeared since previous poll.
+    Added(SpaceUsage),
+    /// Dataset is gone since previous poll. Holds last known usage.
+    Removed(SpaceUsage),
+    /// Dataset is present in both polls, but its usage changed. Values are signed differences in
+    /// bytes, saturated at `i64` bounds.
+    Changed {
+        used:              i64,
+        available:         i64,
+        referenced:        i64,
+        used_by_snapshots: i64,
+        used_by_children:  i64,
+        compress_ratio:    f64,
+    },
+}
+
+impl SpaceDelta {
+    /// Compute per-dataset changes between two snapshots of space usage. Datasets without any
+    /// changes are not included in the result.
+    ///
+    /// * `prev` - Older snapshot.
+    /// * `next` - Newer snapshot.
+    pub fn between(
+        prev: &HashMap<PathBuf, SpaceUsage>,
+        next: &HashMap<PathBuf, SpaceUsage>,
+    ) -> HashMap<PathBuf, SpaceDelta> {
+        let mut ret = HashMap::new();
+        for (name, usage) in next {
+            match prev.get(name) {
+                None => {
+                    ret.insert(name.clone(), SpaceDelta::Added(usage.clone()));
+                },
+                Some(old) if old != usage => {
+                    ret.insert(name.clone(), SpaceDelta::changed(old, usage));
+                },
+                Some(_) => {},
+            }
+        }
+        for (name, usage) in prev {
+            if !next.contains_key(name) {
+                ret.insert(name.clone(), SpaceDelta::Removed(usage.clone()));
+            }
+        }
+        ret
+    }
+
+    fn changed(old: &SpaceUsage, new: &SpaceUsage) -> SpaceDelta {
+        SpaceDelta::Changed {
+            used:              diff(old.used, new.used),
+            available:         diff(old.available, new.available),
+            referenced:        diff(old.referenced, new.referenced),
+            used_by_snapshots: diff(old.used_by_snapshots, new.used_by_snapshots),
+            used_by_children:  diff(old.used_by_children, new.used_by_children),
+            compress_ratio:    new.compress_ratio - old.compress_ratio,
+        }
+    }
+}
+
+fn diff(old: u64, new: u64) -> i64 {
+    let diff = i128::from(new) - i128::from(old);
+    i64::try_from(diff).unwrap_or(if diff > 0 { i64::MAX } else { i64::MIN })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn usage(used: u64, available: u64) -> SpaceUsage {
+        SpaceUsage::builder()
+            .used(used)
+            .available(available)
+            .referenced(used)
+            .used_by_snapshots(0)
+            .used_by_children(0)
+            .compress_ratio(1.0)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_delta_between() {
+        let mut prev = HashMap::new();
+        prev.insert(PathBuf::from("z/usr"), usage(100, 900));
+        prev.insert(PathBuf::from("z/var"), usage(200, 800));
+        prev.insert(PathBuf::from("z/tmp"), usage(300, 700));
+
+        let mut next = HashMap::new();
+        next.insert(PathBuf::from("z/usr"), usage(100, 900));
+        next.insert(PathBuf::from("z/var"), usage(150, 850));
+        next.insert(PathBuf::from("z/home"), usage(50, 950));
+
+        let delta = SpaceDelta::between(&prev, &next);
+        assert_eq!(3, delta.len());
+        assert!(!delta.contains_key(&PathBuf::from("z/usr")));
+
+        let expected = SpaceDelta::Changed {
+            used:              -50,
+            available:         50,
+            referenced:        -50,
+            used_by_snapshots: 0,
+            used_by_children:  0,
+            compress_ratio:    0.0,
+        };
+        assert_eq!(Some(&expected), delta.get(&PathBuf::from("z/var")));
+        assert_eq!(Some(&SpaceDelta::Added(usage(50, 950))), delta.get(&PathBuf::from("z/home")));
+        assert_eq!(Some(&SpaceDelta::Removed(usage(300, 700))), delta.get(&PathBuf::from("z/tmp")));
+    }
+
+    #[test]
+    fn test_delta_between_empty() {
+        let mut next = HashMap::new();
+        next.insert(PathBuf::from("z"), usage(100, 900));
+
+        let delta = SpaceDelta::between(&HashMap::new(), &next);
+        assert_eq!(Some(&SpaceDelta::Added(usage(100, 900))), delta.get(&PathBuf::from("z")));
+
+        let delta = SpaceDelta::between(&next, &HashMap::new());
+        assert_eq!(Some(&SpaceDelta::Removed(usage(100, 900))), delta.get(&PathBuf::from("z")));
+
+        assert!(SpaceDelta::between(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn test_diff_saturates() {
+        assert_eq!(-50, diff(200, 150));
+        assert_eq!(i64::MAX, diff(0, u64::MAX));
+        assert_eq!(i64::MIN, diff(u64::MAX, 0));
+        assert_eq!(1, diff(u64::MAX - 1, u64::MAX));
+        assert_eq!(i64::MAX, diff(1, 1 << 63));
+    }
+}
@@ -1,13 +1,13 @@
 //! If anyone has a better name for this module - hit me up. This module is where consumer friendly
 //! representation of Zpool is defined. This is where pest's
 //! [Pairs](../../../pest/iterators/struct.Pair.html) turned into [Zpool](struct.Zpool.html).
-use std::{path::PathBuf, str::FromStr};
+use std::{fmt::Write, path::PathBuf, str::FromStr};
 
 use pest::iterators::{Pair, Pairs};
 
 use crate::{parsers::Rule,
             zpool::{vdev::{ErrorStatistics, Vdev, VdevType},
-                    CreateZpoolRequest, Disk, Health}};
+                    CreateZpoolRequest, Disk, Health, ScanStatus}};
 
 /// The reason why zpool is in this state. Right now it's just a wrapper around `String`, but in the
 /// future there _might_ be a more machine friendly format.
@@ -16,6 +16,204 @@ pub enum Reason {
     /// Not yet classified reason.
     Other(String),
 }
+
+impl Reason {
+    /// Classify the reason. See [`VdevFaultReason`](enum.VdevFaultReason.html).
+    pub fn fault_reason(&self) -> VdevFaultReason {
+        match self {
+            Reason::Other(text) => VdevFaultReason::from_text(text),
+        }
+    }
+}
+
+/// Why a device or vdev is out, as `zpool status` annotates it after error counters
+/// (`sdb  UNAVAIL  0 0 0  cannot open`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VdevFaultReason {
+    /// `cannot open`: device is missing or can't be opened.
+    CannotOpen,
+    /// `missing device`: some of top-level vdevs are missing.
+    MissingDevice,
+    /// `insufficient replicas`: not enough healthy children to keep vdev working.
+    InsufficientReplicas,
+    /// `corrupted data`: label or metadata on device is damaged.
+    CorruptedData,
+    /// `too many errors`: ZFS faulted the device after it returned too many I/O or checksum
+    /// errors.
+    TooManyErrors,
+    /// `experienced I/O failures`: pool I/O is suspended.
+    IoFailures,
+    /// `bad intent log`: log device is damaged or missing.
+    BadIntentLog,
+    /// `external device fault`: device was faulted by ZED or `zpool offline -f`.
+    ExternalFault,
+    /// `split into new pool`: device went away with `zpool split`.
+    SplitIntoNewPool,
+    /// `unsupported feature(s)`: pool uses features this system doesn't support.
+    UnsupportedFeatures,
+    /// `newer version`: on-disk format is newer than this system supports.
+    NewerVersion,
+    /// `currently in use`: hot spare is already used in place of another device.
+    SpareInUse,
+    /// `all children offline`.
+    AllChildrenOffline,
+    /// `was /dev/sdb`: device is referred to by GUID, it used to be at given path.
+    Was(PathBuf),
+    /// Any other annotation, e.g. `(resilvering)`.
+    Other(String),
+}
+
+impl VdevFaultReason {
+    /// Parse annotation from the end of device line of `zpool status`.
+    pub fn from_text(text: &str) -> VdevFaultReason {
+        let text = text.trim();
+        match text {
+            "cannot open" => VdevFaultReason::CannotOpen,
+            "missing device" => VdevFaultReason::MissingDevice,
+            "insufficient replicas" => VdevFaultReason::InsufficientReplicas,
+            "corrupted data" => VdevFaultReason::CorruptedData,
+            "too many errors" => VdevFaultReason::TooManyErrors,
+            "experienced I/O failures" => VdevFaultReason::IoFailures,
+            "bad intent log" => VdevFaultReason::BadIntentLog,
+            "external device fault" => VdevFaultReason::ExternalFault,
+            "split into new pool" => VdevFaultReason::SplitIntoNewPool,
+            "unsupported feature(s)" => VdevFaultReason::UnsupportedFeatures,
+            "newer version" => VdevFaultReason::NewerVersion,
+            "currently in use" => VdevFaultReason::SpareInUse,
+            "all children offline" => VdevFaultReason::AllChildrenOffline,
+            _ => match text.strip_prefix("was ") {
+                Some(path) => VdevFaultReason::Was(PathBuf::from(path)),
+                None => VdevFaultReason::Other(String::from(text)),
+            },
+        }
+    }
+}
+
+static FAULT_CODE_PREFIX: &str = "ZFS-8000-";
+
+/// Message ID from `see` field of `zpool status` and `zpool import`
+/// (`http://illumos.org/msg/ZFS-8000-8A`). Unlike `status` and `action` text, these stay the same
+/// across ZFS versions, so they are what alerts should be based on.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ZfsFaultCode {
+    /// `ZFS-8000-14`: `zpool.cache` is corrupted.
+    CorruptedCache,
+    /// `ZFS-8000-2Q`: Device is missing, pool keeps running on replicas.
+    MissingDeviceReplicated,
+    /// `ZFS-8000-3C`: Device is missing and there are no replicas.
+    MissingDeviceNonReplicated,
+    /// `ZFS-8000-4J`: Device label is corrupted, pool keeps running on replicas.
+    CorruptedLabelReplicated,
+    /// `ZFS-8000-5E`: Device label is corrupted and there are no replicas.
+    CorruptedLabelNonReplicated,
+    /// `ZFS-8000-6X`: Top-level vdev is missing.
+    MissingTopLevelVdev,
+    /// `ZFS-8000-72`: Pool metadata is corrupted.
+    CorruptedPoolMetadata,
+    /// `ZFS-8000-8A`: Data is corrupted, affected files are listed in `errors`.
+    CorruptedData,
+    /// `ZFS-8000-9P`: Device is failing, but data was repaired.
+    FailingDevice,
+    /// `ZFS-8000-A5`: Pool version or features are not supported.
+    VersionMismatch,
+    /// `ZFS-8000-CS`: Pool can't be opened.
+    PoolUnavailable,
+    /// `ZFS-8000-EY`: Pool was last accessed by another system.
+    HostIdMismatch,
+    /// `ZFS-8000-HC`: I/O failures, pool is suspended (`failmode=wait`).
+    IoFailureWait,
+    /// `ZFS-8000-JQ`: I/O failures, new writes fail (`failmode=continue`).
+    IoFailureContinue,
+    /// `ZFS-8000-K4`: Log device failed.
+    LogDeviceFailure,
+    /// `ZFS-8000-ER`: Pool is affected by a known on-disk format erratum.
+    Errata,
+    /// Code this library doesn't know yet, as is (`ZFS-8000-XX`).
+    Other(String),
+}
+
+impl ZfsFaultCode {
+    /// Take the code from the last segment of `see` URL. `None` if it isn't a ZFS message ID.
+    pub fn from_url(url: &str) -> Option<ZfsFaultCode> {
+        let code = url.trim().trim_end_matches('/').rsplit('/').next()?;
+        if !code.starts_with(FAULT_CODE_PREFIX) {
+            return None;
+        }
+        let ret = match &code[FAULT_CODE_PREFIX.len()..] {
+            "14" => ZfsFaultCode::CorruptedCache,
+            "2Q" => ZfsFaultCode::MissingDeviceReplicated,
+            "3C" => ZfsFaultCode::MissingDeviceNonReplicated,
+            "4J" => ZfsFaultCode::CorruptedLabelReplicated,
+            "5E" => ZfsFaultCode::CorruptedLabelNonReplicated,
+            "6X" => ZfsFaultCode::MissingTopLevelVdev,
+            "72" => ZfsFaultCode::CorruptedPoolMetadata,
+            "8A" => ZfsFaultCode::CorruptedData,
+            "9P" => ZfsFaultCode::FailingDevice,
+            "A5" => ZfsFaultCode::VersionMismatch,
+            "CS" => ZfsFaultCode::PoolUnavailable,
+            "EY" => ZfsFaultCode::HostIdMismatch,
+            "HC" => ZfsFaultCode::IoFailureWait,
+            "JQ" => ZfsFaultCode::IoFailureContinue,
+            "K4" => ZfsFaultCode::LogDeviceFailure,
+            "ER" => ZfsFaultCode::Errata,
+            _ => ZfsFaultCode::Other(String::from(code)),
+        };
+        Some(ret)
+    }
+}
+
+/// File or object with permanent errors, as listed by `zpool status -v`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DamagedEntry {
+    /// File in a mounted filesystem.
+    Path(PathBuf),
+    /// File in a filesystem that isn't mounted, or in a snapshot.
+    DatasetPath {
+        /// Filesystem or snapshot the file belongs to.
+        dataset: PathBuf,
+        /// Path of the file inside of the dataset.
+        path:    PathBuf,
+    },
+    /// Object that has no path: file was deleted since, or it's pool metadata.
+    Object {
+        /// Dataset name, `<metadata>` for pool metadata or object set id like `<0x21>` if dataset
+        /// was destroyed.
+        dataset: String,
+        /// Object number.
+        object:  u64,
+    },
+}
+
+impl DamagedEntry {
+    /// Parse a line of the list, without indentation.
+    pub fn from_line(line: &str) -> DamagedEntry {
+        let line = line.trim();
+        if let Some(idx) = line.rfind(":<0x") {
+            let object = line[idx + 4..].trim_end_matches('>');
+            if let Ok(object) = u64::from_str_radix(object, 16) {
+                return DamagedEntry::Object { dataset: String::from(&line[..idx]), object };
+            }
+        }
+        if !line.starts_with('/') {
+            if let Some(idx) = line.find(":/") {
+                return DamagedEntry::DatasetPath {
+                    dataset: PathBuf::from(&line[..idx]),
+                    path:    PathBuf::from(&line[idx + 1..]),
+                };
+            }
+        }
+        DamagedEntry::Path(PathBuf::from(line))
+    }
+
+    /// Whether it's pool metadata rather than user data.
+    pub fn is_metadata(&self) -> bool {
+        match self {
+            DamagedEntry::Object { dataset, .. } => dataset == "<metadata>",
+            _ => false,
+        }
+    }
+}
+
 /// Consumer friendly Zpool representation. It has generic health status information, structure of
 /// vdevs, devices used to create said vdevs as well as error statistics.
 #[derive(Getters, Builder, Debug, Eq, PartialEq, Clone)]
@@ -40,24 +238,106 @@ pub struct Zpool {
     /// Spare devices.
     #[builder(default)]
     spares:           Vec<Disk>,
+    /// Why pool isn't healthy, as `status` field explains it.
+    #[builder(default)]
+    status:           Option<String>,
     /// Value of action field what ever it is.
     #[builder(default)]
     action:           Option<String>,
+    /// URL from `see` field with more information about the problem.
+    #[builder(default)]
+    see:              Option<String>,
+    /// Fault code from `see` field. `None` for healthy pools.
+    #[builder(default)]
+    fault_code:       Option<ZfsFaultCode>,
     /// Errors?
     #[builder(default)]
     errors:           Option<String>,
+    /// Files and objects with permanent errors. Only listed by verbose
+    /// [`status`](trait.ZpoolEngine.html#tymethod.status).
+    #[builder(default)]
+    damaged:          Vec<DamagedEntry>,
     /// Reason why this Zpool is not healthy.
     #[builder(default)]
     reason:           Option<Reason>,
     /// Error statistics
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// State of the last scrub or resilver.
+    #[builder(default)]
+    scan:             ScanStatus,
+    /// Pool checkpoint as `zpool status` describes it, e.g. `created Fri Jan 10 12:00:00 2020,
+    /// consumes 1.20M` or `discarding`. `None` if pool has no checkpoint.
+    #[builder(default)]
+    checkpoint:       Option<String>,
 }
 
 impl Zpool {
     /// Create a builder - the preferred way to create a structure.
     pub fn builder() -> ZpoolBuilder { ZpoolBuilder::default() }
 
+    /// Render the layout the way `NAME` column of `zpool status` shows it: pool name, then
+    /// top-level vdevs indented by two spaces and their disks by four. Logs, caches and spares go
+    /// into their own sections. Top-level vdevs are numbered like ZFS does it (`mirror-0`,
+    /// `raidz2-1`), data vdevs first, then logs.
+    pub fn display_tree(&self) -> String {
+        let mut ret = String::new();
+        let _ = writeln!(ret, "{}", self.name);
+        let mut index = 0;
+        for vdev in &self.vdevs {
+            write_vdev(&mut ret, vdev, index);
+            index += 1;
+        }
+        if !self.logs.is_empty() {
+            ret.push_str("logs\n");
+            for vdev in &self.logs {
+                write_vdev(&mut ret, vdev, index);
+                index += 1;
+            }
+        }
+        write_disks(&mut ret, "cache", &self.caches);
+        write_disks(&mut ret, "spares", &self.spares);
+        ret
+    }
+
+    /// How many disk failures the pool can survive without losing data, when all disks are
+    /// healthy. Pool is as strong as its weakest top-level vdev, so it's the lowest
+    /// [`Vdev::fault_tolerance`](struct.Vdev.html#method.fault_tolerance) of data vdevs. Logs,
+    /// caches and spares aren't taken into account.
+    pub fn fault_tolerance(&self) -> usize {
+        self.vdevs.iter().map(Vdev::fault_tolerance).min().unwrap_or(0)
+    }
+
+    /// How many more disk failures the pool can survive right now. `0` means that the next
+    /// failure in the weakest vdev loses data.
+    pub fn remaining_fault_tolerance(&self) -> usize {
+        self.vdevs.iter().map(Vdev::remaining_fault_tolerance).min().unwrap_or(0)
+    }
+
+    /// Classified [`reason`](#method.reason) of pool state, if any.
+    pub fn fault_reason(&self) -> Option<VdevFaultReason> {
+        self.reason.as_ref().map(Reason::fault_reason)
+    }
+
+    /// Fill in GUIDs of vdevs and disks from `zpool status -g`. GUIDs go in the order of lines in
+    /// `config:` section, pool line excluded. Nothing is changed if number of GUIDs doesn't match
+    /// the layout.
+    pub(crate) fn set_guids(&mut self, guids: &[u64]) {
+        let vdevs = self.vdevs.iter().chain(&self.logs);
+        let lines =
+            vdevs.map(Vdev::config_lines).sum::<usize>() + self.caches.len() + self.spares.len();
+        if lines != guids.len() {
+            return;
+        }
+        let mut guids = guids.iter().copied();
+        for vdev in self.vdevs.iter_mut().chain(&mut self.logs) {
+            vdev.set_guids(&mut guids);
+        }
+        for disk in self.caches.iter_mut().chain(&mut self.spares) {
+            disk.set_guid(guids.next());
+        }
+    }
+
     #[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
     pub(crate) fn from_pest_pair(pair: Pair<'_, Rule>) -> Zpool {
         debug_assert!(pair.as_rule() == Rule::zpool);
@@ -74,10 +354,19 @@ impl Zpool {
                 Rule::state => {
                     zpool.health(get_health_from_pair(pair));
                 },
+                Rule::status => {
+                    zpool.status(Some(String::from(get_string_from_pair(pair).trim())));
+                },
                 Rule::action => {
                     zpool.action(Some(get_string_from_pair(pair)));
                 },
+                Rule::see => {
+                    let url = get_string_from_pair(pair);
+                    zpool.fault_code(ZfsFaultCode::from_url(&url));
+                    zpool.see(Some(url));
+                },
                 Rule::errors => {
+                    zpool.damaged(get_damaged_from_pair(pair.clone()));
                     zpool.errors(get_error_from_pair(pair));
                 },
                 Rule::vdevs => {
@@ -95,8 +384,13 @@ impl Zpool {
                 Rule::spares => {
                     zpool.spares(get_spares_from_pair(pair));
                 },
-                Rule::config | Rule::status | Rule::see | Rule::pool_headers => {},
-                Rule::scan_line => {},
+                Rule::config | Rule::pool_headers => {},
+                Rule::scan_line => {
+                    zpool.scan(ScanStatus::from_scan_text(&get_string_from_pair(pair)));
+                },
+                Rule::checkpoint_line => {
+                    zpool.checkpoint(Some(get_string_from_pair(pair)));
+                },
                 _ => unreachable!(),
             }
         }
@@ -118,18 +412,61 @@ impl PartialEq<Zpool> for CreateZpoolRequest {
     fn eq(&self, other: &Zpool) -> bool { other == self }
 }
 
+fn write_vdev(out: &mut String, vdev: &Vdev, index: usize) {
+    let kind = match vdev.kind() {
+        VdevType::SingleDisk => {
+            for disk in vdev.disks() {
+                let _ = writeln!(out, "  {}", disk.path().display());
+            }
+            return;
+        },
+        VdevType::Mirror => "mirror",
+        VdevType::RaidZ => "raidz1",
+        VdevType::RaidZ2 => "raidz2",
+        VdevType::RaidZ3 => "raidz3",
+    };
+    let _ = writeln!(out, "  {}-{}", kind, index);
+    for disk in vdev.disks() {
+        let _ = writeln!(out, "    {}", disk.path().display());
+    }
+}
+
+fn write_disks(out: &mut String, section: &str, disks: &[Disk]) {
+    if disks.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "{}", section);
+    for disk in disks {
+        let _ = writeln!(out, "  {}", disk.path().display());
+    }
+}
+
 #[inline]
 #[allow(clippy::option_unwrap_used, clippy::result_unwrap_used, clippy::wildcard_enum_match_arm)]
 fn get_error_statistics_from_pair(pair: Pair<'_, Rule>) -> ErrorStatistics {
     debug_assert_eq!(Rule::error_statistics, pair.as_rule());
     let mut inner = pair.into_inner();
     ErrorStatistics {
-        read:     inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
-        write:    inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
-        checksum: inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
+        read:     parse_error_count(inner.next().unwrap().as_str()),
+        write:    parse_error_count(inner.next().unwrap().as_str()),
+        checksum: parse_error_count(inner.next().unwrap().as_str()),
     }
 }
 
+/// Parse error counter. `zpool status` abbreviates large ones (`1.5K`), those are approximate.
+/// Counters that don't fit are `u64::MAX`.
+fn parse_error_count(value: &str) -> u64 {
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or_else(|| value.len());
+    let (number, suffix) = value.split_at(split);
+    if suffix.is_empty() {
+        return number.parse().unwrap_or(std::u64::MAX);
+    }
+    let exponent = "KMGTPE".find(suffix).map_or(0, |idx| idx as i32 + 1);
+    number.parse::<f64>().map_or(std::u64::MAX, |number| {
+        (number * 1024_f64.powi(exponent)).round().min(std::u64::MAX as f64) as u64
+    })
+}
+
 #[inline]
 #[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
 fn set_stats_and_reason_from_pool_line(pool_line: Pair<'_, Rule>, zpool: &mut ZpoolBuilder) {
@@ -285,6 +622,16 @@ fn get_error_from_pair(pair: Pair<'_, Rule>) -> Option<String> {
     }
 }
 
+#[inline]
+fn get_damaged_from_pair(pair: Pair<'_, Rule>) -> Vec<DamagedEntry> {
+    debug_assert!(pair.as_rule() == Rule::errors);
+    pair.into_inner()
+        .filter(|pair| pair.as_rule() == Rule::damaged_entries)
+        .flat_map(Pair::into_inner)
+        .map(|entry| DamagedEntry::from_line(entry.as_str()))
+        .collect()
+}
+
 #[inline]
 fn get_logs_from_pair(pair: Pair<'_, Rule>) -> Vec<Vdev> {
     debug_assert!(pair.as_rule() == Rule::logs);
@@ -313,7 +660,34 @@ mod test {
 
     use crate::zpool::{CreateVdevRequest, Disk, Health, Vdev, VdevType};
 
-    use super::{CreateZpoolRequest, Zpool};
+    use super::{parse_error_count, CreateZpoolRequest, ZfsFaultCode, Zpool};
+
+    #[test]
+    fn test_fault_code_from_url() {
+        assert_eq!(
+            Some(ZfsFaultCode::CorruptedData),
+            ZfsFaultCode::from_url("http://illumos.org/msg/ZFS-8000-8A")
+        );
+        assert_eq!(
+            Some(ZfsFaultCode::CorruptedLabelReplicated),
+            ZfsFaultCode::from_url("https://openzfs.github.io/openzfs-docs/msg/ZFS-8000-4J")
+        );
+        assert_eq!(
+            Some(ZfsFaultCode::Other(String::from("ZFS-8000-ZZ"))),
+            ZfsFaultCode::from_url("http://zfsonlinux.org/msg/ZFS-8000-ZZ/")
+        );
+        assert_eq!(None, ZfsFaultCode::from_url("https://example.com/help"));
+    }
+
+    #[test]
+    fn test_parse_error_count() {
+        assert_eq!(0, parse_error_count("0"));
+        assert_eq!(42, parse_error_count("42"));
+        assert_eq!(1536, parse_error_count("1.5K"));
+        assert_eq!(2 * 1024 * 1024, parse_error_count("2M"));
+        assert_eq!(std::u64::MAX, parse_error_count("99999999999999999999"));
+        assert_eq!(std::u64::MAX, parse_error_count("99999E"));
+    }
 
     #[test]
     fn test_eq_zpool() {
@@ -340,6 +714,53 @@ mod test {
         assert_eq!(request, zpool);
     }
 
+    fn vdev(kind: VdevType, disks: &[Health]) -> Vdev {
+        let disks = disks
+            .iter()
+            .enumerate()
+            .map(|(idx, health)| {
+                Disk::builder().path(format!("hd{}", idx)).health(health.clone()).build().unwrap()
+            })
+            .collect();
+        Vdev::builder().kind(kind).health(Health::Online).disks(disks).build().unwrap()
+    }
+
+    #[test]
+    fn test_fault_tolerance_mirror_and_raidz2() {
+        let online = Health::Online;
+        let mirror = vdev(VdevType::Mirror, &[online.clone(), online.clone(), online.clone()]);
+        let raidz2 = vdev(VdevType::RaidZ2, &vec![online.clone(); 6]);
+        assert_eq!(2, mirror.fault_tolerance());
+        assert_eq!(2, raidz2.fault_tolerance());
+        assert_eq!(0, vdev(VdevType::SingleDisk, &[online.clone()]).fault_tolerance());
+        assert_eq!(1, vdev(VdevType::RaidZ, &vec![online.clone(); 3]).fault_tolerance());
+        assert_eq!(3, vdev(VdevType::RaidZ3, &vec![online.clone(); 8]).fault_tolerance());
+
+        let zpool = Zpool::builder()
+            .name("wat")
+            .health(Health::Online)
+            .vdevs(vec![mirror, raidz2.clone()])
+            .build()
+            .unwrap();
+        assert_eq!(2, zpool.fault_tolerance());
+        assert_eq!(2, zpool.remaining_fault_tolerance());
+
+        let degraded = vdev(VdevType::Mirror, &[online.clone(), Health::Faulted]);
+        assert_eq!(1, degraded.fault_tolerance());
+        assert_eq!(0, degraded.remaining_fault_tolerance());
+        let zpool = Zpool::builder()
+            .name("wat")
+            .health(Health::Degraded)
+            .vdevs(vec![degraded, raidz2])
+            .build()
+            .unwrap();
+        assert_eq!(1, zpool.fault_tolerance());
+        assert_eq!(0, zpool.remaining_fault_tolerance());
+
+        let empty = Zpool::builder().name("wat").health(Health::Online).vdevs(vec![]).build();
+        assert_eq!(0, empty.unwrap().fault_tolerance());
+    }
+
     #[test]
     fn test_ne_zpool() {
         let request = CreateZpoolRequest::builder()
@@ -0,0 +1,198 @@
+//! Consumer friendly representation of `zpool events` stream. Useful to react on faults the same
+//! way ZED does, without polling `zpool status`.
+use std::{collections::HashMap,
+          io::{BufRead, BufReader, Read},
+          path::PathBuf,
+          process::{Child, ChildStdout}};
+
+use chrono::NaiveDateTime;
+use pest::{iterators::Pair, Parser};
+
+use crate::{parsers::{Rule, StdoutParser},
+            zpool::{ZpoolError, ZpoolResult}};
+
+/// Format of the timestamp used by `zpool events`.
+const EVENT_TIME_FORMAT: &str = "%b %d %Y %H:%M:%S%.f";
+
+/// Single event from pool event stream.
+#[derive(Getters, Debug, Clone, PartialEq, Eq)]
+#[get = "pub"]
+pub struct PoolEvent {
+    /// Time when event was posted.
+    time:       NaiveDateTime,
+    /// Class of the event. For example: `ereport.fs.zfs.io` or `sysevent.fs.zfs.scrub_finish`.
+    class:      String,
+    /// Name of the pool this event is related to, if any.
+    pool:       Option<String>,
+    /// Path of the vdev this event is related to, if any.
+    vdev_path:  Option<PathBuf>,
+    /// All top-level fields of the event payload as they were printed by `zpool events -v`.
+    /// Strings are unquoted, everything else is left as is.
+    properties: HashMap<String, String>,
+}
+
+impl PoolEvent {
+    pub(crate) fn from_pest_pair(pair: Pair<'_, Rule>) -> ZpoolResult<PoolEvent> {
+        debug_assert!(pair.as_rule() == Rule::event);
+        let mut inner = pair.into_inner();
+        let time = inner.next().ok_or(ZpoolError::ParseError)?.as_str();
+        let time = NaiveDateTime::parse_from_str(time.trim(), EVENT_TIME_FORMAT)
+            .map_err(|_| ZpoolError::ParseError)?;
+        let class = inner.next().ok_or(ZpoolError::ParseError)?.as_str().to_string();
+
+        let mut properties = HashMap::new();
+        for property in inner {
+            debug_assert!(property.as_rule() == Rule::event_property);
+            let mut kv = property.into_inner();
+            let key = kv.next().ok_or(ZpoolError::ParseError)?.as_str();
+            let value = kv.next().ok_or(ZpoolError::ParseError)?.as_str();
+            properties.insert(key.to_string(), unquote(value).to_string());
+        }
+
+        // ereports use `pool`, sysevents use `pool_name`.
+        let pool = properties.get("pool").or_else(|| properties.get("pool_name")).cloned();
+        let vdev_path = properties.get("vdev_path").map(PathBuf::from);
+        Ok(PoolEvent { time, class, pool, vdev_path, properties })
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Iterator over output of `zpool events`. In follow mode it blocks until next event is posted.
+/// Underlying `zpool` process is killed when iterator is dropped.
+pub struct EventsIter {
+    child: Option<(Child, BufReader<ChildStdout>)>,
+    done:  bool,
+}
+
+impl EventsIter {
+    pub(crate) fn new(mut child: Child) -> ZpoolResult<EventsIter> {
+        let stdout = child.stdout.take().ok_or(ZpoolError::ParseError)?;
+        Ok(EventsIter { child: Some((child, BufReader::new(stdout))), done: false })
+    }
+
+    /// Iterator without any events. Used in dry run mode.
+    pub(crate) fn empty() -> EventsIter { EventsIter { child: None, done: true } }
+
+    /// Called once stdout is exhausted. Turns non-zero exit status into an error. Stderr is
+    /// drained before waiting, so a chatty child can't block on a full pipe.
+    fn finish(&mut self) -> Option<ZpoolResult<PoolEvent>> {
+        self.done = true;
+        let (child, _) = self.child.as_mut()?;
+        let mut stderr = Vec::new();
+        if let Some(mut pipe) = child.stderr.take() {
+            let _ = pipe.read_to_end(&mut stderr);
+        }
+        match child.wait() {
+            Ok(status) if status.success() => None,
+            Ok(_) => Some(Err(ZpoolError::from_stderr(&stderr))),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl Iterator for EventsIter {
+    type Item = ZpoolResult<PoolEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let stdout = match self.child.as_mut() {
+            Some((_, stdout)) => stdout,
+            None => return None,
+        };
+        let mut block = String::new();
+        loop {
+            let mut line = String::new();
+            match stdout.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) if line == "\n" => {
+                    if block.is_empty() {
+                        continue;
+                    }
+                    break;
+                },
+                Ok(_) => block.push_str(&line),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                },
+            }
+        }
+        if block.is_empty() {
+            return self.finish();
+        }
+        let event = StdoutParser::parse(Rule::event, &block)
+            .map_err(|_| ZpoolError::ParseError)
+            .and_then(|mut pairs| pairs.next().ok_or(ZpoolError::ParseError))
+            .and_then(PoolEvent::from_pest_pair);
+        Some(event)
+    }
+}
+
+impl Drop for EventsIter {
+    fn drop(&mut self) {
+        if let (false, Some((child, _))) = (self.done, self.child.as_mut()) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+
+    static EVENT: &str = "Oct 16 2026 12:34:56.123456789\tereport.fs.zfs.io
+        class = \"ereport.fs.zfs.io\"
+        ena = 0x1b2a3c4d5e600001
+        detector = (embedded nvlist)
+                version = 0x0
+                scheme = \"zfs\"
+                pool = 0x2c2d6a6b3e6f3f2d
+                vdev = 0x7b2f1e3a4c5d6e7f
+        (end detector)
+        pool = \"tank\"
+        pool_guid = 0x2c2d6a6b3e6f3f2d
+        vdev_path = \"/dev/sda1\"
+        zio_err = 0x5
+        time = 0x5f5e1000 0x75bcd15
+        eid = 0x2a
+";
+
+    #[test]
+    fn test_parse_event() {
+        let mut pairs = StdoutParser::parse(Rule::event, EVENT).unwrap_or_else(|e| panic!("{}", e));
+        let event = PoolEvent::from_pest_pair(pairs.next().unwrap()).unwrap();
+
+        let expected_time = NaiveDate::from_ymd(2026, 10, 16).and_hms_nano(12, 34, 56, 123_456_789);
+        assert_eq!(&expected_time, event.time());
+        assert_eq!("ereport.fs.zfs.io", event.class());
+        assert_eq!(&Some(String::from("tank")), event.pool());
+        assert_eq!(&Some(PathBuf::from("/dev/sda1")), event.vdev_path());
+        assert_eq!(Some(&String::from("0x5")), event.properties().get("zio_err"));
+        assert!(event.properties().get("version").is_none());
+    }
+
+    #[test]
+    fn test_parse_sysevent() {
+        let stdout = "Oct 16 2026 12:00:00.000000001\tsysevent.fs.zfs.scrub_finish
+        pool_name = \"tank\"
+        pool_guid = 0x2c2d6a6b3e6f3f2d
+";
+        let mut pairs =
+            StdoutParser::parse(Rule::event, stdout).unwrap_or_else(|e| panic!("{}", e));
+        let event = PoolEvent::from_pest_pair(pairs.next().unwrap()).unwrap();
+        assert_eq!("sysevent.fs.zfs.scrub_finish", event.class());
+        assert_eq!(&Some(String::from("tank")), event.pool());
+        assert!(event.vdev_path().is_none());
+    }
+}
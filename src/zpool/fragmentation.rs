@@ -0,0 +1,150 @@
+//! Fragmentation tracking. Library only takes samples, keeping them around is up to the caller.
+use std::{collections::VecDeque, time::SystemTime};
+
+use super::{ZpoolError, ZpoolResult};
+
+/// Fragmentation of the pool at a given moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct FragmentationSample {
+    /// When sample was taken.
+    at:      SystemTime,
+    /// Fragmentation of free space. In percents.
+    percent: u8,
+}
+
+impl FragmentationSample {
+    /// Create a sample. Useful if samples are persisted somewhere between runs.
+    pub fn new(at: SystemTime, percent: u8) -> FragmentationSample {
+        FragmentationSample { at, percent }
+    }
+
+    /// Parse output of `zpool get -Hp -o value fragmentation`.
+    pub(crate) fn from_stdout(at: SystemTime, stdout: &str) -> ZpoolResult<FragmentationSample> {
+        let value = stdout.trim().trim_end_matches('%');
+        let percent = value.parse()?;
+        if percent > 100 {
+            return Err(ZpoolError::ParseError);
+        }
+        Ok(FragmentationSample { at, percent })
+    }
+}
+
+/// Bounded history of fragmentation samples. Oldest samples are dropped once capacity is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentationHistory {
+    samples:  VecDeque<FragmentationSample>,
+    capacity: usize,
+}
+
+impl FragmentationHistory {
+    /// Create history that keeps at most `capacity` samples.
+    pub fn with_capacity(capacity: usize) -> FragmentationHistory {
+        FragmentationHistory { samples: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Add a sample, dropping the oldest one if history is full.
+    pub fn record(&mut self, sample: FragmentationSample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples in the order they were recorded.
+    pub fn samples(&self) -> &VecDeque<FragmentationSample> { &self.samples }
+
+    /// Slope of the least squares fit over recorded samples, in percents per hour. Positive means
+    /// fragmentation is growing. `None` if there are less than two samples or all of them were
+    /// taken at the same time.
+    pub fn trend(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let origin = self.samples.iter().map(|s| s.at).min()?;
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|s| {
+                let hours = s.at.duration_since(origin).unwrap_or_default().as_secs_f64() / 3600.0;
+                (hours, f64::from(s.percent))
+            })
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if variance == 0.0 {
+            None
+        } else {
+            Some(covariance / variance)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(hours: u64, percent: u8) -> FragmentationSample {
+        FragmentationSample::new(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(hours * 3600),
+            percent,
+        )
+    }
+
+    #[test]
+    fn test_trend_growing() {
+        let mut history = FragmentationHistory::with_capacity(10);
+        for (hour, percent) in &[(0, 10), (1, 12), (2, 14), (3, 16)] {
+            history.record(sample(*hour, *percent));
+        }
+        let trend = history.trend().unwrap();
+        assert!((trend - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_trend_shrinking_and_noisy() {
+        let mut history = FragmentationHistory::with_capacity(10);
+        for (hour, percent) in &[(0, 40), (2, 37), (4, 35), (6, 30)] {
+            history.record(sample(*hour, *percent));
+        }
+        let trend = history.trend().unwrap();
+        assert!(trend < 0.0);
+        assert!((trend - -1.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_trend_not_enough_samples() {
+        let mut history = FragmentationHistory::with_capacity(10);
+        assert_eq!(None, history.trend());
+        history.record(sample(0, 10));
+        assert_eq!(None, history.trend());
+        history.record(sample(0, 20));
+        assert_eq!(None, history.trend());
+    }
+
+    #[test]
+    fn test_record_drops_oldest() {
+        let mut history = FragmentationHistory::with_capacity(2);
+        history.record(sample(0, 50));
+        history.record(sample(1, 10));
+        history.record(sample(2, 10));
+        assert_eq!(2, history.samples().len());
+        assert_eq!(Some(0.0), history.trend());
+    }
+
+    #[test]
+    fn test_from_stdout() {
+        let at = SystemTime::UNIX_EPOCH;
+        assert_eq!(&12, FragmentationSample::from_stdout(at, "12\n").unwrap().percent());
+        assert_eq!(&7, FragmentationSample::from_stdout(at, "7%\n").unwrap().percent());
+        assert!(FragmentationSample::from_stdout(at, "-\n").is_err());
+        assert!(FragmentationSample::from_stdout(at, "250\n").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Options used when looking for pools available for import and importing them.
+//!
+//! ### Examples
+//!
+//! ```rust
+//! use libzetta::zpool::ImportOptions;
+//! use std::path::PathBuf;
+//!
+//! let options = ImportOptions::builder()
+//!     .search_dir(PathBuf::from("/vdevs/import"))
+//!     .force(true)
+//!     .build()
+//!     .unwrap();
+//! ```
+use std::{ffi::OsString, path::PathBuf};
+
+#[derive(Default, Builder, Debug, Clone, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+/// Options for `zpool import`.
+pub struct ImportOptions {
+    /// Directories to search for devices. `/dev/` is used if empty.
+    #[builder(default)]
+    search_dirs:          Vec<PathBuf>,
+    /// Read configuration from given cachefile instead of searching for devices.
+    #[builder(default)]
+    cachefile:            Option<PathBuf>,
+    /// Import pool in read-only mode.
+    #[builder(default)]
+    readonly:             bool,
+    /// Altroot to use for imported pool.
+    #[builder(default)]
+    altroot:              Option<PathBuf>,
+    /// Import pool even if it appears to be potentially active.
+    #[builder(default)]
+    force:                bool,
+    /// Rewind pool to its checkpoint. Everything done after the checkpoint was taken is lost,
+    /// the checkpoint itself is discarded.
+    #[builder(default)]
+    rewind_to_checkpoint: bool,
+}
+
+impl ImportOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> ImportOptionsBuilder { ImportOptionsBuilder::default() }
+
+    /// Make args that tell `zpool import` where to look for pools.
+    pub(crate) fn search_args(&self) -> Vec<OsString> {
+        let mut ret = Vec::with_capacity(self.search_dirs.len() * 2 + 2);
+        for dir in &self.search_dirs {
+            ret.push("-d".into());
+            ret.push(dir.clone().into_os_string());
+        }
+        if let Some(ref cachefile) = self.cachefile {
+            ret.push("-c".into());
+            ret.push(cachefile.clone().into_os_string());
+        }
+        ret
+    }
+
+    /// Make args for `zpool import`.
+    pub(crate) fn into_args(self) -> Vec<OsString> {
+        let mut ret = self.search_args();
+        if self.readonly {
+            ret.push("-o".into());
+            ret.push("readonly=on".into());
+        }
+        if let Some(altroot) = self.altroot {
+            ret.push("-R".into());
+            ret.push(altroot.into_os_string());
+        }
+        if self.force {
+            ret.push("-f".into());
+        }
+        if self.rewind_to_checkpoint {
+            ret.push("--rewind-to-checkpoint".into());
+        }
+        ret
+    }
+}
+
+impl ImportOptionsBuilder {
+    /// Add directory to search for devices.
+    ///
+    /// * `dir` - Directory to look for pools. Useful when you are looking for pool that created
+    ///   from files.
+    pub fn search_dir(&mut self, dir: PathBuf) -> &mut ImportOptionsBuilder {
+        match self.search_dirs {
+            Some(ref mut vec) => vec.push(dir),
+            None => {
+                self.search_dirs = Some(Vec::new());
+                return self.search_dir(dir);
+            },
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args_from_slice(args: &[&str]) -> Vec<OsString> {
+        args.to_vec().into_iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_default_args() {
+        let options = ImportOptions::default();
+        assert!(options.into_args().is_empty());
+    }
+
+    #[test]
+    fn test_args() {
+        let options = ImportOptions::builder()
+            .search_dir(PathBuf::from("/vdevs"))
+            .search_dir(PathBuf::from("/vdevs/import"))
+            .force(true)
+            .build()
+            .unwrap();
+        let expected = args_from_slice(&["-d", "/vdevs", "-d", "/vdevs/import", "-f"]);
+        assert_eq!(expected, options.into_args());
+    }
+
+    #[test]
+    fn test_all_args() {
+        let options = ImportOptions::builder()
+            .search_dir(PathBuf::from("/vdevs"))
+            .cachefile(PathBuf::from("/etc/zfs/zpool.cache"))
+            .readonly(true)
+            .altroot(PathBuf::from("/mnt"))
+            .force(true)
+            .rewind_to_checkpoint(true)
+            .build()
+            .unwrap();
+        let expected = args_from_slice(&["-d", "/vdevs", "-c", "/etc/zfs/zpool.cache"]);
+        assert_eq!(expected, options.search_args());
+
+        let expected = args_from_slice(&[
+            "-d",
+            "/vdevs",
+            "-c",
+            "/etc/zfs/zpool.cache",
+            "-o",
+            "readonly=on",
+            "-R",
+            "/mnt",
+            "-f",
+            "--rewind-to-checkpoint",
+        ]);
+        assert_eq!(expected, options.into_args());
+    }
+}
@@ -0,0 +1,934 @@
+//! In-memory [`ZpoolEngine`](../trait.ZpoolEngine.html) to test code that uses the trait without
+//! ZFS installed and without root. Test only: no device is touched, pools live as long as the
+//! engine. Available with `mock` feature.
+//!
+//! Pools remember their topology, properties and which devices are offline. Errors are the same
+//! typed errors [`ZpoolOpen3`](../open3/struct.ZpoolOpen3.html) returns: `PoolNotFound`,
+//! `PoolAlreadyImported`, `VdevReuse`, `NoSuchDevice` and so on. A scrub runs until it's waited
+//! for or stopped. Things that need a kernel (`events`) fail with `ZpoolError::Other`.
+//!
+//! ```rust
+//! use libzetta::zpool::{mock::MockZpool, CreateVdevRequest, CreateZpoolRequest, ZpoolEngine};
+//!
+//! let zpool = MockZpool::new();
+//! let request = CreateZpoolRequest::builder()
+//!     .name("tank")
+//!     .vdev(CreateVdevRequest::disk("/dev/sda"))
+//!     .build()
+//!     .unwrap();
+//! zpool.create(request).unwrap();
+//! assert!(zpool.exists("tank").unwrap());
+//! ```
+use std::{collections::{BTreeMap, HashMap, HashSet},
+          ffi::OsStr,
+          path::{Path, PathBuf},
+          sync::{Mutex, MutexGuard, PoisonError},
+          time::{Duration, SystemTime}};
+
+use super::{description::ZpoolBuilder,
+            topology,
+            vdev::{DiskBuilder, VdevBuilder},
+            ClearMode, CreateMode, CreateVdevRequest, CreateZpoolRequest, DestroyMode, Disk,
+            EventsIter, ExportMode, FragmentationSample, Health, ImportOptions, OfflineMode,
+            OnlineMode, PropPair, ScrubReport, Vdev, VdevType, WaitActivity, Zpool, ZpoolEngine,
+            ZpoolError, ZpoolProperties, ZpoolPropertiesFull, ZpoolResult};
+
+/// Size every data vdev adds to the pool. Smallest device ZFS accepts.
+const VDEV_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+struct MockPool {
+    name:       String,
+    guid:       u64,
+    imported:   bool,
+    altroot:    Option<PathBuf>,
+    vdevs:      Vec<CreateVdevRequest>,
+    logs:       Vec<CreateVdevRequest>,
+    caches:     Vec<PathBuf>,
+    spares:     Vec<PathBuf>,
+    /// Properties changed with `set_property` or given on create, as `zpool get` prints them.
+    properties: HashMap<String, String>,
+    /// GUID of every device of the pool.
+    guids:      BTreeMap<PathBuf, u64>,
+    offline:    HashSet<PathBuf>,
+    scrubbing:  bool,
+    /// Pool as it was when checkpoint was taken.
+    checkpoint: Option<Box<MockPool>>,
+}
+
+impl MockPool {
+    fn health(&self) -> Health {
+        if self.offline.is_empty() {
+            Health::Online
+        } else {
+            Health::Degraded
+        }
+    }
+
+    fn devices(&self) -> impl Iterator<Item = &PathBuf> {
+        let vdevs = self.vdevs.iter().chain(&self.logs);
+        vdevs.flat_map(disks_of).chain(&self.caches).chain(&self.spares)
+    }
+
+    /// Data or log vdev that has the device.
+    fn vdev_of(&mut self, device: &OsStr) -> Option<&mut CreateVdevRequest> {
+        let guids = &self.guids;
+        self.vdevs
+            .iter_mut()
+            .chain(self.logs.iter_mut())
+            .find(|vdev| disks_of(vdev).iter().any(|disk| is_device(disk, device, guids)))
+    }
+
+    fn find_device(&self, device: &OsStr) -> ZpoolResult<PathBuf> {
+        self.devices()
+            .find(|disk| is_device(disk, device, &self.guids))
+            .cloned()
+            .ok_or(ZpoolError::NoSuchDevice)
+    }
+
+    fn to_vdev(&self, vdev: &CreateVdevRequest) -> Vdev {
+        let disks: Vec<Disk> = disks_of(vdev).iter().map(|disk| self.to_disk(disk)).collect();
+        let health = if disks.iter().any(|disk| disk.health() != &Health::Online) {
+            Health::Degraded
+        } else {
+            Health::Online
+        };
+        let guid = match vdev {
+            CreateVdevRequest::SingleDisk(_) => *disks[0].guid(),
+            _ => None,
+        };
+        VdevBuilder::default()
+            .kind(vdev.kind())
+            .health(health)
+            .disks(disks)
+            .guid(guid)
+            .build()
+            .unwrap()
+    }
+
+    fn to_disk(&self, disk: &Path) -> Disk {
+        let health = if self.offline.contains(disk) { Health::Offline } else { Health::Online };
+        let guid = self.guids.get(disk).copied();
+        DiskBuilder::default().path(disk).health(health).guid(guid).build().unwrap()
+    }
+
+    fn to_zpool(&self) -> Zpool {
+        let to_disks = |disks: &[PathBuf]| -> Vec<Disk> {
+            disks.iter().map(|disk| self.to_disk(disk)).collect()
+        };
+        ZpoolBuilder::default()
+            .name(self.name.clone())
+            .id(Some(self.guid))
+            .health(self.health())
+            .vdevs(self.vdevs.iter().map(|vdev| self.to_vdev(vdev)).collect::<Vec<_>>())
+            .logs(self.logs.iter().map(|vdev| self.to_vdev(vdev)).collect::<Vec<_>>())
+            .caches(to_disks(&self.caches))
+            .spares(to_disks(&self.spares))
+            .checkpoint(self.checkpoint.as_ref().map(|_| String::from("created")))
+            .build()
+            .unwrap()
+    }
+
+    /// Render properties the way `zpool get -Hp all` does and parse them back with the same
+    /// code real output goes through.
+    fn read_properties(&self) -> ZpoolResult<ZpoolPropertiesFull> {
+        let size = VDEV_SIZE * self.vdevs.len() as u64;
+        let health = match self.health() {
+            Health::Online => "ONLINE",
+            _ => "DEGRADED",
+        };
+        let altroot = self.altroot.as_ref().map(|altroot| altroot.display().to_string());
+        let mut properties: BTreeMap<&str, String> = BTreeMap::new();
+        properties.insert("size", size.to_string());
+        properties.insert("free", size.to_string());
+        properties.insert("guid", self.guid.to_string());
+        properties.insert("health", health.to_string());
+        properties.insert("altroot", altroot.clone().unwrap_or_else(|| String::from("-")));
+        properties.insert("cachefile", String::from(if altroot.is_some() { "none" } else { "-" }));
+        for (key, value) in [
+            ("allocated", "0"),
+            ("capacity", "0"),
+            ("comment", "-"),
+            ("dedupratio", "1.00"),
+            ("expandsize", "-"),
+            ("fragmentation", "0"),
+            ("freeing", "0"),
+            ("leaked", "0"),
+            ("readonly", "off"),
+            ("autoexpand", "off"),
+            ("autoreplace", "off"),
+            ("autotrim", "off"),
+            ("bootfs", "-"),
+            ("delegation", "on"),
+            ("failmode", "wait"),
+        ]
+        .iter()
+        {
+            properties.insert(key, value.to_string());
+        }
+        if self.checkpoint.is_some() {
+            properties.insert("checkpoint", String::from("0"));
+        }
+        for (key, value) in &self.properties {
+            properties.insert(key, value.clone());
+        }
+        let stdout: String = properties
+            .iter()
+            .map(|(key, value)| format!("{}\t{}\t{}\t-\n", self.name, key, value))
+            .collect();
+        ZpoolPropertiesFull::try_from_stdout(stdout.as_bytes())
+    }
+}
+
+fn disks_of(vdev: &CreateVdevRequest) -> &[PathBuf] {
+    match vdev {
+        CreateVdevRequest::SingleDisk(disk) => std::slice::from_ref(disk),
+        CreateVdevRequest::Mirror(disks)
+        | CreateVdevRequest::RaidZ(disks)
+        | CreateVdevRequest::RaidZ2(disks)
+        | CreateVdevRequest::RaidZ3(disks) => disks,
+    }
+}
+
+/// Device can be referred to by path, bare name or GUID.
+fn is_device(disk: &Path, device: &OsStr, guids: &BTreeMap<PathBuf, u64>) -> bool {
+    disk.as_os_str() == device
+        || disk.file_name() == Some(device)
+        || guids.get(disk).map_or(false, |guid| device.to_str() == Some(&guid.to_string()))
+}
+
+/// How many devices of the vdev can be offline at the same time.
+fn tolerance(vdev: &CreateVdevRequest) -> usize {
+    match vdev.kind() {
+        VdevType::SingleDisk => 0,
+        VdevType::Mirror => disks_of(vdev).len().saturating_sub(1),
+        VdevType::RaidZ => 1,
+        VdevType::RaidZ2 => 2,
+        VdevType::RaidZ3 => 3,
+    }
+}
+
+fn unsupported(operation: &str) -> ZpoolError {
+    ZpoolError::Other(format!("{} is not supported by MockZpool", operation))
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pools: Vec<MockPool>,
+    /// Source of GUIDs for pools and devices.
+    clock: u64,
+}
+
+impl State {
+    fn next_guid(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn imported(&mut self, name: &str) -> ZpoolResult<&mut MockPool> {
+        self.pools
+            .iter_mut()
+            .find(|pool| pool.imported && pool.name == name)
+            .ok_or(ZpoolError::PoolNotFound)
+    }
+
+    /// Give GUIDs to new devices of the pool.
+    fn track<'a, I: IntoIterator<Item = &'a PathBuf>>(&mut self, name: &str, disks: I) {
+        let guids: Vec<(PathBuf, u64)> =
+            disks.into_iter().map(|disk| (disk.clone(), self.next_guid())).collect();
+        if let Ok(pool) = self.imported(name) {
+            pool.guids.extend(guids);
+        }
+    }
+
+    /// Devices of `disks` that already belong to some pool, with name of that pool.
+    fn in_use<'a, I: IntoIterator<Item = &'a PathBuf>>(&self, disks: I) -> Vec<(String, String)> {
+        disks
+            .into_iter()
+            .filter_map(|disk| {
+                self.pools
+                    .iter()
+                    .find(|pool| pool.guids.contains_key(disk))
+                    .map(|pool| (disk.display().to_string(), pool.name.clone()))
+            })
+            .collect()
+    }
+
+    fn add(&mut self, name: &str, disks: &[PathBuf]) -> ZpoolResult<()> {
+        self.imported(name)?;
+        let reused = self.in_use(disks);
+        if !reused.is_empty() {
+            return Err(ZpoolError::VdevReuse(reused));
+        }
+        self.track(name, disks);
+        Ok(())
+    }
+
+    fn import(&mut self, index: usize, new_name: Option<&str>, rewind: bool) -> ZpoolResult<()> {
+        let name = new_name.map_or_else(|| self.pools[index].name.clone(), String::from);
+        if self.imported(&name).is_ok() {
+            return Err(ZpoolError::PoolAlreadyImported);
+        }
+        let pool = &mut self.pools[index];
+        if rewind {
+            *pool = *pool.checkpoint.take().ok_or(ZpoolError::NoCheckpoint)?;
+        }
+        pool.name = name;
+        pool.imported = true;
+        Ok(())
+    }
+}
+
+/// In-memory implementation of `ZpoolEngine`. See [module documentation](index.html).
+#[derive(Debug, Default)]
+pub struct MockZpool {
+    state: Mutex<State>,
+}
+
+impl MockZpool {
+    /// Engine without pools.
+    pub fn new() -> Self { MockZpool::default() }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_pool<N, F, T>(&self, name: N, f: F) -> ZpoolResult<T>
+    where
+        N: AsRef<str>,
+        F: FnOnce(&mut MockPool) -> ZpoolResult<T>,
+    {
+        f(self.state().imported(name.as_ref())?)
+    }
+}
+
+impl ZpoolEngine for MockZpool {
+    fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool> {
+        Ok(self.state().imported(name.as_ref()).is_ok())
+    }
+
+    fn exists_by_guid(&self, guid: u64) -> ZpoolResult<bool> {
+        Ok(self.state().pools.iter().any(|pool| pool.imported && pool.guid == guid))
+    }
+
+    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
+        request.validate().map_err(ZpoolError::InvalidTopology)?;
+        let mut state = self.state();
+        if state.imported(request.name()).is_ok() {
+            let message = format!("cannot create '{}': pool already exists", request.name());
+            return Err(ZpoolError::Other(message));
+        }
+        let pool = MockPool {
+            name:       request.name().clone(),
+            guid:       state.next_guid(),
+            imported:   true,
+            altroot:    request.altroot().clone(),
+            vdevs:      request.vdevs().clone(),
+            logs:       request.logs().clone(),
+            caches:     request.caches().clone(),
+            spares:     request.spares().clone(),
+            properties: HashMap::new(),
+            guids:      BTreeMap::new(),
+            offline:    HashSet::new(),
+            scrubbing:  false,
+            checkpoint: None,
+        };
+        let disks: Vec<PathBuf> = pool.devices().cloned().collect();
+        let reused = state.in_use(&disks);
+        if !reused.is_empty() {
+            return Err(ZpoolError::VdevReuse(reused));
+        }
+        state.pools.push(pool);
+        state.track(request.name(), &disks);
+        if let Some(props) = request.props().clone() {
+            let pool = state.imported(request.name())?;
+            for pair in props.into_args() {
+                let pair = pair.to_string_lossy().into_owned();
+                let mut split = pair.splitn(2, '=');
+                if let (Some(key), Some(value)) = (split.next(), split.next()) {
+                    pool.properties.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy<N: AsRef<str>>(&self, name: N, _mode: DestroyMode) -> ZpoolResult<()> {
+        let mut state = self.state();
+        state.pools.retain(|pool| !(pool.imported && pool.name == name.as_ref()));
+        Ok(())
+    }
+
+    fn read_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolProperties> {
+        self.read_properties_full(name).map(|full| full.properties().clone())
+    }
+
+    fn read_properties_full<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolPropertiesFull> {
+        self.with_pool(name, |pool| pool.read_properties())
+    }
+
+    fn set_property<N: AsRef<str>, P: PropPair>(
+        &self,
+        name: N,
+        key: &str,
+        value: &P,
+    ) -> ZpoolResult<()> {
+        let pair = value.to_pair(key);
+        let value = pair.splitn(2, '=').nth(1).unwrap_or_default().to_string();
+        self.with_pool(name, |pool| {
+            pool.properties.insert(key.to_string(), value);
+            Ok(())
+        })
+    }
+
+    fn export<N: AsRef<str>>(&self, name: N, _mode: ExportMode) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            pool.imported = false;
+            Ok(())
+        })
+    }
+
+    fn available(&self) -> ZpoolResult<Vec<Zpool>> {
+        let state = self.state();
+        Ok(state.pools.iter().filter(|pool| !pool.imported).map(MockPool::to_zpool).collect())
+    }
+
+    fn available_in_dir(&self, _dir: PathBuf) -> ZpoolResult<Vec<Zpool>> { self.available() }
+
+    fn available_with_options(&self, _options: ImportOptions) -> ZpoolResult<Vec<Zpool>> {
+        self.available()
+    }
+
+    fn import<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.import_with_options(name, ImportOptions::default())
+    }
+
+    fn import_from_dir<N: AsRef<str>>(&self, name: N, _dir: PathBuf) -> ZpoolResult<()> {
+        self.import(name)
+    }
+
+    fn import_with_options<N: AsRef<str>>(
+        &self,
+        name: N,
+        options: ImportOptions,
+    ) -> ZpoolResult<()> {
+        let mut state = self.state();
+        if state.imported(name.as_ref()).is_ok() {
+            return Err(ZpoolError::PoolAlreadyImported);
+        }
+        let candidates: Vec<usize> = (0..state.pools.len())
+            .filter(|&index| {
+                let pool = &state.pools[index];
+                !pool.imported && pool.name == name.as_ref()
+            })
+            .collect();
+        match candidates.as_slice() {
+            [] => Err(ZpoolError::PoolNotFound),
+            [index] => state.import(*index, None, *options.rewind_to_checkpoint()),
+            _ => {
+                let guids = candidates.iter().map(|&index| state.pools[index].guid).collect();
+                Err(ZpoolError::AmbiguousPoolName(guids))
+            },
+        }
+    }
+
+    fn import_by_guid(
+        &self,
+        guid: u64,
+        new_name: Option<&str>,
+        options: ImportOptions,
+    ) -> ZpoolResult<()> {
+        let mut state = self.state();
+        match state.pools.iter().position(|pool| pool.guid == guid) {
+            Some(index) if state.pools[index].imported => Err(ZpoolError::PoolAlreadyImported),
+            Some(index) => state.import(index, new_name, *options.rewind_to_checkpoint()),
+            None => Err(ZpoolError::PoolNotFound),
+        }
+    }
+
+    fn status<N: AsRef<str>>(&self, name: N, _verbose: bool) -> ZpoolResult<Zpool> {
+        self.with_pool(name, |pool| Ok(pool.to_zpool()))
+    }
+
+    fn vdev_guids<N: AsRef<str>>(&self, name: N) -> ZpoolResult<HashMap<u64, PathBuf>> {
+        self.with_pool(name, |pool| {
+            Ok(pool.guids.iter().map(|(disk, guid)| (*guid, disk.clone())).collect())
+        })
+    }
+
+    fn all(&self) -> ZpoolResult<Vec<Zpool>> {
+        let state = self.state();
+        Ok(state.pools.iter().filter(|pool| pool.imported).map(MockPool::to_zpool).collect())
+    }
+
+    fn status_all(&self) -> ZpoolResult<Vec<Zpool>> { self.all() }
+
+    fn scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            if pool.scrubbing {
+                return Err(ZpoolError::ScrubInProgress);
+            }
+            pool.scrubbing = true;
+            Ok(())
+        })
+    }
+
+    fn pause_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> { self.stop_scrub(name) }
+
+    fn stop_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            if !pool.scrubbing {
+                return Err(ZpoolError::NoActiveScrubs);
+            }
+            pool.scrubbing = false;
+            Ok(())
+        })
+    }
+
+    fn wait<N: AsRef<str>>(&self, name: N, activity: WaitActivity) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            if activity == WaitActivity::Scrub {
+                pool.scrubbing = false;
+            }
+            Ok(())
+        })
+    }
+
+    fn scrub_and_report<N: AsRef<str>>(
+        &self,
+        name: N,
+        _timeout: Option<Duration>,
+    ) -> ZpoolResult<ScrubReport> {
+        match self.scrub(&name) {
+            Ok(()) | Err(ZpoolError::ScrubInProgress) => {},
+            Err(e) => return Err(e),
+        }
+        self.wait(&name, WaitActivity::Scrub)?;
+        ScrubReport::from_status("  scan: scrub repaired 0B in 00:00:00 with 0 errors\n")
+    }
+
+    fn replace_and_wait<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+        _timeout: Option<Duration>,
+    ) -> ZpoolResult<()> {
+        self.replace_disk(name, old_disk, new_disk)
+    }
+
+    fn reopen<N: AsRef<str>>(&self, name: N, _scrub_restart: bool) -> ZpoolResult<()> {
+        self.with_pool(name, |_| Ok(()))
+    }
+
+    fn upgrade<N: AsRef<str>>(&self, name: N, _all_features: bool) -> ZpoolResult<()> {
+        self.with_pool(name, |_| Ok(()))
+    }
+
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut state = self.state();
+        let guid = state.next_guid();
+        state.imported(name.as_ref())?.guid = guid;
+        Ok(())
+    }
+
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            if pool.checkpoint.is_some() {
+                return Err(ZpoolError::CheckpointExists);
+            }
+            pool.checkpoint = Some(Box::new(pool.clone()));
+            Ok(())
+        })
+    }
+
+    fn checkpoint_discard<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| match pool.checkpoint.take() {
+            Some(_) => Ok(()),
+            None => Err(ZpoolError::NoCheckpoint),
+        })
+    }
+
+    fn sample_fragmentation<N: AsRef<str>>(&self, name: N) -> ZpoolResult<FragmentationSample> {
+        self.with_pool(name, |_| Ok(FragmentationSample::new(SystemTime::now(), 0)))
+    }
+
+    fn clear<N: AsRef<str>>(&self, name: N, _mode: ClearMode) -> ZpoolResult<Option<Duration>> {
+        self.with_pool(name, |_| Ok(None))
+    }
+
+    fn events(&self, _follow: bool) -> ZpoolResult<EventsIter> { Err(unsupported("events")) }
+
+    fn take_offline<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        _mode: OfflineMode,
+    ) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            let disk = pool.find_device(device.as_ref())?;
+            let offline = pool.offline.clone();
+            let vdev = pool.vdev_of(device.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+            let already = disks_of(vdev).iter().filter(|disk| offline.contains(*disk)).count();
+            if !offline.contains(&disk) && already >= tolerance(vdev) {
+                return Err(ZpoolError::NoValidReplicas);
+            }
+            pool.offline.insert(disk);
+            Ok(())
+        })
+    }
+
+    fn bring_online<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        _mode: OnlineMode,
+    ) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            let disk = pool.find_device(device.as_ref())?;
+            pool.offline.remove(&disk);
+            Ok(())
+        })
+    }
+
+    fn attach<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        new_device: D,
+    ) -> ZpoolResult<()> {
+        let new_device = PathBuf::from(new_device.as_ref());
+        let mut state = self.state();
+        state.imported(name.as_ref())?.vdev_of(device.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+        state.add(name.as_ref(), std::slice::from_ref(&new_device))?;
+        let pool = state.imported(name.as_ref())?;
+        let vdev = pool.vdev_of(device.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+        match vdev {
+            CreateVdevRequest::SingleDisk(disk) => {
+                *vdev = CreateVdevRequest::Mirror(vec![disk.clone(), new_device]);
+            },
+            CreateVdevRequest::Mirror(disks) => disks.push(new_device),
+            _ => {
+                pool.guids.remove(&new_device);
+                return Err(ZpoolError::CannotAttach);
+            },
+        }
+        Ok(())
+    }
+
+    fn detach<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            let disk = pool.find_device(device.as_ref())?;
+            let vdev = pool.vdev_of(device.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+            match vdev {
+                CreateVdevRequest::Mirror(disks) if disks.len() > 2 => {
+                    disks.retain(|other| other != &disk);
+                },
+                CreateVdevRequest::Mirror(disks) => {
+                    let left = disks.iter().find(|other| *other != &disk).cloned();
+                    *vdev = CreateVdevRequest::SingleDisk(left.unwrap_or_default());
+                },
+                _ => return Err(ZpoolError::OnlyDevice),
+            }
+            pool.guids.remove(&disk);
+            pool.offline.remove(&disk);
+            Ok(())
+        })
+    }
+
+    fn add_vdev<N: AsRef<str>>(
+        &self,
+        name: N,
+        new_vdev: CreateVdevRequest,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        topology::validate_vdev(&new_vdev).map_err(ZpoolError::InvalidTopology)?;
+        let mut state = self.state();
+        let pool = state.imported(name.as_ref())?;
+        let mismatched = pool.vdevs.iter().any(|vdev| vdev.kind() != new_vdev.kind());
+        if mismatched && add_mode != CreateMode::Force {
+            return Err(ZpoolError::MismatchedReplicationLevel);
+        }
+        state.add(name.as_ref(), disks_of(&new_vdev))?;
+        state.imported(name.as_ref())?.vdevs.push(new_vdev);
+        Ok(())
+    }
+
+    fn add_zil<N: AsRef<str>>(
+        &self,
+        name: N,
+        new_zil: CreateVdevRequest,
+        _add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        topology::validate_log(&new_zil).map_err(ZpoolError::InvalidTopology)?;
+        let mut state = self.state();
+        state.add(name.as_ref(), disks_of(&new_zil))?;
+        state.imported(name.as_ref())?.logs.push(new_zil);
+        Ok(())
+    }
+
+    fn add_cache<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        new_cache: D,
+        _add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        let new_cache = PathBuf::from(new_cache.as_ref());
+        let mut state = self.state();
+        state.add(name.as_ref(), std::slice::from_ref(&new_cache))?;
+        state.imported(name.as_ref())?.caches.push(new_cache);
+        Ok(())
+    }
+
+    fn add_spare<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        new_spare: D,
+        _add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        let new_spare = PathBuf::from(new_spare.as_ref());
+        let mut state = self.state();
+        state.add(name.as_ref(), std::slice::from_ref(&new_spare))?;
+        state.imported(name.as_ref())?.spares.push(new_spare);
+        Ok(())
+    }
+
+    fn replace_disk<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+    ) -> ZpoolResult<()> {
+        let new_disk = PathBuf::from(new_disk.as_ref());
+        let mut state = self.state();
+        let pool = state.imported(name.as_ref())?;
+        let old = pool.find_device(old_disk.as_ref())?;
+        // Cache and spare devices are found, but can't be replaced.
+        pool.vdev_of(old_disk.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+        state.add(name.as_ref(), std::slice::from_ref(&new_disk))?;
+        let pool = state.imported(name.as_ref())?;
+        let vdev = pool.vdev_of(old_disk.as_ref()).ok_or(ZpoolError::NoSuchDevice)?;
+        match vdev {
+            CreateVdevRequest::SingleDisk(disk) => *disk = new_disk,
+            CreateVdevRequest::Mirror(disks)
+            | CreateVdevRequest::RaidZ(disks)
+            | CreateVdevRequest::RaidZ2(disks)
+            | CreateVdevRequest::RaidZ3(disks) => {
+                for disk in disks.iter_mut().filter(|disk| *disk == &old) {
+                    *disk = new_disk.clone();
+                }
+            },
+        }
+        pool.guids.remove(&old);
+        pool.offline.remove(&old);
+        Ok(())
+    }
+
+    fn remove<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()> {
+        self.with_pool(name, |pool| {
+            let disk = pool.find_device(device.as_ref())?;
+            let single = CreateVdevRequest::SingleDisk(disk.clone());
+            pool.vdevs.retain(|vdev| vdev != &single);
+            pool.logs.retain(|vdev| vdev != &single);
+            pool.caches.retain(|other| other != &disk);
+            pool.spares.retain(|other| other != &disk);
+            if pool.devices().any(|other| other == &disk) {
+                // Part of mirror or raidz, has to be detached instead.
+                return Err(ZpoolError::NoSuchDevice);
+            }
+            pool.guids.remove(&disk);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::{CacheType, FailMode, ZpoolErrorKind, ZpoolPropertiesWriteBuilder};
+
+    fn create(zpool: &MockZpool, name: &str, vdev: CreateVdevRequest) {
+        let request = CreateZpoolRequest::builder().name(name).vdev(vdev).build().unwrap();
+        zpool.create(request).unwrap();
+    }
+
+    fn mirror(disks: &[&str]) -> CreateVdevRequest {
+        CreateVdevRequest::Mirror(disks.iter().map(PathBuf::from).collect())
+    }
+
+    #[test]
+    fn test_create_and_destroy() {
+        let zpool = MockZpool::new();
+        create(&zpool, "tank", mirror(&["/dev/sda", "/dev/sdb"]));
+        assert!(zpool.exists("tank").unwrap());
+
+        let reuse = CreateZpoolRequest::builder()
+            .name("dozer")
+            .vdev(CreateVdevRequest::disk("/dev/sdb"))
+            .build()
+            .unwrap();
+        let err = zpool.create(reuse).unwrap_err();
+        assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
+        if let ZpoolError::VdevReuse(vdevs) = err {
+            assert_eq!(vec![(String::from("/dev/sdb"), String::from("tank"))], vdevs);
+        }
+        let invalid = CreateZpoolRequest::builder().name("dozer").build().unwrap();
+        assert_eq!(ZpoolErrorKind::InvalidTopology, zpool.create(invalid).unwrap_err().kind());
+
+        let status = zpool.status("tank", false).unwrap();
+        assert_eq!(&Health::Online, status.health());
+        assert_eq!(&VdevType::Mirror, status.vdevs()[0].kind());
+        assert_eq!(2, zpool.vdev_guids("tank").unwrap().len());
+        assert_eq!(1, zpool.all().unwrap().len());
+
+        zpool.destroy("tank", DestroyMode::Gentle).unwrap();
+        assert!(!zpool.exists("tank").unwrap());
+        assert!(zpool.available().unwrap().is_empty());
+        // Same as `ZpoolOpen3`, missing pool is not an error.
+        zpool.destroy("tank", DestroyMode::Gentle).unwrap();
+        create(&zpool, "dozer", CreateVdevRequest::disk("/dev/sdb"));
+    }
+
+    #[test]
+    fn test_properties() {
+        let zpool = MockZpool::new();
+        let props = ZpoolPropertiesWriteBuilder::default()
+            .comment("hello")
+            .fail_mode(FailMode::Panic)
+            .cache_file(CacheType::None)
+            .build()
+            .unwrap();
+        let request = CreateZpoolRequest::builder()
+            .name("tank")
+            .vdev(CreateVdevRequest::disk("/dev/sda"))
+            .props(props)
+            .build()
+            .unwrap();
+        zpool.create(request).unwrap();
+
+        let props = zpool.read_properties("tank").unwrap();
+        assert_eq!(&Some(String::from("hello")), props.comment());
+        assert_eq!(&FailMode::Panic, props.fail_mode());
+        assert_eq!(&CacheType::None, props.cache_file());
+        assert_eq!(&Health::Online, props.health());
+
+        let updated = ZpoolPropertiesWriteBuilder::from_props(&props)
+            .auto_expand(true)
+            .clear_comment()
+            .build()
+            .unwrap();
+        let props = zpool.update_properties("tank", updated).unwrap();
+        assert_eq!(&true, props.auto_expand());
+        assert_eq!(&None, props.comment());
+
+        let guid = *props.guid();
+        zpool.reguid("tank").unwrap();
+        assert_ne!(&guid, zpool.read_properties("tank").unwrap().guid());
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let zpool = MockZpool::new();
+        create(&zpool, "tank", CreateVdevRequest::disk("/dev/sda"));
+        zpool.upgrade("tank", false).unwrap();
+        zpool.upgrade("tank", true).unwrap();
+        for all_features in &[false, true] {
+            let err = zpool.upgrade("dozer", *all_features).unwrap_err();
+            assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+        }
+    }
+
+    #[test]
+    fn test_export_import() {
+        let zpool = MockZpool::new();
+        create(&zpool, "tank", CreateVdevRequest::disk("/dev/sda"));
+        let guid = *zpool.read_properties("tank").unwrap().guid();
+        assert_eq!(ZpoolErrorKind::PoolAlreadyImported, zpool.import("tank").unwrap_err().kind());
+
+        assert!(zpool.exists_by_guid(guid).unwrap());
+        zpool.export("tank", ExportMode::Gentle).unwrap();
+        assert!(!zpool.exists("tank").unwrap());
+        assert!(!zpool.exists_by_guid(guid).unwrap());
+        assert_eq!(Some(&guid), zpool.available().unwrap()[0].id().as_ref());
+        zpool.import("tank").unwrap();
+        assert!(zpool.available().unwrap().is_empty());
+
+        zpool.export("tank", ExportMode::Gentle).unwrap();
+        zpool.import_by_guid(guid, Some("renamed"), ImportOptions::default()).unwrap();
+        assert!(zpool.exists("renamed").unwrap());
+        assert!(zpool.exists_by_guid(guid).unwrap());
+        assert_eq!(ZpoolErrorKind::PoolNotFound, zpool.import("tank").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let zpool = MockZpool::new();
+        create(&zpool, "tank", CreateVdevRequest::disk("/dev/sda"));
+        let err = zpool.checkpoint_discard("tank").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
+
+        zpool.checkpoint("tank").unwrap();
+        assert_eq!(ZpoolErrorKind::CheckpointExists, zpool.checkpoint("tank").unwrap_err().kind());
+        assert_eq!(&Some(0), zpool.read_properties("tank").unwrap().checkpoint());
+        assert!(zpool.status("tank", false).unwrap().checkpoint().is_some());
+
+        zpool.add_cache("tank", "/dev/sdb", CreateMode::Gentle).unwrap();
+        zpool.export("tank", ExportMode::Gentle).unwrap();
+        let options = ImportOptions::builder().rewind_to_checkpoint(true).build().unwrap();
+        zpool.import_with_options("tank", options.clone()).unwrap();
+        let status = zpool.status("tank", false).unwrap();
+        assert!(status.caches().is_empty());
+        assert!(status.checkpoint().is_none());
+
+        zpool.export("tank", ExportMode::Gentle).unwrap();
+        let err = zpool.import_with_options("tank", options).unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
+    }
+
+    #[test]
+    fn test_devices_and_scrub() {
+        let zpool = MockZpool::new();
+        create(&zpool, "tank", CreateVdevRequest::disk("/dev/sda"));
+
+        let err = zpool.take_offline("tank", "sda", OfflineMode::UntilReboot).unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoValidReplicas, err.kind());
+        zpool.attach("tank", "/dev/sda", "/dev/sdb").unwrap();
+        let status = zpool.status("tank", false).unwrap();
+        let sda = status.vdevs()[0].disks()[0].guid().unwrap();
+        assert_eq!(Some(sda), zpool.vdev_guid("tank", "/dev/sda").unwrap());
+        zpool.take_offline("tank", sda.to_string(), OfflineMode::UntilReboot).unwrap();
+        let status = zpool.status("tank", false).unwrap();
+        assert_eq!(&Health::Degraded, status.health());
+        assert_eq!(&Health::Offline, status.vdevs()[0].disks()[0].health());
+        zpool.bring_online("tank", "/dev/sda", OnlineMode::Simple).unwrap();
+        assert_eq!(&Health::Online, zpool.status("tank", false).unwrap().health());
+
+        let err = zpool.add_vdev("tank", CreateVdevRequest::disk("/dev/sdc"), CreateMode::Gentle);
+        assert_eq!(ZpoolErrorKind::MismatchedReplicationLevel, err.unwrap_err().kind());
+        let raidz = CreateVdevRequest::RaidZ(vec!["/dev/sdx".into(), "/dev/sdy".into()]);
+        let err = zpool.add_zil("tank", raidz, CreateMode::Gentle).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidTopology, err.kind());
+        zpool.add_cache("tank", "/dev/sdc", CreateMode::Gentle).unwrap();
+        let err = zpool.replace_disk("tank", "sdc", "/dev/sde").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+        let err = zpool.attach("tank", "/dev/sdz", "/dev/sde").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+        // Failed calls above must not leave `/dev/sde` claimed by the pool.
+        create(&zpool, "dozer", CreateVdevRequest::disk("/dev/sde"));
+        zpool.remove("tank", "sdc").unwrap();
+        zpool.replace_disk("tank", "/dev/sdb", "/dev/sdd").unwrap();
+        zpool.detach("tank", "/dev/sda").unwrap();
+        let status = zpool.status("tank", false).unwrap();
+        assert_eq!(&VdevType::SingleDisk, status.vdevs()[0].kind());
+        assert_eq!(Path::new("/dev/sdd"), status.vdevs()[0].disks()[0].path());
+        let err = zpool.detach("tank", "/dev/sda").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+
+        zpool.scrub("tank").unwrap();
+        assert_eq!(ZpoolErrorKind::ScrubInProgress, zpool.scrub("tank").unwrap_err().kind());
+        zpool.stop_scrub("tank").unwrap();
+        assert_eq!(ZpoolErrorKind::NoActiveScrubs, zpool.stop_scrub("tank").unwrap_err().kind());
+        let report = zpool.scrub_and_report("tank", None).unwrap();
+        assert_eq!(&0, report.errors());
+    }
+}
@@ -13,41 +13,58 @@
 //!  - Main [trait](trait.ZpoolEngine.html) for everything Zpool related
 //!     - It's implemented as trait for easy mocking
 //!
-use std::{default::Default,
+use std::{collections::HashMap,
+          default::Default,
           ffi::OsStr,
           io,
           num::{ParseFloatError, ParseIntError},
-          path::PathBuf};
+          path::{Path, PathBuf},
+          time::Duration};
 
 use regex::Regex;
 
-pub use self::{description::{Reason, Zpool},
+pub use self::{description::{DamagedEntry, Reason, VdevFaultReason, ZfsFaultCode, Zpool},
+               events::{EventsIter, PoolEvent},
+               fragmentation::{FragmentationHistory, FragmentationSample},
+               import::{ImportOptions, ImportOptionsBuilder},
                open3::ZpoolOpen3,
-               properties::{CacheType, FailMode, Health, PropPair, ZpoolProperties,
-                            ZpoolPropertiesWrite, ZpoolPropertiesWriteBuilder},
-               topology::{CreateZpoolRequest, CreateZpoolRequestBuilder},
-               vdev::{CreateVdevRequest, Disk, Vdev, VdevType}};
-
+               properties::{CacheType, FailMode, FeatureState, Health, PropPair, ZpoolProperties,
+                            ZpoolPropertiesFull, ZpoolPropertiesWrite,
+                            ZpoolPropertiesWriteBuilder},
+               scrub::{ScanStatus, ScrubReport},
+               topology::{CreateZpoolRequest, CreateZpoolRequestBuilder, TopologyError},
+               vdev::{CreateVdevRequest, DevicePath, Disk, Vdev, VdevType}};
+
+pub mod events;
+pub mod fragmentation;
+pub mod import;
+#[cfg(feature = "mock")] pub mod mock;
+#[cfg(feature = "mock")] pub use mock::MockZpool;
 pub mod open3;
 pub mod properties;
+pub mod scrub;
 pub mod topology;
 pub mod vdev;
 
 pub mod description;
 lazy_static! {
     static ref RE_REUSE_VDEV_ZOL: Regex = Regex::new(r"cannot create \S+: one or more vdevs refer to the same device, or one of\nthe devices is part of an active md or lvm device\n").expect("failed to compile RE_VDEV_REUSE_ZOL)");
-    static ref RE_REUSE_VDEV: Regex = Regex::new(r"following errors:\n(\S+) is part of active pool '(\S+)'").expect("failed to compile RE_VDEV_REUSE)");
-    static ref RE_REUSE_VDEV2: Regex = Regex::new(r"invalid vdev specification\nuse '-f' to override the following errors:\n(\S+) is part of potentially active pool '(\S+)'\n?").expect("failed to compile RE_VDEV_REUSE2)");
-    static ref RE_REUSE_VDEV3: Regex = Regex::new(r"invalid vdev specification\nuse \S+ to override the following errors:\n(\S+) is part of exported pool '(\S+)'\n?").expect("failed to compile RE_VDEV_REUSE3)");
+    static ref RE_REUSE_VDEV: Regex = Regex::new(r"(?m)^(\S+) is part of (?:active |potentially active |exported )?pool '(\S+)'").expect("failed to compile RE_VDEV_REUSE)");
     static ref RE_TOO_SMALL: Regex = Regex::new(r"cannot create \S+: one or more devices is less than the minimum size \S+").expect("failed to compile RE_TOO_SMALL");
-    static ref RE_PERMISSION_DENIED: Regex = Regex::new(r"cannot create \S+: permission denied\n").expect("failed to compile RE_PERMISSION_DENIED");
+    static ref RE_REPLACE_TOO_SMALL: Regex = Regex::new(r"cannot replace \S+ with \S+: device is too small").expect("failed to compile RE_REPLACE_TOO_SMALL");
+    static ref RE_PERMISSION_DENIED: Regex = Regex::new(r"[Pp]ermission denied|[Oo]peration not permitted").expect("failed to compile RE_PERMISSION_DENIED");
     static ref RE_NO_ACTIVE_SCRUBS: Regex = Regex::new(r"cannot (pause|cancel) scrubbing .+: there is no active scrub\n").expect("failed to compile RE_NO_ACTIVE_SCRUBS");
+    static ref RE_SCRUB_IN_PROGRESS: Regex = Regex::new(r"cannot scrub \S+: currently scrubbing").expect("failed to compile RE_SCRUB_IN_PROGRESS");
+    static ref RE_POOL_ALREADY_IMPORTED: Regex = Regex::new(r"cannot import '\S+': a pool with that name already exists").expect("failed to compile RE_POOL_ALREADY_IMPORTED");
     static ref RE_NO_SUCH_POOL: Regex = Regex::new(r"cannot open '\S+': no such pool\n?").expect("failed to compile RE_NO_SUCH_POOL");
     static ref RE_NO_VALID_REPLICAS: Regex = Regex::new(r"cannot offline \S+: no valid replicas\n?").expect("failed to compile RE_NO_VALID_REPLICAS");
     static ref RE_CANNOT_ATTACH: Regex = Regex::new(r"cannot attach \S+ to \S+ can only attach to mirrors and top-level disks").expect("failed to compile RE_CANNOT_ATTACH");
     static ref RE_NO_SUCH_DEVICE: Regex = Regex::new(r"cannot attach \S+ to \S+: no such device in pool").expect("failed to compile RE_NO_SUCH_DEVICE");
     static ref RE_ONLY_DEVICE: Regex = Regex::new(r"cannot detach \S+ only applicable to mirror and replacing vdevs").expect("failed to compile RE_ONLY_DEVICE");
     static ref RE_MISMATCH_REPLICATION: Regex = Regex::new(r"invalid vdev specification\nuse '-f' to override the following errors:\nmismatched replication level:.+").expect("failed to compile RE_MISMATCHED_REPLICATION");
+    static ref RE_AMBIGUOUS_POOL_NAME: Regex = Regex::new(r"cannot import '\S+': more than one matching pool").expect("failed to compile RE_AMBIGUOUS_POOL_NAME");
+    static ref RE_CHECKPOINT_EXISTS: Regex = Regex::new(r"cannot checkpoint '\S+': checkpoint exists").expect("failed to compile RE_CHECKPOINT_EXISTS");
+    static ref RE_NO_CHECKPOINT: Regex = Regex::new(r"cannot discard checkpoint in '\S+': checkpoint does not exist").expect("failed to compile RE_NO_CHECKPOINT");
     static ref RE_INVALID_CACHE_DEVICE: Regex = Regex::new(r"cannot add to \S+: cache device must be a disk or disk slice\n?").expect("failed to compile RE_INVALID_CACHE_DEVICE");
 }
 
@@ -63,11 +80,18 @@ quick_error! {
         }
         /// Trying to manipulate non-existent pool.
         PoolNotFound {}
+        /// Trying to import a pool while pool with the same name is already imported.
+        PoolAlreadyImported {}
         /// Given topology failed validation.
-        InvalidTopology {}
+        InvalidTopology(reason: TopologyError) {
+            display("invalid topology: {}", reason)
+        }
         /// Trying to create new Zpool, but one or more vdevs are lready used in another pool.
-        VdevReuse(vdev: String, pool: String) {
-            display("{} is part of {}", vdev, pool)
+        /// Contains every conflicting vdev with the pool it's part of. Vdev is the path as ZFS
+        /// printed it, use `DevicePath::resolve` to compare it with requested disks. Empty if ZFS
+        /// didn't say which vdevs are in use.
+        VdevReuse(vdevs: Vec<(String, String)>) {
+            display("vdevs are in use: {:?}", vdevs)
         }
         /// Failed to parse value. Ideally you never see it, if you see it - it's a bug.
         ParseError {
@@ -76,12 +100,15 @@ quick_error! {
         }
         /// Device used in CreateZpoolRequest is smaller than 64M (or 128M on some platforms).
         DeviceTooSmall {}
-        /// Permission denied to create zpool. This might happened because:
+        /// Permission denied to operate on zpool. This might happened because:
         /// a) you are not running it as root
         /// b) you are running it inside jail that isn't allowed to operate zfs
+        /// c) operation isn't delegated to the user
         PermissionDenied {}
         /// Trying to pause/stop a scrub that either never started or has already completed
         NoActiveScrubs {}
+        /// Trying to start a scrub while another one is running.
+        ScrubInProgress {}
         /// Trying to take the only device offline.
         NoValidReplicas {}
         /// Couldn't parse string to raid type.
@@ -97,8 +124,31 @@ quick_error! {
         MismatchedReplicationLevel {}
         /// Cache device must a disk or disk slice/partition.
         InvalidCacheDevice {}
+        /// More than one pool available for import shares the given name. Import by GUID instead.
+        AmbiguousPoolName(candidates: Vec<u64>) {
+            display("more than one pool matches given name: {:?}", candidates)
+        }
+        /// Pool already has a checkpoint. Discard it before taking a new one.
+        CheckpointExists {}
+        /// Trying to discard checkpoint of a pool that doesn't have one.
+        NoCheckpoint {}
+        /// Device alias can't be used as a name under `/dev/disk/by-*`, because it's empty or
+        /// would point outside of that directory.
+        InvalidDeviceAlias(alias: String) {
+            display("invalid device alias: {:?}", alias)
+        }
+        /// Operation didn't complete within given time.
+        Timeout {}
+        /// Resilver didn't complete within given time. Contains `scan:` line of `zpool status` at
+        /// the moment of timeout.
+        ResilverTimeout(progress: String) {
+            display("resilver didn't finish in time: {}", progress)
+        }
         /// Don't know (yet) how to categorize this error. If you see this error - open an issue.
-        Other(err: String) {}
+        /// Contains stderr of the failed command.
+        Other(err: String) {
+            display("{}", err.trim_end())
+        }
     }
 }
 
@@ -109,12 +159,14 @@ impl ZpoolError {
             ZpoolError::CmdNotFound => ZpoolErrorKind::CmdNotFound,
             ZpoolError::Io(_) => ZpoolErrorKind::Io,
             ZpoolError::PoolNotFound => ZpoolErrorKind::PoolNotFound,
-            ZpoolError::InvalidTopology => ZpoolErrorKind::InvalidTopology,
+            ZpoolError::PoolAlreadyImported => ZpoolErrorKind::PoolAlreadyImported,
+            ZpoolError::InvalidTopology(_) => ZpoolErrorKind::InvalidTopology,
             ZpoolError::VdevReuse(..) => ZpoolErrorKind::VdevReuse,
             ZpoolError::ParseError => ZpoolErrorKind::ParseError,
             ZpoolError::DeviceTooSmall => ZpoolErrorKind::DeviceTooSmall,
             ZpoolError::PermissionDenied => ZpoolErrorKind::PermissionDenied,
             ZpoolError::NoActiveScrubs => ZpoolErrorKind::NoActiveScrubs,
+            ZpoolError::ScrubInProgress => ZpoolErrorKind::ScrubInProgress,
             ZpoolError::NoValidReplicas => ZpoolErrorKind::NoValidReplicas,
             ZpoolError::UnknownRaidType(_) => ZpoolErrorKind::UnknownRaidType,
             ZpoolError::CannotAttach => ZpoolErrorKind::CannotAttach,
@@ -122,6 +174,11 @@ impl ZpoolError {
             ZpoolError::OnlyDevice => ZpoolErrorKind::OnlyDevice,
             ZpoolError::MismatchedReplicationLevel => ZpoolErrorKind::MismatchedReplicationLevel,
             ZpoolError::InvalidCacheDevice => ZpoolErrorKind::InvalidCacheDevice,
+            ZpoolError::AmbiguousPoolName(_) => ZpoolErrorKind::AmbiguousPoolName,
+            ZpoolError::CheckpointExists => ZpoolErrorKind::CheckpointExists,
+            ZpoolError::NoCheckpoint => ZpoolErrorKind::NoCheckpoint,
+            ZpoolError::InvalidDeviceAlias(_) => ZpoolErrorKind::InvalidDeviceAlias,
+            ZpoolError::Timeout | ZpoolError::ResilverTimeout(_) => ZpoolErrorKind::Timeout,
             ZpoolError::Other(_) => ZpoolErrorKind::Other,
         }
     }
@@ -138,6 +195,8 @@ pub enum ZpoolErrorKind {
     Io,
     /// Trying to manipulate non-existent pool.
     PoolNotFound,
+    /// Trying to import a pool while pool with the same name is already imported.
+    PoolAlreadyImported,
     /// At least one vdev points to incorrect location.
     /// If vdev type is File then it means file not found.
     DeviceNotFound,
@@ -151,12 +210,15 @@ pub enum ZpoolErrorKind {
     ParseError,
     /// Device used in CreateZpoolRequest is smaller than 64M (or 128M on some platforms)
     DeviceTooSmall,
-    /// Permission denied to create zpool. This might happened because:
+    /// Permission denied to operate on zpool. This might happened because:
     /// a) you are not running it as root
     /// b) you are running it inside jail that isn't allowed to operate zfs
+    /// c) operation isn't delegated to the user
     PermissionDenied,
     /// Trying to pause/stop a scrub that either never started or has already completed
     NoActiveScrubs,
+    /// Trying to start a scrub while another one is running.
+    ScrubInProgress,
     /// Trying to take the only device offline.
     NoValidReplicas,
     /// Couldn't parse string to raid type.
@@ -173,6 +235,16 @@ pub enum ZpoolErrorKind {
     MismatchedReplicationLevel,
     /// Cache device must be a disk or disk slice/partition.
     InvalidCacheDevice,
+    /// More than one pool available for import shares the given name.
+    AmbiguousPoolName,
+    /// Pool already has a checkpoint.
+    CheckpointExists,
+    /// Pool doesn't have a checkpoint.
+    NoCheckpoint,
+    /// Device alias can't be used as a name under `/dev/disk/by-*`.
+    InvalidDeviceAlias,
+    /// Operation didn't complete within given time.
+    Timeout,
     /// Don't know (yet) how to categorize this error. If you see this error -
     /// open an issue.
     Other,
@@ -193,32 +265,26 @@ impl ZpoolError {
     #[allow(clippy::option_unwrap_used)]
     pub fn from_stderr(stderr_raw: &[u8]) -> ZpoolError {
         let stderr = String::from_utf8_lossy(stderr_raw);
-        if RE_REUSE_VDEV.is_match(&stderr) {
-            let caps = RE_REUSE_VDEV.captures(&stderr).unwrap();
-            ZpoolError::VdevReuse(
-                caps.get(1).unwrap().as_str().into(),
-                caps.get(2).unwrap().as_str().into(),
-            )
-        } else if RE_REUSE_VDEV2.is_match(&stderr) {
-            let caps = RE_REUSE_VDEV2.captures(&stderr).unwrap();
-            ZpoolError::VdevReuse(
-                caps.get(1).unwrap().as_str().into(),
-                caps.get(2).unwrap().as_str().into(),
-            )
-        } else if RE_REUSE_VDEV3.is_match(&stderr) {
-            let caps = RE_REUSE_VDEV3.captures(&stderr).unwrap();
-            ZpoolError::VdevReuse(
-                caps.get(1).unwrap().as_str().into(),
-                caps.get(2).unwrap().as_str().into(),
-            )
+        let reused: Vec<(String, String)> = RE_REUSE_VDEV
+            .captures_iter(&stderr)
+            .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+            .collect();
+        if !reused.is_empty() {
+            ZpoolError::VdevReuse(reused)
         } else if RE_REUSE_VDEV_ZOL.is_match(&stderr) {
-            ZpoolError::VdevReuse(String::new(), String::new())
+            ZpoolError::VdevReuse(Vec::new())
         } else if RE_TOO_SMALL.is_match(&stderr) {
             ZpoolError::DeviceTooSmall
+        } else if RE_REPLACE_TOO_SMALL.is_match(&stderr) {
+            ZpoolError::DeviceTooSmall
         } else if RE_PERMISSION_DENIED.is_match(&stderr) {
             ZpoolError::PermissionDenied
         } else if RE_NO_ACTIVE_SCRUBS.is_match(&stderr) {
             ZpoolError::NoActiveScrubs
+        } else if RE_SCRUB_IN_PROGRESS.is_match(&stderr) {
+            ZpoolError::ScrubInProgress
+        } else if RE_POOL_ALREADY_IMPORTED.is_match(&stderr) {
+            ZpoolError::PoolAlreadyImported
         } else if RE_NO_SUCH_POOL.is_match(&stderr) {
             ZpoolError::PoolNotFound
         } else if RE_NO_VALID_REPLICAS.is_match(&stderr) {
@@ -233,6 +299,12 @@ impl ZpoolError {
             ZpoolError::MismatchedReplicationLevel
         } else if RE_INVALID_CACHE_DEVICE.is_match(&stderr) {
             ZpoolError::InvalidCacheDevice
+        } else if RE_AMBIGUOUS_POOL_NAME.is_match(&stderr) {
+            ZpoolError::AmbiguousPoolName(Vec::new())
+        } else if RE_CHECKPOINT_EXISTS.is_match(&stderr) {
+            ZpoolError::CheckpointExists
+        } else if RE_NO_CHECKPOINT.is_match(&stderr) {
+            ZpoolError::NoCheckpoint
         } else {
             ZpoolError::Other(stderr.into())
         }
@@ -273,7 +345,8 @@ pub enum CreateMode {
     /// Do not use force mode.
     Gentle,
 }
-/// Strategy to use when destroying Zpool.
+/// Strategy to use when destroying Zpool. Neither of them keeps the pool importable, see
+/// [`ExportMode`](enum.ExportMode.html) for that.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum DestroyMode {
     /// Forces any active datasets contained within the pool to be unmounted. Might result in
@@ -292,10 +365,66 @@ pub enum ExportMode {
     Gentle,
 }
 
+/// Strategy to use when clearing device errors.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClearMode {
+    /// Just clear the errors.
+    Gentle,
+    /// Initiate recovery mode for a pool that can't be opened. Last few transactions might be
+    /// discarded.
+    Rewind,
+    /// Check whether discarding transactions would make the pool openable, but don't actually
+    /// discard anything.
+    RewindDryRun,
+}
+
+/// Background activity to wait for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WaitActivity {
+    /// Resilver, including the one started by replace.
+    Resilver,
+    /// Scrub.
+    Scrub,
+    /// Manual trim of the devices.
+    Trim,
+    /// Device initialization.
+    Initialize,
+    /// Freeing of space released by destroyed datasets (`freeing` property).
+    Free,
+    /// Device removal.
+    Remove,
+}
+
+impl WaitActivity {
+    /// Name of the activity as understood by `zpool wait -t`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WaitActivity::Resilver => "resilver",
+            WaitActivity::Scrub => "scrub",
+            WaitActivity::Trim => "trim",
+            WaitActivity::Initialize => "initialize",
+            WaitActivity::Free => "free",
+            WaitActivity::Remove => "remove",
+        }
+    }
+}
+
 impl Default for CreateMode {
     fn default() -> CreateMode { CreateMode::Gentle }
 }
 
+/// Pool created by
+/// [`ZpoolEngine::create_returning_guid`](trait.ZpoolEngine.html#method.create_returning_guid).
+#[derive(Clone, PartialEq, Eq, Debug, Getters)]
+#[get = "pub"]
+pub struct CreatedPool {
+    /// Name of the pool.
+    name: String,
+    /// GUID of the pool. Unlike name, it never changes (unless pool is reguided), so it's the
+    /// identifier to track the pool by and to import it with.
+    guid: u64,
+}
+
 /// Interface to manage zpools. This documentation implies that you know how to use [`zpool(8)`](https://www.freebsd.org/cgi/man.cgi?zpool(8)).
 pub trait ZpoolEngine {
     /// Check if pool with given name exists. NOTE: this won't return
@@ -305,14 +434,36 @@ pub trait ZpoolEngine {
     /// * `name` - Name of the zpool.
     fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool>;
 
+    /// Check if imported pool with given GUID exists. Unlike name, GUID doesn't change when pool
+    /// is imported under another name, so it's better way to keep track of a pool.
+    ///
+    /// * `guid` - Numeric identifier of the pool, see [`Zpool::id`](struct.Zpool.html#method.id).
+    fn exists_by_guid(&self, guid: u64) -> ZpoolResult<bool>;
+
     /// Create new zpool.
     ///
     /// * request - A request to create a zpool. Consult documentation for
     ///   [`CreateZpoolRequest`](vdev/enum.CreateVdevRequest.html) for more information.
     fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()>;
 
+    /// Same as [`create`](#method.create), but also reads GUID of the new pool right after it's
+    /// created.
+    ///
+    /// * request - A request to create a zpool.
+    fn create_returning_guid(&self, request: CreateZpoolRequest) -> ZpoolResult<CreatedPool> {
+        let name = request.name().clone();
+        self.create(request)?;
+        let guid = *self.read_properties(&name)?.guid();
+        Ok(CreatedPool { name, guid })
+    }
+
     /// Destroy zpool. NOTE: returns `Ok(())` if pool doesn't exist.
     ///
+    /// Pool is marked as destroyed on every device it consists of, so it's no longer available
+    /// for import and devices can be used for something else. Data is gone unless the devices
+    /// are left untouched and the pool is recovered with `zpool import -D`. Use
+    /// [`export`](#tymethod.export) to release the pool while keeping it importable.
+    ///
     /// * `name` - Name of the zpool.
     /// * `mode` - Strategy to use when destroying the pool.
     fn destroy<N: AsRef<str>>(&self, name: N, mode: DestroyMode) -> ZpoolResult<()>;
@@ -322,6 +473,13 @@ pub trait ZpoolEngine {
     /// * `name` - Name of the zpool.
     fn read_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolProperties>;
 
+    /// Read properties of the pool together with state of all `feature@` flags. Unlike
+    /// [`read_properties`](#tymethod.read_properties) this reads everything with a single
+    /// `zpool get all`, so there is no need to query features one by one.
+    ///
+    /// * `name` - Name of the zpool.
+    fn read_properties_full<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolPropertiesFull>;
+
     /// Update zpool properties.
     ///
     /// * `name` - Name of the zpool.
@@ -345,6 +503,15 @@ pub trait ZpoolEngine {
             self.set_property(&name, "autoreplace", props.auto_replace())?;
         }
 
+        if current.auto_trim() != props.auto_trim() {
+            self.set_property(&name, "autotrim", props.auto_trim())?;
+        }
+
+        if current.boot_fs() != props.boot_fs() {
+            let boot_fs = props.boot_fs().clone().unwrap_or_default();
+            self.set_property(&name, "bootfs", &boot_fs)?;
+        }
+
         if current.cache_file() != props.cache_file() {
             self.set_property(&name, "cachefile", props.cache_file())?;
         }
@@ -379,10 +546,11 @@ pub trait ZpoolEngine {
         value: &P,
     ) -> ZpoolResult<()>;
 
-    /// Exports the given pools from the system.
+    /// Exports the given pools from the system. Unlike [`destroy`](#tymethod.destroy), devices
+    /// keep the pool intact and it shows up as available for import.
     ///
     /// * `name` - Name of the zpool.
-    /// * `mode` - Strategy to use when destroying the pool.
+    /// * `mode` - Strategy to use when exporting the pool.
     fn export<N: AsRef<str>>(&self, name: N, mode: ExportMode) -> ZpoolResult<()>;
 
     /// List of pools available for import in `/dev/` directory.
@@ -394,6 +562,12 @@ pub trait ZpoolEngine {
     ///   from files.
     fn available_in_dir(&self, dir: PathBuf) -> ZpoolResult<Vec<Zpool>>;
 
+    /// List of pools available for import. Only `search_dirs` and `cachefile` of `options` are
+    /// taken into account.
+    ///
+    /// * `options` - Where to look for pools.
+    fn available_with_options(&self, options: ImportOptions) -> ZpoolResult<Vec<Zpool>>;
+
     /// Import pool from `/dev/`.
     fn import<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
 
@@ -403,12 +577,97 @@ pub trait ZpoolEngine {
     ///   from files.
     fn import_from_dir<N: AsRef<str>>(&self, name: N, dir: PathBuf) -> ZpoolResult<()>;
 
+    /// Import pool using given options.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `options` - Options to use for import.
+    fn import_with_options<N: AsRef<str>>(
+        &self,
+        name: N,
+        options: ImportOptions,
+    ) -> ZpoolResult<()>;
+
+    /// Import pool by its numeric identifier. The only way to import a pool when more than one
+    /// pool available for import shares the same name.
+    ///
+    /// * `guid` - Numeric identifier of the pool, see [`Zpool::id`](struct.Zpool.html#method.id).
+    /// * `new_name` - Import pool under a different name.
+    /// * `options` - Options to use for import.
+    fn import_by_guid(
+        &self,
+        guid: u64,
+        new_name: Option<&str>,
+        options: ImportOptions,
+    ) -> ZpoolResult<()>;
+
+    /// Import every pool available for import. Unlike `zpool import -a` doesn't stop on the first
+    /// failure, instead outcome of each import is reported next to pool's name. Pools are imported
+    /// by GUID, so pools sharing the same name don't get in the way of each other. A pool whose
+    /// name is already taken by imported pool is reported as
+    /// [`ZpoolError::PoolAlreadyImported`](enum.ZpoolError.html#variant.PoolAlreadyImported).
+    ///
+    /// * `options` - Where to look for pools and options to use for import.
+    fn import_all(&self, options: ImportOptions) -> ZpoolResult<Vec<(String, ZpoolResult<()>)>> {
+        let pools = self.available_with_options(options.clone())?;
+        let results = pools
+            .into_iter()
+            .map(|pool| {
+                let result = match pool.id() {
+                    Some(guid) => self.import_by_guid(*guid, None, options.clone()),
+                    None => self.import_with_options(pool.name(), options.clone()),
+                };
+                (pool.name().clone(), result)
+            })
+            .collect();
+        Ok(results)
+    }
+
     /// Get the detailed status of the given pools.
-    fn status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool>;
+    ///
+    /// * `verbose` - Also list files with permanent errors in
+    ///   [`Zpool::damaged`](struct.Zpool.html#method.damaged). Same as `zpool status -v`.
+    fn status<N: AsRef<str>>(&self, name: N, verbose: bool) -> ZpoolResult<Zpool>;
+
+    /// Get a map of leaf vdev GUID to its current path. Unlike paths, GUIDs survive device
+    /// renumbering across reboots. Symbolic links in paths are resolved.
+    ///
+    /// * `name` - Name of the zpool.
+    fn vdev_guids<N: AsRef<str>>(&self, name: N) -> ZpoolResult<HashMap<u64, PathBuf>>;
+
+    /// Find GUID of a leaf vdev by its path. Path is resolved the same way as in
+    /// [`vdev_guids`](#method.vdev_guids), bare device name (`sda1`) is matched against the last
+    /// component of the path. `None` if device isn't part of the pool.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `device` - Path to the device.
+    fn vdev_guid<N: AsRef<str>, D: AsRef<Path>>(
+        &self,
+        name: N,
+        device: D,
+    ) -> ZpoolResult<Option<u64>> {
+        let device = device.as_ref();
+        let resolved = device.canonicalize().ok();
+        let guid = self.vdev_guids(name)?.into_iter().find_map(|(guid, path)| {
+            let found = path == device
+                || Some(&path) == resolved.as_ref()
+                || (device.is_relative() && path.file_name() == Some(device.as_os_str()));
+            if found {
+                Some(guid)
+            } else {
+                None
+            }
+        });
+        Ok(guid)
+    }
 
     /// Get a status of each active (imported) pool in the system
     fn all(&self) -> ZpoolResult<Vec<Zpool>>;
 
+    /// Get a status of each active (imported) pool in the system with a single `zpool status`
+    /// call. Unlike [`all`](#tymethod.all), fails with `ParseError` if any of the pools can't be
+    /// parsed instead of silently skipping the rest.
+    fn status_all(&self) -> ZpoolResult<Vec<Zpool>>;
+
     /// Begins a scrub or resumes a paused scrub. The scrub examines all data
     /// in the specified pools to verify that it checksums correctly. For
     /// replicated (mirror or raidz) devices, ZFS automatically repairs any
@@ -431,11 +690,126 @@ pub trait ZpoolEngine {
     /// * `name` - Name of the zpool.
     fn stop_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
 
+    /// Block until background activity on the pool finishes. Returns right away if activity
+    /// isn't running. Same as `zpool wait -t <activity>`, on versions without `zpool wait` pool
+    /// status is polled instead.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `activity` - Activity to wait for.
+    fn wait<N: AsRef<str>>(&self, name: N, activity: WaitActivity) -> ZpoolResult<()>;
+
+    /// Scrub the pool, wait for scrub to finish and report what it found. Joins already running
+    /// scrub instead of failing.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `timeout` - How long to wait for scrub to finish. Scrub keeps going after timeout,
+    ///   `Timeout` is returned.
+    fn scrub_and_report<N: AsRef<str>>(
+        &self,
+        name: N,
+        timeout: Option<Duration>,
+    ) -> ZpoolResult<ScrubReport>;
+
+    /// Replace a device and wait for resilver to finish. Errors that prevent replace from
+    /// starting (new device is too small, for example) are returned right away.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `old_disk` - A disk to be replaced.
+    /// * `new_disk` - A new disk.
+    /// * `timeout` - How long to wait for resilver to finish. Resilver keeps going after timeout,
+    ///   `ResilverTimeout` with current progress is returned.
+    fn replace_and_wait<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+        timeout: Option<Duration>,
+    ) -> ZpoolResult<()>;
+
+    /// Reopen all the vdevs associated with the pool. Useful after devices were re-cabled or
+    /// hot-swapped, since it refreshes device references without export/import cycle.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `scrub_restart` - Restart scrub if it's in progress. If `false` then in-progress scrub is
+    ///   left alone.
+    fn reopen<N: AsRef<str>>(&self, name: N, scrub_restart: bool) -> ZpoolResult<()>;
+
+    /// Upgrade the pool, enabling every feature supported by the running software. This is a
+    /// one-way operation: older systems that don't know about some of the enabled features won't
+    /// be able to import the pool anymore. Pool is never touched unless `all_features` is `true`,
+    /// otherwise it's only verified that the pool exists. Upgrading an up-to-date pool is a
+    /// no-op.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `all_features` - Explicit consent to enable all supported features.
+    fn upgrade<N: AsRef<str>>(&self, name: N, all_features: bool) -> ZpoolResult<()>;
+
+    /// Assign a new GUID to the pool. Useful when a copy of a pool (split-off half of a mirror,
+    /// cloned LUN) has to be imported next to the original.
+    ///
+    /// * `name` - Name of the zpool.
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Import pool produced by `zpool split` and give it a new GUID, so it never gets mixed up
+    /// with the original pool. Only the split-off pool is touched. Pool that is already imported
+    /// is reguided as is. Returns the new GUID.
+    ///
+    /// * `new_name` - Name of the split-off pool.
+    /// * `options` - Options to use for import. Set `search_dir` for pools made of files.
+    fn post_split_reguid<N: AsRef<str>>(
+        &self,
+        new_name: N,
+        options: ImportOptions,
+    ) -> ZpoolResult<u64> {
+        match self.import_with_options(&new_name, options) {
+            Ok(()) | Err(ZpoolError::PoolAlreadyImported) => {},
+            Err(e) => return Err(e),
+        }
+        self.reguid(&new_name)?;
+        Ok(*self.read_properties(&new_name)?.guid())
+    }
+
+    /// Take a checkpoint of the pool. Pool can be rewound to it on import with
+    /// [`rewind_to_checkpoint`](import/struct.ImportOptions.html#method.rewind_to_checkpoint).
+    /// Only one checkpoint can exist at a time, otherwise it's
+    /// [`ZpoolError::CheckpointExists`](enum.ZpoolError.html#variant.CheckpointExists). While the
+    /// checkpoint exists, pool can't be reguided, its devices can't be removed, attached, split
+    /// or reguided.
+    ///
+    /// * `name` - Name of the zpool.
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Discard checkpoint of the pool, releasing space it holds. Space is freed in the
+    /// background, new checkpoint can't be taken until it's done.
+    ///
+    /// * `name` - Name of the zpool.
+    fn checkpoint_discard<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Take a sample of pool's free space fragmentation. Record it with
+    /// [`FragmentationHistory`](fragmentation/struct.FragmentationHistory.html) to see the trend.
+    ///
+    /// * `name` - Name of the zpool.
+    fn sample_fragmentation<N: AsRef<str>>(&self, name: N) -> ZpoolResult<FragmentationSample>;
+
+    /// Clear device errors in the pool. If rewind mode is used, then returns approximate amount of
+    /// transactions that would be (or were) discarded. `zpool` reports it as time span, so that's
+    /// what you get.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `mode` - Whether to rewind the pool and whether to do it for real.
+    fn clear<N: AsRef<str>>(&self, name: N, mode: ClearMode) -> ZpoolResult<Option<Duration>>;
+
+    /// Read events generated by ZFS kernel modules. Events are yielded from oldest to newest.
+    ///
+    /// * `follow` - Don't stop at the last event, instead block and wait for new ones. Iterator
+    ///   never ends in this mode, drop it to stop listening.
+    fn events(&self, follow: bool) -> ZpoolResult<EventsIter>;
+
     /// Takes the specified physical device offline. While the device is
     /// offline, no attempt is made to read or write to the device.
     ///
     /// * `name` - Name of the zpool.
-    /// * `device` - Name of the device or path to sparse file.
+    /// * `device` - Name of the device, path to sparse file or GUID of the device.
     /// * `mode` - Strategy to use when taking device offline
     fn take_offline<N: AsRef<str>, D: AsRef<OsStr>>(
         &self,
@@ -447,7 +821,7 @@ pub trait ZpoolEngine {
     /// Brings the specified physical device online.
     ///
     /// * `name` - Name of the zpool.
-    /// * `device` - Name of the device or path to sparse file.
+    /// * `device` - Name of the device, path to sparse file or GUID of the device.
     /// * `mode` - Strategy to use when taking device online
     fn bring_online<N: AsRef<str>, D: AsRef<OsStr>>(
         &self,
@@ -476,10 +850,11 @@ pub trait ZpoolEngine {
     /// other valid replicas of the data.
     ///
     /// * `name` - Name of the zpool
-    /// * `device` - Name of the device or path to sparse file.
+    /// * `device` - Name of the device, path to sparse file or GUID of the device.
     fn detach<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()>;
 
-    /// Add a VDEV to existing Zpool.
+    /// Add a VDEV to existing Zpool. VDEV without enough devices is rejected with `InvalidTopology`
+    /// before `zpool` is called, same as on create.
     ///
     /// * `name` - Name of the zpool
     /// * `new_vdev` - New VDEV
@@ -491,7 +866,8 @@ pub trait ZpoolEngine {
         add_mode: CreateMode,
     ) -> ZpoolResult<()>;
 
-    /// Add a ZIL to existing Zpool.
+    /// Add a ZIL to existing Zpool. Same as on create, only single disks and mirrors with enough
+    /// devices are accepted, anything else is rejected with `InvalidTopology`.
     ///
     /// * `name` - Name of the zpool
     /// * `new_zil` - A VDEV to use as ZIL
@@ -529,7 +905,8 @@ pub trait ZpoolEngine {
 
     /// [Replace](https://docs.oracle.com/cd/E19253-01/819-5461/gazgd/index.html) a device with another.
     ///
-    /// * `old_disk` - A disk to be replaced.
+    /// * `old_disk` - A disk to be replaced. Can be given by GUID, which keeps working even when
+    ///   the disk is missing or was renamed.
     /// * `new_disk` - A new disk.
     fn replace_disk<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
         &self,
@@ -557,13 +934,13 @@ mod test {
         let err = ZpoolError::from_stderr(vdev_reuse_text);
 
         assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
-        if let ZpoolError::VdevReuse(vdev, pool) = err {
-            assert_eq!("/vdevs/vdev0", vdev);
-            assert_eq!("tank", pool);
+        if let ZpoolError::VdevReuse(vdevs) = err {
+            assert_eq!(vec![(String::from("/vdevs/vdev0"), String::from("tank"))], vdevs);
         }
 
         let err = ZpoolError::from_stderr(unknown_text);
         assert_eq!(ZpoolErrorKind::Other, err.kind());
+        assert_eq!("wat", err.to_string());
         if let ZpoolError::Other(text) = err {
             assert_eq!("wat", text);
         }
@@ -579,6 +956,17 @@ mod test {
         let vdev_reuse_text = b"invalid vdev specification\nuse '-f' to override the following errors:\n/vdevs/vdev0 is part of exported pool 'test'\n";
         let err = ZpoolError::from_stderr(vdev_reuse_text);
         assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
+
+        let vdev_reuse_text = b"invalid vdev specification\nuse '-f' to override the following errors:\n/vdevs/vdev0 is part of active pool 'tank'\n/vdevs/vdev1 is part of active pool 'tank'\n/vdevs/vdev2 is part of exported pool 'backup'\n";
+        let expected = vec![
+            (String::from("/vdevs/vdev0"), String::from("tank")),
+            (String::from("/vdevs/vdev1"), String::from("tank")),
+            (String::from("/vdevs/vdev2"), String::from("backup")),
+        ];
+        match ZpoolError::from_stderr(vdev_reuse_text) {
+            ZpoolError::VdevReuse(vdevs) => assert_eq!(expected, vdevs),
+            err => panic!("unexpected error: {:?}", err),
+        }
     }
 
     #[test]
@@ -619,6 +1007,14 @@ mod test {
         let err = ZpoolError::from_stderr(text);
 
         assert_eq!(ZpoolErrorKind::PermissionDenied, err.kind());
+
+        let text = b"cannot open 'tank': Operation not permitted\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::PermissionDenied, err.kind());
+
+        let text = b"Permission denied the ZFS utilities must be run as root.\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::PermissionDenied, err.kind());
     }
 
     #[test]
@@ -687,4 +1083,20 @@ mod test {
         let err = ZpoolError::from_stderr(text);
         assert_eq!(ZpoolErrorKind::InvalidCacheDevice, err.kind());
     }
+
+    #[test]
+    fn test_ambiguous_pool_name() {
+        let text =
+            b"cannot import 'tank': more than one matching pool\nimport by numeric ID instead\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::AmbiguousPoolName, err.kind());
+    }
+
+    #[test]
+    fn test_checkpoint_errors() {
+        let text = b"cannot checkpoint 'tank': checkpoint exists\n";
+        assert_eq!(ZpoolErrorKind::CheckpointExists, ZpoolError::from_stderr(text).kind());
+        let text = b"cannot discard checkpoint in 'tank': checkpoint does not exist\n";
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, ZpoolError::from_stderr(text).kind());
+    }
 }
@@ -4,7 +4,8 @@
 //! It will look for `ZPOOL_CMD` in current
 //! environment and fall back to `zpool` in `PATH`.
 //!
-//! Another way to specify is to use `ZpoolOpen3::new("/path/to/my/zpool")`.
+//! Another way to specify is to use `ZpoolOpen3::with_cmd("/path/to/my/zpool")`. Environment and
+//! working directory of spawned processes can be set with `with_env` and `with_working_dir`.
 //!
 //! ### Usage
 //! ```rust,no_run
@@ -21,35 +22,60 @@
 //!
 //! It's called [open3](https://docs.ruby-lang.org/en/2.0.0/Open3.html) because it opens `stdin`, `stdout`, `stderr`.
 
-use std::{env,
+use std::{collections::HashMap,
+          env,
           ffi::{OsStr, OsString},
+          io::{self, Read},
+          os::unix::process::ExitStatusExt,
           path::PathBuf,
-          process::{Command, Output, Stdio}};
+          process::{Command, ExitStatus, Output, Stdio},
+          sync::Mutex,
+          thread,
+          time::{Duration, Instant, SystemTime}};
 
-use crate::{parsers::{Rule, StdoutParser},
+use crate::{observe,
+            parsers::{Rule, StdoutParser},
             zpool::description::Zpool,
             GlobalLogger};
 use pest::Parser;
+use regex::Regex;
 use slog::Logger;
 
-use super::{CreateMode, CreateVdevRequest, CreateZpoolRequest, DestroyMode, ExportMode,
-            OfflineMode, OnlineMode, PropPair, ZpoolEngine, ZpoolError, ZpoolProperties,
-            ZpoolResult};
+use super::{topology, ClearMode, CreateMode, CreateVdevRequest, CreateZpoolRequest, DestroyMode,
+            EventsIter, ExportMode, FragmentationSample, ImportOptions, OfflineMode, OnlineMode,
+            PropPair, ScrubReport, WaitActivity, ZpoolEngine, ZpoolError, ZpoolErrorKind,
+            ZpoolProperties, ZpoolPropertiesFull, ZpoolResult};
 
 lazy_static! {
     static ref ZPOOL_PROP_ARG: OsString = {
-        let mut arg = OsString::with_capacity(171);
+        let mut arg = OsString::with_capacity(180);
         arg.push("alloc,cap,comment,dedupratio,expandsize,fragmentation,free,");
         arg.push("freeing,guid,health,size,leaked,altroot,readonly,autoexpand,");
-        arg.push("autoreplace,bootfs,cachefile,dedupditto,delegation,failmode");
+        arg.push("autoreplace,bootfs,cachefile,dedupditto,delegation,failmode,");
+        arg.push("autotrim,checkpoint");
         arg
     };
+    static ref RE_DISCARDED: Regex =
+        Regex::new(r"(?:Would discard|Discarded) approximately (\d+) (seconds|minutes)")
+            .expect("failed to compile RE_DISCARDED");
+    static ref RE_NO_WAIT_COMMAND: Regex =
+        Regex::new(r"unrecognized command 'wait'").expect("failed to compile RE_NO_WAIT_COMMAND");
 }
+
+/// How often to check if `zpool wait` exited when waiting with timeout.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often to check pool status when `zpool wait` isn't available.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Open3 implementation of [`ZpoolEngine`](../trait.ZpoolEngine.html). You can use
 /// `ZpoolOpen3::default` to create it.
 pub struct ZpoolOpen3 {
-    cmd_name: OsString,
-    logger:   Logger,
+    cmd_name:     OsString,
+    logger:       Logger,
+    dry_run:      bool,
+    last_command: Mutex<Option<String>>,
+    env:          Vec<(OsString, OsString)>,
+    working_dir:  Option<PathBuf>,
 }
 
 impl Default for ZpoolOpen3 {
@@ -63,7 +89,14 @@ impl Default for ZpoolOpen3 {
 
         let logger =
             GlobalLogger::get().new(o!("zetta_module" => "zpool", "zpool_impl" => "open3"));
-        ZpoolOpen3 { cmd_name, logger }
+        ZpoolOpen3 {
+            cmd_name,
+            logger,
+            dry_run: false,
+            last_command: Mutex::new(None),
+            env: Vec::new(),
+            working_dir: None,
+        }
     }
 }
 impl ZpoolOpen3 {
@@ -75,7 +108,166 @@ impl ZpoolOpen3 {
         z
     }
 
-    fn zpool(&self) -> Command { Command::new(&self.cmd_name) }
+    /// Don't execute anything, only record commands. Every command is treated as successful with
+    /// empty output, so methods that have to parse output will fail with `ParseError`. Use
+    /// [`last_command`](#method.last_command) to see what would have been executed.
+    pub fn dry_run(mut self) -> ZpoolOpen3 {
+        self.dry_run = true;
+        self
+    }
+
+    /// Set environment variable of every spawned `zpool`, on top of inherited environment.
+    pub fn with_env<K: Into<OsString>, V: Into<OsString>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> ZpoolOpen3 {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Run every `zpool` in given directory instead of current one.
+    pub fn with_working_dir<P: Into<PathBuf>>(mut self, dir: P) -> ZpoolOpen3 {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Last command executed (or recorded in dry run mode), rendered the same way as in logs.
+    pub fn last_command(&self) -> Option<String> {
+        self.last_command.lock().ok().and_then(|last| last.clone())
+    }
+
+    fn zpool(&self) -> Command {
+        let mut z = Command::new(&self.cmd_name);
+        z.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(dir) = &self.working_dir {
+            z.current_dir(dir);
+        }
+        z
+    }
+
+    /// Remember the command. Returns `true` if command shouldn't be executed.
+    fn record(&self, z: &Command) -> bool {
+        if let Ok(mut last) = self.last_command.lock() {
+            *last = Some(format!("{:?}", z));
+        }
+        self.dry_run
+    }
+
+    fn output(&self, z: &mut Command) -> io::Result<Output> {
+        if self.record(z) {
+            Ok(Output { status: ExitStatus::from_raw(0), stdout: Vec::new(), stderr: Vec::new() })
+        } else {
+            let started = Instant::now();
+            let out = z.output();
+            match &out {
+                Ok(out) => {
+                    debug!(self.logger, "executed";
+                        "cmd" => format_args!("{:?}", z),
+                        "status" => %out.status,
+                        "elapsed" => format_args!("{:?}", started.elapsed()));
+                    trace!(self.logger, "output";
+                        "stdout" => %String::from_utf8_lossy(&out.stdout),
+                        "stderr" => %String::from_utf8_lossy(&out.stderr));
+                },
+                Err(e) => debug!(self.logger, "failed to execute";
+                    "cmd" => format_args!("{:?}", z),
+                    "error" => %e,
+                    "elapsed" => format_args!("{:?}", started.elapsed())),
+            }
+            let success = out.as_ref().map_or(false, |out| out.status.success());
+            observe::command_finished("zpool", z, started, success);
+            out
+        }
+    }
+
+    /// Wait for `activity` to finish. Falls back to polling status if `zpool wait` isn't
+    /// supported.
+    fn wait_for(
+        &self,
+        activity: WaitActivity,
+        name: &str,
+        timeout: Option<Duration>,
+    ) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["wait", "-t", activity.as_str(), name]);
+        z.stdout(Stdio::null());
+        z.stderr(Stdio::piped());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        if self.record(&z) {
+            return Ok(());
+        }
+        let mut child = z.spawn()?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(status) = child.try_wait()? {
+                if status.success() {
+                    return Ok(());
+                }
+                let mut stderr = Vec::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    pipe.read_to_end(&mut stderr)?;
+                }
+                if RE_NO_WAIT_COMMAND.is_match(&String::from_utf8_lossy(&stderr)) {
+                    return self.poll_until_done(activity, name, deadline);
+                }
+                return Err(ZpoolError::from_stderr(&stderr));
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(ZpoolError::Timeout);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+
+    fn poll_until_done(
+        &self,
+        activity: WaitActivity,
+        name: &str,
+        deadline: Option<Instant>,
+    ) -> ZpoolResult<()> {
+        loop {
+            if !self.in_progress(activity, name)? {
+                return Ok(());
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(ZpoolError::Timeout);
+            }
+            thread::sleep(STATUS_POLL_INTERVAL);
+        }
+    }
+
+    /// Check if activity is running by looking at `zpool status` (or `freeing` property).
+    fn in_progress(&self, activity: WaitActivity, name: &str) -> ZpoolResult<bool> {
+        let mut z = self.zpool();
+        match activity {
+            WaitActivity::Free => z.args(&["get", "-Hp", "-o", "value", "freeing"]),
+            WaitActivity::Trim => z.args(&["status", "-t"]),
+            _ => z.arg("status"),
+        };
+        z.arg(name);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(ZpoolError::from_stderr(&out.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let marker = match activity {
+            WaitActivity::Free => return Ok(stdout.trim().parse::<u64>()? != 0),
+            WaitActivity::Resilver => "resilver in progress",
+            WaitActivity::Scrub => "scrub in progress",
+            WaitActivity::Trim => "trimmed, started at",
+            WaitActivity::Initialize => "initialized, started at",
+            WaitActivity::Remove => {
+                return Ok(stdout.lines().any(|line| {
+                    line.trim_start().starts_with("remove:") && line.contains("in progress")
+                }))
+            },
+        };
+        Ok(stdout.contains(marker))
+    }
 
     #[allow(dead_code)]
     /// Force disable logging by using `/dev/null` as drain.
@@ -99,21 +291,180 @@ impl ZpoolOpen3 {
             Err(ZpoolError::from_stderr(&out.stderr))
         }
     }
+
+    /// Fill in candidates when import by name failed because the name is ambiguous.
+    fn with_candidates(&self, err: ZpoolError, name: &str, options: ImportOptions) -> ZpoolError {
+        if err.kind() != ZpoolErrorKind::AmbiguousPoolName {
+            return err;
+        }
+        match self.available_with_options(options) {
+            Ok(zpools) => ZpoolError::AmbiguousPoolName(
+                zpools.iter().filter(|z| z.name() == name).filter_map(|z| *z.id()).collect(),
+            ),
+            Err(_) => err,
+        }
+    }
+
+    /// Run listing command once more with GUIDs instead of vdev names and fill them in `zpools`.
+    fn fill_guids(&self, zpools: &mut [Zpool], mut z: Command) -> ZpoolResult<()> {
+        if zpools.is_empty() {
+            return Ok(());
+        }
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            set_guids_from_listing(zpools, &String::from_utf8_lossy(&out.stdout));
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn status_stdout(&self, name: &str, flags: &str) -> ZpoolResult<String> {
+        let mut z = self.zpool();
+        z.arg("status");
+        z.arg(flags);
+        z.arg(name);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(String::from_utf8_lossy(&out.stdout).into())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+}
+
+/// Extract first column of every device line in `config:` section of `zpool status` output.
+/// Section headers (`logs`, `cache`, `spares`) and table header are skipped.
+fn config_device_names(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .skip_while(|line| line.trim() != "config:")
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with("errors:"))
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            match (columns.next(), columns.next()) {
+                (Some("NAME"), Some("STATE")) => None,
+                (Some(name), Some(_)) => Some(name),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// GUIDs of every vdev and device out of `zpool status -g` output, in the order they are listed.
+/// GUID of the pool itself is skipped.
+pub(crate) fn guids_from_status(stdout: &str) -> ZpoolResult<Vec<u64>> {
+    config_device_names(stdout).into_iter().skip(1).map(|guid| Ok(guid.parse()?)).collect()
+}
+
+/// Split output listing several pools (`zpool status`, `zpool import`) into one chunk per pool.
+fn pool_sections(stdout: &str) -> Vec<(&str, &str)> {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in stdout.split_inclusive('\n') {
+        if let Some(name) = line.trim().strip_prefix("pool:") {
+            starts.push((name.trim(), offset));
+        }
+        offset += line.len();
+    }
+    let ends = starts.iter().skip(1).map(|(_, start)| *start).chain(Some(stdout.len()));
+    starts.iter().zip(ends).map(|((name, start), end)| (*name, &stdout[*start..end])).collect()
+}
+
+/// Fill in GUIDs of `zpools` out of the same listing printed with GUIDs instead of vdev names.
+/// Pools are matched by position and name, pool that doesn't match or whose section has no GUIDs
+/// (older `zpool` ignoring `ZPOOL_VDEV_NAME_GUID`) is left without them.
+pub(crate) fn set_guids_from_listing(zpools: &mut [Zpool], stdout: &str) {
+    for (zpool, (name, section)) in zpools.iter_mut().zip(pool_sections(stdout)) {
+        match guids_from_status(section) {
+            Ok(guids) if zpool.name() == name => zpool.set_guids(&guids),
+            _ => {},
+        }
+    }
+}
+
+/// Build a map of leaf vdev GUID to its path out of `zpool status -gL` and `zpool status -LP`
+/// outputs. Both outputs list devices in the same order, so the config sections are zipped line by
+/// line. Only lines with absolute path (actual disks and files) end up in the map.
+pub(crate) fn vdev_guids_from_stdout(
+    guids: &str,
+    paths: &str,
+) -> ZpoolResult<HashMap<u64, PathBuf>> {
+    let guids = config_device_names(guids);
+    let paths = config_device_names(paths);
+    if guids.len() != paths.len() {
+        return Err(ZpoolError::ParseError);
+    }
+    let mut ret = HashMap::with_capacity(guids.len());
+    for (guid, path) in guids.into_iter().zip(paths.into_iter()) {
+        let path = PathBuf::from(path);
+        if path.is_absolute() {
+            ret.insert(guid.parse()?, path);
+        }
+    }
+    Ok(ret)
+}
+
+/// Parse output of `zpool status` without pool name. Every pool must be parsed.
+pub(crate) fn zpools_from_status(stdout: &str) -> ZpoolResult<Vec<Zpool>> {
+    if stdout.trim() == "no pools available" {
+        return Ok(Vec::new());
+    }
+    StdoutParser::parse(Rule::zpools_status, stdout).map_err(|_| ZpoolError::ParseError).map(
+        |pairs| {
+            pairs.filter(|pair| pair.as_rule() == Rule::zpool).map(Zpool::from_pest_pair).collect()
+        },
+    )
+}
+
+/// Parse how much was (or would be) discarded by `zpool clear -F`. Nothing in output means
+/// nothing to discard.
+fn parse_discarded(stdout: &str) -> ZpoolResult<Duration> {
+    match RE_DISCARDED.captures(stdout) {
+        Some(caps) => {
+            let amount: u64 = caps[1].parse()?;
+            match &caps[2] {
+                "minutes" => Ok(Duration::from_secs(amount * 60)),
+                _ => Ok(Duration::from_secs(amount)),
+            }
+        },
+        None => Ok(Duration::from_secs(0)),
+    }
 }
 
 impl ZpoolEngine for ZpoolOpen3 {
     fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool> {
-        let mut z = self.zpool_mute();
-        z.arg("list").arg(name.as_ref());
+        let mut z = self.zpool();
+        z.args(&["list", "-H", "-o", "name"]).arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let status = z.status()?;
-        Ok(status.success())
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            return Ok(true);
+        }
+        match ZpoolError::from_stderr(&out.stderr) {
+            ZpoolError::PoolNotFound => Ok(false),
+            err => Err(err),
+        }
     }
 
-    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
-        if !request.is_suitable_for_create() {
-            return Err(ZpoolError::InvalidTopology);
+    fn exists_by_guid(&self, guid: u64) -> ZpoolResult<bool> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "-o", "value", "guid"]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            Ok(stdout.lines().any(|line| line.trim().parse() == Ok(guid)))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
         }
+    }
+
+    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
+        request.validate().map_err(ZpoolError::InvalidTopology)?;
         let mut z = self.zpool();
         z.arg("create");
         if request.create_mode() == &CreateMode::Force {
@@ -136,7 +487,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(request.name());
         z.args(request.into_args());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -145,14 +496,21 @@ impl ZpoolEngine for ZpoolOpen3 {
     }
 
     fn destroy<N: AsRef<str>>(&self, name: N, mode: DestroyMode) -> ZpoolResult<()> {
-        let mut z = self.zpool_mute();
+        let mut z = self.zpool();
         z.arg("destroy");
         if let DestroyMode::Force = mode {
             z.arg("-f");
         }
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        z.status().map(|_| Ok(()))?
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            return Ok(());
+        }
+        match ZpoolError::from_stderr(&out.stderr) {
+            ZpoolError::PoolNotFound => Ok(()),
+            e => Err(e),
+        }
     }
 
     fn read_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolProperties> {
@@ -161,7 +519,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(&*ZPOOL_PROP_ARG);
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             ZpoolProperties::try_from_stdout(&out.stdout)
         } else {
@@ -169,6 +527,19 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn read_properties_full<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolPropertiesFull> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "all"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            ZpoolPropertiesFull::try_from_stdout(&out.stdout)
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
     fn set_property<N: AsRef<str>, P: PropPair>(
         &self,
         name: N,
@@ -180,7 +551,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(OsString::from(PropPair::to_pair(value, key)));
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -196,7 +567,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -205,44 +576,79 @@ impl ZpoolEngine for ZpoolOpen3 {
     }
 
     fn available(&self) -> ZpoolResult<Vec<Zpool>> {
-        let mut z = self.zpool();
-        z.arg("import");
-        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
-        self.zpools_from_import(out)
+        self.available_with_options(ImportOptions::default())
     }
 
     fn available_in_dir(&self, dir: PathBuf) -> ZpoolResult<Vec<Zpool>> {
+        let options = ImportOptions::builder()
+            .search_dir(dir)
+            .build()
+            .expect("Failed to build ImportOptions. Please report at: https://github.com/Inner-Heaven/libzetta-rs");
+        self.available_with_options(options)
+    }
+
+    fn available_with_options(&self, options: ImportOptions) -> ZpoolResult<Vec<Zpool>> {
         let mut z = self.zpool();
         z.arg("import");
-        z.arg("-d");
-        z.arg(dir);
+        z.args(options.search_args());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
-        self.zpools_from_import(out)
+        let out = self.output(&mut z)?;
+        let mut zpools = self.zpools_from_import(out)?;
+        // `zpool import` has no `-g`, but honors the variable that does the same.
+        let mut g = self.zpool();
+        g.arg("import");
+        g.args(options.search_args());
+        g.env("ZPOOL_VDEV_NAME_GUID", "1");
+        self.fill_guids(&mut zpools, g)?;
+        Ok(zpools)
     }
 
     fn import<N: AsRef<str>>(&self, name: N) -> Result<(), ZpoolError> {
+        self.import_with_options(name, ImportOptions::default())
+    }
+
+    fn import_from_dir<N: AsRef<str>>(&self, name: N, dir: PathBuf) -> ZpoolResult<()> {
+        let options = ImportOptions::builder()
+            .search_dir(dir)
+            .build()
+            .expect("Failed to build ImportOptions. Please report at: https://github.com/Inner-Heaven/libzetta-rs");
+        self.import_with_options(name, options)
+    }
+
+    fn import_with_options<N: AsRef<str>>(
+        &self,
+        name: N,
+        options: ImportOptions,
+    ) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("import");
+        z.args(options.clone().into_args());
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
-            Err(ZpoolError::from_stderr(&out.stderr))
+            let err = ZpoolError::from_stderr(&out.stderr);
+            Err(self.with_candidates(err, name.as_ref(), options))
         }
     }
 
-    fn import_from_dir<N: AsRef<str>>(&self, name: N, dir: PathBuf) -> ZpoolResult<()> {
+    fn import_by_guid(
+        &self,
+        guid: u64,
+        new_name: Option<&str>,
+        options: ImportOptions,
+    ) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("import");
-        z.arg("-d");
-        z.arg(dir);
-        z.arg(name.as_ref());
+        z.args(options.into_args());
+        z.arg(guid.to_string());
+        if let Some(new_name) = new_name {
+            z.arg(new_name);
+        }
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -250,30 +656,60 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
-    fn status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+    fn status<N: AsRef<str>>(&self, name: N, verbose: bool) -> ZpoolResult<Zpool> {
         let mut z = self.zpool();
         z.arg("status");
+        if verbose {
+            z.arg("-v");
+        }
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         let zpools =
             self.zpools_from_import(out).expect("Failed to unwrap zpool from status check");
         if zpools.is_empty() {
             return Err(ZpoolError::PoolNotFound);
         }
-        let zpool = zpools.into_iter().next().expect("Can't build zpool out of pair. Please report at: https://github.com/Inner-Heaven/libzetta-rs");
+        let mut zpool = zpools.into_iter().next().expect("Can't build zpool out of pair. Please report at: https://github.com/Inner-Heaven/libzetta-rs");
         if zpool.name().as_str() != name.as_ref() {
             unreachable!();
         }
+        let guids = self.status_stdout(name.as_ref(), "-g")?;
+        zpool.set_guids(&guids_from_status(&guids)?);
         Ok(zpool)
     }
 
+    fn vdev_guids<N: AsRef<str>>(&self, name: N) -> ZpoolResult<HashMap<u64, PathBuf>> {
+        let guids = self.status_stdout(name.as_ref(), "-gL")?;
+        let paths = self.status_stdout(name.as_ref(), "-LP")?;
+        vdev_guids_from_stdout(&guids, &paths)
+    }
+
     fn all(&self) -> ZpoolResult<Vec<Zpool>> {
         let mut z = self.zpool();
         z.arg("status");
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
-        self.zpools_from_import(out)
+        let out = self.output(&mut z)?;
+        let mut zpools = self.zpools_from_import(out)?;
+        let mut g = self.zpool();
+        g.args(&["status", "-g"]);
+        self.fill_guids(&mut zpools, g)?;
+        Ok(zpools)
+    }
+
+    fn status_all(&self) -> ZpoolResult<Vec<Zpool>> {
+        let mut z = self.zpool();
+        z.arg("status");
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(ZpoolError::from_stderr(&out.stderr));
+        }
+        let mut zpools = zpools_from_status(&String::from_utf8_lossy(&out.stdout))?;
+        let mut g = self.zpool();
+        g.args(&["status", "-g"]);
+        self.fill_guids(&mut zpools, g)?;
+        Ok(zpools)
     }
 
     fn scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
@@ -281,7 +717,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg("scrub");
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -289,13 +725,39 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn wait<N: AsRef<str>>(&self, name: N, activity: WaitActivity) -> ZpoolResult<()> {
+        self.wait_for(activity, name.as_ref(), None)
+    }
+
+    fn scrub_and_report<N: AsRef<str>>(
+        &self,
+        name: N,
+        timeout: Option<Duration>,
+    ) -> ZpoolResult<ScrubReport> {
+        match self.scrub(&name) {
+            Ok(()) | Err(ZpoolError::ScrubInProgress) => {},
+            Err(e) => return Err(e),
+        }
+        self.wait_for(WaitActivity::Scrub, name.as_ref(), timeout)?;
+
+        let mut z = self.zpool();
+        z.args(&["status", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            ScrubReport::from_status(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
     fn pause_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("scrub");
         z.arg("-p");
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -309,7 +771,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg("-s");
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -317,6 +779,131 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn reopen<N: AsRef<str>>(&self, name: N, scrub_restart: bool) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("reopen");
+        if !scrub_restart {
+            z.arg("-n");
+        }
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn upgrade<N: AsRef<str>>(&self, name: N, all_features: bool) -> ZpoolResult<()> {
+        if !all_features {
+            return if self.exists(&name)? { Ok(()) } else { Err(ZpoolError::PoolNotFound) };
+        }
+        let mut z = self.zpool();
+        z.arg("upgrade");
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("reguid");
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("checkpoint");
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn checkpoint_discard<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["checkpoint", "-d"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn sample_fragmentation<N: AsRef<str>>(&self, name: N) -> ZpoolResult<FragmentationSample> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "-o", "value", "fragmentation"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            FragmentationSample::from_stdout(SystemTime::now(), &stdout)
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn clear<N: AsRef<str>>(&self, name: N, mode: ClearMode) -> ZpoolResult<Option<Duration>> {
+        let mut z = self.zpool();
+        z.arg("clear");
+        match mode {
+            ClearMode::Gentle => {},
+            ClearMode::Rewind => {
+                z.arg("-F");
+            },
+            ClearMode::RewindDryRun => {
+                z.arg("-nF");
+            },
+        }
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(ZpoolError::from_stderr(&out.stderr));
+        }
+        if mode == ClearMode::Gentle {
+            Ok(None)
+        } else {
+            parse_discarded(&String::from_utf8_lossy(&out.stdout)).map(Some)
+        }
+    }
+
+    fn events(&self, follow: bool) -> ZpoolResult<EventsIter> {
+        let mut z = self.zpool();
+        z.args(&["events", "-H", "-v"]);
+        if follow {
+            z.arg("-f");
+        }
+        z.stdout(Stdio::piped());
+        z.stderr(Stdio::piped());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        if self.record(&z) {
+            return Ok(EventsIter::empty());
+        }
+        EventsIter::new(z.spawn()?)
+    }
+
     fn take_offline<N: AsRef<str>, D: AsRef<OsStr>>(
         &self,
         name: N,
@@ -331,7 +918,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(name.as_ref());
         z.arg(device.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -353,7 +940,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(name.as_ref());
         z.arg(device.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -373,7 +960,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(device.as_ref());
         z.arg(new_device.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -387,7 +974,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(name.as_ref());
         z.arg(device.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -401,6 +988,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         new_vdev: CreateVdevRequest,
         add_mode: CreateMode,
     ) -> Result<(), ZpoolError> {
+        topology::validate_vdev(&new_vdev).map_err(ZpoolError::InvalidTopology)?;
         let mut z = self.zpool();
         z.arg("add");
         if add_mode == CreateMode::Force {
@@ -409,7 +997,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(name.as_ref());
         z.args(new_vdev.into_args());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -423,6 +1011,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         new_zil: CreateVdevRequest,
         add_mode: CreateMode,
     ) -> Result<(), ZpoolError> {
+        topology::validate_log(&new_zil).map_err(ZpoolError::InvalidTopology)?;
         let mut z = self.zpool();
         z.arg("add");
         if add_mode == CreateMode::Force {
@@ -432,7 +1021,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg("log");
         z.args(new_zil.into_args());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -455,7 +1044,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg("cache");
         z.arg(new_cache.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -478,7 +1067,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg("spare");
         z.arg(new_spare.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -498,7 +1087,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         z.arg(old_disk.as_ref());
         z.arg(new_disk.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -506,13 +1095,44 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn replace_and_wait<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+        timeout: Option<Duration>,
+    ) -> ZpoolResult<()> {
+        self.replace_disk(&name, old_disk, new_disk)?;
+        match self.wait_for(WaitActivity::Resilver, name.as_ref(), timeout) {
+            Err(ZpoolError::Timeout) => {},
+            result => return result,
+        }
+
+        let mut z = self.zpool();
+        z.args(&["status", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = self.output(&mut z)?;
+        if !out.status.success() {
+            return Err(ZpoolError::from_stderr(&out.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let progress = stdout
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with("scan:"))
+            .take_while(|line| !line.trim_start().starts_with("config:"))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Err(ZpoolError::ResilverTimeout(progress))
+    }
+
     fn remove<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("remove");
         z.arg(name.as_ref());
         z.arg(device.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let out = z.output()?;
+        let out = self.output(&mut z)?;
         if out.status.success() {
             Ok(())
         } else {
@@ -520,3 +1140,520 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::DamagedEntry;
+
+    static STATUS_GUIDS: &str = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME                      STATE     READ WRITE CKSUM
+        tank                      ONLINE       0     0     0
+          11386428474218151431    ONLINE       0     0     0
+            8936296367223394355   ONLINE       0     0     0
+            1728379261224566458   ONLINE       0     0     0
+        logs
+          5617428315128337283     ONLINE       0     0     0
+        cache
+          14403183936624375396    ONLINE       0     0     0
+        spares
+          2541380925342442012     AVAIL
+
+errors: No known data errors
+"#;
+
+    static STATUS_PATHS: &str = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME                STATE     READ WRITE CKSUM
+        tank                ONLINE       0     0     0
+          mirror-0          ONLINE       0     0     0
+            /dev/sda1       ONLINE       0     0     0
+            /dev/sdb1       ONLINE       0     0     0
+        logs
+          /dev/nvme0n1p1    ONLINE       0     0     0
+        cache
+          /dev/nvme0n1p2    ONLINE       0     0     0
+        spares
+          /dev/sdc1         AVAIL
+
+errors: No known data errors
+"#;
+
+    /// `ZpoolOpen3` that runs `script` with `/bin/sh` instead of zpool. Script is stored in the
+    /// returned directory, files it needs can be found next to it with `$(dirname "$0")`.
+    fn fake_zpool(script: &str) -> (tempdir::TempDir, ZpoolOpen3) {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let dir = tempdir::TempDir::new("zpool-mock").unwrap();
+        let script_path = dir.path().join("zpool");
+        fs::write(&script_path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755)).unwrap();
+        let zpool = ZpoolOpen3::with_cmd(script_path.as_os_str());
+        (dir, zpool)
+    }
+
+    #[test]
+    fn test_zpools_from_status_checkpoint() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+checkpoint: created Fri Jan 10 12:00:00 2020, consumes 1.20M
+config:
+
+        NAME            STATE     READ WRITE CKSUM
+        tank            ONLINE       0     0     0
+          /vdevs/vdev0  ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpools = zpools_from_status(stdout).unwrap();
+        let expected = String::from("created Fri Jan 10 12:00:00 2020, consumes 1.20M");
+        assert_eq!(&Some(expected), zpools[0].checkpoint());
+    }
+
+    #[test]
+    fn test_zpools_from_status_long_errors() {
+        let stdout = r#"  pool: damaged
+ state: ONLINE
+status: One or more devices has experienced an error resulting in data
+        corruption.  Applications may be affected.
+action: Restore the file in question if possible.  Otherwise restore the
+        entire pool from backup.
+   see: http://illumos.org/msg/ZFS-8000-8A
+  scan: scrub repaired 0 in 0 days 00:00:01 with 3 errors on Tue Nov 28 02:04:11 2017
+config:
+
+        NAME            STATE     READ WRITE CKSUM
+        damaged         ONLINE       0     0     6
+          /vdevs/vdev0  ONLINE       0     0    12
+
+errors: Permanent errors have been detected in the following files:
+
+        /damaged/file0
+        /damaged/file1
+        /damaged/file2
+        /damaged/file3
+        /damaged/file4
+        /damaged/file5
+        damaged/fs@snap:/file6
+        damaged/fs:<0x1f>
+        <metadata>:<0x0>
+        <0x21>:<0x5>
+
+  pool: healthy
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME            STATE     READ WRITE CKSUM
+        healthy         ONLINE       0     0     0
+          /vdevs/vdev1  ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpools = zpools_from_status(stdout).unwrap();
+        assert_eq!(2, zpools.len());
+        assert_eq!("damaged", zpools[0].name());
+        assert_eq!(&None, zpools[0].checkpoint());
+        assert!(zpools[0].errors().is_some());
+        let damaged = zpools[0].damaged();
+        assert_eq!(10, damaged.len());
+        assert_eq!(DamagedEntry::Path(PathBuf::from("/damaged/file0")), damaged[0]);
+        let expected = DamagedEntry::DatasetPath {
+            dataset: PathBuf::from("damaged/fs@snap"),
+            path:    PathBuf::from("/file6"),
+        };
+        assert_eq!(expected, damaged[6]);
+        let expected = DamagedEntry::Object { dataset: String::from("damaged/fs"), object: 31 };
+        assert_eq!(expected, damaged[7]);
+        assert!(damaged[8].is_metadata());
+        let expected = DamagedEntry::Object { dataset: String::from("<0x21>"), object: 5 };
+        assert_eq!(expected, damaged[9]);
+        assert_eq!(&Some(crate::zpool::ZfsFaultCode::CorruptedData), zpools[0].fault_code());
+        assert_eq!(&Some(String::from("http://illumos.org/msg/ZFS-8000-8A")), zpools[0].see());
+        assert!(zpools[0].status().as_ref().unwrap().starts_with("One or more devices"));
+        assert_eq!("healthy", zpools[1].name());
+        assert!(zpools[1].errors().is_none());
+        assert!(zpools[1].damaged().is_empty());
+        assert_eq!(&None, zpools[1].fault_code());
+        assert_eq!(&None, zpools[1].status());
+        assert_eq!(
+            &CreateVdevRequest::SingleDisk(PathBuf::from("/vdevs/vdev1")),
+            &zpools[1].vdevs()[0]
+        );
+    }
+
+    #[test]
+    fn test_zpools_from_status_delimits_sections() {
+        // Spares of the first pool must not leak into the next one.
+        let stdout = format!(
+            "{}\n  pool: small\n state: ONLINE\n  scan: none requested\nconfig:\n\n\
+             \tNAME        STATE     READ WRITE CKSUM\n\
+             \tsmall       ONLINE       0     0     0\n\
+             \t  /dev/sdd1 ONLINE       0     0     0\n\n\
+             errors: No known data errors\n",
+            STATUS_PATHS
+        );
+        let zpools = zpools_from_status(&stdout).unwrap();
+        assert_eq!(2, zpools.len());
+        assert_eq!("tank", zpools[0].name());
+        assert_eq!(1, zpools[0].vdevs().len());
+        assert_eq!(1, zpools[0].logs().len());
+        assert_eq!(1, zpools[0].caches().len());
+        assert_eq!(1, zpools[0].spares().len());
+        assert_eq!("small", zpools[1].name());
+        assert_eq!(1, zpools[1].vdevs().len());
+        assert!(zpools[1].logs().is_empty());
+        assert!(zpools[1].caches().is_empty());
+        assert!(zpools[1].spares().is_empty());
+    }
+
+    #[test]
+    fn test_display_tree() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME                STATE     READ WRITE CKSUM
+        tank                ONLINE       0     0     0
+          mirror-0          ONLINE       0     0     0
+            /dev/sda1       ONLINE       0     0     0
+            /dev/sdb1       ONLINE       0     0     0
+          raidz2-1          ONLINE       0     0     0
+            /dev/sdc1       ONLINE       0     0     0
+            /dev/sdd1       ONLINE       0     0     0
+            /dev/sde1       ONLINE       0     0     0
+            /dev/sdf1       ONLINE       0     0     0
+            /dev/sdg1       ONLINE       0     0     0
+        logs
+          mirror-2          ONLINE       0     0     0
+            /dev/nvme0n1p1  ONLINE       0     0     0
+            /dev/nvme1n1p1  ONLINE       0     0     0
+        cache
+          /dev/nvme0n1p2    ONLINE       0     0     0
+        spares
+          /dev/sdi1         AVAIL
+
+errors: No known data errors
+"#;
+        let zpools = zpools_from_status(stdout).unwrap();
+        let tree = zpools[0].display_tree();
+        // NAME column of the status above.
+        let expected: String = stdout
+            .lines()
+            .skip(6)
+            .take_while(|line| !line.is_empty())
+            .map(|line| {
+                let line = &line[8..];
+                let indent = line.len() - line.trim_start().len();
+                format!("{}{}\n", &line[..indent], line.split_whitespace().next().unwrap())
+            })
+            .collect();
+        assert_eq!(expected, tree);
+        assert!(tree.starts_with("tank\n  mirror-0\n    /dev/sda1\n    /dev/sdb1\n  raidz2-1\n"));
+    }
+
+    #[test]
+    fn test_zpools_from_status_garbage() {
+        assert!(zpools_from_status("no pools available\n").unwrap().is_empty());
+        assert!(zpools_from_status("").unwrap().is_empty());
+        let stdout = "  pool: broken\n state: WAT\n";
+        assert_eq!(ZpoolErrorKind::ParseError, zpools_from_status(stdout).unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_parse_discarded() {
+        let stdout = "Would be able to return tank to its state as of Tue Jan  1 00:00:00 2019.\n\
+                      Would discard approximately 5 seconds of transactions.\n";
+        assert_eq!(Duration::from_secs(5), parse_discarded(stdout).unwrap());
+        let stdout = "Pool tank returned to its state as of Tue Jan  1 00:00:00 2019.\n\
+                      Discarded approximately 3 minutes of transactions.\n";
+        assert_eq!(Duration::from_secs(180), parse_discarded(stdout).unwrap());
+        assert_eq!(Duration::from_secs(0), parse_discarded("").unwrap());
+    }
+
+    #[test]
+    fn test_clear_rewind_dry_run() {
+        let (dir, zpool) = fake_zpool(
+            "echo \"$@\" > \"$(dirname \"$0\")/args\"\n\
+             echo 'Would be able to return tank to its state as of Tue Jan  1 00:00:00 2019.'\n\
+             echo 'Would discard approximately 5 seconds of transactions.'\n",
+        );
+        let discarded = zpool.clear("tank", ClearMode::RewindDryRun).unwrap();
+        assert_eq!(Some(Duration::from_secs(5)), discarded);
+
+        // -n makes sure nothing is applied.
+        let args = std::fs::read_to_string(dir.path().join("args")).unwrap();
+        assert_eq!("clear -nF tank\n", args);
+    }
+
+    #[test]
+    fn test_env_and_working_dir() {
+        let dir = tempdir::TempDir::new("zpool-cwd").unwrap();
+        let zpool = ZpoolOpen3::with_cmd("sh")
+            .with_env("LIBZETTA_TEST", "from-env")
+            .with_working_dir(dir.path());
+        let mut z = zpool.zpool();
+        z.args(&["-c", "echo $LIBZETTA_TEST; pwd"]);
+        let out = zpool.output(&mut z).unwrap();
+        let expected = format!("from-env\n{}\n", dir.path().canonicalize().unwrap().display());
+        assert_eq!(expected, String::from_utf8_lossy(&out.stdout));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let zpool = ZpoolOpen3::with_cmd("zpool-not-found").dry_run();
+        assert_eq!(None, zpool.last_command());
+
+        let topo = CreateZpoolRequest::builder()
+            .name("tank")
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let expected = r#""zpool-not-found" "create" "tank" "/vdevs/vdev0""#;
+        assert_eq!(Some(expected.to_string()), zpool.last_command());
+
+        zpool.scrub("tank").unwrap();
+        let expected = r#""zpool-not-found" "scrub" "tank""#;
+        assert_eq!(Some(expected.to_string()), zpool.last_command());
+        assert_eq!(0, zpool.events(false).unwrap().count());
+
+        // Invalid vdevs never reach `zpool`.
+        let last = zpool.last_command();
+        let mirror = CreateVdevRequest::Mirror(vec!["/vdevs/vdev1".into()]);
+        let err = zpool.add_vdev("tank", mirror, CreateMode::Gentle).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidTopology, err.kind());
+        let raidz = CreateVdevRequest::RaidZ(vec!["a".into(), "b".into(), "c".into()]);
+        let err = zpool.add_zil("tank", raidz, CreateMode::Gentle).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidTopology, err.kind());
+        assert_eq!(last, zpool.last_command());
+    }
+
+    #[test]
+    fn test_replace_and_wait_timeout_reports_progress() {
+        let script = "case \"$1\" in
+    replace) exit 0 ;;
+    wait) exec sleep 10 ;;
+esac
+cat <<EOF
+  pool: tank
+ state: ONLINE
+  scan: resilver in progress since Fri Oct 16 12:00:00 2026
+        1.50G scanned at 100M/s, 512M issued at 50M/s, 3.00G total
+        500M resilvered, 16.67% done, 00:00:50 to go
+config:
+EOF
+";
+        let (_dir, zpool) = fake_zpool(script);
+        let err = zpool
+            .replace_and_wait("tank", "vdev0", "vdev2", Some(Duration::from_millis(300)))
+            .unwrap_err();
+        assert_eq!(ZpoolErrorKind::Timeout, err.kind());
+        if let ZpoolError::ResilverTimeout(progress) = err {
+            assert!(progress.starts_with("scan: resilver in progress"), "{}", progress);
+            assert!(progress.contains("16.67% done"), "{}", progress);
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_wait_without_wait_command() {
+        let script = "case \"$1\" in
+    wait)
+        echo \"unrecognized command 'wait'\" >&2
+        exit 2 ;;
+    get) echo 0; exit 0 ;;
+esac
+counter=\"$(dirname \"$0\")/counter\"
+echo x >> \"$counter\"
+if [ $(wc -l < \"$counter\") -lt 2 ]; then
+    echo '  scan: scrub in progress since Fri Oct 16 12:00:00 2026'
+else
+    echo '  scan: scrub repaired 0B in 00:00:01 with 0 errors on Fri Oct 16 12:00:01 2026'
+fi
+";
+        let (dir, zpool) = fake_zpool(script);
+        zpool.wait("tank", WaitActivity::Scrub).unwrap();
+        let counter = std::fs::read_to_string(dir.path().join("counter")).unwrap();
+        assert_eq!(2, counter.lines().count());
+        zpool.wait("tank", WaitActivity::Free).unwrap();
+        assert!(zpool.wait("tank", WaitActivity::Resilver).is_ok());
+    }
+
+    #[test]
+    fn test_replace_too_small() {
+        let stderr = b"cannot replace /vdevs/vdev0 with /vdevs/vdev2: device is too small";
+        assert_eq!(ZpoolErrorKind::DeviceTooSmall, ZpoolError::from_stderr(stderr).kind());
+    }
+
+    #[test]
+    fn test_exists_keeps_stderr() {
+        let script = "case \"$*\" in
+    *tank) echo tank ;;
+    *missing)
+        echo \"cannot open 'missing': no such pool\" >&2
+        exit 1 ;;
+    *)
+        echo \"internal error: out of memory\" >&2
+        exit 1 ;;
+esac
+";
+        let (_dir, zpool) = fake_zpool(script);
+        assert!(zpool.exists("tank").unwrap());
+        assert!(!zpool.exists("missing").unwrap());
+        let err = zpool.exists("broken").unwrap_err();
+        assert_eq!(ZpoolErrorKind::Other, err.kind());
+        assert_eq!("internal error: out of memory", err.to_string());
+    }
+
+    #[test]
+    fn test_import_all_reports_each_pool() {
+        let script = "case \"$*\" in
+    *11111111) exit 0 ;;
+    *22222222)
+        echo \"cannot import 'second': a pool with that name already exists\" >&2
+        exit 1 ;;
+esac
+cat <<EOF
+   pool: first
+     id: 11111111
+  state: ONLINE
+ action: The pool can be imported using its name or numeric identifier.
+ config:
+
+        first                  ONLINE
+          /vdevs/import/vdev0  ONLINE
+
+   pool: second
+     id: 22222222
+  state: ONLINE
+ action: The pool can be imported using its name or numeric identifier.
+ config:
+
+        second                 ONLINE
+          /vdevs/import/vdev1  ONLINE
+EOF
+";
+        let (_dir, zpool) = fake_zpool(script);
+        let results = zpool.import_all(ImportOptions::default()).unwrap();
+        assert_eq!(2, results.len());
+        assert_eq!("first", results[0].0);
+        assert!(results[0].1.is_ok());
+        assert_eq!("second", results[1].0);
+        let err = results[1].1.as_ref().unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolAlreadyImported, err.kind());
+    }
+
+    #[test]
+    fn test_config_device_names() {
+        let names = config_device_names(STATUS_GUIDS);
+        let expected = vec![
+            "tank",
+            "11386428474218151431",
+            "8936296367223394355",
+            "1728379261224566458",
+            "5617428315128337283",
+            "14403183936624375396",
+            "2541380925342442012",
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn test_guids_from_status() {
+        let guids = guids_from_status(STATUS_GUIDS).unwrap();
+        assert_eq!(6, guids.len());
+        assert_eq!(11_386_428_474_218_151_431, guids[0]);
+
+        let mut zpool = zpools_from_status(STATUS_PATHS).unwrap().pop().unwrap();
+        zpool.set_guids(&guids);
+        let mirror = &zpool.vdevs()[0];
+        assert_eq!(&Some(11_386_428_474_218_151_431), mirror.guid());
+        assert_eq!(&Some(8_936_296_367_223_394_355), mirror.disks()[0].guid());
+        assert_eq!(&Some(1_728_379_261_224_566_458), mirror.disks()[1].guid());
+        let log = &zpool.logs()[0];
+        assert_eq!(&Some(5_617_428_315_128_337_283), log.guid());
+        assert_eq!(&Some(5_617_428_315_128_337_283), log.disks()[0].guid());
+        assert_eq!(&Some(14_403_183_936_624_375_396), zpool.caches()[0].guid());
+        assert_eq!(&Some(2_541_380_925_342_442_012), zpool.spares()[0].guid());
+
+        let mut zpool = zpools_from_status(STATUS_PATHS).unwrap().pop().unwrap();
+        zpool.set_guids(&guids[1..]);
+        assert_eq!(&None, zpool.vdevs()[0].guid());
+        assert_eq!(&None, zpool.spares()[0].guid());
+    }
+
+    #[test]
+    fn test_set_guids_from_listing() {
+        let backup = |disk: &str| {
+            format!(
+                "  pool: backup\n state: ONLINE\n  scan: none requested\nconfig:\n\n\
+                 \tNAME  STATE  READ WRITE CKSUM\n\tbackup  ONLINE  0  0  0\n\
+                 \t  {}  ONLINE  0  0  0\n\nerrors: No known data errors\n",
+                disk
+            )
+        };
+        let paths = format!("{}\n{}", STATUS_PATHS, backup("/dev/sdd1"));
+        let guids = format!("{}\n{}", STATUS_GUIDS, backup("4242"));
+        let mut zpools = zpools_from_status(&paths).unwrap();
+        assert_eq!(2, zpools.len());
+
+        set_guids_from_listing(&mut zpools, &guids);
+        assert_eq!(&Some(11_386_428_474_218_151_431), zpools[0].vdevs()[0].guid());
+        assert_eq!(&Some(2_541_380_925_342_442_012), zpools[0].spares()[0].guid());
+        assert_eq!(&Some(4242), zpools[1].vdevs()[0].guid());
+
+        let mut zpools = zpools_from_status(&paths).unwrap();
+        set_guids_from_listing(&mut zpools, &backup("4242"));
+        assert_eq!(&None, zpools[0].vdevs()[0].guid());
+        assert_eq!(&None, zpools[1].vdevs()[0].guid());
+
+        let mut zpools = zpools_from_status(&paths).unwrap();
+        set_guids_from_listing(&mut zpools, &paths);
+        assert_eq!(&None, zpools[1].vdevs()[0].guid());
+    }
+
+    #[test]
+    fn test_vdev_guids_from_stdout() {
+        let map = vdev_guids_from_stdout(STATUS_GUIDS, STATUS_PATHS).unwrap();
+        assert_eq!(5, map.len());
+        assert_eq!(Some(&PathBuf::from("/dev/sda1")), map.get(&8_936_296_367_223_394_355));
+        assert_eq!(Some(&PathBuf::from("/dev/sdb1")), map.get(&1_728_379_261_224_566_458));
+        assert_eq!(Some(&PathBuf::from("/dev/nvme0n1p1")), map.get(&5_617_428_315_128_337_283));
+        assert_eq!(Some(&PathBuf::from("/dev/nvme0n1p2")), map.get(&14_403_183_936_624_375_396));
+        assert_eq!(Some(&PathBuf::from("/dev/sdc1")), map.get(&2_541_380_925_342_442_012));
+        assert!(!map.contains_key(&11_386_428_474_218_151_431));
+    }
+
+    #[test]
+    fn test_vdev_guid_by_path() {
+        let script = "case \"$2\" in
+    -gL) cat \"$(dirname \"$0\")/guids\" ;;
+    *) cat \"$(dirname \"$0\")/paths\" ;;
+esac
+";
+        let (dir, zpool) = fake_zpool(script);
+        std::fs::write(dir.path().join("guids"), STATUS_GUIDS).unwrap();
+        std::fs::write(dir.path().join("paths"), STATUS_PATHS).unwrap();
+        assert_eq!(Some(8_936_296_367_223_394_355), zpool.vdev_guid("tank", "/dev/sda1").unwrap());
+        assert_eq!(Some(2_541_380_925_342_442_012), zpool.vdev_guid("tank", "sdc1").unwrap());
+        assert_eq!(None, zpool.vdev_guid("tank", "/dev/sdz1").unwrap());
+        assert_eq!(None, zpool.vdev_guid("tank", "tank").unwrap());
+    }
+
+    #[test]
+    fn test_vdev_guids_mismatch() {
+        let err = vdev_guids_from_stdout(STATUS_GUIDS, "config:\n").unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+    }
+}
@@ -1,9 +1,40 @@
 //! Consumer friendly representation of Zpool's properties.
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+use pest::Parser;
 
 use super::{ZpoolError, ZpoolResult};
-use crate::utils::parse_float;
+use crate::{parsers::{Rule, StdoutParser},
+            utils::parse_float};
+
+/// Properties that make up [`ZpoolProperties`](struct.ZpoolProperties.html), in the same order
+/// `ZpoolProperties::try_from_stdout` expects them.
+static PROPERTY_NAMES: &[&str] = &[
+    "allocated",
+    "capacity",
+    "comment",
+    "dedupratio",
+    "expandsize",
+    "fragmentation",
+    "free",
+    "freeing",
+    "guid",
+    "health",
+    "size",
+    "leaked",
+    "altroot",
+    "readonly",
+    "autoexpand",
+    "autoreplace",
+    "bootfs",
+    "cachefile",
+    "dedupditto",
+    "delegation",
+    "failmode",
+    "autotrim",
+    "checkpoint",
+];
 
 /// Implement this for your custom properties.
 pub trait PropPair {
@@ -48,6 +79,8 @@ pub enum Health {
     Unavailable,
     /// Physically removed while the system was running.
     Removed,
+    /// All I/O is suspended because of catastrophic failure. See `failmode` property.
+    Suspended,
 }
 
 impl Health {
@@ -63,6 +96,7 @@ impl Health {
             "AVAIL" => Ok(Health::Available),
             "UNAVAIL" => Ok(Health::Unavailable),
             "REMOVED" => Ok(Health::Removed),
+            "SUSPENDED" => Ok(Health::Suspended),
             _ => Err(ZpoolError::ParseError),
         }
     }
@@ -173,6 +207,10 @@ pub struct ZpoolPropertiesWrite {
     #[builder(default = "false")]
     auto_replace: bool,
 
+    /// Controls automatic TRIM of freed space on devices that support it.
+    #[builder(default = "false")]
+    auto_trim: bool,
+
     ///  Identifies the default bootable dataset for the root pool.
     #[builder(default)]
     boot_fs: Option<String>,
@@ -182,7 +220,8 @@ pub struct ZpoolPropertiesWrite {
     cache_file: CacheType,
 
     /// An administrator can provide additional information about a pool using
-    /// this property.
+    /// this property. ZFS treats empty comment as unset, so empty (or whitespace-only) comment
+    /// clears it and is read back as `None`.
     #[builder(default)]
     #[builder(setter(into))]
     comment:    String,
@@ -205,11 +244,16 @@ impl ZpoolPropertiesWrite {
 
     #[doc(hidden)]
     pub fn into_args(self) -> Vec<OsString> {
-        let mut ret = Vec::with_capacity(7);
+        let mut ret = Vec::with_capacity(8);
         ret.push(PropPair::to_pair(&self.auto_expand, "autoexpand"));
         ret.push(PropPair::to_pair(&self.auto_replace, "autoreplace"));
+        ret.push(PropPair::to_pair(&self.auto_trim, "autotrim"));
         ret.push(PropPair::to_pair(&self.cache_file, "cachefile"));
-        ret.push(PropPair::to_pair(&self.comment, "comment"));
+        if self.comment.trim().is_empty() {
+            ret.push(String::from("comment="));
+        } else {
+            ret.push(PropPair::to_pair(&self.comment, "comment"));
+        }
         ret.push(PropPair::to_pair(&self.delegation, "delegation"));
         ret.push(PropPair::to_pair(&self.fail_mode, "failmode"));
         if let Some(ref btfs) = self.boot_fs {
@@ -220,19 +264,28 @@ impl ZpoolPropertiesWrite {
 }
 
 impl ZpoolPropertiesWriteBuilder {
+    /// Clear comment of the pool. Same as setting it to empty string.
+    pub fn clear_comment(&mut self) -> &mut ZpoolPropertiesWriteBuilder {
+        self.comment(String::new())
+    }
+
     /// Construct new builder given existing properties. Useful for updates.
+    ///
+    /// Every field of the builder is set from `props`, so writing the result back without changes
+    /// leaves the pool as is. Properties that can't be changed with `zpool set` are left out:
+    /// statistics (`size`, `health`, `guid`, ...), `altroot` (set on create or import only) and
+    /// deprecated `dedupditto`.
     pub fn from_props(props: &ZpoolProperties) -> ZpoolPropertiesWriteBuilder {
         let mut b = ZpoolPropertiesWriteBuilder::default();
         b.read_only(props.read_only);
         b.auto_expand(props.auto_expand);
         b.auto_replace(props.auto_replace);
+        b.auto_trim(props.auto_trim);
         b.boot_fs(props.boot_fs.clone());
         b.cache_file(props.cache_file.clone());
         b.delegation(props.delegation);
         b.fail_mode(props.fail_mode.clone());
-        if let Some(ref comment) = props.comment {
-            b.comment(comment.clone());
-        }
+        b.comment(props.comment.clone().unwrap_or_default());
         b
     }
 }
@@ -243,7 +296,7 @@ impl ZpoolPropertiesWriteBuilder {
 #[get = "pub"]
 pub struct ZpoolProperties {
     /// Amount of storage space within the pool that has been physically
-    /// allocated.
+    /// allocated. Exact number of bytes, not rounded.
     alloc:         usize,
     /// Percentage of pool space used. Percentage.
     capacity:      u8,
@@ -267,7 +320,8 @@ pub struct ZpoolProperties {
     expand_size:   Option<usize>,
     /// The amount of fragmentation in the pool. In percents.
     fragmentation: i8,
-    /// Number of blocks within the pool that are not allocated.
+    /// Number of blocks within the pool that are not allocated. Exact number of bytes, not
+    /// rounded.
     free:          i64,
     ///  After a file system or snapshot is destroyed, the space it
     ///  was using is returned to the pool asynchronously.  freeing is
@@ -278,10 +332,12 @@ pub struct ZpoolProperties {
     guid:          u64,
     /// The current health of the pool.
     health:        Health,
-    /// Total size of the storage pool.
+    /// Total size of the storage pool. Exact number of bytes, not rounded.
     size:          usize,
     /// Leaked space?
     leaked:        usize,
+    /// Space used by the checkpoint. `None` if pool has no checkpoint.
+    checkpoint:    Option<u64>,
     // writable
     /// Alternate root directory, can only be set during creation or import.
     alt_root:      Option<PathBuf>,
@@ -295,6 +351,8 @@ pub struct ZpoolProperties {
     /// pool, is automatically
     /// formatted and replaced. The default behavior is "off".
     auto_replace:  bool,
+    /// Controls automatic TRIM of freed space on devices that support it.
+    auto_trim:     bool,
     ///  Identifies the default bootable dataset for the root pool.
     boot_fs:       Option<String>,
     /// Controls the location of where the pool configuration is cached.
@@ -350,7 +408,8 @@ impl ZpoolProperties {
 
         let comment_str = cols.next().ok_or(ZpoolError::ParseError)?;
         let comment = match comment_str {
-            "-" | "" => None,
+            "-" => None,
+            c if c.trim().is_empty() => None,
             c => Some(String::from(c)),
         };
 
@@ -400,6 +459,11 @@ impl ZpoolProperties {
         let dedup_ditto = parse_usize(cols.next())?;
         let delegation = parse_bool(cols.next())?;
         let fail_mode = FailMode::try_from_str(cols.next())?;
+        let auto_trim = parse_bool(cols.next())?;
+        let checkpoint = match cols.next() {
+            None | Some("-") => None,
+            Some(c) => Some(c.parse()?),
+        };
 
         Ok(ZpoolProperties {
             alloc,
@@ -414,10 +478,12 @@ impl ZpoolProperties {
             health,
             size,
             leaked,
+            checkpoint,
             alt_root,
             read_only,
             auto_expand,
             auto_replace,
+            auto_trim,
             boot_fs,
             cache_file,
             dedup_ditto,
@@ -427,6 +493,95 @@ impl ZpoolProperties {
     }
 }
 
+/// State of a single `feature@` flag.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FeatureState {
+    /// Feature is supported by the software, but not enabled on the pool.
+    Disabled,
+    /// Feature is enabled, but its on-disk format changes are not in use yet.
+    Enabled,
+    /// Feature is in use. Pool can't be imported by software that doesn't support it.
+    Active,
+}
+
+impl FeatureState {
+    /// parse str to FeatureState.
+    pub fn try_from_str(val: Option<&str>) -> ZpoolResult<FeatureState> {
+        let val_str = val.ok_or(ZpoolError::ParseError)?;
+        match val_str {
+            "disabled" => Ok(FeatureState::Disabled),
+            "enabled" => Ok(FeatureState::Enabled),
+            "active" => Ok(FeatureState::Active),
+            _ => Err(ZpoolError::ParseError),
+        }
+    }
+}
+
+/// Properties of the pool together with state of every `feature@` flag. Read with a single `zpool
+/// get all`.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct ZpoolPropertiesFull {
+    /// Pre-defined properties. Same as returned by
+    /// [`read_properties`](../trait.ZpoolEngine.html#tymethod.read_properties).
+    properties: ZpoolProperties,
+    /// State of feature flags, keyed by feature name without `feature@` prefix.
+    features:   HashMap<String, FeatureState>,
+}
+
+impl ZpoolPropertiesFull {
+    /// Check if feature is enabled or active on the pool. Unknown features are reported as not
+    /// enabled.
+    pub fn is_feature_enabled(&self, feature: &str) -> bool {
+        match self.features.get(feature) {
+            Some(FeatureState::Enabled) | Some(FeatureState::Active) => true,
+            _ => false,
+        }
+    }
+
+    /// Check if feature is active on the pool.
+    pub fn is_feature_active(&self, feature: &str) -> bool {
+        self.features.get(feature) == Some(&FeatureState::Active)
+    }
+
+    pub(crate) fn try_from_stdout(out: &[u8]) -> ZpoolResult<ZpoolPropertiesFull> {
+        let stdout = String::from_utf8_lossy(out);
+        let rows = StdoutParser::parse(Rule::property_rows, &stdout)
+            .map_err(|_| ZpoolError::ParseError)?;
+
+        let mut values = HashMap::new();
+        let mut features = HashMap::new();
+        for row in rows.filter(|pair| pair.as_rule() == Rule::property_row) {
+            let mut fields = row.into_inner().map(|field| field.as_str());
+            let _pool = fields.next();
+            let property = fields.next().ok_or(ZpoolError::ParseError)?;
+            let value = fields.next();
+            if property.starts_with("feature@") {
+                features.insert(
+                    property["feature@".len()..].to_string(),
+                    FeatureState::try_from_str(value)?,
+                );
+            } else {
+                values.insert(property, value.ok_or(ZpoolError::ParseError)?);
+            }
+        }
+
+        // Reuse column parser of `zpool list`. dedupditto is gone since OpenZFS 2.0.
+        let line: Vec<&str> = PROPERTY_NAMES
+            .iter()
+            .map(|name| match values.get(name) {
+                Some(value) => *value,
+                None if *name == "dedupditto" => "0",
+                None => "-",
+            })
+            .collect();
+        let properties =
+            ZpoolProperties::try_from_stdout(format!("{}\n", line.join("\t")).as_bytes())?;
+
+        Ok(ZpoolPropertiesFull { properties, features })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -438,6 +593,7 @@ mod test {
             read_only:    false,
             auto_expand:  false,
             auto_replace: false,
+            auto_trim:    false,
             boot_fs:      None,
             cache_file:   CacheType::Default,
             comment:      String::new(),
@@ -455,7 +611,7 @@ mod test {
             .build()
             .unwrap();
         let args = built.into_args();
-        assert_eq!(7, args.len());
+        assert_eq!(8, args.len());
     }
 
     #[test]
@@ -466,6 +622,7 @@ mod test {
         let offline = Some("OFFLINE");
         let unavailable = Some("UNAVAIL");
         let removed = Some("REMOVED");
+        let suspended = Some("SUSPENDED");
         let bad = Some("wat");
 
         assert_eq!(Health::Online, Health::try_from_str(online).unwrap());
@@ -474,6 +631,7 @@ mod test {
         assert_eq!(Health::Offline, Health::try_from_str(offline).unwrap());
         assert_eq!(Health::Unavailable, Health::try_from_str(unavailable).unwrap());
         assert_eq!(Health::Removed, Health::try_from_str(removed).unwrap());
+        assert_eq!(Health::Suspended, Health::try_from_str(suspended).unwrap());
 
         let err = Health::try_from_str(bad);
         assert!(err.is_err());
@@ -517,54 +675,99 @@ mod test {
 
     #[test]
     fn parsing_props_u64_guid() {
-        let line = b"69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
+        let line = b"69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line);
         assert!(props.is_ok());
     }
 
+    #[test]
+    fn parsing_exact_sizes() {
+        // `zpool list -p` prints exact numbers, nothing is lost to rounding.
+        let line = b"1234567890123	30	-	1.00	-	4	2764046061453	0	5667188105885376774	ONLINE	3998613951576	0	-	off	off	off	-	-	0	on	wait	off
+";
+        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        assert_eq!(&1_234_567_890_123, props.alloc());
+        assert_eq!(&2_764_046_061_453, props.free());
+        assert_eq!(&3_998_613_951_576, props.size());
+        assert_eq!(&30, props.capacity());
+        assert_eq!(props.size() - props.alloc(), *props.free() as usize);
+    }
+
     #[test]
     fn parsing_on_zol() {
-        let line = b"99840\t0\t-\t1.00\t-\t1\t67009024\t0\t5667188105885376774\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
+        let line = b"99840\t0\t-\t1.00\t-\t1\t67009024\t0\t5667188105885376774\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line);
         assert!(props.is_ok());
     }
 
     #[test]
     fn parsing_props() {
-        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
+        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line);
         assert!(props.is_ok());
 
-        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\tpanic\n";
+        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\tpanic\toff\n";
         let props = ZpoolProperties::try_from_stdout(line).unwrap();
         assert_eq!(Some(String::from("touch it")), props.comment);
         assert_eq!(FailMode::Panic, props.fail_mode);
 
-        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\t-\t-\t0\ton\twait\n";
+        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\t-\t-\t0\ton\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line).unwrap();
         assert_eq!(Health::Offline, props.health);
         assert_eq!(Some(PathBuf::from("/mnt")), props.alt_root);
 
+        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\tnone\t0\ton\twait\ton\n";
+        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        assert_eq!(CacheType::None, props.cache_file);
+        assert!(props.auto_trim);
+
         let line = b"waf\tasd";
         let props = ZpoolProperties::try_from_stdout(line);
         assert!(props.is_err());
 
-        let line = b"69120\t0\ttouch it\t1.50x\t1\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\tz/ROOT/default\t-\t0\ton\twait\n";
+        let line = b"69120\t0\ttouch it\t1.50x\t1\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\tz/ROOT/default\t-\t0\ton\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line).unwrap();
         assert_eq!(Some(String::from("z/ROOT/default")), props.boot_fs);
         assert_eq!(Some(1), props.expand_size);
 
-        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\tomn\twait\n";
+        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\tomn\twait\toff\n";
         let props = ZpoolProperties::try_from_stdout(line);
         assert!(props.is_err());
     }
 
+    #[test]
+    fn test_from_props_round_trip() {
+        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t/mnt\ton\ton\ton\tz/ROOT/default\t/etc/zfs/custom.cache\t0\ton\tcontinue\ton\n";
+        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        let write = ZpoolPropertiesWriteBuilder::from_props(&props).build().unwrap();
+        let expected = ZpoolPropertiesWrite {
+            read_only:    true,
+            auto_expand:  true,
+            auto_replace: true,
+            auto_trim:    true,
+            boot_fs:      Some(String::from("z/ROOT/default")),
+            cache_file:   CacheType::Custom(String::from("/etc/zfs/custom.cache")),
+            comment:      String::from("touch it"),
+            delegation:   true,
+            fail_mode:    FailMode::Continue,
+        };
+        assert_eq!(expected, write);
+
+        // Defaults survive as well, without comment turning into something.
+        let line = b"69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\toff\twait\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        let write = ZpoolPropertiesWriteBuilder::from_props(&props).build().unwrap();
+        let expected = ZpoolPropertiesWriteBuilder::default().delegation(false).build().unwrap();
+        assert_eq!(expected, write);
+    }
+
     #[test]
     fn to_arg() {
         let props = ZpoolPropertiesWriteBuilder::default().build().unwrap();
         let expected: Vec<OsString> = vec![
             "autoexpand=off",
             "autoreplace=off",
+            "autotrim=off",
             "cachefile=",
             "comment=",
             "delegation=off",
@@ -585,6 +788,7 @@ mod test {
         let expected: Vec<OsString> = vec![
             "autoexpand=on",
             "autoreplace=off",
+            "autotrim=off",
             "cachefile=none",
             "comment=",
             "delegation=off",
@@ -604,6 +808,7 @@ mod test {
         let expected: Vec<OsString> = vec![
             "autoexpand=off",
             "autoreplace=off",
+            "autotrim=off",
             "cachefile=wat",
             "comment=",
             "delegation=off",
@@ -623,6 +828,7 @@ mod test {
         let expected: Vec<OsString> = vec![
             "autoexpand=off",
             "autoreplace=on",
+            "autotrim=off",
             "cachefile=",
             "comment=a test",
             "delegation=off",
@@ -635,8 +841,73 @@ mod test {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_comment_clearing() {
+        let cleared =
+            |props: ZpoolPropertiesWrite| props.into_args().contains(&OsString::from("comment="));
+        let props = ZpoolPropertiesWriteBuilder::default().comment("wat").clear_comment().build();
+        assert!(cleared(props.unwrap()));
+        let props = ZpoolPropertiesWriteBuilder::default().comment("   ").build();
+        assert!(cleared(props.unwrap()));
+        let props = ZpoolPropertiesWriteBuilder::default().comment(" wat ").build();
+        assert!(props.unwrap().into_args().contains(&OsString::from("comment= wat ")));
+
+        let line = b"69120\t0\t  \t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\toff\twait\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        assert_eq!(None, props.comment);
+    }
+
     #[test]
     fn write_builder() {
         let _right: ZpoolPropertiesWriteBuilder = ZpoolPropertiesWrite::builder();
     }
+
+    #[test]
+    fn parsing_full_props() {
+        let stdout = b"tank\tsize\t67108864\t-
+tank\tcapacity\t0\t-
+tank\taltroot\t-\tdefault
+tank\thealth\tONLINE\t-
+tank\tguid\t5667188105885376774\t-
+tank\tversion\t-\tdefault
+tank\tbootfs\t-\tdefault
+tank\tdelegation\ton\tdefault
+tank\tautoreplace\toff\tdefault
+tank\tcachefile\t-\tdefault
+tank\tfailmode\twait\tdefault
+tank\tlistsnapshots\toff\tdefault
+tank\tautoexpand\toff\tdefault
+tank\tdedupratio\t1.00\t-
+tank\tfree\t67009024\t-
+tank\tallocated\t99840\t-
+tank\treadonly\toff\t-
+tank\tashift\t0\tdefault
+tank\tcomment\t-\tdefault
+tank\texpandsize\t-\t-
+tank\tfreeing\t0\t-
+tank\tfragmentation\t1\t-
+tank\tleaked\t0\t-
+tank\tmultihost\toff\tdefault
+tank\tautotrim\toff\tdefault
+tank\tfeature@async_destroy\tenabled\tlocal
+tank\tfeature@empty_bpobj\tactive\tlocal
+tank\tfeature@encryption\tdisabled\tlocal
+";
+        let props = ZpoolPropertiesFull::try_from_stdout(stdout).unwrap();
+        assert_eq!(&99840, props.properties().alloc());
+        assert_eq!(&Health::Online, props.properties().health());
+        assert_eq!(&0, props.properties().dedup_ditto());
+        assert_eq!(&true, props.properties().delegation());
+
+        assert_eq!(3, props.features().len());
+        assert_eq!(Some(&FeatureState::Enabled), props.features().get("async_destroy"));
+        assert!(props.is_feature_enabled("async_destroy"));
+        assert!(!props.is_feature_active("async_destroy"));
+        assert!(props.is_feature_active("empty_bpobj"));
+        assert!(!props.is_feature_enabled("encryption"));
+        assert!(!props.is_feature_enabled("no_such_feature"));
+
+        let bad = b"tank\tfeature@encryption\tmaybe\tlocal\n";
+        assert!(ZpoolPropertiesFull::try_from_stdout(bad).is_err());
+    }
 }
@@ -0,0 +1,362 @@
+//! Parsing of `scan:` line of `zpool status`: state of the last scan, result of a completed scrub,
+//! durations and timestamps.
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use regex::{Captures, Regex};
+
+use super::{ZpoolError, ZpoolResult};
+
+lazy_static! {
+    static ref RE_SCRUB_DONE: Regex =
+        Regex::new(r"scrub repaired (\S+) in (.+?) with (\d+) errors")
+            .expect("failed to compile RE_SCRUB_DONE");
+    static ref RE_DAYS_HMS: Regex =
+        Regex::new(r"^(?:(\d+) days? )?(\d+):(\d+):(\d+)$").expect("failed to compile RE_DAYS_HMS");
+    static ref RE_HMS_UNITS: Regex = Regex::new(r"^(?:(\d+)h)? ?(?:(\d+)m)? ?(?:(\d+)s)?$")
+        .expect("failed to compile RE_HMS_UNITS");
+    static ref RE_RESILVER_DONE: Regex = Regex::new(r"resilvered (\S+) in (.+?) with (\d+) errors")
+        .expect("failed to compile RE_RESILVER_DONE");
+    static ref RE_PERCENT_DONE: Regex =
+        Regex::new(r"(\d+(?:\.\d+)?)% done").expect("failed to compile RE_PERCENT_DONE");
+    static ref RE_TO_GO: Regex =
+        Regex::new(r"((?:\d+ days? )?\S+) to go").expect("failed to compile RE_TO_GO");
+    static ref RE_REPAIRED: Regex =
+        Regex::new(r"(\S+) (repaired|resilvered),").expect("failed to compile RE_REPAIRED");
+    static ref RE_SINCE: Regex =
+        Regex::new(r"since (\w{3} +\w{3} +\d{1,2} +\d{1,2}:\d{2}:\d{2} +\d{4})")
+            .expect("failed to compile RE_SINCE");
+}
+
+/// Outcome of the last completed scrub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ScrubReport {
+    /// Amount of repaired data in bytes. `zpool` rounds it for humans, so it's approximate.
+    repaired: u64,
+    /// Amount of errors scrub couldn't repair.
+    errors:   u64,
+    /// How long scrub took. `None` if `zpool` reported it in unknown format.
+    duration: Option<Duration>,
+}
+
+impl ScrubReport {
+    /// Find `scan:` line of completed scrub in `zpool status` output and parse it. Returns `Other`
+    /// with the line if the last scrub didn't complete (was canceled, for example).
+    pub(crate) fn from_status(stdout: &str) -> ZpoolResult<ScrubReport> {
+        let caps = match RE_SCRUB_DONE.captures(stdout) {
+            Some(caps) => caps,
+            None => {
+                let scan = stdout.lines().find(|line| line.trim_start().starts_with("scan:"));
+                return Err(ZpoolError::Other(scan.unwrap_or_default().trim().to_string()));
+            },
+        };
+        let repaired = parse_size(&caps[1])?;
+        let duration = parse_duration(&caps[2]);
+        let errors = caps[3].parse()?;
+        Ok(ScrubReport { repaired, errors, duration })
+    }
+}
+
+/// State of the last scan (scrub or resilver) as reported by `scan:` line of `zpool status`.
+/// Amounts of data are rounded by `zpool` for humans, so they're approximate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanStatus {
+    /// Pool was never scanned.
+    None,
+    /// Scrub is running.
+    ScrubInProgress {
+        /// When scrub started, in local time of the host.
+        since:    Option<NaiveDateTime>,
+        /// How much is done in tenths of a percent, from 0 to 1000.
+        permille: u16,
+        /// Estimated time left. `None` if `zpool` can't estimate it yet.
+        eta:      Option<Duration>,
+        /// Amount of data repaired so far in bytes.
+        repaired: u64,
+    },
+    /// The last scrub completed.
+    ScrubCompleted {
+        /// Amount of repaired data in bytes.
+        repaired: u64,
+        /// Amount of errors scrub couldn't repair.
+        errors:   u64,
+        /// How long scrub took.
+        duration: Option<Duration>,
+    },
+    /// Resilver is running.
+    ResilverInProgress {
+        /// When resilver started, in local time of the host.
+        since:      Option<NaiveDateTime>,
+        /// How much is done in tenths of a percent, from 0 to 1000.
+        permille:   u16,
+        /// Estimated time left. `None` if `zpool` can't estimate it yet.
+        eta:        Option<Duration>,
+        /// Amount of data resilvered so far in bytes.
+        resilvered: u64,
+    },
+    /// The last resilver completed.
+    ResilverCompleted {
+        /// Amount of resilvered data in bytes.
+        resilvered: u64,
+        /// Amount of errors resilver ran into.
+        errors:     u64,
+        /// How long resilver took.
+        duration:   Option<Duration>,
+    },
+    /// Anything else: canceled or paused scrub, scan of unknown format. Contains the text of
+    /// `scan:` line.
+    Other(String),
+}
+
+impl Default for ScanStatus {
+    fn default() -> ScanStatus { ScanStatus::None }
+}
+
+impl ScanStatus {
+    /// Parse text after `scan:` including continuation lines of scan in progress.
+    pub(crate) fn from_scan_text(text: &str) -> ScanStatus {
+        let text = text.trim();
+        let parsed = if text == "none requested" {
+            Some(ScanStatus::None)
+        } else if text.starts_with("scrub in progress") {
+            ScanStatus::in_progress(text).map(|(since, permille, eta, repaired)| {
+                ScanStatus::ScrubInProgress { since, permille, eta, repaired }
+            })
+        } else if text.starts_with("resilver in progress") {
+            ScanStatus::in_progress(text).map(|(since, permille, eta, resilvered)| {
+                ScanStatus::ResilverInProgress { since, permille, eta, resilvered }
+            })
+        } else if let Some(caps) = RE_SCRUB_DONE.captures(text) {
+            ScanStatus::completed(&caps).map(|(repaired, errors, duration)| {
+                ScanStatus::ScrubCompleted { repaired, errors, duration }
+            })
+        } else if let Some(caps) = RE_RESILVER_DONE.captures(text) {
+            ScanStatus::completed(&caps).map(|(resilvered, errors, duration)| {
+                ScanStatus::ResilverCompleted { resilvered, errors, duration }
+            })
+        } else {
+            None
+        };
+        parsed.unwrap_or_else(|| ScanStatus::Other(text.to_string()))
+    }
+
+    #[allow(clippy::as_conversion)]
+    fn in_progress(text: &str) -> Option<(Option<NaiveDateTime>, u16, Option<Duration>, u64)> {
+        let since = parse_since(text);
+        let percent: f64 = RE_PERCENT_DONE.captures(text)?[1].parse().ok()?;
+        // Regex only lets non-negative numbers through, cast can't wrap.
+        let permille = (percent * 10.0).round().min(1000.0) as u16;
+        let eta = RE_TO_GO.captures(text).and_then(|caps| parse_duration(&caps[1]));
+        let amount = match RE_REPAIRED.captures(text) {
+            Some(caps) => parse_size(&caps[1]).ok()?,
+            None => 0,
+        };
+        Some((since, permille, eta, amount))
+    }
+
+    fn completed(caps: &Captures<'_>) -> Option<(u64, u64, Option<Duration>)> {
+        let amount = parse_size(&caps[1]).ok()?;
+        let errors = caps[3].parse().ok()?;
+        Some((amount, errors, parse_duration(&caps[2])))
+    }
+}
+
+/// Parse human readable size like `0B`, `12.5K` or legacy `0`.
+fn parse_size(value: &str) -> ZpoolResult<u64> {
+    let split = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or_else(|| value.len());
+    let (number, suffix) = value.split_at(split);
+    let number: f64 = number.parse()?;
+    let exponent = match suffix {
+        "" | "B" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        "E" => 6,
+        _ => return Err(ZpoolError::ParseError),
+    };
+    Ok((number * 1024_f64.powi(exponent)).round() as u64)
+}
+
+/// Parse duration in one of the formats used across versions: `00:00:01`, `1 days 02:03:04`,
+/// legacy `0h0m` or `1h 2m 3s`. `None` if format isn't recognized or doesn't fit in `u64` seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Some(caps) = RE_DAYS_HMS.captures(value) {
+        let days: u64 = caps.get(1).map_or(Some(0), |days| days.as_str().parse().ok())?;
+        let hours: u64 = caps[2].parse().ok()?;
+        let minutes: u64 = caps[3].parse().ok()?;
+        let seconds: u64 = caps[4].parse().ok()?;
+        from_units(days, hours, minutes, seconds)
+    } else {
+        let caps = RE_HMS_UNITS.captures(value).filter(|_| !value.is_empty())?;
+        let unit = |idx: usize| caps.get(idx).map_or(Some(0), |unit| unit.as_str().parse().ok());
+        let (hours, minutes, seconds): (u64, u64, u64) = (unit(1)?, unit(2)?, unit(3)?);
+        from_units(0, hours, minutes, seconds)
+    }
+}
+
+fn from_units(days: u64, hours: u64, minutes: u64, seconds: u64) -> Option<Duration> {
+    let hours = days.checked_mul(24)?.checked_add(hours)?;
+    let minutes = hours.checked_mul(60)?.checked_add(minutes)?;
+    let seconds = minutes.checked_mul(60)?.checked_add(seconds)?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Find when the scan started in `... in progress since Tue Aug 13 23:03:11 2019` form. Time is
+/// local to the host that produced the output. `None` if there is no timestamp or it isn't
+/// recognized.
+pub fn parse_since(line: &str) -> Option<NaiveDateTime> {
+    let caps = RE_SINCE.captures(line)?;
+    let timestamp = caps[1].split_whitespace().collect::<Vec<_>>().join(" ");
+    NaiveDateTime::parse_from_str(&timestamp, "%a %b %d %H:%M:%S %Y").ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    #[test]
+    fn test_openzfs_format() {
+        let stdout = "  pool: tank
+ state: ONLINE
+  scan: scrub repaired 12.5K in 00:01:02 with 0 errors on Fri Oct 16 12:00:00 2026
+config:
+";
+        let report = ScrubReport::from_status(stdout).unwrap();
+        assert_eq!(&12800, report.repaired());
+        assert_eq!(&0, report.errors());
+        assert_eq!(&Some(Duration::from_secs(62)), report.duration());
+    }
+
+    #[test]
+    fn test_days_and_legacy_formats() {
+        let stdout = "scan: scrub repaired 0B in 1 days 02:03:04 with 3 errors on Fri Oct 16 2026";
+        let report = ScrubReport::from_status(stdout).unwrap();
+        assert_eq!(&0, report.repaired());
+        assert_eq!(&3, report.errors());
+        assert_eq!(&Some(Duration::from_secs(93_784)), report.duration());
+
+        let stdout = "scan: scrub repaired 0 in 0h5m with 0 errors on Fri Oct 16 12:00:00 2026";
+        let report = ScrubReport::from_status(stdout).unwrap();
+        assert_eq!(&Some(Duration::from_secs(300)), report.duration());
+    }
+
+    #[test]
+    fn test_unknown_duration_is_none() {
+        let stdout = "scan: scrub repaired 0B in a while with 0 errors on Fri Oct 16 12:00:00 2026";
+        let report = ScrubReport::from_status(stdout).unwrap();
+        assert_eq!(&None, report.duration());
+        assert_eq!(&0, report.errors());
+    }
+
+    #[test]
+    fn test_parse_duration_formats() {
+        let secs = |secs| Some(Duration::from_secs(secs));
+        assert_eq!(secs(3723), parse_duration("01:02:03"));
+        assert_eq!(secs(0), parse_duration("00:00:00"));
+        assert_eq!(secs(86_400 + 3723), parse_duration("1 days 01:02:03"));
+        assert_eq!(secs(2 * 86_400), parse_duration("2 days 00:00:00"));
+        assert_eq!(secs(86_400), parse_duration("1 day 00:00:00"));
+        assert_eq!(secs(3900), parse_duration("1h5m"));
+        assert_eq!(secs(3900), parse_duration("1h 5m"));
+        assert_eq!(secs(3903), parse_duration("1h5m3s"));
+        assert_eq!(secs(300), parse_duration("5m"));
+        assert_eq!(secs(42), parse_duration(" 42s "));
+        assert_eq!(None, parse_duration(""));
+        assert_eq!(None, parse_duration("no estimated completion time"));
+        assert_eq!(None, parse_duration("01:02"));
+        assert_eq!(None, parse_duration("99999999999999999999:00:00"));
+        assert_eq!(None, parse_duration("5124095576030432:00:00"));
+        assert_eq!(None, parse_duration("213503982334602 days 00:00:00"));
+        assert_eq!(None, parse_duration("307445734561825861m"));
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let line = "scan: resilver in progress since Tue Aug 13 23:03:11 2019";
+        let expected = NaiveDateTime::parse_from_str("2019-08-13 23:03:11", "%Y-%m-%d %H:%M:%S");
+        assert_eq!(expected.ok(), parse_since(line));
+
+        let line = "scan: scrub in progress since Sat Feb  1 01:02:03 2020\n\t1G scanned";
+        let expected = NaiveDateTime::parse_from_str("2020-02-01 01:02:03", "%Y-%m-%d %H:%M:%S");
+        assert_eq!(expected.ok(), parse_since(line));
+
+        assert_eq!(None, parse_since("scan: scrub in progress since yesterday"));
+        assert_eq!(None, parse_since("scan: none requested"));
+    }
+
+    #[test]
+    fn test_scan_status_in_progress() {
+        let text = "scrub in progress since Sun Jul 25 16:07:49 2021
+\t1.23G scanned at 100M/s, 456M issued at 50M/s, 10G total
+\t12K repaired, 45.67% done, 00:01:23 to go";
+        let since = NaiveDateTime::parse_from_str("2021-07-25 16:07:49", "%Y-%m-%d %H:%M:%S");
+        let expected = ScanStatus::ScrubInProgress {
+            since:    since.ok(),
+            permille: 457,
+            eta:      Some(Duration::from_secs(83)),
+            repaired: 12288,
+        };
+        assert_eq!(expected, ScanStatus::from_scan_text(text));
+
+        let text = "resilver in progress since Tue Aug 13 23:03:11 2019
+\t1.2G scanned out of 10G at 100M/s, 1 days 02:00:00 to go
+\t300M resilvered, 12.00% done";
+        let status = ScanStatus::from_scan_text(text);
+        if let ScanStatus::ResilverInProgress { since, permille, eta, resilvered } = status {
+            assert!(since.is_some());
+            assert_eq!(120, permille);
+            assert_eq!(Some(Duration::from_secs(93_600)), eta);
+            assert_eq!(300 * 1024 * 1024, resilvered);
+        } else {
+            panic!("unexpected status: {:?}", status);
+        }
+
+        let text = "scrub in progress since Sat Feb  1 01:02:03 2020
+    0B scanned at 0B/s, 0B issued at 0B/s, 10G total
+    0B repaired, 0.00% done, no estimated completion time";
+        let status = ScanStatus::from_scan_text(text);
+        if let ScanStatus::ScrubInProgress { eta, repaired, .. } = status {
+            assert_eq!(None, eta);
+            assert_eq!(0, repaired);
+        } else {
+            panic!("unexpected status: {:?}", status);
+        }
+    }
+
+    #[test]
+    fn test_scan_status_completed_and_other() {
+        assert_eq!(ScanStatus::None, ScanStatus::from_scan_text("none requested\n"));
+        let text = "scrub repaired 0B in 01:23:45 with 2 errors on Sun Oct 11 12:00:00 2026";
+        let expected = ScanStatus::ScrubCompleted {
+            repaired: 0,
+            errors:   2,
+            duration: Some(Duration::from_secs(5025)),
+        };
+        assert_eq!(expected, ScanStatus::from_scan_text(text));
+
+        let text = "resilvered 1.5G in 0h5m with 0 errors on Sun Oct 11 12:00:00 2026";
+        let expected = ScanStatus::ResilverCompleted {
+            resilvered: 1536 * 1024 * 1024,
+            errors:     0,
+            duration:   Some(Duration::from_secs(300)),
+        };
+        assert_eq!(expected, ScanStatus::from_scan_text(text));
+
+        let text = "scrub canceled on Fri Oct 16 12:00:00 2026";
+        assert_eq!(ScanStatus::Other(text.to_string()), ScanStatus::from_scan_text(text));
+        let text = "scrub in progress since yesterday, almost done";
+        assert_eq!(ScanStatus::Other(text.to_string()), ScanStatus::from_scan_text(text));
+    }
+
+    #[test]
+    fn test_canceled_scrub() {
+        let stdout = "  scan: scrub canceled on Fri Oct 16 12:00:00 2026\n";
+        let err = ScrubReport::from_status(stdout).unwrap_err();
+        assert_eq!(ZpoolErrorKind::Other, err.kind());
+    }
+}
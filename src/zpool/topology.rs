@@ -41,7 +41,55 @@
 
 use std::{ffi::OsString, path::PathBuf};
 
-use crate::zpool::{properties::ZpoolPropertiesWrite, vdev::CreateVdevRequest, CreateMode};
+use crate::zpool::{properties::{CacheType, ZpoolPropertiesWrite},
+                   vdev::{CreateVdevRequest, VdevType},
+                   CreateMode};
+
+quick_error! {
+    /// Specific reason topology can't be used to create a zpool.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TopologyError {
+        /// There are no vdevs to store data on.
+        NoDataVdevs {
+            display("no data vdevs")
+        }
+        /// There are cache devices, but no vdevs to store data on.
+        CacheWithoutData {
+            display("cache devices without data vdevs")
+        }
+        /// Data vdevs have different redundancy (a mirror next to a raidz, for example). `zpool`
+        /// refuses to create such pool unless it's forced.
+        MixedRedundancy(first: VdevType, second: VdevType) {
+            display("mixed redundancy: {:?} and {:?} vdevs", first, second)
+        }
+        /// Vdev doesn't have enough devices for its kind.
+        InsufficientDevices(kind: VdevType, need: usize, got: usize) {
+            display("{:?} needs at least {} devices, got {}", kind, need, got)
+        }
+        /// Log devices can be single disks or mirrors, RAID-Z isn't supported.
+        UnsupportedLogVdev(kind: VdevType) {
+            display("{:?} can't be used as log", kind)
+        }
+        /// Mirrored log next to a single disk log. Same as with data vdevs, `zpool` refuses it
+        /// unless it's forced.
+        MixedLogRedundancy(first: VdevType, second: VdevType) {
+            display("mixed log redundancy: {:?} and {:?} logs", first, second)
+        }
+        /// Altroot isn't an absolute path.
+        RelativeAltroot(altroot: PathBuf) {
+            display("altroot {} is not an absolute path", altroot.display())
+        }
+        /// Mount point is neither an absolute path nor `none` or `legacy`.
+        InvalidMountpoint(mount: PathBuf) {
+            display("mountpoint {} must be an absolute path, none or legacy", mount.display())
+        }
+        /// Altroot implies `cachefile=none`, pool created with other cache file won't be imported
+        /// the way it's expected to.
+        AltrootWithCacheFile(cache_file: CacheType) {
+            display("altroot requires cachefile=none, got {:?}", cache_file)
+        }
+    }
+}
 #[derive(Default, Builder, Debug, Clone, Getters, PartialEq, Eq)]
 #[builder(setter(into))]
 #[get = "pub"]
@@ -109,11 +157,61 @@ impl CreateZpoolRequest {
     ///
     /// That means it as at least one valid vdev and all optional devices are
     /// valid if present.
-    pub fn is_suitable_for_create(&self) -> bool {
+    pub fn is_suitable_for_create(&self) -> bool { self.validate().is_ok() }
+
+    /// Same as `is_suitable_for_create`, but tells what exactly is wrong. Mixed redundancy (of
+    /// data vdevs or of logs) is allowed when `create_mode` is `Force`. Besides devices, checks
+    /// that `altroot` and `mount` are absolute (`mount` can be `none` or `legacy` as well) and
+    /// that `altroot` isn't combined with a cache file.
+    pub fn validate(&self) -> Result<(), TopologyError> {
         if self.vdevs.is_empty() {
-            return false;
+            if self.caches.is_empty() {
+                return Err(TopologyError::NoDataVdevs);
+            }
+            return Err(TopologyError::CacheWithoutData);
         }
-        self.is_suitable_for_update()
+        for vdev in self.vdevs.iter().chain(self.logs.iter()) {
+            validate_vdev(vdev)?;
+        }
+        for log in &self.logs {
+            validate_log(log)?;
+        }
+        if self.create_mode != CreateMode::Force {
+            let first = self.vdevs[0].kind();
+            if let Some(other) =
+                self.vdevs.iter().map(CreateVdevRequest::kind).find(|k| k != &first)
+            {
+                return Err(TopologyError::MixedRedundancy(first, other));
+            }
+            if let Some(first) = self.logs.first().map(CreateVdevRequest::kind) {
+                if let Some(other) =
+                    self.logs.iter().map(CreateVdevRequest::kind).find(|k| k != &first)
+                {
+                    return Err(TopologyError::MixedLogRedundancy(first, other));
+                }
+            }
+        }
+        self.validate_mount()
+    }
+
+    fn validate_mount(&self) -> Result<(), TopologyError> {
+        if let Some(mount) = &self.mount {
+            if !mount.is_absolute() && mount.as_os_str() != "none" && mount.as_os_str() != "legacy"
+            {
+                return Err(TopologyError::InvalidMountpoint(mount.clone()));
+            }
+        }
+        if let Some(altroot) = &self.altroot {
+            if !altroot.is_absolute() {
+                return Err(TopologyError::RelativeAltroot(altroot.clone()));
+            }
+            if let Some(props) = &self.props {
+                if props.cache_file() != &CacheType::None {
+                    return Err(TopologyError::AltrootWithCacheFile(props.cache_file().clone()));
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Make CreateZpoolRequest usable as arg for [`Command`](https://doc.rust-lang.org/std/process/struct.Command.html).
@@ -144,6 +242,32 @@ impl CreateZpoolRequest {
     }
 }
 
+fn is_valid_log_kind(kind: &VdevType) -> bool {
+    match kind {
+        VdevType::SingleDisk | VdevType::Mirror => true,
+        _ => false,
+    }
+}
+
+/// Check that vdev has enough devices for its kind. Vdevs added to existing pool go through it
+/// too.
+pub(crate) fn validate_vdev(vdev: &CreateVdevRequest) -> Result<(), TopologyError> {
+    let (got, need) = vdev.disk_count();
+    if got < need {
+        return Err(TopologyError::InsufficientDevices(vdev.kind(), need, got));
+    }
+    Ok(())
+}
+
+/// Same as `validate_vdev`, but also rejects kinds that can't be used as log.
+pub(crate) fn validate_log(log: &CreateVdevRequest) -> Result<(), TopologyError> {
+    validate_vdev(log)?;
+    if !is_valid_log_kind(&log.kind()) {
+        return Err(TopologyError::UnsupportedLogVdev(log.kind()));
+    }
+    Ok(())
+}
+
 impl CreateZpoolRequestBuilder {
     /// Add vdev to request.
     ///
@@ -210,6 +334,7 @@ mod test {
     use tempdir::TempDir;
 
     use super::*;
+    use crate::zpool::ZpoolPropertiesWriteBuilder;
 
     fn get_disks(num: usize, path: &PathBuf) -> Vec<PathBuf> {
         (0..num).map(|_| path.clone()).collect()
@@ -261,6 +386,108 @@ mod test {
         assert!(!topo.is_suitable_for_create());
     }
 
+    #[test]
+    fn test_validate() {
+        let disks = |num| get_disks(num, &PathBuf::from("sd0"));
+        let topo = |vdevs: Vec<CreateVdevRequest>| {
+            CreateZpoolRequestBuilder::default().name("tank").vdevs(vdevs).build().unwrap()
+        };
+
+        assert_eq!(Ok(()), topo(vec![CreateVdevRequest::RaidZ2(disks(5))]).validate());
+        assert_eq!(Err(TopologyError::NoDataVdevs), topo(Vec::new()).validate());
+        let cache_only =
+            CreateZpoolRequestBuilder::default().name("tank").cache("sd0".into()).build().unwrap();
+        assert_eq!(Err(TopologyError::CacheWithoutData), cache_only.validate());
+
+        let expected = TopologyError::InsufficientDevices(VdevType::RaidZ2, 5, 2);
+        assert_eq!(Err(expected), topo(vec![CreateVdevRequest::RaidZ2(disks(2))]).validate());
+
+        let mixed =
+            topo(vec![CreateVdevRequest::Mirror(disks(2)), CreateVdevRequest::RaidZ(disks(3))]);
+        let expected = TopologyError::MixedRedundancy(VdevType::Mirror, VdevType::RaidZ);
+        assert_eq!(Err(expected), mixed.validate());
+        assert!(!mixed.is_suitable_for_create());
+        let forced = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdevs(mixed.vdevs().clone())
+            .create_mode(CreateMode::Force)
+            .build()
+            .unwrap();
+        assert_eq!(Ok(()), forced.validate());
+
+        let raidz_log = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdev(CreateVdevRequest::Mirror(disks(2)))
+            .zil(CreateVdevRequest::RaidZ(disks(3)))
+            .build()
+            .unwrap();
+        let expected = TopologyError::UnsupportedLogVdev(VdevType::RaidZ);
+        assert_eq!(Err(expected), raidz_log.validate());
+
+        let mut mixed_logs = CreateZpoolRequestBuilder::default();
+        mixed_logs
+            .name("tank")
+            .vdev(CreateVdevRequest::Mirror(disks(2)))
+            .zil(CreateVdevRequest::Mirror(disks(2)))
+            .zil(CreateVdevRequest::SingleDisk("sd0".into()));
+        let expected = TopologyError::MixedLogRedundancy(VdevType::Mirror, VdevType::SingleDisk);
+        assert_eq!(Err(expected), mixed_logs.build().unwrap().validate());
+        let forced = mixed_logs.create_mode(CreateMode::Force).build().unwrap();
+        assert_eq!(Ok(()), forced.validate());
+    }
+
+    #[test]
+    fn test_validate_mount() {
+        let mut request = CreateZpoolRequestBuilder::default();
+        request.name("tank").vdev(CreateVdevRequest::SingleDisk("sd0".into()));
+        let mount = |request: &mut CreateZpoolRequestBuilder, mount: &str| {
+            request.mount(PathBuf::from(mount)).build().unwrap().validate()
+        };
+        assert_eq!(Ok(()), mount(&mut request, "/mnt/tank"));
+        assert_eq!(Ok(()), mount(&mut request, "none"));
+        assert_eq!(Ok(()), mount(&mut request, "legacy"));
+        let expected = TopologyError::InvalidMountpoint(PathBuf::from("mnt/tank"));
+        assert_eq!(Err(expected), mount(&mut request, "mnt/tank"));
+
+        request.mount(PathBuf::from("/tank"));
+        let relative = request.altroot(PathBuf::from("mnt")).build().unwrap();
+        let expected = TopologyError::RelativeAltroot(PathBuf::from("mnt"));
+        assert_eq!(Err(expected), relative.validate());
+        assert!(!relative.is_suitable_for_create());
+
+        request.altroot(PathBuf::from("/mnt"));
+        assert_eq!(Ok(()), request.build().unwrap().validate());
+        let props = |cache_file| {
+            ZpoolPropertiesWriteBuilder::default().cache_file(cache_file).build().unwrap()
+        };
+        let none = request.props(props(CacheType::None)).build().unwrap();
+        assert_eq!(Ok(()), none.validate());
+        let default = request.props(props(CacheType::Default)).build().unwrap();
+        let expected = TopologyError::AltrootWithCacheFile(CacheType::Default);
+        assert_eq!(Err(expected), default.validate());
+    }
+
+    #[test]
+    fn test_args_mirrored_logs_and_spares() {
+        let disk = |name: &str| PathBuf::from(name);
+        let topo = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdev(CreateVdevRequest::Mirror(vec![disk("d4"), disk("d5")]))
+            .zil(CreateVdevRequest::Mirror(vec![disk("d0"), disk("d1")]))
+            .zil(CreateVdevRequest::Mirror(vec![disk("d6"), disk("d7")]))
+            .spare(disk("d2"))
+            .spare(disk("d3"))
+            .build()
+            .unwrap();
+        assert_eq!(Ok(()), topo.validate());
+
+        let expected = args_from_slice(&[
+            "mirror", "d4", "d5", "log", "mirror", "d0", "d1", "mirror", "d6", "d7", "spare", "d2",
+            "d3",
+        ]);
+        assert_eq!(expected, topo.into_args());
+    }
+
     #[test]
     fn test_builder() {
         let result = CreateZpoolRequest::builder().build();
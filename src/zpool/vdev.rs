@@ -29,11 +29,12 @@ use std::{default::Default,
           path::{Path, PathBuf},
           str::FromStr};
 
-use crate::zpool::{Health, Reason, ZpoolError};
+use crate::zpool::{Health, Reason, VdevFaultReason, ZpoolError, ZpoolResult};
 
 /// Error statistics.
 ///
 /// NOTE: Due to imperfections of our world number of errors limited to [`std::u64::MAX`](https://doc.rust-lang.org/std/u64/constant.MAX.html).
+/// Large counters are abbreviated by `zpool status` (`1.5K`), so they are approximate.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ErrorStatistics {
     /// I/O errors that occurred while issuing a read request
@@ -70,10 +71,100 @@ pub struct Disk {
     /// reset.
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// GUID of the device. Unlike path, it doesn't change when device is renamed, so it's the
+    /// most reliable way to refer to the device in `take_offline`, `replace_disk` and so on
+    /// (pass `guid.to_string()` as device). `None` if it's unknown.
+    #[builder(default)]
+    guid:             Option<u64>,
 }
 
 impl Disk {
     pub fn builder() -> DiskBuilder { DiskBuilder::default() }
+
+    /// Resolve path of this disk to the device behind it. See
+    /// [`DevicePath::resolve`](struct.DevicePath.html#method.resolve).
+    pub fn device_path(&self) -> DevicePath { DevicePath::resolve(&self.path) }
+
+    pub(crate) fn set_guid(&mut self, guid: Option<u64>) { self.guid = guid; }
+
+    /// Classified [`reason`](#method.reason) of device state, e.g. `cannot open` for a missing
+    /// disk or `too many errors` for a disk ZFS gave up on.
+    pub fn fault_reason(&self) -> Option<VdevFaultReason> {
+        self.reason.as_ref().map(Reason::fault_reason)
+    }
+
+    /// Path to a device by its id, like `ata-WDC_WD40EFRX-68N32N0_WD-WCC7K0123456`. Unlike
+    /// `/dev/sdX`, it doesn't change between boots, so prefer it for pool creation.
+    ///
+    /// ```rust
+    /// use libzetta::zpool::{CreateVdevRequest, Disk};
+    ///
+    /// let path = Disk::by_id("ata-WDC_WD40EFRX-68N32N0_WD-WCC7K0123456").unwrap();
+    /// let vdev = CreateVdevRequest::disk(path);
+    /// ```
+    pub fn by_id(id: &str) -> ZpoolResult<PathBuf> { alias_path("by-id", id) }
+
+    /// Path to a partition by its GPT partition UUID.
+    pub fn by_partuuid(uuid: &str) -> ZpoolResult<PathBuf> { alias_path("by-partuuid", uuid) }
+
+    /// Path to a device by its physical location, like `pci-0000:00:1f.2-ata-1`.
+    pub fn by_path(location: &str) -> ZpoolResult<PathBuf> { alias_path("by-path", location) }
+}
+
+/// Build `/dev/disk/<dir>/<alias>` making sure alias stays inside of that directory.
+fn alias_path(dir: &str, alias: &str) -> ZpoolResult<PathBuf> {
+    if alias.is_empty() || alias == "." || alias == ".." || alias.contains(&['/', '\0'][..]) {
+        return Err(ZpoolError::InvalidDeviceAlias(alias.into()));
+    }
+    Ok(Path::new("/dev/disk").join(dir).join(alias))
+}
+
+/// Directories searched for devices given by bare name, same order as `zpool import` uses.
+static DEVICE_SEARCH_DIRS: &[&str] = &[
+    "/dev",
+    "/dev/disk/by-vdev",
+    "/dev/disk/by-id",
+    "/dev/disk/by-path",
+    "/dev/disk/by-partlabel",
+    "/dev/disk/by-uuid",
+    "/dev/mapper",
+];
+
+/// Device name together with the device it refers to. Pools are usually built on stable names
+/// (`/dev/disk/by-id/...`, vdev aliases) that are symlinks to kernel names like `/dev/sda`, that
+/// can change between boots. Compare `device` to find out whether two names are the same disk.
+#[derive(Debug, Clone, Getters, PartialEq, Eq)]
+#[get = "pub"]
+pub struct DevicePath {
+    /// Name as given or as reported by ZFS.
+    name:   PathBuf,
+    /// Canonical path to the device with all symlinks resolved. Same as `name` if it wasn't
+    /// resolved.
+    device: PathBuf,
+}
+
+impl DevicePath {
+    /// Take path as is, without touching filesystem.
+    pub fn new<P: Into<PathBuf>>(path: P) -> DevicePath {
+        let name = path.into();
+        DevicePath { device: name.clone(), name }
+    }
+
+    /// Resolve symlinks of the path. Bare names, like ZFS prints them in `zpool status`
+    /// (`sda`, `ata-WDC_WD40...`), are looked up in `/dev` and `/dev/disk/by-*`. If nothing is
+    /// found, `device` is the same as `name`.
+    pub fn resolve<P: AsRef<Path>>(path: P) -> DevicePath {
+        let name = path.as_ref();
+        let device = if name.is_absolute() {
+            name.canonicalize().ok()
+        } else {
+            DEVICE_SEARCH_DIRS.iter().find_map(|dir| Path::new(dir).join(name).canonicalize().ok())
+        };
+        DevicePath { device: device.unwrap_or_else(|| name.to_path_buf()), name: name.into() }
+    }
+
+    /// Check if both paths refer to the same device.
+    pub fn is_same_device(&self, other: &DevicePath) -> bool { self.device == other.device }
 }
 
 /// Equal if path is the same.
@@ -161,6 +252,17 @@ impl CreateVdevRequest {
         true
     }
 
+    /// Amount of disks in this vdev and minimum amount `is_valid` accepts.
+    pub(crate) fn disk_count(&self) -> (usize, usize) {
+        match self {
+            CreateVdevRequest::SingleDisk(_) => (1, 1),
+            CreateVdevRequest::Mirror(disks) => (disks.len(), 2),
+            CreateVdevRequest::RaidZ(disks) => (disks.len(), 3),
+            CreateVdevRequest::RaidZ2(disks) => (disks.len(), 5),
+            CreateVdevRequest::RaidZ3(disks) => (disks.len(), 8),
+        }
+    }
+
     /// Check if given CreateVdevRequest is valid.
     ///
     /// For SingleDisk it means that what ever it points to exists.
@@ -245,11 +347,62 @@ pub struct Vdev {
     /// reset.
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// GUID of top-level vdev. For a single disk vdev it's the same as GUID of the disk. `None`
+    /// if it's unknown.
+    #[builder(default)]
+    guid:             Option<u64>,
 }
 
 impl Vdev {
     /// Create a builder - a referred way of creating Vdev structure.
     pub fn builder() -> VdevBuilder { VdevBuilder::default() }
+
+    /// Classified [`reason`](#method.reason) of vdev state, e.g. `insufficient replicas`.
+    pub fn fault_reason(&self) -> Option<VdevFaultReason> {
+        self.reason.as_ref().map(Reason::fault_reason)
+    }
+
+    /// How many lines vdev takes in `config:` section of `zpool status`.
+    pub(crate) fn config_lines(&self) -> usize {
+        match self.kind {
+            VdevType::SingleDisk => 1,
+            _ => 1 + self.disks.len(),
+        }
+    }
+
+    /// Take GUIDs in the order of `config:` section lines.
+    pub(crate) fn set_guids(&mut self, guids: &mut impl Iterator<Item = u64>) {
+        if let VdevType::SingleDisk = self.kind {
+            self.guid = guids.next();
+            for disk in &mut self.disks {
+                disk.guid = self.guid;
+            }
+        } else {
+            self.guid = guids.next();
+            for disk in &mut self.disks {
+                disk.guid = guids.next();
+            }
+        }
+    }
+
+    /// How many disk failures this vdev can survive when all its disks are healthy: one less than
+    /// number of disks for a mirror, parity level for RAID-Z and none for a single disk.
+    pub fn fault_tolerance(&self) -> usize {
+        match self.kind {
+            VdevType::SingleDisk => 0,
+            VdevType::Mirror => self.disks.len().saturating_sub(1),
+            VdevType::RaidZ => 1,
+            VdevType::RaidZ2 => 2,
+            VdevType::RaidZ3 => 3,
+        }
+    }
+
+    /// How many more disk failures this vdev can survive right now. Disks that aren't online
+    /// are counted as already failed.
+    pub fn remaining_fault_tolerance(&self) -> usize {
+        let failed = self.disks.iter().filter(|disk| disk.health() != &Health::Online).count();
+        self.fault_tolerance().saturating_sub(failed)
+    }
 }
 /// Vdevs are equal of their type and backing disks are equal.
 impl PartialEq for Vdev {
@@ -281,6 +434,7 @@ mod test {
     use tempdir::TempDir;
 
     use super::*;
+    use crate::zpool::ZpoolErrorKind;
 
     fn get_disks(num: usize, path: &PathBuf) -> Vec<PathBuf> {
         (0..num).map(|_| path.clone()).collect()
@@ -506,4 +660,50 @@ mod test {
 
         assert_ne!(left, right);
     }
+
+    #[test]
+    fn test_device_path_resolve() {
+        let tmp_dir = TempDir::new("zpool-tests").unwrap();
+        let device = tmp_dir.path().join("sdz");
+        let _device_file = File::create(&device).unwrap();
+        let by_id = tmp_dir.path().join("ata-DISK_SERIAL");
+        std::os::unix::fs::symlink(&device, &by_id).unwrap();
+
+        let resolved = DevicePath::resolve(&by_id);
+        assert_eq!(&by_id, resolved.name());
+        assert_eq!(&device.canonicalize().unwrap(), resolved.device());
+        assert!(resolved.is_same_device(&DevicePath::resolve(&device)));
+
+        let unresolved = DevicePath::new(&by_id);
+        assert_eq!(&by_id, unresolved.device());
+        assert!(!resolved.is_same_device(&unresolved));
+
+        let missing = DevicePath::resolve(tmp_dir.path().join("gone"));
+        assert_eq!(missing.name(), missing.device());
+        assert_eq!(&PathBuf::from("/dev/null"), DevicePath::resolve("null").device());
+
+        let disk = Disk::builder().path(&by_id).health(Health::Online).build().unwrap();
+        assert_eq!(resolved, disk.device_path());
+    }
+
+    #[test]
+    fn test_disk_aliases() {
+        assert_eq!(
+            PathBuf::from("/dev/disk/by-id/ata-DISK_SERIAL-part1"),
+            Disk::by_id("ata-DISK_SERIAL-part1").unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("/dev/disk/by-partuuid/2c1ea3c5-5d4b-4d2e-9a3b-94b1f3c2d8e1"),
+            Disk::by_partuuid("2c1ea3c5-5d4b-4d2e-9a3b-94b1f3c2d8e1").unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("/dev/disk/by-path/pci-0000:00:1f.2-ata-1"),
+            Disk::by_path("pci-0000:00:1f.2-ata-1").unwrap()
+        );
+
+        for alias in &["", ".", "..", "../../sda", "ata-DISK/../../sda", "a\0b"] {
+            let err = Disk::by_id(alias).unwrap_err();
+            assert_eq!(ZpoolErrorKind::InvalidDeviceAlias, err.kind());
+        }
+    }
 }
@@ -10,12 +10,15 @@ use cavity::{fill, Bytes, WriteMode};
 use rand::Rng;
 
 use libzetta::{slog::*,
-               zfs::{BookmarkRequest, Copies, CreateDatasetRequest, DatasetKind, Error,
-                     Properties, SendFlags, SnapDir, ZfsEngine, ZfsLzc},
+               zfs::{BookmarkRequest, Copies, CreateDatasetRequest, DatasetKind, Delegation,
+                     Encryption, Error, ErrorKind, Properties, PropertySource, ReceiveOptions,
+                     SendFlags, ShareProtocol, SnapDir, StreamKind, ValidationError, ZfsEngine,
+                     ZfsLzc},
                zpool::{CreateVdevRequest, CreateZpoolRequest, ZpoolEngine, ZpoolOpen3}};
 
-use libzetta::{zfs::{properties::VolumeMode, DelegatingZfsEngine, DestroyTiming},
-               zpool::CreateMode};
+use libzetta::{zfs::{properties::VolumeMode, CanMount, DelegatingZfsEngine, DestroyTiming,
+                     MountPoint, ZfsOpen3},
+               zpool::{CreateMode, DestroyMode}};
 
 static ONE_MB_IN_BYTES: u64 = 1024 * 1024;
 
@@ -99,6 +102,35 @@ fn exists_on_fake() {
     assert!(!result);
 }
 
+#[test]
+fn lzc_shared_between_threads() {
+    let zpool = SHARED_ZPOOL.clone();
+    let shared = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+
+    let handles: Vec<_> = (0..8)
+        .map(|idx| {
+            let zpool = zpool.clone();
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                // Fresh engine per "request" next to the shared one.
+                let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+                let fake_dataset = format!("{}/very/fake/dataset-{}", zpool, idx);
+                assert!(!zfs.exists(fake_dataset.clone()).unwrap());
+                assert!(!shared.exists(fake_dataset).unwrap());
+                assert!(shared.exists(zpool).unwrap());
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // Works again after every instance is gone.
+    drop(shared);
+    let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+    assert!(zfs.exists(zpool).unwrap());
+}
+
 #[test]
 fn create_dumb() {
     let zpool = SHARED_ZPOOL.clone();
@@ -125,29 +157,27 @@ fn easy_invalid_zfs() {
     let zpool = SHARED_ZPOOL.clone();
     let dataset_path = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
 
-    let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
-
     let request = CreateDatasetRequest::builder()
         .name(dataset_path.clone())
         .user_properties(std::collections::HashMap::new())
         .kind(DatasetKind::Filesystem)
         .volume_size(2)
-        .build()
-        .unwrap();
+        .build();
 
-    let res = zfs.create(request).unwrap_err();
-    assert_eq!(Error::invalid_input(), res);
+    let res = request.unwrap_err();
+    let expected = Error::from(ValidationError::VolumePropertyOnFilesystem(dataset_path.clone()));
+    assert_eq!(expected, res);
 
     let request = CreateDatasetRequest::builder()
         .name(dataset_path.clone())
         .user_properties(std::collections::HashMap::new())
         .kind(DatasetKind::Filesystem)
         .volume_block_size(2)
-        .build()
-        .unwrap();
+        .build();
 
-    let res = zfs.create(request).unwrap_err();
-    assert_eq!(Error::invalid_input(), res);
+    let res = request.unwrap_err();
+    let expected = Error::from(ValidationError::VolumePropertyOnFilesystem(dataset_path.clone()));
+    assert_eq!(expected, res);
 
     let request = CreateDatasetRequest::builder()
         .name(dataset_path.clone())
@@ -155,21 +185,20 @@ fn easy_invalid_zfs() {
         .kind(DatasetKind::Filesystem)
         .volume_size(2)
         .volume_block_size(2)
-        .build()
-        .unwrap();
+        .build();
 
-    let res = zfs.create(request).unwrap_err();
-    assert_eq!(Error::invalid_input(), res);
+    let res = request.unwrap_err();
+    let expected = Error::from(ValidationError::VolumePropertyOnFilesystem(dataset_path.clone()));
+    assert_eq!(expected, res);
 
     let request = CreateDatasetRequest::builder()
-        .name(dataset_path)
+        .name(dataset_path.clone())
         .user_properties(std::collections::HashMap::new())
         .kind(DatasetKind::Volume)
-        .build()
-        .unwrap();
+        .build();
 
-    let res = zfs.create(request).unwrap_err();
-    assert_eq!(Error::invalid_input(), res);
+    let res = request.unwrap_err();
+    assert_eq!(Error::from(ValidationError::MissingVolumeSize(dataset_path)), res);
 }
 
 #[test]
@@ -195,6 +224,118 @@ fn create_and_destroy() {
     assert!(!res);
 }
 
+#[test]
+fn kind_of_datasets() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    let volume = root.join("vol");
+    zfs.create(CreateDatasetRequest::volume(volume.clone(), ONE_MB_IN_BYTES).build().unwrap())
+        .unwrap();
+    let snapshot = PathBuf::from(format!("{}@kind", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).unwrap();
+
+    assert_eq!(Some(DatasetKind::Filesystem), zfs.kind_of(root.clone()).unwrap());
+    assert_eq!(Some(DatasetKind::Volume), zfs.kind_of(volume.clone()).unwrap());
+    assert_eq!(Some(DatasetKind::Snapshot), zfs.kind_of(snapshot.clone()).unwrap());
+    assert_eq!(None, zfs.kind_of(root.join("nope")).unwrap());
+
+    let open3 = ZfsOpen3::new();
+    assert_eq!(Some(DatasetKind::Volume), open3.kind_of(volume.clone()).unwrap());
+    assert_eq!(Some(DatasetKind::Snapshot), open3.kind_of(snapshot.clone()).unwrap());
+    assert_eq!(None, open3.kind_of(root.join("nope")).unwrap());
+
+    zfs.destroy(snapshot).unwrap();
+    zfs.destroy(volume).unwrap();
+    zfs.destroy(root).unwrap();
+}
+
+#[test]
+fn create_with_parents() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let leaf = root.join("a/b");
+
+    let request = CreateDatasetRequest::filesystem(leaf.clone()).atime(false).build().unwrap();
+    assert!(zfs.create(request).is_err());
+    assert!(!zfs.exists(root.clone()).unwrap());
+
+    let request = CreateDatasetRequest::filesystem(leaf.clone())
+        .atime(false)
+        .create_parents(true)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create the dataset with parents");
+    assert!(zfs.exists(root.join("a")).unwrap());
+
+    match zfs.read_properties(leaf.clone()).unwrap() {
+        Properties::Filesystem(properties) => assert!(!properties.atime()),
+        other => panic!("not a filesystem: {:?}", other),
+    }
+    match zfs.read_properties(root.clone()).unwrap() {
+        Properties::Filesystem(properties) => assert!(properties.atime()),
+        other => panic!("not a filesystem: {:?}", other),
+    }
+
+    // Existing parents are fine.
+    let request =
+        CreateDatasetRequest::filesystem(root.join("a/c")).create_parents(true).build().unwrap();
+    zfs.create(request).unwrap();
+
+    zfs.destroy(leaf).unwrap();
+    zfs.destroy(root.join("a/c")).unwrap();
+    zfs.destroy(root.join("a")).unwrap();
+    zfs.destroy(root).unwrap();
+}
+
+#[test]
+fn create_with_inherited_encryption() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+
+    let key_file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(key_file.path(), "0".repeat(64)).unwrap();
+    let out = std::process::Command::new("zfs")
+        .args(&["create", "-o", "encryption=on", "-o", "keyformat=hex"])
+        .arg("-o")
+        .arg(format!("keylocation=file://{}", key_file.path().display()))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let child = root.join("child");
+    let request = CreateDatasetRequest::builder()
+        .name(child.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create inheriting dataset");
+
+    if let Properties::Filesystem(properties) = zfs.read_properties(&child).unwrap() {
+        assert_ne!(&Encryption::Off, properties.encryption());
+        assert_eq!(&Some(root.clone()), properties.encryption_root());
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    let unencrypted = root.join("unencrypted");
+    let request = CreateDatasetRequest::builder()
+        .name(unencrypted.clone())
+        .kind(DatasetKind::Filesystem)
+        .encryption(Encryption::Off)
+        .build()
+        .unwrap();
+    let err = zfs.create(request).unwrap_err();
+    assert_eq!(Error::from(ValidationError::UnencryptedChild(unencrypted)), err);
+
+    zfs.destroy(child).unwrap();
+    zfs.destroy(root).unwrap();
+}
+
 #[test]
 fn create_and_list() {
     let zpool = SHARED_ZPOOL.clone();
@@ -249,6 +390,280 @@ fn create_and_list() {
     assert_eq!(expected, datasets);
 }
 
+#[test]
+fn create_and_read_space() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Volume)
+        .volume_size(ONE_MB_IN_BYTES)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+
+    let space = zfs.space(root.clone()).unwrap();
+    assert!(*space.used() >= ONE_MB_IN_BYTES);
+    assert!(*space.available() > 0);
+    assert_eq!(&0, space.used_by_snapshots());
+    assert!(*space.compress_ratio() >= 1.0);
+
+    let snapshot = zfs.space_snapshot(zpool.clone()).unwrap();
+    assert_eq!(Some(&space), snapshot.get(&root));
+
+    let err = zfs.space(format!("{}/nonexistent", zpool)).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+}
+
+#[test]
+fn resize_volume() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let volume = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(volume.clone())
+        .kind(DatasetKind::Volume)
+        .volume_size(ONE_MB_IN_BYTES)
+        .volume_block_size(8192u64)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a volume");
+
+    zfs.resize_volume(volume.clone(), 2 * ONE_MB_IN_BYTES, true).unwrap();
+    let properties = match zfs.read_properties(volume.clone()).unwrap() {
+        Properties::Volume(properties) => properties,
+        other => panic!("not a volume: {:?}", other),
+    };
+    assert_eq!(&(2 * ONE_MB_IN_BYTES), properties.volume_size());
+    assert!(*properties.ref_reservation() >= 2 * ONE_MB_IN_BYTES);
+
+    let unaligned = ONE_MB_IN_BYTES + 512;
+    let err = zfs.resize_volume(volume.clone(), unaligned, true).unwrap_err();
+    let expected = ValidationError::UnalignedVolumeSize(volume.clone(), unaligned, 8192);
+    assert_eq!(Error::from(expected), err);
+
+    let err = zfs.resize_volume(zpool.clone(), 2 * ONE_MB_IN_BYTES, true).unwrap_err();
+    assert_eq!(Error::from(ValidationError::NotAVolume(PathBuf::from(zpool))), err);
+
+    zfs.destroy(volume).unwrap();
+}
+
+#[test]
+fn read_snapshot_info() {
+    use std::time::{Duration, SystemTime};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    let snapshot = PathBuf::from(format!("{}@info", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).unwrap();
+
+    let info = zfs.snapshot_info(snapshot.clone()).unwrap();
+    let age = SystemTime::now().duration_since(*info.creation()).unwrap();
+    assert!(age < Duration::from_secs(600));
+    assert_ne!(&0, info.guid());
+    assert!(*info.referenced() > 0);
+    if let Properties::Snapshot(properties) = zfs.read_properties(&snapshot).unwrap() {
+        assert_eq!(properties.guid(), &Some(*info.guid()));
+    } else {
+        panic!("Read not snapshot properties");
+    }
+
+    assert_eq!(&snapshot, info.name());
+
+    let err = zfs.snapshot_info(format!("{}@nope", root.display())).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+    let err = zfs.snapshot_info(root.clone()).unwrap_err();
+    assert_eq!(ErrorKind::ValidationErrors, err.kind());
+
+    zfs.destroy(snapshot).unwrap();
+    zfs.destroy(root).unwrap();
+}
+
+#[test]
+fn list_snapshots_by_creation() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let child = root.join("child");
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    zfs.create(CreateDatasetRequest::filesystem(child.clone()).build().unwrap()).unwrap();
+    // Names sort the other way around, so order can only come from creation.
+    let names = ["c", "b", "a"];
+    for name in &names {
+        let snapshots = [
+            PathBuf::from(format!("{}@{}", root.display(), name)),
+            PathBuf::from(format!("{}@{}", child.display(), name)),
+        ];
+        zfs.snapshot(&snapshots, None).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+    }
+
+    let snapshots = zfs.list_snapshots_by_creation(root.clone(), false).unwrap();
+    let expected: Vec<PathBuf> =
+        names.iter().map(|n| PathBuf::from(format!("{}@{}", root.display(), n))).collect();
+    let found: Vec<PathBuf> = snapshots.iter().map(|s| s.name().clone()).collect();
+    assert_eq!(expected, found);
+    assert!(snapshots.windows(2).all(|w| w[0].creation() <= w[1].creation()));
+
+    let snapshots = zfs.list_snapshots_by_creation(root.clone(), true).unwrap();
+    assert_eq!(6, snapshots.len());
+    assert!(snapshots.windows(2).all(|w| w[0].creation() <= w[1].creation()));
+}
+
+#[test]
+fn latest_and_common_snapshot() {
+    use std::io::{Seek, SeekFrom};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let src = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(src.clone()).build().unwrap()).unwrap();
+    assert_eq!(None, zfs.latest_snapshot(src.clone()).unwrap());
+
+    let snapshot = |name: &str| PathBuf::from(format!("{}@{}", src.display(), name));
+    zfs.snapshot(&[snapshot("a")], None).unwrap();
+    let mut tmpfile = tempfile::tempfile().unwrap();
+    zfs.send_full(snapshot("a"), tmpfile.try_clone().unwrap(), SendFlags::empty()).unwrap();
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+    let dst = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let options = ReceiveOptions::builder().unmounted(true).build().unwrap();
+    zfs.receive(dst.clone(), tmpfile, options).unwrap();
+
+    zfs.snapshot(&[snapshot("b")], None).unwrap();
+    assert_eq!(Some(snapshot("b")), zfs.latest_snapshot(src.clone()).unwrap());
+    assert_eq!(Some(snapshot("a")), zfs.common_snapshot(src.clone(), dst.clone()).unwrap());
+
+    let unrelated = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(unrelated.clone()).build().unwrap()).unwrap();
+    zfs.snapshot(&[PathBuf::from(format!("{}@a", unrelated.display()))], None).unwrap();
+    assert_eq!(None, zfs.common_snapshot(src, unrelated).unwrap());
+}
+
+#[test]
+fn receive_onto_modified_destination() {
+    use std::io::{Seek, SeekFrom};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let src = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(src.clone()).build().unwrap()).unwrap();
+    let base = PathBuf::from(format!("{}@base", src.display()));
+    let next = PathBuf::from(format!("{}@next", src.display()));
+    zfs.snapshot(&[base.clone()], None).unwrap();
+    zfs.snapshot(&[next.clone()], None).unwrap();
+
+    let mut full = tempfile::tempfile().unwrap();
+    zfs.send_full(base.clone(), full.try_clone().unwrap(), SendFlags::empty()).unwrap();
+    full.seek(SeekFrom::Start(0)).unwrap();
+    let dst = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let options = ReceiveOptions::builder().unmounted(true).build().unwrap();
+    zfs.receive(dst.clone(), full, options).unwrap();
+    let out = std::process::Command::new("zfs").arg("mount").arg(&dst).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    fs::write(Path::new("/").join(&dst).join("oops"), b"written by mistake").unwrap();
+
+    let mut incremental = tempfile::tempfile().unwrap();
+    let fd = incremental.try_clone().unwrap();
+    zfs.send_incremental(next, base, fd, SendFlags::empty()).unwrap();
+    incremental.seek(SeekFrom::Start(0)).unwrap();
+    let err = zfs
+        .receive(dst.clone(), incremental.try_clone().unwrap(), ReceiveOptions::default())
+        .unwrap_err();
+    assert_eq!(Error::DestinationModified(dst.clone()), err);
+
+    incremental.seek(SeekFrom::Start(0)).unwrap();
+    let options = ReceiveOptions::builder().force(true).build().unwrap();
+    zfs.receive(dst.clone(), incremental, options).unwrap();
+    assert!(zfs.exists(format!("{}@next", dst.display())).unwrap());
+}
+
+#[test]
+fn create_and_resolve_zvol_device() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let volume = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(volume.clone())
+        .kind(DatasetKind::Volume)
+        .volume_size(ONE_MB_IN_BYTES)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a volume");
+
+    // udev/devfs might need a moment to create the node.
+    let mut device = None;
+    for _ in 0..50 {
+        device = zfs.zvol_device_path(volume.clone()).unwrap();
+        if device.is_some() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert_eq!(Some(Path::new("/dev/zvol").join(&volume)), device);
+
+    let out = std::process::Command::new("zfs")
+        .arg("set")
+        .arg("volmode=none")
+        .arg(&volume)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(None, zfs.zvol_device_path(volume).unwrap());
+
+    let filesystem = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(filesystem.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a filesystem");
+    assert_eq!(None, zfs.zvol_device_path(filesystem).unwrap());
+}
+
+#[test]
+fn create_and_unmount_all() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+
+    let mut expected = vec![root.clone()];
+    for idx in 0..3 {
+        let mut path = root.clone();
+        path.push(format!("{}", idx));
+        let request = CreateDatasetRequest::builder()
+            .name(path.clone())
+            .kind(DatasetKind::Filesystem)
+            .build()
+            .unwrap();
+        zfs.create(request).expect("Failed to create a dataset");
+        expected.push(path);
+    }
+    let zfs_open3 = ZfsOpen3::new();
+    for dataset in &expected {
+        let out = std::process::Command::new("zfs").arg("mount").arg(dataset).output().unwrap();
+        assert!(out.status.success());
+    }
+
+    zfs.unmount_all(root, false).unwrap();
+
+    for dataset in expected {
+        if let Properties::Filesystem(properties) = zfs_open3.read_properties(&dataset).unwrap() {
+            assert!(!properties.mounted());
+        } else {
+            panic!("Read not fs properties");
+        }
+    }
+}
+
 #[test]
 fn easy_snapshot_and_bookmark() {
     let zpool = SHARED_ZPOOL.clone();
@@ -289,6 +704,70 @@ fn easy_snapshot_and_bookmark() {
     assert!(bookmarks.is_empty())
 }
 
+#[test]
+fn bookmark_from_bookmark() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    let snapshot = PathBuf::from(format!("{}@snap", root.display()));
+    let first = PathBuf::from(format!("{}#first", root.display()));
+    let second = PathBuf::from(format!("{}#second", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).unwrap();
+    zfs.bookmark(&[BookmarkRequest::new(snapshot.clone(), first.clone())]).unwrap();
+    zfs.destroy_snapshots(&[snapshot], DestroyTiming::RightNow).unwrap();
+
+    // Source must be a snapshot or a bookmark.
+    let err = zfs.bookmark(&[BookmarkRequest::new(root.clone(), second.clone())]).unwrap_err();
+    let expected = Error::from(ValidationError::InvalidBookmarkSource(root.clone()));
+    assert_eq!(expected, err);
+
+    match zfs.bookmark(&[BookmarkRequest::new(first.clone(), second.clone())]) {
+        Ok(()) => {
+            let bookmarks = zfs.list_bookmarks(root.clone()).unwrap();
+            assert_eq!(vec![first.clone(), second.clone()], bookmarks);
+            zfs.destroy_bookmarks(&[second]).unwrap();
+        },
+        Err(e) => assert_eq!(ErrorKind::BookmarkFromBookmarkUnsupported, e.kind()),
+    }
+    zfs.destroy_bookmarks(&[first]).unwrap();
+}
+
+#[test]
+fn send_incremental_from_bookmark() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    let first = PathBuf::from(format!("{}@first", root.display()));
+    let second = PathBuf::from(format!("{}@second", root.display()));
+    let bookmark = PathBuf::from(format!("{}#first", root.display()));
+    zfs.snapshot(&[first.clone()], None).unwrap();
+    zfs.bookmark(&[BookmarkRequest::new(first.clone(), bookmark.clone())]).unwrap();
+    zfs.destroy_snapshots(&[first], DestroyTiming::RightNow).unwrap();
+    zfs.snapshot(&[second.clone()], None).unwrap();
+
+    let tmpfile = tempfile::tempfile().unwrap();
+    zfs.send_incremental_from_bookmark(
+        second.clone(),
+        bookmark.clone(),
+        tmpfile.try_clone().unwrap(),
+        SendFlags::empty(),
+    )
+    .unwrap();
+    assert!(tmpfile.metadata().unwrap().len() > 0);
+
+    let other = PathBuf::from(format!("{}/other#first", zpool));
+    let err = zfs
+        .send_incremental_from_bookmark(second.clone(), other.clone(), tmpfile, SendFlags::empty())
+        .unwrap_err();
+    let expected = Error::from(ValidationError::BookmarkOfOtherDataset(other, second.clone()));
+    assert_eq!(expected, err);
+
+    zfs.destroy_snapshots(&[second], DestroyTiming::RightNow).unwrap();
+    zfs.destroy_bookmarks(&[bookmark]).unwrap();
+}
+
 #[test]
 fn read_properties_of_filesystem() {
     let zpool = SHARED_ZPOOL.clone();
@@ -311,6 +790,49 @@ fn read_properties_of_filesystem() {
     }
 }
 
+#[test]
+fn create_with_version() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).version(4).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset with version");
+
+    if let Properties::Filesystem(properties) = zfs.read_properties(&root).unwrap() {
+        assert_eq!(&4, properties.version());
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).version(6).build();
+    let expected = Error::from(ValidationError::InvalidVersion(root, 6));
+    assert_eq!(expected, request.unwrap_err());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_with_selinux_context() {
+    // Context properties are only honored on SELinux-capable kernels.
+    if !Path::new("/sys/fs/selinux").exists() {
+        return;
+    }
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let context = String::from("system_u:object_r:tmp_t:s0");
+    let request =
+        CreateDatasetRequest::filesystem(root.clone()).context(context.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset with context");
+
+    if let Properties::Filesystem(properties) = zfs.read_properties(&root).unwrap() {
+        assert_eq!(&Some(context), properties.context());
+        assert_eq!(&None, properties.fs_context());
+    } else {
+        panic!("Read not fs properties");
+    }
+}
+
 #[test]
 #[cfg(target_os = "freebsd")]
 fn read_properties_of_snapshot_and_bookmark_blessed_os() {
@@ -434,6 +956,336 @@ fn send_snapshot() {
 
     zfs.send_full(snapshot, tmpfile, SendFlags::empty()).unwrap();
 }
+#[test]
+fn create_dataset_out_of_space() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .ref_reservation(1024 * 1024 * ONE_MB_IN_BYTES)
+        .build()
+        .unwrap();
+
+    let err = zfs.create(request).unwrap_err();
+    assert_eq!(Error::OutOfSpace(root.clone()), err);
+    assert!(!zfs.exists(root).unwrap());
+}
+#[test]
+fn snapshot_on_full_pool() {
+    let vdev = setup_vdev("/vdevs/zfs-full/vdev0", &Bytes::MegaBytes(64 + 10));
+    let zpool_name = get_zpool_name();
+    let zpool = ZpoolOpen3::default();
+    let topo = CreateZpoolRequest::builder()
+        .name(zpool_name.clone())
+        .vdev(CreateVdevRequest::SingleDisk(vdev))
+        .create_mode(CreateMode::Force)
+        .build()
+        .unwrap();
+    zpool.create(topo).unwrap();
+
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool_name, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).unwrap();
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..36 * ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(Path::new("/").join(&root).join("data"), data).unwrap();
+    // Snapshot of dataset with refreservation needs as much free space as dataset references.
+    let out = std::process::Command::new("zfs")
+        .arg("set")
+        .arg(format!("refreservation={}", 36 * ONE_MB_IN_BYTES))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let snapshot = PathBuf::from(format!("{}@full", root.display()));
+    let err = zfs.snapshot(&[snapshot.clone()], None).unwrap_err();
+    assert_eq!(ErrorKind::OutOfSpace, err.kind());
+    assert!(!zfs.exists(snapshot).unwrap());
+
+    zpool.destroy(&zpool_name, DestroyMode::Force).unwrap();
+}
+#[test]
+fn send_snapshot_with_progress() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..8 * ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(Path::new("/").join(&root).join("data"), data).unwrap();
+
+    let snapshot = PathBuf::from(format!("{}/{}@tosend", zpool, &root_name));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+
+    let tmpfile = tempfile::tempfile().unwrap();
+    let mut reports = Vec::new();
+    zfs.send_with_progress(
+        snapshot.clone(),
+        None,
+        tmpfile.try_clone().unwrap(),
+        SendFlags::empty(),
+        Some(&mut |sent| reports.push(sent)),
+    )
+    .unwrap();
+    assert!(reports.len() > 1);
+    assert_eq!(Some(&tmpfile.metadata().unwrap().len()), reports.last());
+
+    let tmpfile = tempfile::tempfile().unwrap();
+    zfs.send_with_progress(snapshot, None, tmpfile.try_clone().unwrap(), SendFlags::empty(), None)
+        .unwrap();
+    assert!(tmpfile.metadata().unwrap().len() > 8 * ONE_MB_IN_BYTES);
+}
+#[test]
+fn send_space_and_resume_remaining() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..4 * ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(Path::new("/").join(&root).join("data"), data).unwrap();
+
+    let snapshot = PathBuf::from(format!("{}/{}@tosend", zpool, &root_name));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+    let total = zfs.send_space(snapshot.clone(), None, SendFlags::empty()).unwrap();
+    assert!(total > 4 * ONE_MB_IN_BYTES);
+
+    let stream = tempfile::NamedTempFile::new().unwrap();
+    zfs.send_full(snapshot, stream.reopen().unwrap(), SendFlags::empty()).unwrap();
+    stream.as_file().set_len(total / 2).unwrap();
+
+    let dst = PathBuf::from(format!("{}/{}-recv", zpool, &root_name));
+    let out = std::process::Command::new("zfs")
+        .args(&["receive", "-s"])
+        .arg(&dst)
+        .stdin(stream.reopen().unwrap())
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert_eq!(None, zfs.resume_token(root.clone()).unwrap());
+    let token = zfs.resume_token(dst.clone()).unwrap().expect("No resume token");
+    let missing = PathBuf::from(format!("{}/{}-missing", zpool, &root_name));
+    assert_eq!(ErrorKind::DatasetNotFound, zfs.resume_token(missing).unwrap_err().kind());
+
+    let decoded = zfs.decode_resume_token(&token).unwrap();
+    assert!(*decoded.bytes() > 0);
+    let remaining = zfs.resume_remaining(&token).unwrap();
+    assert!(remaining > 0 && remaining < total);
+
+    // Drop the partially received dataset.
+    let out =
+        std::process::Command::new("zfs").args(&["receive", "-A"]).arg(&dst).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+}
+#[test]
+fn send_replication_stream() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let child = root.join("child");
+    for name in &[&root, &child] {
+        let request = CreateDatasetRequest::builder()
+            .name(PathBuf::from(name))
+            .kind(DatasetKind::Filesystem)
+            .build()
+            .unwrap();
+        zfs.create(request).expect("Failed to create a dataset");
+    }
+    let snapshots = vec![
+        PathBuf::from(format!("{}@first", root.display())),
+        PathBuf::from(format!("{}@first", child.display())),
+    ];
+    zfs.snapshot(&snapshots, None).expect("Failed to create snapshots");
+
+    let stream_path = format!("/tmp/{}.zstream", root_name);
+    let stream = fs::File::create(&stream_path).unwrap();
+    let skipped =
+        zfs.send_replication(snapshots[0].clone(), None, stream, SendFlags::empty(), false).unwrap();
+    assert!(skipped.is_empty());
+
+    let dst = PathBuf::from(format!("{}/{}-recv", zpool, &root_name));
+    let out = std::process::Command::new("zfs")
+        .args(&["receive", "-u"])
+        .arg(&dst)
+        .stdin(fs::File::open(&stream_path).unwrap())
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(zfs.exists(dst.join("child")).unwrap());
+    assert!(zfs.exists(format!("{}/child@first", dst.display())).unwrap());
+    fs::remove_file(&stream_path).unwrap();
+}
+
+#[test]
+fn send_replication_skip_missing() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let with_snapshot = root.join("with");
+    let without_snapshot = root.join("without");
+    for name in &[&root, &with_snapshot, &without_snapshot] {
+        let request = CreateDatasetRequest::filesystem(PathBuf::from(name)).build().unwrap();
+        zfs.create(request).expect("Failed to create a dataset");
+    }
+    let snapshots = vec![
+        PathBuf::from(format!("{}@first", root.display())),
+        PathBuf::from(format!("{}@first", with_snapshot.display())),
+    ];
+    zfs.snapshot(&snapshots, None).expect("Failed to create snapshots");
+
+    let stream_path = format!("/tmp/{}.zstream", root_name);
+    let stream = fs::File::create(&stream_path).unwrap();
+    let skipped =
+        zfs.send_replication(snapshots[0].clone(), None, stream, SendFlags::empty(), true).unwrap();
+    assert_eq!(vec![without_snapshot], skipped);
+
+    let dst = PathBuf::from(format!("{}/{}-recv", zpool, &root_name));
+    let out = std::process::Command::new("zfs")
+        .args(&["receive", "-u"])
+        .arg(&dst)
+        .stdin(fs::File::open(&stream_path).unwrap())
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(zfs.exists(format!("{}/with@first", dst.display())).unwrap());
+    assert!(!zfs.exists(dst.join("without")).unwrap());
+    fs::remove_file(&stream_path).unwrap();
+}
+
+#[test]
+fn destroy_snapshot_range_with_holds() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let snapshot = |name: &str| PathBuf::from(format!("{}@{}", root.display(), name));
+    for name in &["s1", "s2", "s3", "s4"] {
+        zfs.snapshot(&[snapshot(name)], None).expect("Failed to create snapshot");
+    }
+    let held = snapshot("s2");
+    let out =
+        std::process::Command::new("zfs").args(&["hold", "keep"]).arg(&held).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let err = zfs
+        .destroy_snapshot_range(root.clone(), None, Some("s3"), DestroyTiming::RightNow)
+        .unwrap_err();
+    assert_eq!(ErrorKind::SnapshotsHeld, err.kind());
+    if let Error::SnapshotsHeld(holds) = err {
+        assert_eq!(1, holds.len());
+        assert_eq!(Some(&vec![String::from("keep")]), holds.get(&held));
+    }
+    assert!(zfs.exists(snapshot("s1")).unwrap());
+
+    zfs.destroy_snapshot_range(root.clone(), None, Some("s3"), DestroyTiming::Defer).unwrap();
+    assert!(!zfs.exists(snapshot("s1")).unwrap());
+    assert!(zfs.exists(held.clone()).unwrap());
+    assert!(!zfs.exists(snapshot("s3")).unwrap());
+    assert!(zfs.exists(snapshot("s4")).unwrap());
+
+    let out =
+        std::process::Command::new("zfs").args(&["release", "keep"]).arg(&held).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!zfs.exists(held).unwrap());
+
+    zfs.destroy_snapshot_range(root.clone(), Some("s4"), None, DestroyTiming::RightNow).unwrap();
+    assert!(!zfs.exists(snapshot("s4")).unwrap());
+}
+
+#[test]
+fn destroy_snapshots_reports_deferred() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let free = PathBuf::from(format!("{}@free", root.display()));
+    let held = PathBuf::from(format!("{}@held", root.display()));
+    zfs.snapshot(&[free.clone(), held.clone()], None).expect("Failed to create snapshots");
+    let out =
+        std::process::Command::new("zfs").args(&["hold", "keep"]).arg(&held).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let snapshots = [free.clone(), held.clone()];
+    let report = zfs.destroy_snapshots_with_report(&snapshots, DestroyTiming::Defer).unwrap();
+    assert_eq!(&vec![free], report.destroyed());
+    assert_eq!(&vec![held.clone()], report.deferred());
+    assert!(!report.is_complete());
+    if let Properties::Snapshot(properties) = zfs.read_properties(&held).unwrap() {
+        assert!(*properties.defer_destroy());
+    } else {
+        panic!("Read not snapshot properties");
+    }
+
+    let out =
+        std::process::Command::new("zfs").args(&["release", "keep"]).arg(&held).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!zfs.exists(held).unwrap());
+}
+
+#[test]
+fn replicate_once_to_pipe() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@once", root.display()));
+
+    let mut reader = std::process::Command::new("wc")
+        .arg("-c")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pipe = reader.stdin.take().unwrap();
+    zfs.replicate_once(root.clone(), "once", pipe, true, SendFlags::empty()).unwrap();
+    let out = reader.wait_with_output().unwrap();
+    let sent: u64 = String::from_utf8_lossy(&out.stdout).trim().parse().unwrap();
+    assert!(sent > 0);
+    assert!(!zfs.exists(snapshot.clone()).unwrap());
+
+    // Snapshot is kept without cleanup.
+    let sink = fs::File::create("/dev/null").unwrap();
+    zfs.replicate_once(root.clone(), "once", sink, false, SendFlags::empty()).unwrap();
+    assert!(zfs.exists(snapshot.clone()).unwrap());
+    zfs.destroy_snapshots(&[snapshot.clone()], DestroyTiming::RightNow).unwrap();
+
+    // Failed send still cleans up.
+    let read_only = fs::File::open("/dev/null").unwrap();
+    assert!(zfs.replicate_once(root, "once", read_only, true, SendFlags::empty()).is_err());
+    assert!(!zfs.exists(snapshot).unwrap());
+}
+
 #[test]
 fn send_snapshot_incremental() {
     let zpool = SHARED_ZPOOL.clone();
@@ -460,4 +1312,497 @@ fn send_snapshot_incremental() {
     let tmpfile = tempfile::tempfile().unwrap();
 
     zfs.send_incremental(snapshot, src_snapshot, tmpfile, SendFlags::empty()).unwrap();
-}
\ No newline at end of file
+}
+
+#[test]
+fn receive_incremental_as_clone() {
+    use std::io::{Seek, SeekFrom};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let base = PathBuf::from(format!("{}@base", root.display()));
+    let next = PathBuf::from(format!("{}@next", root.display()));
+    zfs.snapshot(&[base.clone()], None).expect("Failed to create snapshots");
+    zfs.snapshot(&[next.clone()], None).expect("Failed to create snapshots");
+
+    let mut tmpfile = tempfile::tempfile().unwrap();
+    let fd = tmpfile.try_clone().unwrap();
+    zfs.send_incremental(next, base.clone(), fd, SendFlags::empty()).unwrap();
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let options = ReceiveOptions::builder().origin(base).unmounted(true).build().unwrap();
+    zfs.receive(clone.clone(), tmpfile.try_clone().unwrap(), options).unwrap();
+    assert!(zfs.exists(format!("{}@next", clone.display())).unwrap());
+
+    // Stream isn't based on the snapshot of unrelated dataset.
+    let other = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(other.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let other_base = PathBuf::from(format!("{}@base", other.display()));
+    zfs.snapshot(&[other_base.clone()], None).expect("Failed to create snapshots");
+
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+    let mismatched = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let options = ReceiveOptions::builder().origin(other_base).build().unwrap();
+    let err = zfs.receive(mismatched.clone(), tmpfile, options).unwrap_err();
+    assert_eq!(ErrorKind::IncrementalSourceMismatch, err.kind());
+    assert!(!zfs.exists(mismatched).unwrap());
+}
+
+#[test]
+fn raw_send_receive_of_encrypted_dataset() {
+    use std::io::{Seek, SeekFrom};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    // Removed when dropped at the end of the test, key is needed until `load_key`.
+    let key_file = tempfile::NamedTempFile::new().unwrap();
+    let key_location = format!("file://{}", key_file.path().display());
+    fs::write(key_file.path(), "0".repeat(64)).unwrap();
+    let out = std::process::Command::new("zfs")
+        .args(&["create", "-o", "encryption=on", "-o", "keyformat=hex"])
+        .arg("-o")
+        .arg(format!("keylocation={}", key_location))
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let snapshot = PathBuf::from(format!("{}@raw", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).unwrap();
+
+    let mut tmpfile = tempfile::tempfile().unwrap();
+    let fd = tmpfile.try_clone().unwrap();
+    zfs.send_full(snapshot, fd, SendFlags::LZC_SEND_FLAG_RAW).unwrap();
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+
+    // No key is needed to receive raw stream.
+    let restored = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let options = ReceiveOptions::builder().key_location(key_location.clone()).build().unwrap();
+    zfs.receive(restored.clone(), tmpfile, options).unwrap();
+    if let Properties::Filesystem(properties) = zfs.read_properties(&restored).unwrap() {
+        assert_ne!(&Encryption::Off, properties.encryption());
+        assert_eq!(&Some(restored.clone()), properties.encryption_root());
+        assert!(!properties.mounted());
+    } else {
+        panic!("Read not fs properties");
+    }
+    let key_status = |name: &Path| {
+        let out = std::process::Command::new("zfs")
+            .args(&["get", "-H", "-o", "value", "keystatus,keylocation"])
+            .arg(name)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    assert_eq!(format!("unavailable\n{}\n", key_location), key_status(&restored));
+
+    zfs.load_key(restored.clone(), None).unwrap();
+    assert_eq!(format!("available\n{}\n", key_location), key_status(&restored));
+
+    zfs.destroy(restored).unwrap();
+}
+
+#[test]
+fn receive_dry_run_reports_streams() {
+    use std::io::{Seek, SeekFrom};
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@full", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+
+    let mut tmpfile = tempfile::tempfile().unwrap();
+    zfs.send_full(snapshot.clone(), tmpfile.try_clone().unwrap(), SendFlags::empty()).unwrap();
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+
+    let dst = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let fd = tmpfile.try_clone().unwrap();
+    let streams = zfs.receive_dry_run(dst.clone(), fd, ReceiveOptions::default()).unwrap();
+    assert_eq!(1, streams.len());
+    assert_eq!(&StreamKind::Full, streams[0].kind());
+    assert_eq!(&snapshot, streams[0].source());
+    assert_eq!(&PathBuf::from(format!("{}@full", dst.display())), streams[0].destination());
+    assert!(!zfs.exists(dst.clone()).unwrap());
+
+    // Actual receive works with the same stream after the dry run.
+    tmpfile.seek(SeekFrom::Start(0)).unwrap();
+    zfs.receive(dst.clone(), tmpfile, ReceiveOptions::default()).unwrap();
+    assert!(zfs.exists(format!("{}@full", dst.display())).unwrap());
+}
+
+#[test]
+fn upgrade_up_to_date_dataset() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+
+    zfs.upgrade_dataset(root.clone(), false).unwrap();
+    zfs.upgrade_dataset(root, true).unwrap();
+}
+
+#[test]
+fn share_without_share_property() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+
+    for protocol in &[ShareProtocol::Nfs, ShareProtocol::Smb, ShareProtocol::All] {
+        let err = zfs.share(root.clone(), *protocol).unwrap_err();
+        assert_eq!(Error::ShareNotEnabled(root.clone()), err);
+    }
+}
+
+#[test]
+fn allow_and_unallow_permissions() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    assert!(zfs.list_permissions(root.clone()).unwrap().is_empty());
+
+    let root_user = Delegation::User(String::from("root"));
+    zfs.allow(root.clone(), root_user.clone(), &["mount", "snapshot"]).unwrap();
+    zfs.allow(root.clone(), Delegation::PermissionSet(String::from("backup")), &["send"]).unwrap();
+
+    let permissions = zfs.list_permissions(root.clone()).unwrap();
+    assert_eq!(1, permissions.len());
+    assert_eq!(&root, permissions[0].dataset());
+    assert_eq!(Some(&vec![String::from("send")]), permissions[0].sets().get("backup"));
+    let expected = vec![String::from("mount"), String::from("snapshot")];
+    assert_eq!(&vec![(root_user.clone(), expected)], permissions[0].local_descendent());
+
+    zfs.unallow(root.clone(), root_user, &[]).unwrap();
+    let permissions = zfs.list_permissions(root.clone()).unwrap();
+    assert!(permissions[0].local_descendent().is_empty());
+}
+
+#[test]
+fn read_clones_of_snapshot() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@origin", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    assert!(zfs.clones(snapshot.clone()).unwrap().is_empty());
+
+    let expected = vec![root.join("clone-a"), root.join("clone-b")];
+    for clone in &expected {
+        let out = std::process::Command::new("zfs")
+            .arg("clone")
+            .arg(&snapshot)
+            .arg(clone)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    }
+
+    let mut clones = zfs.clones(snapshot).unwrap();
+    clones.sort();
+    assert_eq!(expected, clones);
+}
+
+#[test]
+fn read_properties_with_source() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).atime(false).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let child = root.join("child");
+    let request = CreateDatasetRequest::filesystem(child.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+
+    let props = zfs.read_properties_with_source(root.clone()).unwrap();
+    assert_eq!("off", props["atime"].value());
+    assert_eq!(&PropertySource::Local, props["atime"].source());
+    assert_eq!(&PropertySource::Default, props["snapdir"].source());
+    assert_eq!(&PropertySource::None, props["used"].source());
+
+    let props = zfs.read_properties_with_source(child).unwrap();
+    assert_eq!("off", props["atime"].value());
+    assert_eq!(&PropertySource::Inherited(root), props["atime"].source());
+
+    let missing = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let err = zfs.read_properties_with_source(missing).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+}
+
+#[test]
+fn bookmark_kind_and_list() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    let snapshot = PathBuf::from(format!("{}@kind", root.display()));
+    let bookmark = PathBuf::from(format!("{}#kind", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).unwrap();
+    zfs.bookmark(&[BookmarkRequest::new(snapshot.clone(), bookmark.clone())]).unwrap();
+
+    assert_eq!(Ok(true), zfs.exists(bookmark.clone()));
+    assert_eq!(Some(DatasetKind::Bookmark), zfs.kind_of(bookmark.clone()).unwrap());
+    assert_eq!(Some(DatasetKind::Bookmark), ZfsOpen3::new().kind_of(bookmark.clone()).unwrap());
+    let expected = vec![
+        (DatasetKind::Filesystem, root.clone()),
+        (DatasetKind::Bookmark, bookmark.clone()),
+        (DatasetKind::Snapshot, snapshot.clone()),
+    ];
+    let mut listed = zfs.list(root.clone()).unwrap();
+    listed.sort_by(|a, b| a.1.cmp(&b.1));
+    assert_eq!(expected, listed);
+
+    zfs.destroy_bookmarks(&[bookmark.clone()]).unwrap();
+    assert_eq!(Ok(false), zfs.exists(bookmark.clone()));
+    assert_eq!(None, zfs.kind_of(bookmark).unwrap());
+    zfs.destroy(snapshot).unwrap();
+    zfs.destroy(root).unwrap();
+}
+
+#[test]
+fn replicate_full_then_incremental() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let source = root.join("source");
+    let dest = root.join("dest");
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    zfs.create(CreateDatasetRequest::filesystem(source.clone()).build().unwrap()).unwrap();
+
+    let err = zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), false);
+    assert_eq!(ErrorKind::NoSnapshots, err.unwrap_err().kind());
+
+    let first = PathBuf::from(format!("{}@first", source.display()));
+    zfs.snapshot(&[first.clone()], None).unwrap();
+    let report =
+        zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), true).unwrap();
+    assert_eq!(&first, report.snapshot());
+    assert_eq!(&PathBuf::from(format!("{}@first", dest.display())), report.received());
+    assert_eq!(&None, report.from());
+    assert!(*report.bytes() > 0);
+    let bookmark = PathBuf::from(format!("{}#first", source.display()));
+    assert_eq!(&Some(bookmark.clone()), report.bookmark());
+    assert_eq!(Some(DatasetKind::Bookmark), zfs.kind_of(bookmark).unwrap());
+    assert!(zfs.exists(report.received().clone()).unwrap());
+
+    let report =
+        zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), false).unwrap();
+    assert!(report.is_up_to_date());
+    assert_eq!(&0, report.bytes());
+
+    let second = PathBuf::from(format!("{}@second", source.display()));
+    zfs.snapshot(&[second.clone()], None).unwrap();
+    let report =
+        zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), false).unwrap();
+    assert_eq!(&second, report.snapshot());
+    assert_eq!(&Some(first), report.from());
+    assert_eq!(&None, report.bookmark());
+    assert_eq!(2, zfs.list_snapshots(dest).unwrap().len());
+}
+
+#[test]
+fn replicate_from_bookmark_after_snapshot_is_destroyed() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let source = root.join("source");
+    let dest = root.join("dest");
+    zfs.create(CreateDatasetRequest::filesystem(root.clone()).build().unwrap()).unwrap();
+    zfs.create(CreateDatasetRequest::filesystem(source.clone()).build().unwrap()).unwrap();
+
+    let first = PathBuf::from(format!("{}@first", source.display()));
+    zfs.snapshot(&[first.clone()], None).unwrap();
+    let report =
+        zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), true).unwrap();
+    let bookmark = report.bookmark().clone().expect("bookmark wasn't created");
+    zfs.destroy_snapshots(&[first], DestroyTiming::RightNow).unwrap();
+
+    let second = PathBuf::from(format!("{}@second", source.display()));
+    zfs.snapshot(&[second.clone()], None).unwrap();
+    let report =
+        zfs.replicate(source.clone(), &zfs, dest.clone(), SendFlags::empty(), true).unwrap();
+    assert_eq!(&second, report.snapshot());
+    assert_eq!(&Some(bookmark), report.from());
+    assert_eq!(2, zfs.list_snapshots(dest).unwrap().len());
+}
+
+#[test]
+fn reclaimable_space_of_snapshots() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let snapshot = |name: &str| PathBuf::from(format!("{}@{}", root.display(), name));
+    let data_path = Path::new("/").join(&root).join("data");
+
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..2 * ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(&data_path, data).unwrap();
+    zfs.snapshot(&[snapshot("s1"), snapshot("s2")], None).expect("Failed to create snapshots");
+    fs::remove_file(&data_path).unwrap();
+    zfs.snapshot(&[snapshot("s3")], None).expect("Failed to create snapshot");
+
+    // Data is shared by s1 and s2, destroying just one of them frees nothing.
+    let one = zfs.reclaimable_space(root.clone(), &[snapshot("s1")]).unwrap();
+    let both = zfs.reclaimable_space(root.clone(), &[snapshot("s1"), snapshot("s2")]).unwrap();
+    assert!(one < ONE_MB_IN_BYTES);
+    assert!(both > 2 * ONE_MB_IN_BYTES);
+    assert!(zfs.exists(snapshot("s1")).unwrap());
+    assert_eq!(0, zfs.reclaimable_space(root.clone(), &[]).unwrap());
+
+    let other = PathBuf::from(format!("{}/other@s1", zpool));
+    let err = zfs.reclaimable_space(root.clone(), &[other.clone()]).unwrap_err();
+    assert_eq!(Error::from(ValidationError::SnapshotOfOtherDataset(other, root)), err);
+}
+
+#[test]
+fn create_with_mount_properties() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let name = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let mount_point = PathBuf::from(format!("/tmp/{}-mnt", get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(name.clone())
+        .can_mount(CanMount::NoAuto)
+        .mount_point(MountPoint::Path(mount_point.clone()))
+        .overlay(true)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+
+    if let Properties::Filesystem(properties) = zfs.read_properties(&name).unwrap() {
+        assert_eq!(&CanMount::NoAuto, properties.can_mount());
+        assert_eq!(&Some(mount_point), properties.mount_point());
+        assert_eq!(&Some(true), properties.overlay());
+        assert!(!properties.mounted());
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    let legacy = name.join("legacy");
+    let request =
+        CreateDatasetRequest::filesystem(legacy.clone()).mount_point(MountPoint::Legacy).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    if let Properties::Filesystem(properties) = zfs.read_properties(&legacy).unwrap() {
+        assert_eq!(&Some(PathBuf::from("legacy")), properties.mount_point());
+    } else {
+        panic!("Read not fs properties");
+    }
+}
+
+#[test]
+fn written_since_snapshot() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let snapshot = PathBuf::from(format!("{}@base", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+    assert_eq!(0, zfs.written_since(root.clone(), "base").unwrap());
+
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(Path::new("/").join(&root).join("data"), data).unwrap();
+    let out = std::process::Command::new("zpool").arg("sync").arg(&zpool).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(zfs.written_since(root.clone(), "base").unwrap() >= ONE_MB_IN_BYTES);
+    let full_name = snapshot.to_str().unwrap();
+    assert!(zfs.written_since(root.clone(), full_name).unwrap() >= ONE_MB_IN_BYTES);
+
+    let err = zfs.written_since(root.clone(), "missing").unwrap_err();
+    assert_eq!(Error::DatasetNotFound(PathBuf::from(format!("{}@missing", root.display()))), err);
+}
+
+#[test]
+fn set_property_on_many_datasets() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let first = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let second = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let missing = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    for name in &[&first, &second] {
+        let request = CreateDatasetRequest::filesystem((*name).clone()).build().unwrap();
+        zfs.create(request).expect("Failed to create dataset");
+    }
+
+    let datasets = vec![first.clone(), missing.clone(), second.clone()];
+    let result = zfs.set_property_many(&datasets, "atime", "off").unwrap();
+    assert_eq!(3, result.len());
+    assert_eq!((first.clone(), Ok(())), result[0]);
+    assert_eq!((missing.clone(), Err(Error::DatasetNotFound(missing))), result[1]);
+    assert_eq!((second.clone(), Ok(())), result[2]);
+    for name in &[first, second] {
+        if let Properties::Filesystem(props) = zfs.read_properties(name).unwrap() {
+            assert!(!props.atime());
+        } else {
+            panic!("Read not fs properties");
+        }
+    }
+
+    let err = zfs.set_property_many(&datasets, "", "off").unwrap_err();
+    assert_eq!(ErrorKind::Io, err.kind());
+}
+
+/// FNV-1a, just to have something to compare the stream against.
+struct Fnv(u64);
+
+impl libzetta::zfs::StreamHasher for Fnv {
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&mut self) -> Vec<u8> { self.0.to_be_bytes().to_vec() }
+}
+
+#[test]
+fn send_snapshot_with_digest() {
+    use libzetta::zfs::StreamHasher;
+
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::filesystem(root.clone()).build().unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    let out = std::process::Command::new("zfs").arg("mount").arg(&root).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..2 * ONE_MB_IN_BYTES).map(|_| rng.gen()).collect();
+    fs::write(Path::new("/").join(&root).join("data"), data).unwrap();
+    let snapshot = PathBuf::from(format!("{}@tosend", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+
+    let path = std::env::temp_dir().join(format!("{}.zstream", get_dataset_name()));
+    let file = fs::File::create(&path).unwrap();
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    let sent = zfs.send_with_digest(snapshot, None, file, SendFlags::empty(), &mut hasher).unwrap();
+
+    let written = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(written.len() as u64, *sent.size());
+    let mut expected = Fnv(0xcbf2_9ce4_8422_2325);
+    expected.update(&written);
+    assert_eq!(&expected.finish(), sent.digest());
+    assert_eq!(16, sent.hex_digest().len());
+}
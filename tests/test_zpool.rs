@@ -6,15 +6,18 @@ use slog_term;
 use std::{fs::{self, DirBuilder},
           panic,
           path::{Path, PathBuf},
-          sync::Mutex};
+          process::Command,
+          sync::Mutex,
+          time::Duration};
 
 use cavity::{fill, Bytes, WriteMode};
 use rand::Rng;
 
 use libzetta::{slog::*,
-               zpool::{CreateMode, CreateVdevRequest, CreateZpoolRequestBuilder, DestroyMode,
-                       ExportMode, FailMode, Health, OfflineMode, OnlineMode, Zpool, ZpoolEngine,
-                       ZpoolError, ZpoolErrorKind, ZpoolOpen3, ZpoolPropertiesWriteBuilder}};
+               zpool::{CacheType, ClearMode, CreateMode, CreateVdevRequest, CreateZpoolRequestBuilder,
+                       DestroyMode, ExportMode, FailMode, Health, ImportOptions, OfflineMode,
+                       OnlineMode, TopologyError, WaitActivity, Zpool, ZpoolEngine, ZpoolError,
+                       ZpoolErrorKind, ZpoolOpen3, ZpoolPropertiesWriteBuilder}};
 
 static ZPOOL_NAME_PREFIX: &'static str = "tests-zpool-";
 lazy_static! {
@@ -140,6 +143,14 @@ fn create_check_update_delete() {
         assert_eq!(&None, props.comment());
         assert_eq!(&true, props.delegation());
 
+        let updated_props = ZpoolPropertiesWriteBuilder::from_props(&props)
+            .comment("   ")
+            .build()
+            .unwrap();
+        zpool.update_properties(&name, updated_props).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(&None, props.comment());
+
         zpool.destroy(&name, DestroyMode::Force).unwrap();
 
         let result = zpool.exists(&name).unwrap();
@@ -147,6 +158,45 @@ fn create_check_update_delete() {
     })
 }
 
+#[test]
+fn update_properties_round_trip() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let props = ZpoolPropertiesWriteBuilder::default()
+            .auto_expand(true)
+            .auto_trim(true)
+            .comment("round trip")
+            .fail_mode(FailMode::Continue)
+            .build()
+            .unwrap();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .props(props)
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let before = zpool.read_properties(&name).unwrap();
+        let unchanged = ZpoolPropertiesWriteBuilder::from_props(&before).build().unwrap();
+        zpool.update_properties(&name, unchanged.clone()).unwrap();
+        let after = zpool.read_properties(&name).unwrap();
+
+        let rebuilt = ZpoolPropertiesWriteBuilder::from_props(&after).build().unwrap();
+        assert_eq!(unchanged, rebuilt);
+        assert_eq!(before.guid(), after.guid());
+        assert_eq!(before.health(), after.health());
+        assert_eq!(before.alt_root(), after.alt_root());
+        assert_eq!(before.dedup_ditto(), after.dedup_ditto());
+        assert_eq!(&Some(String::from("round trip")), after.comment());
+        assert_eq!(&FailMode::Continue, after.fail_mode());
+        assert!(after.auto_expand());
+        assert!(after.auto_trim());
+
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+    });
+}
+
 #[test]
 fn cmd_not_found() {
     run_test(|name| {
@@ -192,13 +242,57 @@ fn reuse_vdev() {
         let err = result.unwrap_err();
         assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
         println!("{:?}", &err);
-        if let ZpoolError::VdevReuse(vdev, pool) = err {
-            assert_eq!(vdev_file, vdev);
-            assert_eq!(name_1, pool);
+        if let ZpoolError::VdevReuse(vdevs) = err {
+            assert_eq!(vec![(String::from(vdev_file), name_1.clone())], vdevs);
         }
         zpool.destroy(&name_1, DestroyMode::Force).unwrap();
     });
 }
+#[test]
+fn create_returning_guid() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+
+        let created = zpool.create_returning_guid(topo).unwrap();
+        assert_eq!(&name, created.name());
+        assert_eq!(zpool.read_properties(&name).unwrap().guid(), created.guid());
+        assert_ne!(&0, created.guid());
+        assert!(zpool.exists_by_guid(*created.guid()).unwrap());
+
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+        assert!(!zpool.exists_by_guid(*created.guid()).unwrap());
+    });
+}
+
+#[test]
+fn read_properties_full() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let props = zpool.read_properties(&name).unwrap();
+        let full = zpool.read_properties_full(&name).unwrap();
+        assert_eq!(props.guid(), full.properties().guid());
+        assert_eq!(props.health(), full.properties().health());
+        assert_eq!(props.fail_mode(), full.properties().fail_mode());
+        assert!(!full.features().is_empty());
+        assert!(full.is_feature_enabled("async_destroy"));
+
+        let result = zpool.read_properties_full("fake-pool");
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+    });
+}
+
 #[test]
 fn create_invalid_topo() {
     let zpool = ZpoolOpen3::default();
@@ -214,6 +308,29 @@ fn create_invalid_topo() {
 
     let err = result.unwrap_err();
     assert_eq!(ZpoolErrorKind::InvalidTopology, err.kind());
+    if let ZpoolError::InvalidTopology(reason) = err {
+        assert_eq!(TopologyError::CacheWithoutData, reason);
+    }
+}
+
+#[test]
+fn create_relative_altroot() {
+    let zpool = ZpoolOpen3::default();
+    let name = get_zpool_name();
+
+    let topo = CreateZpoolRequestBuilder::default()
+        .name(name.clone())
+        .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+        .altroot(PathBuf::from("mnt"))
+        .build()
+        .unwrap();
+
+    let err = zpool.create(topo).unwrap_err();
+    assert_eq!(ZpoolErrorKind::InvalidTopology, err.kind());
+    if let ZpoolError::InvalidTopology(reason) = err {
+        assert_eq!(TopologyError::RelativeAltroot(PathBuf::from("mnt")), reason);
+    }
+    assert!(!zpool.exists(&name).unwrap());
 }
 
 #[test]
@@ -317,6 +434,8 @@ fn create_with_props() {
         let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let props = ZpoolPropertiesWriteBuilder::default()
             .auto_expand(true)
+            .auto_trim(true)
+            .cache_file(CacheType::None)
             .comment(comment.clone())
             .fail_mode(FailMode::Panic)
             .build()
@@ -335,6 +454,8 @@ fn create_with_props() {
 
         let props = zpool.read_properties(&name).unwrap();
         assert_eq!(&true, props.auto_expand());
+        assert_eq!(&true, props.auto_trim());
+        assert_eq!(&CacheType::None, props.cache_file());
         assert_eq!(&FailMode::Panic, props.fail_mode());
         assert_eq!(&Some(comment.clone()), props.comment());
         zpool.destroy(&name, DestroyMode::Force).unwrap();
@@ -400,6 +521,95 @@ fn test_export_import_force() {
     });
 }
 
+#[test]
+fn test_export_import_by_guid() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/import");
+        setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).expect("Failed to create pool for export");
+
+        let result = zpool.export(&name, ExportMode::Gentle);
+        assert!(result.is_ok());
+        let list = zpool.available_in_dir(PathBuf::from(&vdev_dir)).unwrap();
+        assert_eq!(list.len(), 1);
+        let guid = list[0].id().expect("Pool available for import without id");
+
+        let options = ImportOptions::builder().search_dir(PathBuf::from(vdev_dir)).build().unwrap();
+        let result = zpool.import_by_guid(guid, None, options);
+        assert!(result.is_ok());
+        assert!(zpool.exists(&name).unwrap());
+    });
+}
+
+#[test]
+fn test_post_split_reguid() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/import");
+        let vdev0_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev(vdev_dir.join("vdev1"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path, vdev1_path]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let original_guid = *zpool.read_properties(&name).unwrap().guid();
+
+        let new_name = format!("{}-split", name);
+        let status = Command::new("zpool").args(&["split", &name, &new_name]).status().unwrap();
+        assert!(status.success());
+
+        let options = ImportOptions::builder().search_dir(PathBuf::from(vdev_dir)).build().unwrap();
+        let result = zpool.post_split_reguid(&new_name, options);
+        let _ = zpool.destroy(&new_name, DestroyMode::Force);
+        let new_guid = result.unwrap();
+
+        assert_ne!(original_guid, new_guid);
+        assert_eq!(original_guid, *zpool.read_properties(&name).unwrap().guid());
+    });
+}
+
+#[test]
+fn test_export_import_with_options() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/import");
+        setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).expect("Failed to create pool for export");
+
+        let result = zpool.export(&name, ExportMode::Gentle);
+        assert!(result.is_ok());
+
+        let options = ImportOptions::builder()
+            .search_dir(PathBuf::from(vdev_dir))
+            .readonly(true)
+            .build()
+            .unwrap();
+        let list = zpool.available_with_options(options.clone()).unwrap();
+        assert_eq!(list.len(), 1);
+
+        let result = zpool.import_with_options(&name, options);
+        assert!(result.is_ok());
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(&true, props.read_only());
+    });
+}
+
 #[test]
 fn test_status() {
     run_test(|name| {
@@ -413,7 +623,7 @@ fn test_status() {
             .unwrap();
         zpool.create(topo.clone()).unwrap();
 
-        let result = zpool.status(&name).unwrap();
+        let result = zpool.status(&name, false).unwrap();
         assert_eq!(&name, result.name());
         assert_eq!(&result, &topo);
     });
@@ -495,6 +705,177 @@ fn test_zpool_scrub() {
     });
 }
 
+#[test]
+fn test_zpool_reopen_not_found() {
+    let zpool = ZpoolOpen3::default();
+    let name = "non-existent";
+
+    let result = zpool.reopen(name, true);
+    assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+}
+
+#[test]
+fn test_zpool_reopen() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        zpool.reopen(&name, true).unwrap();
+        zpool.reopen(&name, false).unwrap();
+    });
+}
+
+#[test]
+fn test_zpool_upgrade() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        zpool.upgrade(&name, false).unwrap();
+        // Freshly created pool already has everything enabled.
+        zpool.upgrade(&name, true).unwrap();
+        let result = zpool.upgrade("non-existent", true);
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+        let result = zpool.upgrade("non-existent", false);
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+    });
+}
+
+#[test]
+fn test_zpool_scrub_and_report() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let report = zpool.scrub_and_report(&name, Some(Duration::from_secs(60))).unwrap();
+        assert_eq!(&0, report.errors());
+        assert_eq!(&0, report.repaired());
+
+        // Joining running scrub is fine too.
+        zpool.scrub(&name).unwrap();
+        let report = zpool.scrub_and_report(&name, None).unwrap();
+        assert_eq!(&0, report.errors());
+    });
+}
+
+#[test]
+fn test_zpool_wait() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        // Nothing is running, returns right away.
+        zpool.wait(&name, WaitActivity::Resilver).unwrap();
+
+        zpool.scrub(&name).unwrap();
+        zpool.wait(&name, WaitActivity::Scrub).unwrap();
+        let status = Command::new("zpool").args(&["status", &name]).output().unwrap();
+        let stdout = String::from_utf8_lossy(&status.stdout);
+        assert!(!stdout.contains("scrub in progress"), "{}", stdout);
+
+        let result = zpool.wait("non-existent", WaitActivity::Scrub);
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+    });
+}
+
+#[test]
+fn test_zpool_status_all() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let zpools = zpool.status_all().unwrap();
+        let found = zpools.iter().find(|z| z.name() == &name).expect("Pool is missing");
+        assert_eq!(&Health::Online, found.health());
+        assert_eq!(zpool.status(&name, false).unwrap(), *found);
+    });
+}
+
+#[test]
+fn test_zpool_sample_fragmentation() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let sample = zpool.sample_fragmentation(&name).unwrap();
+        assert!(*sample.percent() <= 100);
+
+        let result = zpool.sample_fragmentation("nonexistent");
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+    });
+}
+
+#[test]
+fn test_zpool_clear() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        assert_eq!(None, zpool.clear(&name, ClearMode::Gentle).unwrap());
+        let discarded = zpool.clear(&name, ClearMode::RewindDryRun).unwrap();
+        assert_eq!(Some(Duration::from_secs(0)), discarded);
+    });
+}
+
+#[test]
+fn test_zpool_events() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let events: Vec<_> = zpool.events(false).unwrap().map(|e| e.unwrap()).collect();
+        assert!(events.iter().any(|event| event.pool() == &Some(name.clone())));
+    });
+}
+
 #[test]
 fn test_zpool_take_single_device_offline() {
     run_test(|name| {
@@ -530,13 +911,13 @@ fn test_zpool_take_device_from_mirror_offline() {
         let result = zpool.take_offline(&name, &vdev0_path, OfflineMode::UntilReboot);
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(&Health::Degraded, z.health());
 
         let result = zpool.bring_online(&name, &vdev0_path, OnlineMode::Simple);
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(&Health::Online, z.health());
     });
 }
@@ -557,13 +938,13 @@ fn test_zpool_take_device_from_mirror_offline_expand() {
         let result = zpool.take_offline(&name, &vdev0_path, OfflineMode::UntilReboot);
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(&Health::Degraded, z.health());
 
         let result = zpool.bring_online(&name, &vdev0_path, OnlineMode::Expand);
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(&Health::Online, z.health());
     });
 }
@@ -584,7 +965,7 @@ fn test_zpool_attach_then_detach_single() {
 
         zpool.attach(&name, &vdev0_path, &vdev1_path).unwrap();
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         let topo_actual = CreateZpoolRequestBuilder::default()
             .name(name.clone())
             .create_mode(CreateMode::Force)
@@ -594,7 +975,7 @@ fn test_zpool_attach_then_detach_single() {
         assert_eq!(&z, &topo_actual);
 
         zpool.detach(&name, &vdev1_path).unwrap();
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(&z, &topo);
 
         let err = zpool.detach(&name, &vdev0_path).unwrap_err();
@@ -630,7 +1011,7 @@ fn test_zpool_add_naked() {
 
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
 
         assert_eq!(topo_expected, z);
     });
@@ -663,7 +1044,7 @@ fn test_zpool_add_naked_force() {
 
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
 
         assert_eq!(topo_expected, z);
     });
@@ -698,7 +1079,7 @@ fn test_zpool_add_mirror() {
 
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
 
         assert_eq!(topo_expected, z);
     });
@@ -764,7 +1145,7 @@ fn test_zpool_remove_zil() {
             .build()
             .unwrap();
 
-        let result = zpool.status(&name).unwrap();
+        let result = zpool.status(&name, false).unwrap();
 
         assert_eq!(topo, result);
     });
@@ -796,7 +1177,7 @@ fn test_zpool_add_cache() {
 
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(topo_expected, z);
     });
 }
@@ -816,7 +1197,7 @@ fn test_create_with_spare() {
             .build()
             .unwrap();
         zpool.create(topo.clone()).unwrap();
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(topo, z);
     });
 }
@@ -847,7 +1228,65 @@ fn test_zpool_add_spare() {
 
         assert!(result.is_ok());
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
+        assert_eq!(topo_expected, z);
+    });
+}
+
+#[test]
+fn test_zpool_vdev_guid() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let guids = zpool.vdev_guids(&name).unwrap();
+        let guid = zpool.vdev_guid(&name, &vdev0_path).unwrap().expect("vdev0 not found");
+        assert_eq!(Some(&vdev0_path), guids.get(&guid));
+        let other = zpool.vdev_guid(&name, &vdev1_path).unwrap().expect("vdev1 not found");
+        assert_ne!(guid, other);
+
+        assert_eq!(None, zpool.vdev_guid(&name, "/vdevs/not-in-pool").unwrap());
+    });
+}
+
+#[test]
+fn test_zpool_replace_and_wait() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev2_path = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let vdev3_path = setup_vdev("/vdevs/vdev3", &Bytes::MegaBytes(32));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let err = zpool.replace_and_wait(&name, &vdev0_path, &vdev3_path, None).unwrap_err();
+        assert_eq!(ZpoolErrorKind::DeviceTooSmall, err.kind());
+
+        zpool
+            .replace_and_wait(&name, &vdev0_path, &vdev2_path, Some(Duration::from_secs(60)))
+            .unwrap();
+
+        let topo_expected = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev2_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(topo_expected, z);
     });
 }
@@ -883,7 +1322,122 @@ fn test_zpool_replace_disk() {
         let wait_time = time::Duration::from_secs(13);
         thread::sleep(wait_time);
 
-        let z = zpool.status(&name).unwrap();
+        let z = zpool.status(&name, false).unwrap();
         assert_eq!(topo_expected, z);
     });
 }
+
+#[test]
+fn test_zpool_status_verbose_healthy() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::disk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).unwrap();
+
+        let z = zpool.status(&name, true).unwrap();
+        assert_eq!(topo, z);
+        assert!(z.errors().is_none());
+        assert!(z.damaged().is_empty());
+    });
+}
+
+#[test]
+fn test_checkpoint_and_rewind() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/import");
+        setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let err = zpool.checkpoint_discard(&name).unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
+        zpool.checkpoint(&name).unwrap();
+        let err = zpool.checkpoint(&name).unwrap_err();
+        assert_eq!(ZpoolErrorKind::CheckpointExists, err.kind());
+        assert!(zpool.read_properties(&name).unwrap().checkpoint().is_some());
+        assert!(zpool.status(&name, false).unwrap().checkpoint().is_some());
+
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+        let options = ImportOptions::builder()
+            .search_dir(PathBuf::from(vdev_dir))
+            .rewind_to_checkpoint(true)
+            .build()
+            .unwrap();
+        zpool.import_with_options(&name, options).unwrap();
+        assert!(zpool.read_properties(&name).unwrap().checkpoint().is_none());
+        assert!(zpool.status(&name, false).unwrap().checkpoint().is_none());
+    });
+}
+
+#[test]
+fn test_destroy_is_not_export() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/destroy");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).expect("Failed to create pool");
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+        let list = zpool.available_in_dir(PathBuf::from(vdev_dir)).unwrap();
+        assert_eq!(1, list.len());
+        assert_eq!(&name, list[0].name());
+
+        zpool.import_from_dir(&name, PathBuf::from(vdev_dir)).unwrap();
+        zpool.destroy(&name, DestroyMode::Gentle).unwrap();
+        assert!(!zpool.exists(&name).unwrap());
+        let list = zpool.available_in_dir(PathBuf::from(vdev_dir)).unwrap();
+        assert!(list.is_empty());
+
+        // Device is free to be used again without force.
+        zpool.create(topo).expect("Failed to reuse device of destroyed pool");
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+    });
+}
+
+#[test]
+fn test_zpool_take_device_offline_by_guid() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/guid/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/guid/vdev1", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let z = zpool.status(&name, false).unwrap();
+        let mirror = &z.vdevs()[0];
+        assert!(mirror.guid().is_some());
+        let guid = mirror.disks()[0].guid().expect("status didn't read guid of the disk");
+        assert_ne!(mirror.guid(), mirror.disks()[0].guid());
+        assert_eq!(Some(guid), zpool.vdev_guid(&name, &vdev0_path).unwrap());
+
+        zpool.take_offline(&name, guid.to_string(), OfflineMode::UntilReboot).unwrap();
+        let z = zpool.status(&name, false).unwrap();
+        assert_eq!(&Health::Degraded, z.health());
+        assert_eq!(&Health::Offline, z.vdevs()[0].disks()[0].health());
+
+        zpool.bring_online(&name, guid.to_string(), OnlineMode::Simple).unwrap();
+        let z = zpool.status(&name, false).unwrap();
+        assert_eq!(&Health::Online, z.health());
+    });
+}